@@ -1,9 +1,9 @@
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
 use poem::{
     get, handler,
     listener::TcpListener,
     web::{
-        websocket::{Message, WebSocket},
+        websocket::{Message, WebSocket, WsHub},
         Data, Html, Path,
     },
     EndpointExt, IntoResponse, Route, Server,
@@ -60,33 +60,17 @@ fn index() -> Html<&'static str> {
 }
 
 #[handler]
-fn ws(
-    Path(name): Path<String>,
-    ws: WebSocket,
-    sender: Data<&tokio::sync::broadcast::Sender<String>>,
-) -> impl IntoResponse {
-    let sender = sender.clone();
-    let mut receiver = sender.subscribe();
+fn ws(Path(name): Path<String>, ws: WebSocket, hub: Data<&WsHub<String>>) -> impl IntoResponse {
+    let hub = hub.clone();
     ws.on_upgrade(move |socket| async move {
-        let (mut sink, mut stream) = socket.split();
+        let mut conn = hub.connect_with(socket, name.clone());
+        conn.join("lobby");
 
-        tokio::spawn(async move {
-            while let Some(Ok(msg)) = stream.next().await {
-                if let Message::Text(text) = msg {
-                    if sender.send(format!("{}: {}", name, text)).is_err() {
-                        break;
-                    }
-                }
+        while let Some(Ok(msg)) = conn.next().await {
+            if let Message::Text(text) = msg {
+                hub.broadcast_to("lobby", Message::Text(format!("{}: {}", name, text)));
             }
-        });
-
-        tokio::spawn(async move {
-            while let Ok(msg) = receiver.recv().await {
-                if sink.send(Message::Text(msg)).await.is_err() {
-                    break;
-                }
-            }
-        });
+        }
     })
 }
 
@@ -97,10 +81,9 @@ async fn main() -> Result<(), std::io::Error> {
     }
     tracing_subscriber::fmt::init();
 
-    let app = Route::new().at("/", get(index)).at(
-        "/ws/:name",
-        get(ws.data(tokio::sync::broadcast::channel::<String>(32).0)),
-    );
+    let app = Route::new()
+        .at("/", get(index))
+        .at("/ws/:name", get(ws.data(WsHub::<String>::new())));
 
     Server::new(TcpListener::bind("127.0.0.1:3000"))
         .run(app)
@@ -1,14 +1,34 @@
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 /// A configuration for database.
 pub struct DatabaseConfig {
+    pub(crate) schema_name: Option<String>,
     pub(crate) table_name: String,
+    pub(crate) id_column: String,
+    pub(crate) session_column: String,
+    pub(crate) expires_column: String,
+    pub(crate) index_table_name: Option<String>,
+    pub(crate) run_migrations: bool,
+    pub(crate) jsonb: bool,
+    pub(crate) cleanup_interval: Option<Duration>,
+    pub(crate) cleanup_batch_size: u64,
 }
 
 impl Default for DatabaseConfig {
     fn default() -> Self {
         DatabaseConfig {
+            schema_name: None,
             table_name: "poem_sessions".to_string(),
+            id_column: "id".to_string(),
+            session_column: "session".to_string(),
+            expires_column: "expires".to_string(),
+            index_table_name: None,
+            run_migrations: false,
+            jsonb: true,
+            cleanup_interval: None,
+            cleanup_batch_size: 1000,
         }
     }
 }
@@ -19,10 +39,91 @@ impl DatabaseConfig {
         Default::default()
     }
 
+    /// Specifies the schema (or database) which contains the tables.
+    pub fn schema_name(self, schema_name: impl Into<String>) -> Self {
+        Self {
+            schema_name: Some(schema_name.into()),
+            ..self
+        }
+    }
+
     /// Specifies the table name.
     pub fn table_name(self, table_name: impl Into<String>) -> Self {
         Self {
             table_name: table_name.into(),
+            ..self
+        }
+    }
+
+    /// Specifies the name of the session id column. Default is `id`.
+    pub fn id_column(self, column: impl Into<String>) -> Self {
+        Self {
+            id_column: column.into(),
+            ..self
+        }
+    }
+
+    /// Specifies the name of the session data column. Default is `session`.
+    pub fn session_column(self, column: impl Into<String>) -> Self {
+        Self {
+            session_column: column.into(),
+            ..self
+        }
+    }
+
+    /// Specifies the name of the expiration time column. Default is
+    /// `expires`.
+    pub fn expires_column(self, column: impl Into<String>) -> Self {
+        Self {
+            expires_column: column.into(),
+            ..self
+        }
+    }
+
+    /// Specifies the table name of the per-user session index, which is
+    /// disabled by default.
+    pub fn index_table_name(self, table_name: impl Into<String>) -> Self {
+        Self {
+            index_table_name: Some(table_name.into()),
+            ..self
+        }
+    }
+
+    /// If `true`, the tables are created when creating the storage if they do
+    /// not exist. Default is `false`, so the schema is managed externally.
+    pub fn run_migrations(self, run: bool) -> Self {
+        Self {
+            run_migrations: run,
+            ..self
+        }
+    }
+
+    /// If `true`, the session data is stored in a `jsonb` column so that it
+    /// can be queried, otherwise in a `text` column. Default is `true`.
+    ///
+    /// Only Postgres supports this option.
+    pub fn jsonb(self, jsonb: bool) -> Self {
+        Self { jsonb, ..self }
+    }
+
+    /// Sets the interval of removing expired sessions in the background,
+    /// which is disabled by default.
+    ///
+    /// The background task is stopped when all clones of the storage are
+    /// dropped.
+    pub fn cleanup_interval(self, interval: impl Into<Option<Duration>>) -> Self {
+        Self {
+            cleanup_interval: interval.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of expired sessions removed by one statement
+    /// in the background cleanup. Default is `1000`.
+    pub fn cleanup_batch_size(self, batch_size: u64) -> Self {
+        Self {
+            cleanup_batch_size: batch_size.max(1),
+            ..self
         }
     }
 }
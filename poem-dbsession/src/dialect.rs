@@ -0,0 +1,709 @@
+use crate::DatabaseConfig;
+
+/// A SQL dialect, which describes the differences between databases needed
+/// to generate the statements of the session storage.
+///
+/// The statements are generated by [`SessionQueries`], so the session storage
+/// can be implemented with any database driver, and the builtin dialects can
+/// be reused with drivers other than `sqlx`.
+pub trait SqlDialect: Send + Sync + 'static {
+    /// Returns the placeholder of the parameter at `index`, which starts from
+    /// `1`. Default is `?`.
+    fn placeholder(&self, index: usize) -> String {
+        let _ = index;
+        "?".to_string()
+    }
+
+    /// Returns the column type of strings, `max_len` is `None` for strings of
+    /// unlimited length.
+    fn string_type(&self, max_len: Option<usize>) -> String;
+
+    /// Returns the column type of timestamps.
+    fn timestamp_type(&self) -> String;
+
+    /// Returns the column type of the session data. Default is
+    /// `string_type(None)`.
+    fn session_type(&self, config: &DatabaseConfig) -> String {
+        let _ = config;
+        self.string_type(None)
+    }
+
+    /// Returns the expression of the session data parameter, which is bound
+    /// as a JSON string. Default is the placeholder itself.
+    fn session_param(&self, config: &DatabaseConfig, placeholder: String) -> String {
+        let _ = config;
+        placeholder
+    }
+
+    /// Returns the expression which selects the session data column as a JSON
+    /// string. Default is the column itself.
+    fn session_select(&self, config: &DatabaseConfig, column: &str) -> String {
+        let _ = config;
+        column.to_string()
+    }
+
+    /// Returns the parameter which identifies the entry `name` in
+    /// [`SqlDialect::session_patch`]. Default is the JSON path `$."name"`.
+    fn session_key(&self, name: &str) -> String {
+        format!("$.\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    /// Returns the expression which removes the entries `removed` from the
+    /// session data column, and then sets the entries `changed`.
+    ///
+    /// The entries are identified by placeholders bound to
+    /// [`SqlDialect::session_key`], and the values of `changed` are
+    /// placeholders bound as JSON strings. Default uses `json_remove` and
+    /// `json_set`.
+    fn session_patch(
+        &self,
+        config: &DatabaseConfig,
+        column: &str,
+        removed: &[String],
+        changed: &[(String, String)],
+    ) -> String {
+        let _ = config;
+        let mut expr = column.to_string();
+        if !removed.is_empty() {
+            expr = format!("json_remove({}, {})", expr, removed.join(", "));
+        }
+        if !changed.is_empty() {
+            expr = format!(
+                "json_set({}, {})",
+                expr,
+                changed
+                    .iter()
+                    .map(|(key, value)| format!("{}, json({})", key, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        expr
+    }
+
+    /// Returns the statements which create a table if it does not exist.
+    ///
+    /// `columns` are pairs of the column name and the column definition, and
+    /// `indexes` are pairs of the index name and the indexed column.
+    fn create_table(
+        &self,
+        schema: Option<&str>,
+        table: &str,
+        columns: &[(&str, String)],
+        primary_key: &str,
+        indexes: &[(String, &str)],
+    ) -> Vec<String> {
+        let mut stmts = vec![format!(
+            "create table if not exists {} ({}, primary key ({}))",
+            qualified(schema, table),
+            column_definitions(columns),
+            primary_key
+        )];
+        for (name, column) in indexes {
+            stmts.push(self.create_index(schema, name, table, column));
+        }
+        stmts
+    }
+
+    /// Returns the statement which creates an index if it does not exist.
+    fn create_index(&self, schema: Option<&str>, name: &str, table: &str, column: &str) -> String {
+        format!(
+            "create index if not exists {} on {} ({})",
+            name,
+            qualified(schema, table),
+            column
+        )
+    }
+
+    /// Returns the statement which inserts a row, or updates `update_columns`
+    /// if a row with the same primary key already exists.
+    fn upsert(
+        &self,
+        table: &str,
+        columns: &[&str],
+        values: &[String],
+        primary_key: &str,
+        update_columns: &[&str],
+    ) -> String {
+        format!(
+            "insert into {} ({}) values ({}) on conflict({}) do update set {}",
+            table,
+            columns.join(", "),
+            values.join(", "),
+            primary_key,
+            update_columns
+                .iter()
+                .map(|column| format!("{0} = excluded.{0}", column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Returns the statement which deletes at most `limit` rows matching the
+    /// condition.
+    fn delete_limit(&self, table: &str, primary_key: &str, condition: &str, limit: &str) -> String {
+        format!(
+            "delete from {0} where {1} in (select {1} from {0} where {2} limit {3})",
+            table, primary_key, condition, limit
+        )
+    }
+}
+
+fn qualified(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", schema, name),
+        None => name.to_string(),
+    }
+}
+
+fn column_definitions(columns: &[(&str, String)]) -> String {
+    columns
+        .iter()
+        .map(|(name, definition)| format!("{} {}", name, definition))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The SQL dialect of Mysql.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MysqlDialect;
+
+impl SqlDialect for MysqlDialect {
+    fn string_type(&self, max_len: Option<usize>) -> String {
+        match max_len {
+            Some(max_len) => format!("varchar({})", max_len),
+            None => "text".to_string(),
+        }
+    }
+
+    fn timestamp_type(&self) -> String {
+        "timestamp(6)".to_string()
+    }
+
+    fn session_patch(
+        &self,
+        _config: &DatabaseConfig,
+        column: &str,
+        removed: &[String],
+        changed: &[(String, String)],
+    ) -> String {
+        let mut expr = column.to_string();
+        if !removed.is_empty() {
+            expr = format!("json_remove({}, {})", expr, removed.join(", "));
+        }
+        if !changed.is_empty() {
+            expr = format!(
+                "json_set({}, {})",
+                expr,
+                changed
+                    .iter()
+                    .map(|(key, value)| format!("{}, cast({} as json)", key, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        expr
+    }
+
+    fn create_table(
+        &self,
+        schema: Option<&str>,
+        table: &str,
+        columns: &[(&str, String)],
+        primary_key: &str,
+        indexes: &[(String, &str)],
+    ) -> Vec<String> {
+        let mut definitions = column_definitions(columns);
+        definitions.push_str(&format!(", primary key ({})", primary_key));
+        for (name, column) in indexes {
+            definitions.push_str(&format!(", key {} ({})", name, column));
+        }
+        vec![format!(
+            "create table if not exists {} ({}) engine=innodb default charset=utf8",
+            qualified(schema, table),
+            definitions
+        )]
+    }
+
+    fn upsert(
+        &self,
+        table: &str,
+        columns: &[&str],
+        values: &[String],
+        _primary_key: &str,
+        update_columns: &[&str],
+    ) -> String {
+        format!(
+            "insert into {} ({}) values ({}) on duplicate key update {}",
+            table,
+            columns.join(", "),
+            values.join(", "),
+            update_columns
+                .iter()
+                .map(|column| format!("{0} = values({0})", column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn delete_limit(
+        &self,
+        table: &str,
+        _primary_key: &str,
+        condition: &str,
+        limit: &str,
+    ) -> String {
+        format!("delete from {} where {} limit {}", table, condition, limit)
+    }
+}
+
+/// The SQL dialect of Postgres.
+///
+/// The session data is stored as `jsonb` unless [`DatabaseConfig::jsonb`] is
+/// disabled.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PostgresDialect;
+
+impl SqlDialect for PostgresDialect {
+    fn placeholder(&self, index: usize) -> String {
+        format!("${}", index)
+    }
+
+    fn string_type(&self, _max_len: Option<usize>) -> String {
+        "varchar".to_string()
+    }
+
+    fn timestamp_type(&self) -> String {
+        "timestamp with time zone".to_string()
+    }
+
+    fn session_type(&self, config: &DatabaseConfig) -> String {
+        if config.jsonb { "jsonb" } else { "text" }.to_string()
+    }
+
+    fn session_param(&self, config: &DatabaseConfig, placeholder: String) -> String {
+        if config.jsonb {
+            format!("cast({} as jsonb)", placeholder)
+        } else {
+            placeholder
+        }
+    }
+
+    fn session_select(&self, config: &DatabaseConfig, column: &str) -> String {
+        if config.jsonb {
+            format!("cast({} as text)", column)
+        } else {
+            column.to_string()
+        }
+    }
+
+    fn session_key(&self, name: &str) -> String {
+        name.to_string()
+    }
+
+    fn session_patch(
+        &self,
+        config: &DatabaseConfig,
+        column: &str,
+        removed: &[String],
+        changed: &[(String, String)],
+    ) -> String {
+        let mut expr = if config.jsonb {
+            column.to_string()
+        } else {
+            format!("cast({} as jsonb)", column)
+        };
+        for key in removed {
+            expr = format!("({} - cast({} as text))", expr, key);
+        }
+        if !changed.is_empty() {
+            expr = format!(
+                "({} || jsonb_build_object({}))",
+                expr,
+                changed
+                    .iter()
+                    .map(|(key, value)| format!("cast({} as text), cast({} as jsonb)", key, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if config.jsonb {
+            expr
+        } else {
+            format!("cast({} as text)", expr)
+        }
+    }
+}
+
+/// The SQL dialect of Sqlite.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SqliteDialect;
+
+impl SqlDialect for SqliteDialect {
+    fn string_type(&self, _max_len: Option<usize>) -> String {
+        "text".to_string()
+    }
+
+    fn timestamp_type(&self) -> String {
+        "integer".to_string()
+    }
+
+    fn create_index(&self, schema: Option<&str>, name: &str, table: &str, column: &str) -> String {
+        // Sqlite qualifies the index name instead of the table name.
+        format!(
+            "create index if not exists {} on {} ({})",
+            qualified(schema, name),
+            table,
+            column
+        )
+    }
+}
+
+/// The statements of the session storage generated by a [`SqlDialect`].
+///
+/// The parameters of each statement are listed in its documentation, the
+/// session data is bound as a JSON string and the timestamps are bound as
+/// the native timestamp type of the database.
+#[derive(Debug, Clone)]
+pub struct SessionQueries {
+    /// The statements which create the session table.
+    pub create_table: Vec<String>,
+    /// Loads the session data.
+    ///
+    /// Parameters: `session_id`, `now`.
+    pub load_session: String,
+    /// Inserts or updates a session.
+    ///
+    /// Parameters: `session_id`, `session`, `expires`.
+    pub update_session: String,
+    /// Removes a session.
+    ///
+    /// Parameters: `session_id`.
+    pub remove_session: String,
+    /// Updates the expiration time of a session.
+    ///
+    /// Parameters: `expires`, `session_id`.
+    pub touch_session: String,
+    /// Removes all expired sessions.
+    ///
+    /// Parameters: `now`.
+    pub cleanup: String,
+    /// Removes at most `limit` expired sessions.
+    ///
+    /// Parameters: `now`, `limit`.
+    pub cleanup_batch: String,
+    /// The statements of the per-user session index, only available if
+    /// [`DatabaseConfig::index_table_name`] is set.
+    pub index: Option<IndexQueries>,
+}
+
+/// The statements of the per-user session index generated by a
+/// [`SqlDialect`].
+#[derive(Debug, Clone)]
+pub struct IndexQueries {
+    /// The statements which create the index table.
+    pub create_table: Vec<String>,
+    /// Inserts or updates the index of a session.
+    ///
+    /// Parameters: `session_id`, `user_id`, `created_at`, `last_seen`, `ip`,
+    /// `user_agent`, `expires`.
+    pub index_session: String,
+    /// Lists the sessions of a user.
+    ///
+    /// Parameters: `user_id`, `now`.
+    ///
+    /// Columns: `session_id`, `created_at`, `last_seen`, `ip`, `user_agent`.
+    pub list_sessions: String,
+    /// Removes all sessions of a user.
+    ///
+    /// Parameters: `user_id`.
+    pub revoke_sessions: String,
+    /// Removes the index of all sessions of a user.
+    ///
+    /// Parameters: `user_id`.
+    pub revoke_index: String,
+    /// Removes the index of a session.
+    ///
+    /// Parameters: `session_id`.
+    pub unindex_session: String,
+    /// Updates the expiration time of the index of a session.
+    ///
+    /// Parameters: `expires`, `session_id`.
+    pub touch_index: String,
+    /// Removes the index of all expired sessions.
+    ///
+    /// Parameters: `now`.
+    pub cleanup: String,
+    /// Removes the index of at most `limit` expired sessions.
+    ///
+    /// Parameters: `now`, `limit`.
+    pub cleanup_batch: String,
+}
+
+impl SessionQueries {
+    /// Generates the statements for the configuration with the dialect.
+    pub fn new(dialect: &dyn SqlDialect, config: &DatabaseConfig) -> Self {
+        let p = |index| dialect.placeholder(index);
+        let schema = config.schema_name.as_deref();
+        let table = qualified(schema, &config.table_name);
+        let id = &config.id_column;
+        let session = &config.session_column;
+        let expires = &config.expires_column;
+
+        let create_table = dialect.create_table(
+            schema,
+            &config.table_name,
+            &[
+                (id, format!("{} not null", dialect.string_type(Some(128)))),
+                (expires, format!("{} null", dialect.timestamp_type())),
+                (
+                    session,
+                    format!("{} not null", dialect.session_type(config)),
+                ),
+            ],
+            id,
+            &[(format!("{}_{}_idx", config.table_name, expires), expires)],
+        );
+
+        let index = config.index_table_name.as_ref().map(|index_table_name| {
+            let index_table = qualified(schema, index_table_name);
+            IndexQueries {
+                create_table: dialect.create_table(
+                    schema,
+                    index_table_name,
+                    &[
+                        (
+                            "session_id",
+                            format!("{} not null", dialect.string_type(Some(128))),
+                        ),
+                        (
+                            "user_id",
+                            format!("{} not null", dialect.string_type(Some(128))),
+                        ),
+                        (
+                            "created_at",
+                            format!("{} not null", dialect.timestamp_type()),
+                        ),
+                        (
+                            "last_seen",
+                            format!("{} not null", dialect.timestamp_type()),
+                        ),
+                        ("ip", format!("{} null", dialect.string_type(Some(64)))),
+                        ("user_agent", format!("{} null", dialect.string_type(None))),
+                        ("expires", format!("{} null", dialect.timestamp_type())),
+                    ],
+                    "session_id",
+                    &[
+                        (format!("{}_user_id_idx", index_table_name), "user_id"),
+                        (format!("{}_expires_idx", index_table_name), "expires"),
+                    ],
+                ),
+                index_session: dialect.upsert(
+                    &index_table,
+                    &[
+                        "session_id",
+                        "user_id",
+                        "created_at",
+                        "last_seen",
+                        "ip",
+                        "user_agent",
+                        "expires",
+                    ],
+                    &(1..=7).map(p).collect::<Vec<_>>(),
+                    "session_id",
+                    &["user_id", "last_seen", "ip", "user_agent", "expires"],
+                ),
+                list_sessions: format!(
+                    "select session_id, created_at, last_seen, ip, user_agent from {} \
+                     where user_id = {} and (expires is null or expires > {})",
+                    index_table,
+                    p(1),
+                    p(2)
+                ),
+                revoke_sessions: format!(
+                    "delete from {} where {} in (select session_id from {} where user_id = {})",
+                    table,
+                    id,
+                    index_table,
+                    p(1)
+                ),
+                revoke_index: format!("delete from {} where user_id = {}", index_table, p(1)),
+                unindex_session: format!("delete from {} where session_id = {}", index_table, p(1)),
+                touch_index: format!(
+                    "update {} set expires = {} where session_id = {}",
+                    index_table,
+                    p(1),
+                    p(2)
+                ),
+                cleanup: format!("delete from {} where expires < {}", index_table, p(1)),
+                cleanup_batch: dialect.delete_limit(
+                    &index_table,
+                    "session_id",
+                    &format!("expires < {}", p(1)),
+                    &p(2),
+                ),
+            }
+        });
+
+        Self {
+            create_table,
+            load_session: format!(
+                "select {} from {} where {} = {} and ({} is null or {} > {})",
+                dialect.session_select(config, session),
+                table,
+                id,
+                p(1),
+                expires,
+                expires,
+                p(2)
+            ),
+            update_session: dialect.upsert(
+                &table,
+                &[id, session, expires],
+                &[p(1), dialect.session_param(config, p(2)), p(3)],
+                id,
+                &[expires, session],
+            ),
+            remove_session: format!("delete from {} where {} = {}", table, id, p(1)),
+            touch_session: format!(
+                "update {} set {} = {} where {} = {}",
+                table,
+                expires,
+                p(1),
+                id,
+                p(2)
+            ),
+            cleanup: format!("delete from {} where {} < {}", table, expires, p(1)),
+            cleanup_batch: dialect.delete_limit(
+                &table,
+                id,
+                &format!("{} < {}", expires, p(1)),
+                &p(2),
+            ),
+            index,
+        }
+    }
+
+    /// Generates the statement which removes `removed` entries and sets
+    /// `changed` entries of a session which is not expired, in a single
+    /// update.
+    ///
+    /// Parameters: the keys of the removed entries, the key and value of each
+    /// changed entry, `expires`, `session_id`, `now`.
+    pub fn patch_session(
+        dialect: &dyn SqlDialect,
+        config: &DatabaseConfig,
+        removed: usize,
+        changed: usize,
+    ) -> String {
+        let p = |index| dialect.placeholder(index);
+        let removed_params = (1..=removed).map(p).collect::<Vec<_>>();
+        let changed_params = (0..changed)
+            .map(|i| (p(removed + i * 2 + 1), p(removed + i * 2 + 2)))
+            .collect::<Vec<_>>();
+        let next = removed + changed * 2;
+        let session = &config.session_column;
+        let expires = &config.expires_column;
+        format!(
+            "update {} set {} = {}, {} = {} where {} = {} and ({} is null or {} > {})",
+            qualified(config.schema_name.as_deref(), &config.table_name),
+            session,
+            dialect.session_patch(config, session, &removed_params, &changed_params),
+            expires,
+            p(next + 1),
+            config.id_column,
+            p(next + 2),
+            expires,
+            expires,
+            p(next + 3)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries() {
+        let config = DatabaseConfig::new()
+            .schema_name("app")
+            .index_table_name("poem_session_index");
+
+        let queries = SessionQueries::new(&PostgresDialect, &config);
+        assert_eq!(
+            queries.create_table,
+            vec![
+                "create table if not exists app.poem_sessions (id varchar not null, expires \
+                 timestamp with time zone null, session jsonb not null, primary key (id))",
+                "create index if not exists poem_sessions_expires_idx on app.poem_sessions \
+                 (expires)",
+            ]
+        );
+        assert_eq!(
+            queries.load_session,
+            "select cast(session as text) from app.poem_sessions where id = $1 and (expires is \
+             null or expires > $2)"
+        );
+        assert_eq!(
+            queries.update_session,
+            "insert into app.poem_sessions (id, session, expires) values ($1, cast($2 as \
+             jsonb), $3) on conflict(id) do update set expires = excluded.expires, session = \
+             excluded.session"
+        );
+        assert_eq!(
+            queries.cleanup_batch,
+            "delete from app.poem_sessions where id in (select id from app.poem_sessions where \
+             expires < $1 limit $2)"
+        );
+
+        let queries = SessionQueries::new(&MysqlDialect, &config);
+        assert_eq!(
+            queries.create_table,
+            vec![
+                "create table if not exists app.poem_sessions (id varchar(128) not null, expires \
+                 timestamp(6) null, session text not null, primary key (id), key \
+                 poem_sessions_expires_idx (expires)) engine=innodb default charset=utf8"
+            ]
+        );
+        assert_eq!(
+            queries.update_session,
+            "insert into app.poem_sessions (id, session, expires) values (?, ?, ?) on duplicate \
+             key update expires = values(expires), session = values(session)"
+        );
+        let index = queries.index.unwrap();
+        assert_eq!(
+            index.cleanup_batch,
+            "delete from app.poem_session_index where expires < ? limit ?"
+        );
+        assert_eq!(
+            index.touch_index,
+            "update app.poem_session_index set expires = ? where session_id = ?"
+        );
+
+        assert_eq!(
+            SessionQueries::patch_session(&MysqlDialect, &config, 1, 1),
+            "update app.poem_sessions set session = json_set(json_remove(session, ?), ?, cast(? \
+             as json)), expires = ? where id = ? and (expires is null or expires > ?)"
+        );
+        assert_eq!(
+            SessionQueries::patch_session(&PostgresDialect, &config, 2, 1),
+            "update app.poem_sessions set session = (((session - cast($1 as text)) - cast($2 as \
+             text)) || jsonb_build_object(cast($3 as text), cast($4 as jsonb))), expires = $5 \
+             where id = $6 and (expires is null or expires > $7)"
+        );
+        assert_eq!(
+            SessionQueries::patch_session(&SqliteDialect, &DatabaseConfig::new(), 0, 0),
+            "update poem_sessions set session = session, expires = ? where id = ? and (expires \
+             is null or expires > ?)"
+        );
+        assert_eq!(MysqlDialect.session_key("a\"b"), "$.\"a\\\"b\"");
+
+        let queries = SessionQueries::new(&SqliteDialect, &DatabaseConfig::new().jsonb(false));
+        assert_eq!(
+            queries.load_session,
+            "select session from poem_sessions where id = ? and (expires is null or expires > ?)"
+        );
+        assert!(queries.index.is_none());
+    }
+}
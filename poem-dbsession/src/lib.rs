@@ -13,6 +13,18 @@
 //! | sqlx-sqlite-rustls        | sqlite   | rustls     |
 //! | sqlx-sqlite-native-tls    | sqlite   | native-tls |
 //!
+//! ## [`mongodb`](https://crates.io/crates/mongodb)
+//!
+//! | feature                   | database |
+//! |---------------------------|----------|
+//! | mongodb                   | mongodb  |
+//!
+//! ## Other databases
+//!
+//! The SQL statements used by the storages are generated by [`SqlDialect`]
+//! and [`SessionQueries`], which do not depend on `sqlx`, so they can be
+//! used to implement the session storage with other database libraries.
+//!
 //! ## Example
 //!
 //! ```rust,ignore
@@ -49,8 +61,15 @@
 ))]
 pub mod sqlx;
 
+#[cfg(feature = "mongodb")]
+pub mod mongodb;
+
 mod config;
+mod dialect;
 #[cfg(test)]
 mod test_harness;
 
 pub use config::DatabaseConfig;
+pub use dialect::{
+    IndexQueries, MysqlDialect, PostgresDialect, SessionQueries, SqlDialect, SqliteDialect,
+};
@@ -0,0 +1,408 @@
+//! MongoDB-backed session storage.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use mongodb::{
+    bson::{self, doc, Bson, DateTime, Document},
+    options::{IndexOptions, ReplaceOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
+use poem::{
+    error::{InternalServerError, SessionIndexNotSupportedError, SessionNotFoundError},
+    session::{SessionMetadata, SessionStorage},
+    Result,
+};
+use serde_json::Value;
+
+use crate::DatabaseConfig;
+
+/// Session storage using MongoDB.
+///
+/// The sessions are stored in the collection named
+/// [`DatabaseConfig::table_name`], with the session id as `_id`, the session
+/// data as a document in the [`DatabaseConfig::session_column`] field and the
+/// expiration time in the [`DatabaseConfig::expires_column`] field.
+///
+/// Expired sessions are removed by a [TTL index](https://www.mongodb.com/docs/manual/core/index-ttl/)
+/// on the expiration time, so [`DatabaseConfig::cleanup_interval`] is not
+/// used. The other options which only apply to SQL databases are ignored.
+///
+/// # Errors
+///
+/// - [`mongodb::error::Error`]
+/// - [`mongodb::bson::ser::Error`]
+/// - [`mongodb::bson::de::Error`]
+///
+/// # Indexes
+///
+/// The indexes are created when creating the storage if
+/// [`DatabaseConfig::run_migrations`] is set. With the default configuration,
+/// it is equivalent to:
+///
+/// ```javascript
+/// db.poem_sessions.createIndex({ expires: 1 }, { expireAfterSeconds: 0 })
+/// ```
+///
+/// # Per-user session index
+///
+/// If [`DatabaseConfig::index_table_name`] is set, the storage supports
+/// [`SessionStorage::list_sessions_for`] and [`SessionStorage::revoke_all_for`],
+/// and the index is stored in the collection with the following indexes:
+///
+/// ```javascript
+/// db.poem_session_index.createIndex({ user_id: 1 })
+/// db.poem_session_index.createIndex({ expires: 1 }, { expireAfterSeconds: 0 })
+/// ```
+#[derive(Clone)]
+pub struct MongoSessionStorage {
+    collection: Collection<Document>,
+    index_collection: Option<Collection<Document>>,
+    session_field: String,
+    expires_field: String,
+}
+
+fn ttl_index(field: &str) -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! { field: 1 })
+        .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+        .build()
+}
+
+fn expires_at(expires: Option<Duration>) -> Option<DateTime> {
+    expires.map(|expires| DateTime::from_system_time(SystemTime::now() + expires))
+}
+
+/// Matches the documents which are not expired.
+fn not_expired(field: &str) -> Bson {
+    bson::bson!([{ field: null }, { field: { "$gt": DateTime::now() } }])
+}
+
+impl MongoSessionStorage {
+    /// Create an [`MongoSessionStorage`].
+    pub async fn try_new(
+        config: DatabaseConfig,
+        database: Database,
+    ) -> mongodb::error::Result<Self> {
+        let collection = database.collection::<Document>(&config.table_name);
+        let index_collection = config
+            .index_table_name
+            .as_ref()
+            .map(|name| database.collection::<Document>(name));
+
+        if config.run_migrations {
+            collection
+                .create_index(ttl_index(&config.expires_column), None)
+                .await?;
+            if let Some(index_collection) = &index_collection {
+                index_collection
+                    .create_indexes(
+                        [
+                            IndexModel::builder().keys(doc! { "user_id": 1 }).build(),
+                            ttl_index("expires"),
+                        ],
+                        None,
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(Self {
+            collection,
+            index_collection,
+            session_field: config.session_column,
+            expires_field: config.expires_column,
+        })
+    }
+
+    /// Cleanup expired sessions.
+    ///
+    /// The expired sessions are removed by the TTL index periodically, so it
+    /// is only needed if the TTL monitor of the server is disabled.
+    pub async fn cleanup(&self) -> mongodb::error::Result<()> {
+        let expires = &self.expires_field;
+        self.collection
+            .delete_many(doc! { expires: { "$lt": DateTime::now() } }, None)
+            .await?;
+        if let Some(index_collection) = &self.index_collection {
+            index_collection
+                .delete_many(doc! { "expires": { "$lt": DateTime::now() } }, None)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[poem::async_trait]
+impl SessionStorage for MongoSessionStorage {
+    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
+        let filter = doc! {
+            "_id": session_id,
+            "$or": not_expired(&self.expires_field),
+        };
+        let mut document = match self
+            .collection
+            .find_one(filter, None)
+            .await
+            .map_err(InternalServerError)?
+        {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        match document.remove(&self.session_field) {
+            Some(session) => Ok(Some(bson::from_bson(session).map_err(InternalServerError)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update_session(
+        &self,
+        session_id: &str,
+        entries: &BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let session = &self.session_field;
+        let expires_field = &self.expires_field;
+        let replacement = doc! {
+            "_id": session_id,
+            session: bson::to_document(entries).map_err(InternalServerError)?,
+            expires_field: expires_at(expires),
+        };
+        self.collection
+            .replace_one(
+                doc! { "_id": session_id },
+                replacement,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<()> {
+        self.collection
+            .delete_one(doc! { "_id": session_id }, None)
+            .await
+            .map_err(InternalServerError)?;
+        if let Some(index_collection) = &self.index_collection {
+            index_collection
+                .delete_one(doc! { "_id": session_id }, None)
+                .await
+                .map_err(InternalServerError)?;
+        }
+        Ok(())
+    }
+
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let session = &self.session_field;
+        let expires_field = &self.expires_field;
+        // The entries are replaced with an aggregation pipeline instead of
+        // `$set` and `$unset`, which would treat the names as dotted paths.
+        let names = removed
+            .iter()
+            .chain(changed.keys())
+            .map(|name| Bson::String(name.clone()))
+            .collect::<Vec<_>>();
+        let entries = changed
+            .iter()
+            .map(|(name, value)| Ok(bson::bson!({ "k": name, "v": bson::to_bson(value)? })))
+            .collect::<Result<Vec<_>, bson::ser::Error>>()
+            .map_err(InternalServerError)?;
+        let pipeline = vec![doc! {
+            "$set": {
+                session: {
+                    "$arrayToObject": {
+                        "$concatArrays": [
+                            {
+                                "$filter": {
+                                    "input": { "$objectToArray": format!("${}", session) },
+                                    "cond": { "$not": [{ "$in": ["$$this.k", names] }] },
+                                },
+                            },
+                            { "$literal": entries },
+                        ],
+                    },
+                },
+                expires_field: expires_at(expires),
+            },
+        }];
+        let res = self
+            .collection
+            .update_one(
+                doc! {
+                    "_id": session_id,
+                    "$or": not_expired(expires_field),
+                },
+                pipeline,
+                None,
+            )
+            .await
+            .map_err(InternalServerError)?;
+        if res.matched_count == 0 {
+            return Err(SessionNotFoundError.into());
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()> {
+        let expires_field = &self.expires_field;
+        self.collection
+            .update_one(
+                doc! { "_id": session_id },
+                doc! { "$set": { expires_field: expires_at(expires) } },
+                None,
+            )
+            .await
+            .map_err(InternalServerError)?;
+        if let Some(index_collection) = &self.index_collection {
+            index_collection
+                .update_one(
+                    doc! { "_id": session_id },
+                    doc! { "$set": { "expires": expires_at(expires) } },
+                    None,
+                )
+                .await
+                .map_err(InternalServerError)?;
+        }
+        Ok(())
+    }
+
+    async fn index_session(
+        &self,
+        user: &str,
+        metadata: &SessionMetadata,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let index_collection = match &self.index_collection {
+            Some(index_collection) => index_collection,
+            None => return Ok(()),
+        };
+        index_collection
+            .update_one(
+                doc! { "_id": &metadata.session_id },
+                doc! {
+                    "$set": {
+                        "user_id": user,
+                        "last_seen": DateTime::from_system_time(metadata.last_seen),
+                        "ip": metadata.ip.as_deref(),
+                        "user_agent": metadata.user_agent.as_deref(),
+                        "expires": expires_at(expires),
+                    },
+                    "$setOnInsert": {
+                        "created_at": DateTime::from_system_time(metadata.created_at),
+                    },
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn list_sessions_for(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        let index_collection = self
+            .index_collection
+            .as_ref()
+            .ok_or(SessionIndexNotSupportedError)?;
+        let mut cursor = index_collection
+            .find(
+                doc! { "user_id": user, "$or": not_expired("expires") },
+                None,
+            )
+            .await
+            .map_err(InternalServerError)?;
+
+        let mut sessions = Vec::new();
+        while cursor.advance().await.map_err(InternalServerError)? {
+            let document = cursor.deserialize_current().map_err(InternalServerError)?;
+            sessions.push(SessionMetadata {
+                session_id: document
+                    .get_str("_id")
+                    .map_err(InternalServerError)?
+                    .to_string(),
+                created_at: document
+                    .get_datetime("created_at")
+                    .map_err(InternalServerError)?
+                    .to_system_time(),
+                last_seen: document
+                    .get_datetime("last_seen")
+                    .map_err(InternalServerError)?
+                    .to_system_time(),
+                ip: document.get_str("ip").ok().map(ToString::to_string),
+                user_agent: document.get_str("user_agent").ok().map(ToString::to_string),
+            });
+        }
+        Ok(sessions)
+    }
+
+    async fn revoke_all_for(&self, user: &str) -> Result<()> {
+        let index_collection = self
+            .index_collection
+            .as_ref()
+            .ok_or(SessionIndexNotSupportedError)?;
+        let mut cursor = index_collection
+            .find(doc! { "user_id": user }, None)
+            .await
+            .map_err(InternalServerError)?;
+        let mut session_ids = Vec::new();
+        while cursor.advance().await.map_err(InternalServerError)? {
+            let document = cursor.deserialize_current().map_err(InternalServerError)?;
+            if let Some(session_id) = document.get("_id") {
+                session_ids.push(session_id.clone());
+            }
+        }
+
+        self.collection
+            .delete_many(doc! { "_id": { "$in": session_ids } }, None)
+            .await
+            .map_err(InternalServerError)?;
+        index_collection
+            .delete_many(doc! { "user_id": user }, None)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::Client;
+
+    use super::*;
+    use crate::test_harness;
+
+    #[tokio::test]
+    async fn test() {
+        let client = Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let database = client.database("test_poem_sessions");
+        database.drop(None).await.unwrap();
+
+        let storage = MongoSessionStorage::try_new(
+            DatabaseConfig::new().run_migrations(true),
+            database.clone(),
+        )
+        .await
+        .unwrap();
+        test_harness::test_storage(storage).await;
+
+        let storage = MongoSessionStorage::try_new(
+            DatabaseConfig::new()
+                .index_table_name("poem_session_index")
+                .run_migrations(true),
+            database,
+        )
+        .await
+        .unwrap();
+        test_harness::test_session_index(storage).await;
+    }
+}
@@ -1,5 +1,22 @@
 //! sqlx-backed session storages.
 
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use poem::{
+    error::{InternalServerError, SessionIndexNotSupportedError, SessionNotFoundError},
+    session::{SessionMetadata, SessionStorage},
+    Result,
+};
+use serde_json::Value;
+use sqlx::{
+    database::HasArguments, ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool,
+    Row, Type,
+};
+use tokio::task::JoinHandle;
+
+use crate::{DatabaseConfig, SessionQueries, SqlDialect};
+
 #[cfg(any(feature = "sqlx-mysql-rustls", feature = "sqlx-mysql-native-tls"))]
 mod mysql;
 #[cfg(any(feature = "sqlx-postgres-rustls", feature = "sqlx-postgres-native-tls"))]
@@ -13,3 +30,379 @@ pub use mysql::MysqlSessionStorage;
 pub use postgres::PgSessionStorage;
 #[cfg(any(feature = "sqlx-sqlite-rustls", feature = "sqlx-sqlite-native-tls"))]
 pub use sqlite::SqliteSessionStorage;
+
+/// A database supported by [`SqlxSessionStorage`].
+pub trait SqlxDatabase: Database {
+    /// The SQL dialect used by [`SqlxSessionStorage::try_new`].
+    type Dialect: SqlDialect + Default;
+
+    /// Returns the number of rows affected by a statement.
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+/// Stops the background cleanup task when dropped.
+pub(crate) struct Reaper(JoinHandle<()>);
+
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns a task which calls `cleanup` every `interval`, and calls it again
+/// immediately while it removes `batch_size` rows.
+pub(crate) fn spawn_reaper<F, R>(interval: Duration, batch_size: u64, mut cleanup: F) -> Reaper
+where
+    F: FnMut() -> R + Send + 'static,
+    R: Future<Output = sqlx::Result<u64>> + Send,
+{
+    Reaper(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            loop {
+                match cleanup().await {
+                    Ok(removed) if removed >= batch_size => continue,
+                    Ok(_) => break,
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to cleanup expired sessions");
+                        break;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Session storage using a database supported by sqlx.
+///
+/// The statements are generated by a [`SqlDialect`], see
+/// [`MysqlSessionStorage`], [`PgSessionStorage`] and [`SqliteSessionStorage`]
+/// for the schema of each database.
+///
+/// # Errors
+///
+/// - [`sqlx::Error`]
+pub struct SqlxSessionStorage<DB: Database> {
+    pool: Pool<DB>,
+    queries: Arc<SessionQueries>,
+    dialect: Arc<dyn SqlDialect>,
+    config: Arc<DatabaseConfig>,
+    _reaper: Option<Arc<Reaper>>,
+}
+
+impl<DB: Database> Clone for SqlxSessionStorage<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            queries: self.queries.clone(),
+            dialect: self.dialect.clone(),
+            config: self.config.clone(),
+            _reaper: self._reaper.clone(),
+        }
+    }
+}
+
+fn expires_at(expires: Option<Duration>) -> Result<Option<DateTime<Utc>>> {
+    match expires {
+        Some(expires) => Ok(Some(
+            Utc::now() + chrono::Duration::from_std(expires).map_err(InternalServerError)?,
+        )),
+        None => Ok(None),
+    }
+}
+
+impl<DB> SqlxSessionStorage<DB>
+where
+    DB: SqlxDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> DateTime<Utc>: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> Option<&'q str>: Encode<'q, DB>,
+    for<'q> Option<String>: Decode<'q, DB>,
+    for<'q> Option<DateTime<Utc>>: Encode<'q, DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    /// Create a session storage with the default dialect of the database.
+    pub async fn try_new(config: DatabaseConfig, pool: Pool<DB>) -> sqlx::Result<Self> {
+        Self::try_with_dialect(config, pool, DB::Dialect::default()).await
+    }
+
+    /// Create a session storage with the specified dialect.
+    pub async fn try_with_dialect(
+        config: DatabaseConfig,
+        pool: Pool<DB>,
+        dialect: impl SqlDialect,
+    ) -> sqlx::Result<Self> {
+        let queries = Arc::new(SessionQueries::new(&dialect, &config));
+
+        let mut conn = pool.acquire().await?;
+        if config.run_migrations {
+            let index_stmts = queries
+                .index
+                .iter()
+                .flat_map(|index| index.create_table.iter());
+            for stmt in queries.create_table.iter().chain(index_stmts) {
+                sqlx::query(stmt).execute(&mut *conn).await?;
+            }
+        }
+        // Fails early if the tables do not match the configuration.
+        conn.prepare(&queries.load_session).await?;
+        drop(conn);
+
+        let reaper = config.cleanup_interval.map(|interval| {
+            let pool = pool.clone();
+            let queries = queries.clone();
+            let batch_size = config.cleanup_batch_size;
+            Arc::new(spawn_reaper(interval, batch_size, move || {
+                let pool = pool.clone();
+                let queries = queries.clone();
+                async move {
+                    let mut conn = pool.acquire().await?;
+                    let res = sqlx::query(&queries.cleanup_batch)
+                        .bind(Utc::now())
+                        .bind(batch_size as i64)
+                        .execute(&mut *conn)
+                        .await?;
+                    let mut removed = DB::rows_affected(&res);
+                    if let Some(index) = &queries.index {
+                        let res = sqlx::query(&index.cleanup_batch)
+                            .bind(Utc::now())
+                            .bind(batch_size as i64)
+                            .execute(&mut *conn)
+                            .await?;
+                        removed = removed.max(DB::rows_affected(&res));
+                    }
+                    Ok(removed)
+                }
+            }))
+        });
+
+        Ok(Self {
+            pool,
+            queries,
+            dialect: Arc::new(dialect),
+            config: Arc::new(config),
+            _reaper: reaper,
+        })
+    }
+
+    /// Cleanup expired sessions.
+    ///
+    /// See also [`DatabaseConfig::cleanup_interval`] for removing expired
+    /// sessions in the background.
+    pub async fn cleanup(&self) -> sqlx::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query(&self.queries.cleanup)
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await?;
+        if let Some(index) = &self.queries.index {
+            sqlx::query(&index.cleanup)
+                .bind(Utc::now())
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[poem::async_trait]
+impl<DB> SessionStorage for SqlxSessionStorage<DB>
+where
+    DB: SqlxDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> DateTime<Utc>: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> Option<&'q str>: Encode<'q, DB>,
+    for<'q> Option<String>: Decode<'q, DB>,
+    for<'q> Option<DateTime<Utc>>: Encode<'q, DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        let row = sqlx::query(&self.queries.load_session)
+            .bind(session_id)
+            .bind(Utc::now())
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        match row {
+            Some(row) => {
+                let value: String = row.try_get(0).map_err(InternalServerError)?;
+                Ok(Some(
+                    serde_json::from_str(&value).map_err(InternalServerError)?,
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn update_session(
+        &self,
+        session_id: &str,
+        entries: &BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        sqlx::query(&self.queries.update_session)
+            .bind(session_id)
+            .bind(serde_json::to_string(entries).map_err(InternalServerError)?)
+            .bind(expires_at(expires)?)
+            .execute(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        sqlx::query(&self.queries.remove_session)
+            .bind(session_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        if let Some(index) = &self.queries.index {
+            sqlx::query(&index.unindex_session)
+                .bind(session_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(InternalServerError)?;
+        }
+        Ok(())
+    }
+
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let sql = SessionQueries::patch_session(
+            &*self.dialect,
+            &self.config,
+            removed.len(),
+            changed.len(),
+        );
+        let mut query = sqlx::query(&sql);
+        for name in removed {
+            query = query.bind(self.dialect.session_key(name));
+        }
+        for (name, value) in changed {
+            query = query
+                .bind(self.dialect.session_key(name))
+                .bind(serde_json::to_string(value).map_err(InternalServerError)?);
+        }
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        let res = query
+            .bind(expires_at(expires)?)
+            .bind(session_id)
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        if DB::rows_affected(&res) == 0 {
+            return Err(SessionNotFoundError.into());
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()> {
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        let expires = expires_at(expires)?;
+        sqlx::query(&self.queries.touch_session)
+            .bind(expires)
+            .bind(session_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        if let Some(index) = &self.queries.index {
+            sqlx::query(&index.touch_index)
+                .bind(expires)
+                .bind(session_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(InternalServerError)?;
+        }
+        Ok(())
+    }
+
+    async fn index_session(
+        &self,
+        user: &str,
+        metadata: &SessionMetadata,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let index = match &self.queries.index {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        sqlx::query(&index.index_session)
+            .bind(metadata.session_id.as_str())
+            .bind(user)
+            .bind(DateTime::<Utc>::from(metadata.created_at))
+            .bind(DateTime::<Utc>::from(metadata.last_seen))
+            .bind(metadata.ip.as_deref())
+            .bind(metadata.user_agent.as_deref())
+            .bind(expires_at(expires)?)
+            .execute(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn list_sessions_for(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        let index = self
+            .queries
+            .index
+            .as_ref()
+            .ok_or(SessionIndexNotSupportedError)?;
+        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
+        let rows = sqlx::query(&index.list_sessions)
+            .bind(user)
+            .bind(Utc::now())
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(InternalServerError)?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(SessionMetadata {
+                    session_id: row.try_get(0)?,
+                    created_at: row.try_get::<DateTime<Utc>, _>(1)?.into(),
+                    last_seen: row.try_get::<DateTime<Utc>, _>(2)?.into(),
+                    ip: row.try_get(3)?,
+                    user_agent: row.try_get(4)?,
+                })
+            })
+            .collect::<sqlx::Result<_>>()
+            .map_err(InternalServerError)
+    }
+
+    async fn revoke_all_for(&self, user: &str) -> Result<()> {
+        let index = self
+            .queries
+            .index
+            .as_ref()
+            .ok_or(SessionIndexNotSupportedError)?;
+        let mut tx = self.pool.begin().await.map_err(InternalServerError)?;
+        sqlx::query(&index.revoke_sessions)
+            .bind(user)
+            .execute(&mut *tx)
+            .await
+            .map_err(InternalServerError)?;
+        sqlx::query(&index.revoke_index)
+            .bind(user)
+            .execute(&mut *tx)
+            .await
+            .map_err(InternalServerError)?;
+        tx.commit().await.map_err(InternalServerError)?;
+        Ok(())
+    }
+}
@@ -1,31 +1,17 @@
-use std::{collections::BTreeMap, time::Duration};
+use sqlx::{mysql::MySqlQueryResult, MySql};
 
-use chrono::Utc;
-use poem::{error::InternalServerError, session::SessionStorage, Result};
-use serde_json::Value;
-use sqlx::{mysql::MySqlStatement, types::Json, Executor, MySqlPool, Statement};
+use crate::{
+    sqlx::{SqlxDatabase, SqlxSessionStorage},
+    MysqlDialect,
+};
 
-use crate::DatabaseConfig;
+impl SqlxDatabase for MySql {
+    type Dialect = MysqlDialect;
 
-const LOAD_SESSION_SQL: &str = r#"
-    select session from {table_name}
-        where id = ? and (expires is null or expires > ?)
-    "#;
-
-const UPDATE_SESSION_SQL: &str = r#"
-    insert into {table_name} (id, session, expires) values (?, ?, ?)
-        on duplicate key update
-            expires = values(expires),
-            session = values(session)
-"#;
-
-const REMOVE_SESSION_SQL: &str = r#"
-    delete from {table_name} where id = ?
-"#;
-
-const CLEANUP_SQL: &str = r#"
-    delete from {table_name} where expires < ?
-"#;
+    fn rows_affected(result: &MySqlQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
 
 /// Session storage using Mysql.
 ///
@@ -35,133 +21,56 @@ const CLEANUP_SQL: &str = r#"
 ///
 /// # Create the table for session storage
 ///
+/// The table is created when creating the storage if
+/// [`DatabaseConfig::run_migrations`](crate::DatabaseConfig::run_migrations)
+/// is set. With the default configuration, it is equivalent to:
+///
 /// ```sql
 /// create table if not exists poem_sessions (
 ///     id varchar(128) not null,
 ///     expires timestamp(6) null,
 ///     session text not null,
 ///     primary key (id),
-///     key expires (expires)
+///     key poem_sessions_expires_idx (expires)
 /// )
 /// engine=innodb
 /// default charset=utf8
 /// ```
-#[derive(Clone)]
-pub struct MysqlSessionStorage {
-    pool: MySqlPool,
-    load_stmt: MySqlStatement<'static>,
-    update_stmt: MySqlStatement<'static>,
-    remove_stmt: MySqlStatement<'static>,
-    cleanup_stmt: MySqlStatement<'static>,
-}
-
-impl MysqlSessionStorage {
-    /// Create an [`MysqlSessionStorage`].
-    pub async fn try_new(config: DatabaseConfig, pool: MySqlPool) -> sqlx::Result<Self> {
-        let mut conn = pool.acquire().await?;
-
-        let load_stmt = Statement::to_owned(
-            &conn
-                .prepare(&LOAD_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let update_stmt = Statement::to_owned(
-            &conn
-                .prepare(&UPDATE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let remove_stmt = Statement::to_owned(
-            &conn
-                .prepare(&REMOVE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let cleanup_stmt = Statement::to_owned(
-            &conn
-                .prepare(&CLEANUP_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        Ok(Self {
-            pool,
-            load_stmt,
-            update_stmt,
-            remove_stmt,
-            cleanup_stmt,
-        })
-    }
-
-    /// Cleanup expired sessions.
-    pub async fn cleanup(&self) -> sqlx::Result<()> {
-        let mut conn = self.pool.acquire().await?;
-        self.cleanup_stmt
-            .query()
-            .bind(Utc::now())
-            .execute(&mut conn)
-            .await?;
-        Ok(())
-    }
-}
-
-#[poem::async_trait]
-impl SessionStorage for MysqlSessionStorage {
-    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        let res: Option<(Json<BTreeMap<String, Value>>,)> = self
-            .load_stmt
-            .query_as()
-            .bind(session_id)
-            .bind(Utc::now())
-            .fetch_optional(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(res.map(|(value,)| value.0))
-    }
-
-    async fn update_session(
-        &self,
-        session_id: &str,
-        entries: &BTreeMap<String, Value>,
-        expires: Option<Duration>,
-    ) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-
-        let expires = match expires {
-            Some(expires) => {
-                Some(chrono::Duration::from_std(expires).map_err(InternalServerError)?)
-            }
-            None => None,
-        };
-
-        self.update_stmt
-            .query()
-            .bind(session_id)
-            .bind(Json(entries))
-            .bind(expires.map(|expires| Utc::now() + expires))
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-
-    async fn remove_session(&self, session_id: &str) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        self.remove_stmt
-            .query()
-            .bind(session_id)
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-}
+///
+/// # Per-user session index
+///
+/// If [`DatabaseConfig::index_table_name`](crate::DatabaseConfig::index_table_name)
+/// is set, the storage supports
+/// [`SessionStorage::list_sessions_for`](poem::session::SessionStorage::list_sessions_for)
+/// and [`SessionStorage::revoke_all_for`](poem::session::SessionStorage::revoke_all_for),
+/// and the index is stored in the following table:
+///
+/// ```sql
+/// create table if not exists poem_session_index (
+///     session_id varchar(128) not null,
+///     user_id varchar(128) not null,
+///     created_at timestamp(6) not null,
+///     last_seen timestamp(6) not null,
+///     ip varchar(64) null,
+///     user_agent text null,
+///     expires timestamp(6) null,
+///     primary key (session_id),
+///     key poem_session_index_user_id_idx (user_id),
+///     key poem_session_index_expires_idx (expires)
+/// )
+/// engine=innodb
+/// default charset=utf8
+/// ```
+pub type MysqlSessionStorage = SqlxSessionStorage<MySql>;
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use sqlx::MySqlPool;
+
     use super::*;
-    use crate::test_harness;
+    use crate::{test_harness, DatabaseConfig};
 
     #[tokio::test]
     async fn test() {
@@ -187,7 +96,7 @@ mod tests {
         .await
         .unwrap();
 
-        let storage = MysqlSessionStorage::try_new(DatabaseConfig::new(), pool)
+        let storage = MysqlSessionStorage::try_new(DatabaseConfig::new(), pool.clone())
             .await
             .unwrap();
 
@@ -202,5 +111,35 @@ mod tests {
         });
         test_harness::test_storage(storage).await;
         join_handle.abort();
+
+        sqlx::query(
+            r#"
+        create table if not exists poem_session_index (
+            session_id varchar(128) not null,
+            user_id varchar(128) not null,
+            created_at timestamp(6) not null,
+            last_seen timestamp(6) not null,
+            ip varchar(64) null,
+            user_agent text null,
+            expires timestamp(6) null,
+            primary key (session_id),
+            key user_id (user_id),
+            key expires (expires)
+        )
+        engine=innodb
+        default charset=utf8
+        "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let storage = MysqlSessionStorage::try_new(
+            DatabaseConfig::new().index_table_name("poem_session_index"),
+            pool,
+        )
+        .await
+        .unwrap();
+        test_harness::test_session_index(storage).await;
     }
 }
@@ -1,31 +1,17 @@
-use std::{collections::BTreeMap, time::Duration};
+use sqlx::{postgres::PgQueryResult, Postgres};
 
-use chrono::Utc;
-use poem::{error::InternalServerError, session::SessionStorage, Result};
-use serde_json::Value;
-use sqlx::{postgres::PgStatement, types::Json, Executor, PgPool, Statement};
+use crate::{
+    sqlx::{SqlxDatabase, SqlxSessionStorage},
+    PostgresDialect,
+};
 
-use crate::DatabaseConfig;
+impl SqlxDatabase for Postgres {
+    type Dialect = PostgresDialect;
 
-const LOAD_SESSION_SQL: &str = r#"
-    select session from {table_name}
-        where id = $1 and (expires is null or expires > $2)
-    "#;
-
-const UPDATE_SESSION_SQL: &str = r#"
-    insert into {table_name} (id, session, expires) values ($1, $2, $3)
-        on conflict(id) do update set
-            expires = excluded.expires,
-            session = excluded.session
-"#;
-
-const REMOVE_SESSION_SQL: &str = r#"
-    delete from {table_name} where id = $1
-"#;
-
-const CLEANUP_SQL: &str = r#"
-    delete from {table_name} where expires < $1
-"#;
+    fn rows_affected(result: &PgQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
 
 /// Session storage using Postgres.
 ///
@@ -35,131 +21,59 @@ const CLEANUP_SQL: &str = r#"
 ///
 /// # Create the table for session storage
 ///
+/// The table is created when creating the storage if
+/// [`DatabaseConfig::run_migrations`](crate::DatabaseConfig::run_migrations)
+/// is set. With the default configuration, it is equivalent to:
+///
 /// ```sql
 /// create table if not exists poem_sessions (
-///     id varchar not null primary key,
+///     id varchar not null,
 ///     expires timestamp with time zone null,
-///     session jsonb not null
+///     session jsonb not null,
+///     primary key (id)
 /// );
 ///
 /// create index if not exists poem_sessions_expires_idx on poem_sessions (expires);
 /// ```
-#[derive(Clone)]
-pub struct PgSessionStorage {
-    pool: PgPool,
-    load_stmt: PgStatement<'static>,
-    update_stmt: PgStatement<'static>,
-    remove_stmt: PgStatement<'static>,
-    cleanup_stmt: PgStatement<'static>,
-}
-
-impl PgSessionStorage {
-    /// Create an [`PgSessionStorage`].
-    pub async fn try_new(config: DatabaseConfig, pool: PgPool) -> sqlx::Result<Self> {
-        let mut conn = pool.acquire().await?;
-
-        let load_stmt = Statement::to_owned(
-            &conn
-                .prepare(&LOAD_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let update_stmt = Statement::to_owned(
-            &conn
-                .prepare(&UPDATE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let remove_stmt = Statement::to_owned(
-            &conn
-                .prepare(&REMOVE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let cleanup_stmt = Statement::to_owned(
-            &conn
-                .prepare(&CLEANUP_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        Ok(Self {
-            pool,
-            load_stmt,
-            update_stmt,
-            remove_stmt,
-            cleanup_stmt,
-        })
-    }
-
-    /// Cleanup expired sessions.
-    pub async fn cleanup(&self) -> sqlx::Result<()> {
-        let mut conn = self.pool.acquire().await?;
-        self.cleanup_stmt
-            .query()
-            .bind(Utc::now())
-            .execute(&mut conn)
-            .await?;
-        Ok(())
-    }
-}
-
-#[poem::async_trait]
-impl SessionStorage for PgSessionStorage {
-    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        let res: Option<(Json<BTreeMap<String, Value>>,)> = self
-            .load_stmt
-            .query_as()
-            .bind(session_id)
-            .bind(Utc::now())
-            .fetch_optional(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(res.map(|(value,)| value.0))
-    }
-
-    async fn update_session(
-        &self,
-        session_id: &str,
-        entries: &BTreeMap<String, Value>,
-        expires: Option<Duration>,
-    ) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-
-        let expires = match expires {
-            Some(expires) => {
-                Some(chrono::Duration::from_std(expires).map_err(InternalServerError)?)
-            }
-            None => None,
-        };
-
-        self.update_stmt
-            .query()
-            .bind(session_id)
-            .bind(Json(entries))
-            .bind(expires.map(|expires| Utc::now() + expires))
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-
-    async fn remove_session(&self, session_id: &str) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        self.remove_stmt
-            .query()
-            .bind(session_id)
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-}
+///
+/// The session data is stored as `jsonb` by default, so the sessions can be
+/// queried with the JSON operators, such as
+/// `select id from poem_sessions where session ->> 'user_id' = '1'`. Use
+/// [`DatabaseConfig::jsonb`](crate::DatabaseConfig::jsonb) to store it as `text`.
+///
+/// # Per-user session index
+///
+/// If [`DatabaseConfig::index_table_name`](crate::DatabaseConfig::index_table_name)
+/// is set, the storage supports
+/// [`SessionStorage::list_sessions_for`](poem::session::SessionStorage::list_sessions_for)
+/// and [`SessionStorage::revoke_all_for`](poem::session::SessionStorage::revoke_all_for),
+/// and the index is stored in the following table:
+///
+/// ```sql
+/// create table if not exists poem_session_index (
+///     session_id varchar not null,
+///     user_id varchar not null,
+///     created_at timestamp with time zone not null,
+///     last_seen timestamp with time zone not null,
+///     ip varchar null,
+///     user_agent varchar null,
+///     expires timestamp with time zone null,
+///     primary key (session_id)
+/// );
+///
+/// create index if not exists poem_session_index_user_id_idx on poem_session_index (user_id);
+/// create index if not exists poem_session_index_expires_idx on poem_session_index (expires);
+/// ```
+pub type PgSessionStorage = SqlxSessionStorage<Postgres>;
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
     use super::*;
-    use crate::test_harness;
+    use crate::{test_harness, DatabaseConfig};
 
     #[tokio::test]
     async fn test() {
@@ -190,7 +104,7 @@ mod tests {
         .await
         .unwrap();
 
-        let storage = PgSessionStorage::try_new(DatabaseConfig::new(), pool)
+        let storage = PgSessionStorage::try_new(DatabaseConfig::new(), pool.clone())
             .await
             .unwrap();
 
@@ -205,5 +119,30 @@ mod tests {
         });
         test_harness::test_storage(storage).await;
         join_handle.abort();
+
+        sqlx::query(
+            r#"
+        create table if not exists poem_session_index (
+            session_id varchar not null primary key,
+            user_id varchar not null,
+            created_at timestamp with time zone not null,
+            last_seen timestamp with time zone not null,
+            ip varchar null,
+            user_agent varchar null,
+            expires timestamp with time zone null
+        )
+        "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let storage = PgSessionStorage::try_new(
+            DatabaseConfig::new().index_table_name("poem_session_index"),
+            pool,
+        )
+        .await
+        .unwrap();
+        test_harness::test_session_index(storage).await;
     }
 }
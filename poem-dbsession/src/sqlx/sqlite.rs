@@ -1,31 +1,17 @@
-use std::{collections::BTreeMap, time::Duration};
+use sqlx::{sqlite::SqliteQueryResult, Sqlite};
 
-use chrono::Utc;
-use poem::{error::InternalServerError, session::SessionStorage, Result};
-use serde_json::Value;
-use sqlx::{sqlite::SqliteStatement, types::Json, Executor, SqlitePool, Statement};
+use crate::{
+    sqlx::{SqlxDatabase, SqlxSessionStorage},
+    SqliteDialect,
+};
 
-use crate::DatabaseConfig;
+impl SqlxDatabase for Sqlite {
+    type Dialect = SqliteDialect;
 
-const LOAD_SESSION_SQL: &str = r#"
-    select session from {table_name}
-        where id = ? and (expires is null or expires > ?)
-    "#;
-
-const UPDATE_SESSION_SQL: &str = r#"
-    insert into {table_name} (id, session, expires) values (?, ?, ?)
-        on conflict(id) do update set
-            expires = excluded.expires,
-            session = excluded.session
-"#;
-
-const REMOVE_SESSION_SQL: &str = r#"
-    delete from {table_name} where id = ?
-"#;
-
-const CLEANUP_SQL: &str = r#"
-    delete from {table_name} where expires < $1
-"#;
+    fn rows_affected(result: &SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
 
 /// Session storage using Sqlite.
 ///
@@ -35,129 +21,56 @@ const CLEANUP_SQL: &str = r#"
 ///
 /// # Create the table for session storage
 ///
+/// The table is created when creating the storage if
+/// [`DatabaseConfig::run_migrations`](crate::DatabaseConfig::run_migrations)
+/// is set. With the default configuration, it is equivalent to:
+///
 /// ```sql
-/// create table poem_sessions (
-///     id text primary key not null,
+/// create table if not exists poem_sessions (
+///     id text not null,
 ///     expires integer null,
-///     session text not null
-/// )
+///     session text not null,
+///     primary key (id)
+/// );
+///
+/// create index if not exists poem_sessions_expires_idx on poem_sessions (expires);
 /// ```
-#[derive(Clone)]
-pub struct SqliteSessionStorage {
-    pool: SqlitePool,
-    load_stmt: SqliteStatement<'static>,
-    update_stmt: SqliteStatement<'static>,
-    remove_stmt: SqliteStatement<'static>,
-    cleanup_stmt: SqliteStatement<'static>,
-}
-
-impl SqliteSessionStorage {
-    /// Create an [`SqliteSessionStorage`].
-    pub async fn try_new(config: DatabaseConfig, pool: SqlitePool) -> sqlx::Result<Self> {
-        let mut conn = pool.acquire().await?;
-
-        let load_stmt = Statement::to_owned(
-            &conn
-                .prepare(&LOAD_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let update_stmt = Statement::to_owned(
-            &conn
-                .prepare(&UPDATE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let remove_stmt = Statement::to_owned(
-            &conn
-                .prepare(&REMOVE_SESSION_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        let cleanup_stmt = Statement::to_owned(
-            &conn
-                .prepare(&CLEANUP_SQL.replace("{table_name}", &config.table_name))
-                .await?,
-        );
-
-        Ok(Self {
-            pool,
-            load_stmt,
-            update_stmt,
-            remove_stmt,
-            cleanup_stmt,
-        })
-    }
-
-    /// Cleanup expired sessions.
-    pub async fn cleanup(&self) -> sqlx::Result<()> {
-        let mut conn = self.pool.acquire().await?;
-        self.cleanup_stmt
-            .query()
-            .bind(Utc::now())
-            .execute(&mut conn)
-            .await?;
-        Ok(())
-    }
-}
-
-#[poem::async_trait]
-impl SessionStorage for SqliteSessionStorage {
-    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        let res: Option<(Json<BTreeMap<String, Value>>,)> = self
-            .load_stmt
-            .query_as()
-            .bind(session_id)
-            .bind(Utc::now())
-            .fetch_optional(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(res.map(|(value,)| value.0))
-    }
-
-    async fn update_session(
-        &self,
-        session_id: &str,
-        entries: &BTreeMap<String, Value>,
-        expires: Option<Duration>,
-    ) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-
-        let expires = match expires {
-            Some(expires) => {
-                Some(chrono::Duration::from_std(expires).map_err(InternalServerError)?)
-            }
-            None => None,
-        };
-
-        self.update_stmt
-            .query()
-            .bind(session_id)
-            .bind(Json(entries))
-            .bind(expires.map(|expires| Utc::now() + expires))
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-
-    async fn remove_session(&self, session_id: &str) -> Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(InternalServerError)?;
-        self.remove_stmt
-            .query()
-            .bind(session_id)
-            .execute(&mut conn)
-            .await
-            .map_err(InternalServerError)?;
-        Ok(())
-    }
-}
+///
+/// # Per-user session index
+///
+/// If [`DatabaseConfig::index_table_name`](crate::DatabaseConfig::index_table_name)
+/// is set, the storage supports
+/// [`SessionStorage::list_sessions_for`](poem::session::SessionStorage::list_sessions_for)
+/// and [`SessionStorage::revoke_all_for`](poem::session::SessionStorage::revoke_all_for),
+/// and the index is stored in the following table:
+///
+/// ```sql
+/// create table if not exists poem_session_index (
+///     session_id text not null,
+///     user_id text not null,
+///     created_at integer not null,
+///     last_seen integer not null,
+///     ip text null,
+///     user_agent text null,
+///     expires integer null,
+///     primary key (session_id)
+/// );
+///
+/// create index if not exists poem_session_index_user_id_idx on poem_session_index (user_id);
+/// create index if not exists poem_session_index_expires_idx on poem_session_index (expires);
+/// ```
+pub type SqliteSessionStorage = SqlxSessionStorage<Sqlite>;
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use poem::session::SessionStorage;
+    use serde_json::Value;
+    use sqlx::SqlitePool;
+
     use super::*;
-    use crate::test_harness;
+    use crate::{test_harness, DatabaseConfig};
 
     #[tokio::test]
     async fn test() {
@@ -177,7 +90,7 @@ mod tests {
         .await
         .unwrap();
 
-        let storage = SqliteSessionStorage::try_new(DatabaseConfig::new(), pool)
+        let storage = SqliteSessionStorage::try_new(DatabaseConfig::new(), pool.clone())
             .await
             .unwrap();
 
@@ -192,5 +105,82 @@ mod tests {
         });
         test_harness::test_storage(storage).await;
         join_handle.abort();
+
+        sqlx::query(
+            r#"
+        create table poem_session_index (
+            session_id text primary key not null,
+            user_id text not null,
+            created_at integer not null,
+            last_seen integer not null,
+            ip text null,
+            user_agent text null,
+            expires integer null
+        )
+        "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let storage = SqliteSessionStorage::try_new(
+            DatabaseConfig::new().index_table_name("poem_session_index"),
+            pool,
+        )
+        .await
+        .unwrap();
+        test_harness::test_session_index(storage).await;
+    }
+
+    #[tokio::test]
+    async fn migrations_and_cleanup() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+
+        let result = SqliteSessionStorage::try_new(
+            DatabaseConfig::new().table_name("external_sessions"),
+            pool.clone(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let storage = SqliteSessionStorage::try_new(
+            DatabaseConfig::new()
+                .schema_name("main")
+                .table_name("custom_sessions")
+                .id_column("sid")
+                .session_column("data")
+                .expires_column("expires_at")
+                .run_migrations(true)
+                .cleanup_interval(Duration::from_millis(500))
+                .cleanup_batch_size(2),
+            pool.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut entries = BTreeMap::new();
+        entries.insert("a".to_string(), Value::from(1));
+        for i in 0..5 {
+            storage
+                .update_session(&format!("s{}", i), &entries, Some(Duration::from_secs(1)))
+                .await
+                .unwrap();
+        }
+        storage.update_session("s5", &entries, None).await.unwrap();
+        assert_eq!(
+            storage.load_session("s0").await.unwrap().as_ref(),
+            Some(&entries)
+        );
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        let (count,): (i64,) = sqlx::query_as("select count(*) from custom_sessions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            storage.load_session("s5").await.unwrap().as_ref(),
+            Some(&entries)
+        );
     }
 }
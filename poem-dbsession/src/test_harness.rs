@@ -1,8 +1,12 @@
 #![allow(dead_code)]
 
-use std::{collections::BTreeMap, time::Duration};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use poem::session::SessionStorage;
+use poem::session::{SessionMetadata, SessionStorage};
+use serde_json::Value;
 
 pub(crate) async fn test_storage(storage: impl SessionStorage) {
     let mut entries1 = BTreeMap::new();
@@ -47,4 +51,91 @@ pub(crate) async fn test_storage(storage: impl SessionStorage) {
 
     storage.remove_session("a2").await.unwrap();
     assert_eq!(storage.load_session("a2").await.unwrap().as_ref(), None);
+
+    storage
+        .update_session("a3", &entries1, Some(Duration::from_secs(2)))
+        .await
+        .unwrap();
+    storage.touch_session("a3", None).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(
+        storage.load_session("a3").await.unwrap().as_ref(),
+        Some(&entries1)
+    );
+    storage.remove_session("a3").await.unwrap();
+
+    storage.update_session("a4", &entries1, None).await.unwrap();
+    let mut changed = BTreeMap::new();
+    changed.insert("b".to_string(), serde_json::json!({ "x": [1, null] }));
+    changed.insert("c.d".to_string(), Value::Null);
+    storage
+        .patch_session("a4", &changed, &["a".to_string()], None)
+        .await
+        .unwrap();
+    assert_eq!(
+        storage.load_session("a4").await.unwrap().as_ref(),
+        Some(&changed)
+    );
+    storage.remove_session("a4").await.unwrap();
+    assert!(storage
+        .patch_session("a4", &changed, &[], None)
+        .await
+        .is_err());
+    assert_eq!(storage.load_session("a4").await.unwrap(), None);
+}
+
+pub(crate) async fn test_session_index(storage: impl SessionStorage) {
+    let mut entries = BTreeMap::new();
+    entries.insert("user_id".to_string(), "sunli".into());
+
+    let metadata = |session_id: &str| SessionMetadata {
+        session_id: session_id.to_string(),
+        created_at: UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+        last_seen: SystemTime::now(),
+        ip: Some("127.0.0.1".to_string()),
+        user_agent: None,
+    };
+
+    for session_id in ["b1", "b2", "b3"] {
+        storage
+            .update_session(session_id, &entries, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+    }
+    storage
+        .index_session("sunli", &metadata("b1"), Some(Duration::from_secs(60)))
+        .await
+        .unwrap();
+    storage
+        .index_session("sunli", &metadata("b2"), Some(Duration::from_secs(60)))
+        .await
+        .unwrap();
+    storage
+        .index_session("other", &metadata("b3"), Some(Duration::from_secs(60)))
+        .await
+        .unwrap();
+
+    let mut sessions = storage.list_sessions_for("sunli").await.unwrap();
+    sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    assert_eq!(
+        sessions
+            .iter()
+            .map(|metadata| metadata.session_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["b1", "b2"]
+    );
+    assert_eq!(sessions[0].created_at, metadata("b1").created_at);
+    assert_eq!(sessions[0].ip.as_deref(), Some("127.0.0.1"));
+
+    storage.remove_session("b2").await.unwrap();
+    assert_eq!(storage.list_sessions_for("sunli").await.unwrap().len(), 1);
+
+    storage.revoke_all_for("sunli").await.unwrap();
+    assert!(storage.list_sessions_for("sunli").await.unwrap().is_empty());
+    assert_eq!(storage.load_session("b1").await.unwrap(), None);
+    assert_eq!(
+        storage.load_session("b3").await.unwrap().as_ref(),
+        Some(&entries)
+    );
+    assert_eq!(storage.list_sessions_for("other").await.unwrap().len(), 1);
 }
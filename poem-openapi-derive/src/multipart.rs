@@ -27,6 +27,8 @@ struct MultipartField {
     default: Option<DefaultValue>,
     #[darling(default)]
     validator: Option<Validators>,
+    #[darling(default)]
+    max_size: Option<u64>,
 }
 
 #[derive(FromDeriveInput)]
@@ -42,6 +44,14 @@ struct MultipartArgs {
     rename_all: Option<RenameRule>,
     #[darling(default)]
     deny_unknown_fields: bool,
+    #[darling(default)]
+    max_size: Option<u64>,
+    #[darling(default)]
+    max_field_size: Option<u64>,
+    #[darling(default)]
+    max_fields: Option<usize>,
+    #[darling(default)]
+    max_files: Option<usize>,
 }
 
 pub(crate) fn generate(args: DeriveInput) -> GeneratorResult<TokenStream> {
@@ -67,6 +77,20 @@ pub(crate) fn generate(args: DeriveInput) -> GeneratorResult<TokenStream> {
     let mut meta_fields = Vec::new();
     let mut register_fields = Vec::new();
     let mut required_fields = Vec::new();
+    let mut config = Vec::new();
+
+    if let Some(max_size) = args.max_size {
+        config.push(quote!(.max_size(#max_size)));
+    }
+    if let Some(max_field_size) = args.max_field_size {
+        config.push(quote!(.max_field_size(#max_field_size)));
+    }
+    if let Some(max_fields) = args.max_fields {
+        config.push(quote!(.max_fields(#max_fields)));
+    }
+    if let Some(max_files) = args.max_files {
+        config.push(quote!(.max_files(#max_files)));
+    }
 
     for field in &s.fields {
         let field_ident = field.ident.as_ref().unwrap();
@@ -93,6 +117,10 @@ pub(crate) fn generate(args: DeriveInput) -> GeneratorResult<TokenStream> {
 
         fields.push(field_ident);
 
+        if let Some(max_size) = field.max_size {
+            config.push(quote!(.field_size_limit(#field_name, #max_size)));
+        }
+
         let parse_err = quote! {{
             #crate_name::error::ParseMultipartError {
                 reason: ::std::format!("failed to parse field `{}`: {}", #field_name, err.into_message()),
@@ -241,7 +269,13 @@ pub(crate) fn generate(args: DeriveInput) -> GeneratorResult<TokenStream> {
             const IS_REQUIRED: bool = true;
 
             async fn from_request(request: &#crate_name::__private::poem::Request, body: &mut #crate_name::__private::poem::RequestBody) -> #crate_name::__private::poem::Result<Self> {
-                let mut multipart = <#crate_name::__private::poem::web::Multipart as #crate_name::__private::poem::FromRequest>::from_request(request, body).await?;
+                let config = request
+                    .extensions()
+                    .get::<#crate_name::__private::poem::web::MultipartConfig>()
+                    .cloned()
+                    .unwrap_or_default()
+                    #(#config)*;
+                let mut multipart = #crate_name::__private::poem::web::Multipart::from_request_with_config(request, body, config).await?;
                 #(#skip_fields)*
                 #(let mut #fields = ::std::option::Option::None;)*
                 while let ::std::option::Option::Some(field) = multipart.next_field().await? {
@@ -13,8 +13,42 @@ use crate::{
     ApiResponse,
 };
 
+/// An item of the [`EventStream`].
+///
+/// It is implemented for the types which implement [`Type`] and [`ToJSON`],
+/// and the `(id, value)` tuples which set the id of the event, such as the
+/// items yielded by [`SseBroadcaster::subscribe`](poem::web::sse::SseBroadcaster::subscribe).
+pub trait EventStreamItem {
+    /// The type of the value.
+    type Value: Type + ToJSON;
+
+    /// Returns the id of the event and the value.
+    fn into_parts(self) -> (Option<String>, Self::Value);
+}
+
+impl<T: Type + ToJSON> EventStreamItem for T {
+    type Value = T;
+
+    fn into_parts(self) -> (Option<String>, Self::Value) {
+        (None, self)
+    }
+}
+
+impl<T: Type + ToJSON> EventStreamItem for (String, T) {
+    type Value = T;
+
+    fn into_parts(self) -> (Option<String>, Self::Value) {
+        (Some(self.0), self.1)
+    }
+}
+
 /// An event stream payload.
 ///
+/// The stream yields the values, or the `(id, value)` tuples to set the ids of
+/// the events, which allows the clients to resume from the
+/// [`LastEventId`](poem::web::sse::LastEventId) with a
+/// [`SseBroadcaster`](poem::web::sse::SseBroadcaster).
+///
 /// Reference: <https://github.com/OAI/OpenAPI-Specification/issues/396#issuecomment-894718960>
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct EventStream<T> {
@@ -41,28 +75,38 @@ impl<T> EventStream<T> {
     }
 }
 
-impl<T: Stream<Item = E> + Send + 'static, E: Type + ToJSON> Payload for EventStream<T> {
+impl<T: Stream<Item = E> + Send + 'static, E: EventStreamItem> Payload for EventStream<T> {
     const CONTENT_TYPE: &'static str = "text/event-stream";
 
     fn schema_ref() -> MetaSchemaRef {
         MetaSchemaRef::Inline(Box::new(MetaSchema {
-            items: Some(Box::new(E::schema_ref())),
+            items: Some(Box::new(E::Value::schema_ref())),
             ..MetaSchema::new_with_format("array", "event-stream")
         }))
     }
 
     fn register(registry: &mut Registry) {
-        E::register(registry);
+        E::Value::register(registry);
     }
 }
 
-impl<T: Stream<Item = E> + Send + 'static, E: Type + ToJSON> IntoResponse for EventStream<T> {
+impl<T: Stream<Item = E> + Send + 'static, E: EventStreamItem> IntoResponse for EventStream<T> {
     fn into_response(self) -> Response {
         let mut sse = SSE::new(
             self.stream
-                .map(|value| serde_json::to_string(&value.to_json()))
+                .map(|item| {
+                    let (id, value) = item.into_parts();
+                    serde_json::to_string(&value.to_json()).map(|data| (id, data))
+                })
                 .take_while(|value| futures_util::future::ready(value.is_ok()))
-                .map(|value| Event::message(value.unwrap())),
+                .map(|value| {
+                    let (id, data) = value.unwrap();
+                    let event = Event::message(data);
+                    match id {
+                        Some(id) => event.id(id),
+                        None => event,
+                    }
+                }),
         );
 
         if let Some(keep_alive) = self.keep_alive {
@@ -73,7 +117,7 @@ impl<T: Stream<Item = E> + Send + 'static, E: Type + ToJSON> IntoResponse for Ev
     }
 }
 
-impl<T: Stream<Item = E> + Send + 'static, E: Type + ToJSON> ApiResponse for EventStream<T> {
+impl<T: Stream<Item = E> + Send + 'static, E: EventStreamItem> ApiResponse for EventStream<T> {
     fn meta() -> MetaResponses {
         MetaResponses {
             responses: vec![MetaResponse {
@@ -89,6 +133,6 @@ impl<T: Stream<Item = E> + Send + 'static, E: Type + ToJSON> ApiResponse for Eve
     }
 
     fn register(registry: &mut Registry) {
-        E::register(registry);
+        E::Value::register(registry);
     }
 }
@@ -12,8 +12,14 @@ mod response;
 use poem::{Request, RequestBody, Result};
 
 pub use self::{
-    attachment::Attachment, base64_payload::Base64, binary::Binary, event_stream::EventStream,
-    html::Html, json::Json, plain_text::PlainText, response::Response,
+    attachment::Attachment,
+    base64_payload::Base64,
+    binary::Binary,
+    event_stream::{EventStream, EventStreamItem},
+    html::Html,
+    json::Json,
+    plain_text::PlainText,
+    response::Response,
 };
 use crate::registry::{MetaSchemaRef, Registry};
 
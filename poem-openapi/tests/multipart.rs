@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use poem::{Request, RequestBody};
+use poem::{http::StatusCode, web::MultipartConfig, Request, RequestBody};
 use poem_openapi::{
     payload::{ParsePayload, Payload},
     registry::{MetaSchema, MetaSchemaRef},
@@ -519,3 +519,64 @@ async fn deny_unknown_fields() {
     .unwrap_err();
     assert_eq!(err.to_string(), "parse multipart error: unknown field `c`");
 }
+
+#[tokio::test]
+async fn limits() {
+    #[derive(Multipart, Debug)]
+    #[oai(max_files = 1)]
+    struct A {
+        #[oai(max_size = 4)]
+        name: String,
+        files: Vec<Upload>,
+    }
+
+    async fn parse(
+        parts: &[(&str, Option<&str>, &[u8])],
+        config: Option<MultipartConfig>,
+    ) -> poem::Result<A> {
+        let mut request = Request::builder()
+            .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+            .finish();
+        if let Some(config) = config {
+            request.extensions_mut().insert(config);
+        }
+        let data = create_multipart_payload(parts);
+        A::from_request(&request, &mut RequestBody::new(data.into())).await
+    }
+
+    let a = parse(
+        &[("name", None, b"abcd"), ("files", Some("1.txt"), b"1")],
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(a.name, "abcd");
+    assert_eq!(a.files.len(), 1);
+
+    let err = parse(&[("name", None, b"abcde")], None).await.unwrap_err();
+    assert!(err.to_string().contains("exceeded the size limit"));
+
+    let err = parse(
+        &[
+            ("name", None, b"abcd"),
+            ("files", Some("1.txt"), b"1"),
+            ("files", Some("2.txt"), b"2"),
+        ],
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.as_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // The limits of the `MultipartConfig` in the request data are also applied.
+    let err = parse(
+        &[("name", None, b"abcd"), ("files", Some("1.txt"), b"1")],
+        Some(MultipartConfig::new().allowed_content_types(["image/*"])),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        err.as_response().status(),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+}
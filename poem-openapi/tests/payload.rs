@@ -1,7 +1,8 @@
+use futures_util::stream::BoxStream;
 use poem::{http::StatusCode, test::TestClient, Error};
 use poem_openapi::{
     param::Query,
-    payload::{Json, Response},
+    payload::{EventStream, Json, Response},
     ApiResponse, OpenApi, OpenApiService,
 };
 
@@ -51,3 +52,35 @@ async fn response_wrapper() {
     resp.assert_status(StatusCode::BAD_REQUEST);
     resp.assert_header("MY-HEADER1", "def");
 }
+
+#[tokio::test]
+async fn event_stream_with_ids() {
+    struct Api;
+
+    #[OpenApi]
+    impl Api {
+        #[oai(path = "/a", method = "get")]
+        async fn a(&self) -> EventStream<BoxStream<'static, i32>> {
+            EventStream::new(Box::pin(futures_util::stream::iter(vec![1, 2])))
+        }
+
+        #[oai(path = "/b", method = "get")]
+        async fn b(&self) -> EventStream<BoxStream<'static, (String, i32)>> {
+            EventStream::new(Box::pin(futures_util::stream::iter(vec![
+                ("a".to_string(), 1),
+                ("b".to_string(), 2),
+            ])))
+        }
+    }
+
+    let ep = OpenApiService::new(Api, "test", "1.0");
+    let cli = TestClient::new(ep);
+
+    let resp = cli.get("/a").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text("data: 1\n\ndata: 2\n\n").await;
+
+    let resp = cli.get("/b").send().await;
+    resp.assert_status_is_ok();
+    resp.assert_text("id: a\ndata: 1\n\nid: b\ndata: 2\n\n").await;
+}
@@ -1,24 +1,42 @@
 use std::{
-    fmt::{Debug, Display, Formatter},
+    error::Error as StdError,
+    fmt::{Debug, Formatter},
+    future::Future,
     io::{Error as IoError, ErrorKind},
     pin::Pin,
     task::{Context, Poll},
 };
 
 use bytes::{Bytes, BytesMut};
-use futures_util::{Stream, TryStreamExt};
-use hyper::body::HttpBody;
+use futures_util::{Stream, StreamExt};
+use hyper::body::{HttpBody, SizeHint};
+use parking_lot::Mutex;
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::{io::AsyncRead, sync::oneshot};
 
 use crate::{
     error::{ParseJsonError, ReadBodyError},
+    http::HeaderMap,
     Result,
 };
 
 /// A body object for requests and responses.
 #[derive(Default)]
-pub struct Body(pub(crate) hyper::Body);
+pub struct Body(pub(crate) BodyKind);
+
+pub(crate) enum BodyKind {
+    Hyper(hyper::Body),
+    Limited(LimitedBody),
+    /// The mutex only makes the body `Sync`, it is accessed through `&mut`
+    /// when the body is polled.
+    Stream(Mutex<StreamBody>),
+}
+
+impl Default for BodyKind {
+    fn default() -> Self {
+        Self::Hyper(hyper::Body::empty())
+    }
+}
 
 impl Debug for Body {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -28,48 +46,55 @@ impl Debug for Body {
 
 impl From<hyper::Body> for Body {
     fn from(body: hyper::Body) -> Self {
-        Body(body)
+        Body(BodyKind::Hyper(body))
     }
 }
 
+/// The bodies which are not created from a [`hyper::Body`], such as the
+/// bodies returned by [`Body::limit`], are converted with
+/// [`hyper::Body::wrap_stream`], so their trailers are dropped. The server
+/// sends the [`Body`] directly as an [`HttpBody`], which keeps them.
 impl From<Body> for hyper::Body {
     fn from(body: Body) -> Self {
-        body.0
+        match body.0 {
+            BodyKind::Hyper(body) => body,
+            body => hyper::Body::wrap_stream(Body(body).into_bytes_stream()),
+        }
     }
 }
 
 impl From<&'static [u8]> for Body {
     #[inline]
     fn from(data: &'static [u8]) -> Self {
-        Self(data.into())
+        hyper::Body::from(data).into()
     }
 }
 
 impl From<&'static str> for Body {
     #[inline]
     fn from(data: &'static str) -> Self {
-        Self(data.into())
+        hyper::Body::from(data).into()
     }
 }
 
 impl From<Bytes> for Body {
     #[inline]
     fn from(data: Bytes) -> Self {
-        Self(data.into())
+        hyper::Body::from(data).into()
     }
 }
 
 impl From<Vec<u8>> for Body {
     #[inline]
     fn from(data: Vec<u8>) -> Self {
-        Self(data.into())
+        hyper::Body::from(data).into()
     }
 }
 
 impl From<String> for Body {
     #[inline]
     fn from(data: String) -> Self {
-        Self(data.into())
+        hyper::Body::from(data).into()
     }
 }
 
@@ -102,9 +127,7 @@ impl Body {
     /// Create a body object from reader.
     #[inline]
     pub fn from_async_read(reader: impl AsyncRead + Send + 'static) -> Self {
-        Self(hyper::Body::wrap_stream(tokio_util::io::ReaderStream::new(
-            reader,
-        )))
+        hyper::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader)).into()
     }
 
     /// Create a body object from bytes stream.
@@ -114,7 +137,48 @@ impl Body {
         O: Into<Bytes> + 'static,
         E: std::error::Error + Send + Sync + 'static,
     {
-        Self(hyper::Body::wrap_stream(stream))
+        hyper::Body::wrap_stream(stream).into()
+    }
+
+    /// Create a body object from bytes stream, which sends the trailers
+    /// returned by `trailers` after the stream ends.
+    ///
+    /// The trailers future is polled only after the stream ends, and if the
+    /// stream returns an error, the body is aborted without the trailers.
+    ///
+    /// NOTE: The trailers are only transmitted over HTTP/2.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::stream;
+    /// use poem::{handler, http::HeaderMap, Body};
+    ///
+    /// #[handler]
+    /// fn index() -> Body {
+    ///     let data = ["hello", " world"].into_iter().map(Ok::<_, std::io::Error>);
+    ///     Body::from_stream_with_trailers(stream::iter(data), async {
+    ///         let mut trailers = HeaderMap::new();
+    ///         trailers.insert("grpc-status", "0".parse().unwrap());
+    ///         trailers
+    ///     })
+    /// }
+    /// ```
+    pub fn from_stream_with_trailers<S, O, E, F>(stream: S, trailers: F) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + Send + 'static,
+        E: std::error::Error + Send + Sync + 'static,
+        F: Future<Output = HeaderMap> + Send + 'static,
+    {
+        let stream = stream.map(|res| match res {
+            Ok(data) => Ok(data.into()),
+            Err(err) => Err(IoError::other(err)),
+        });
+        Self(BodyKind::Stream(Mutex::new(StreamBody {
+            stream: Some(Box::pin(stream)),
+            trailers: Some(Box::pin(trailers)),
+        })))
     }
 
     /// Create a body object from JSON.
@@ -125,29 +189,61 @@ impl Body {
     /// Create an empty body.
     #[inline]
     pub fn empty() -> Self {
-        Self(hyper::Body::empty())
+        hyper::Body::empty().into()
     }
 
     /// Returns `true` if this body is empty.
     pub fn is_empty(&self) -> bool {
-        let size_hint = hyper::body::HttpBody::size_hint(&self.0);
+        let size_hint = HttpBody::size_hint(&self.0);
         size_hint.lower() == 0 && size_hint.upper() == Some(0)
     }
 
+    /// Consumes this body object to return a body which fails with
+    /// [`ReadBodyError::PayloadTooLarge`] when more than `limit` bytes are
+    /// read from it.
+    ///
+    /// Unlike checking the `Content-Length` header, the bytes are counted as
+    /// they are streamed, so it also works for the chunked bodies. The
+    /// trailers and the size hint of the body are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{error::ReadBodyError, Body};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let body = Body::from_bytes_stream(futures_util::stream::iter(vec![
+    ///     Ok::<_, std::io::Error>("123"),
+    ///     Ok("456"),
+    /// ]));
+    /// assert!(matches!(
+    ///     body.limit(5).into_bytes().await,
+    ///     Err(ReadBodyError::PayloadTooLarge)
+    /// ));
+    /// # });
+    /// ```
+    pub fn limit(self, limit: usize) -> Self {
+        Self(BodyKind::Limited(match self.0 {
+            BodyKind::Limited(body) => LimitedBody {
+                remaining: body.remaining.min(limit),
+                ..body
+            },
+            inner => LimitedBody {
+                inner: Box::new(inner),
+                remaining: limit,
+            },
+        }))
+    }
+
     /// Consumes this body object to return a [`Bytes`] that contains all data.
     pub async fn into_bytes(self) -> Result<Bytes, ReadBodyError> {
-        hyper::body::to_bytes(self.0)
-            .await
-            .map_err(|err| ReadBodyError::Io(IoError::new(ErrorKind::Other, err)))
+        hyper::body::to_bytes(self.0).await.map_err(read_body_error)
     }
 
     /// Consumes this body object to return a [`Vec<u8>`] that contains all
     /// data.
     pub async fn into_vec(self) -> Result<Vec<u8>, ReadBodyError> {
-        Ok(hyper::body::to_bytes(self.0)
-            .await
-            .map_err(|err| ReadBodyError::Io(IoError::new(ErrorKind::Other, err)))?
-            .to_vec())
+        Ok(self.into_bytes().await?.to_vec())
     }
 
     /// Consumes this body object to return a [`Bytes`] that contains all
@@ -176,19 +272,18 @@ impl Body {
     /// # });
     /// ```
     pub async fn into_bytes_limit(self, limit: usize) -> Result<Bytes, ReadBodyError> {
-        let mut reader = self.into_async_read();
-        let mut buf = [0; 4096];
-        let mut data = BytesMut::new();
+        let mut body = self.0;
+        if HttpBody::size_hint(&body).lower() > limit as u64 {
+            return Err(ReadBodyError::PayloadTooLarge);
+        }
 
-        loop {
-            let sz = reader.read(&mut buf).await?;
-            if sz == 0 {
-                break;
-            }
-            if data.len() + sz > limit {
+        let mut data = BytesMut::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(read_body_error)?;
+            if data.len() + chunk.len() > limit {
                 return Err(ReadBodyError::PayloadTooLarge);
             }
-            data.extend_from_slice(&buf[..sz]);
+            data.extend_from_slice(&chunk);
         }
 
         Ok(data.freeze())
@@ -211,46 +306,322 @@ impl Body {
 
     /// Consumes this body object to return a reader.
     pub fn into_async_read(self) -> impl AsyncRead + Unpin + Send + 'static {
-        tokio_util::io::StreamReader::new(BodyStream::new(self.0))
+        tokio_util::io::StreamReader::new(self.into_bytes_stream())
     }
 
     /// Consumes this body object to return a bytes stream.
     pub fn into_bytes_stream(self) -> impl Stream<Item = Result<Bytes, IoError>> + Send + 'static {
-        TryStreamExt::map_err(self.0, |err| IoError::new(ErrorKind::Other, err))
+        let mut body = self.0;
+        futures_util::stream::poll_fn(move |cx| Pin::new(&mut body).poll_data(cx))
+    }
+
+    /// Consumes this body object to return a bytes stream and a [`Trailers`]
+    /// future, which resolves to the trailers after the stream is fully read.
+    ///
+    /// NOTE: The trailers are only transmitted over HTTP/2.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::TryStreamExt;
+    /// use poem::{error::ReadBodyError, handler, Body, Result};
+    ///
+    /// #[handler]
+    /// async fn index(body: Body) -> Result<String> {
+    ///     let (stream, trailers) = body.into_bytes_stream_with_trailers();
+    ///     let size = stream
+    ///         .try_fold(0, |size, data| async move { Ok(size + data.len()) })
+    ///         .await
+    ///         .map_err(ReadBodyError::Io)?;
+    ///     let checksum = trailers
+    ///         .await?
+    ///         .and_then(|trailers| trailers.get("x-checksum").cloned());
+    ///     Ok(format!("{} {:?}", size, checksum))
+    /// }
+    /// ```
+    pub fn into_bytes_stream_with_trailers(
+        self,
+    ) -> (
+        impl Stream<Item = Result<Bytes, IoError>> + Send + 'static,
+        Trailers,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let stream = TrailersStream {
+            inner: self.0,
+            tx: Some(tx),
+        };
+        (stream, Trailers(rx))
     }
 }
 
-pin_project_lite::pin_project! {
-    pub(crate) struct BodyStream<T> {
-        #[pin] inner: T,
+/// A future which resolves to the trailers of a body, returned by
+/// [`Body::into_bytes_stream_with_trailers`].
+///
+/// It resolves to an error if the stream is dropped before it is fully read.
+pub struct Trailers(oneshot::Receiver<Option<HeaderMap>>);
+
+impl Future for Trailers {
+    type Output = Result<Option<HeaderMap>, ReadBodyError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map_err(|_| {
+            ReadBodyError::Io(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "the body is not fully read",
+            ))
+        })
     }
 }
 
-impl<T> BodyStream<T> {
-    #[inline]
-    pub(crate) fn new(inner: T) -> Self {
-        Self { inner }
+struct TrailersStream {
+    inner: BodyKind,
+    tx: Option<oneshot::Sender<Option<HeaderMap>>>,
+}
+
+impl Stream for TrailersStream {
+    type Item = Result<Bytes, IoError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.tx.is_none() {
+            return Poll::Ready(None);
+        }
+
+        let res = match futures_util::ready!(Pin::new(&mut self.inner).poll_data(cx)) {
+            Some(Ok(data)) => return Poll::Ready(Some(Ok(data))),
+            Some(Err(err)) => Err(err),
+            None => futures_util::ready!(Pin::new(&mut self.inner).poll_trailers(cx)),
+        };
+
+        // Dropping the sender after an error makes the `Trailers` fail.
+        let tx = self.tx.take();
+        match res {
+            Ok(trailers) => {
+                if let Some(tx) = tx {
+                    let _ = tx.send(trailers);
+                }
+                Poll::Ready(None)
+            }
+            Err(err) => Poll::Ready(Some(Err(err))),
+        }
     }
 }
 
-impl<T> Stream for BodyStream<T>
-where
-    T: HttpBody,
-    T::Error: Display,
-{
-    type Item = Result<T::Data, std::io::Error>;
+/// Converts the error of reading the body to [`ReadBodyError`], the error of
+/// a body returned by [`Body::limit`] is converted to
+/// [`ReadBodyError::PayloadTooLarge`].
+fn read_body_error(err: IoError) -> ReadBodyError {
+    if is_payload_too_large(&err) {
+        ReadBodyError::PayloadTooLarge
+    } else {
+        ReadBodyError::Io(err)
+    }
+}
+
+/// Returns `true` if the error is caused by [`ReadBodyError::PayloadTooLarge`].
+pub(crate) fn is_payload_too_large(err: &(dyn StdError + 'static)) -> bool {
+    let mut err = err;
+    loop {
+        if let Some(ReadBodyError::PayloadTooLarge) = err.downcast_ref::<ReadBodyError>() {
+            return true;
+        }
+        // The source of `std::io::Error` skips the wrapped error.
+        let source = match err.downcast_ref::<IoError>() {
+            Some(err) => err.get_ref().map(|err| err as &(dyn StdError + 'static)),
+            None => err.source(),
+        };
+        match source {
+            Some(source) => err = source,
+            None => return false,
+        }
+    }
+}
+
+impl HttpBody for Body {
+    type Data = Bytes;
+    type Error = IoError;
+
+    #[inline]
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.get_mut().0).poll_data(cx)
+    }
 
     #[inline]
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.project()
-            .inner
-            .poll_data(cx)
-            .map_err(|err| IoError::new(ErrorKind::Other, err.to_string()))
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().0).poll_trailers(cx)
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.0.is_end_stream()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> SizeHint {
+        HttpBody::size_hint(&self.0)
+    }
+}
+
+impl HttpBody for BodyKind {
+    type Data = Bytes;
+    type Error = IoError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        match self.get_mut() {
+            BodyKind::Hyper(body) => Pin::new(body).poll_data(cx).map_err(IoError::other),
+            BodyKind::Limited(body) => Pin::new(body).poll_data(cx),
+            BodyKind::Stream(body) => Pin::new(body.get_mut()).poll_data(cx),
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        match self.get_mut() {
+            BodyKind::Hyper(body) => Pin::new(body).poll_trailers(cx).map_err(IoError::other),
+            BodyKind::Limited(body) => Pin::new(body).poll_trailers(cx),
+            BodyKind::Stream(body) => Pin::new(body.get_mut()).poll_trailers(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            BodyKind::Hyper(body) => body.is_end_stream(),
+            BodyKind::Limited(body) => body.is_end_stream(),
+            BodyKind::Stream(body) => body.lock().is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            BodyKind::Hyper(body) => HttpBody::size_hint(body),
+            BodyKind::Limited(body) => body.size_hint(),
+            BodyKind::Stream(body) => body.lock().size_hint(),
+        }
+    }
+}
+
+/// A body returned by [`Body::limit`].
+pub(crate) struct LimitedBody {
+    inner: Box<BodyKind>,
+    remaining: usize,
+}
+
+impl HttpBody for LimitedBody {
+    type Data = Bytes;
+    type Error = IoError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        if HttpBody::size_hint(&*this.inner).lower() > this.remaining as u64 {
+            return Poll::Ready(Some(Err(IoError::other(ReadBodyError::PayloadTooLarge))));
+        }
+
+        match futures_util::ready!(Pin::new(&mut *this.inner).poll_data(cx)) {
+            Some(Ok(data)) if data.len() > this.remaining => {
+                Poll::Ready(Some(Err(IoError::other(ReadBodyError::PayloadTooLarge))))
+            }
+            Some(Ok(data)) => {
+                this.remaining -= data.len();
+                Poll::Ready(Some(Ok(data)))
+            }
+            Some(Err(err)) => Poll::Ready(Some(Err(err))),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        HttpBody::size_hint(&*self.inner)
+    }
+}
+
+type BoxDataStream = Pin<Box<dyn Stream<Item = Result<Bytes, IoError>> + Send>>;
+type BoxTrailersFuture = Pin<Box<dyn Future<Output = HeaderMap> + Send>>;
+
+/// A body returned by [`Body::from_stream_with_trailers`].
+pub(crate) struct StreamBody {
+    stream: Option<BoxDataStream>,
+    trailers: Option<BoxTrailersFuture>,
+}
+
+impl HttpBody for StreamBody {
+    type Data = Bytes;
+    type Error = IoError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let stream = match &mut this.stream {
+            Some(stream) => stream,
+            None => return Poll::Ready(None),
+        };
+        match futures_util::ready!(stream.as_mut().poll_next(cx)) {
+            Some(Ok(data)) => Poll::Ready(Some(Ok(data))),
+            Some(Err(err)) => {
+                // The body is aborted without the trailers.
+                this.stream = None;
+                this.trailers = None;
+                Poll::Ready(Some(Err(err)))
+            }
+            None => {
+                this.stream = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        if this.stream.is_some() {
+            return Poll::Ready(Ok(None));
+        }
+        match &mut this.trailers {
+            Some(trailers) => {
+                let trailers = futures_util::ready!(trailers.as_mut().poll(cx));
+                this.trailers = None;
+                Poll::Ready(Ok(Some(trailers)))
+            }
+            None => Poll::Ready(Ok(None)),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.stream.is_none() && self.trailers.is_none()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use futures_util::TryStreamExt;
+
     use super::*;
 
     #[tokio::test]
@@ -298,4 +669,160 @@ mod tests {
         let body = Body::from_json("abc").unwrap();
         assert_eq!(body.into_json::<String>().await.unwrap(), "abc");
     }
+
+    #[tokio::test]
+    async fn limited_http_body() {
+        let (mut sender, body) = hyper::Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from_static(b"abc")).await.unwrap();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-checksum", "abc".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+
+        let mut body = Body::from(body).limit(5);
+        assert_eq!(body.data().await.unwrap().unwrap(), "abc");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers.get("x-checksum").unwrap(), "abc");
+
+        let body = Body::from("abc").limit(5);
+        assert_eq!(HttpBody::size_hint(&body).exact(), Some(3));
+    }
+
+    #[test]
+    fn into_hyper_body_without_runtime() {
+        let body = hyper::Body::from(Body::from("abc").limit(5));
+        let data = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(hyper::body::to_bytes(body))
+            .unwrap();
+        assert_eq!(data, "abc");
+    }
+
+    #[tokio::test]
+    async fn trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", "abc".parse().unwrap());
+
+        let data = ["hello", " world"].into_iter().map(Ok::<_, IoError>);
+        let body = Body::from_stream_with_trailers(futures_util::stream::iter(data), {
+            let trailers = trailers.clone();
+            async move { trailers }
+        });
+        let (stream, rx) = body.into_bytes_stream_with_trailers();
+        let data = stream.map_ok(|data| data.to_vec()).try_concat().await;
+        assert_eq!(data.unwrap(), b"hello world");
+        assert_eq!(rx.await.unwrap(), Some(trailers));
+
+        let (stream, rx) = Body::from("abc").into_bytes_stream_with_trailers();
+        let data = stream.map_ok(|data| data.to_vec()).try_concat().await;
+        assert_eq!(data.unwrap(), b"abc");
+        assert_eq!(rx.await.unwrap(), None);
+
+        // The stream is dropped before it is fully read.
+        let (_, rx) = Body::from("abc").into_bytes_stream_with_trailers();
+        assert!(rx.await.is_err());
+
+        // The body is aborted if the stream returns an error.
+        let data = futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(b"hello")),
+            Err(IoError::other("error")),
+        ]);
+        let body = Body::from_stream_with_trailers(data, async { HeaderMap::new() });
+        let (stream, rx) = body.into_bytes_stream_with_trailers();
+        assert!(stream.try_collect::<Vec<_>>().await.is_err());
+        assert!(rx.await.is_err());
+    }
+
+    #[test]
+    fn trailers_lazy() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        // The body is created without a runtime, and the trailers future is
+        // only polled after the stream ends.
+        let polled = Arc::new(AtomicBool::new(false));
+        let data = ["hello"].into_iter().map(Ok::<_, IoError>);
+        let mut body = Body::from_stream_with_trailers(futures_util::stream::iter(data), {
+            let polled = polled.clone();
+            async move {
+                polled.store(true, Ordering::SeqCst);
+                HeaderMap::new()
+            }
+        });
+
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                assert_eq!(body.data().await.unwrap().unwrap(), "hello");
+                assert!(!polled.load(Ordering::SeqCst));
+                assert!(body.data().await.is_none());
+                assert_eq!(body.trailers().await.unwrap(), Some(HeaderMap::new()));
+                assert!(polled.load(Ordering::SeqCst));
+            });
+        assert!(body.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn trailers_endpoint() {
+        use crate::{
+            handler, http::StatusCode, middleware::SizeLimit, test::TestClient, web::BodyLimits,
+            EndpointExt,
+        };
+
+        #[handler(internal)]
+        async fn index(body: Body) -> Result<Body> {
+            let (stream, trailers) = body.into_bytes_stream_with_trailers();
+            let data = stream
+                .map_ok(|data| data.to_vec())
+                .try_concat()
+                .await
+                .map_err(ReadBodyError::Io)?;
+            let trailers = trailers.await?.unwrap_or_default();
+            Ok(Body::from_stream_with_trailers(
+                futures_util::stream::iter(vec![Ok::<_, IoError>(data)]),
+                async move { trailers },
+            ))
+        }
+
+        let body = || {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-checksum", "abc".parse().unwrap());
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            Body::from_stream_with_trailers(
+                futures_util::stream::iter(vec![Ok::<_, IoError>("hello")]),
+                async move { trailers },
+            )
+        };
+
+        let endpoints = vec![
+            index.boxed(),
+            // The body read through the size limit.
+            index.with(SizeLimit::new(5)).boxed(),
+            // The `Body` extractor is not limited by `BodyLimits`.
+            index.data(BodyLimits::new().bytes(1)).boxed(),
+        ];
+        for ep in endpoints {
+            let cli = TestClient::new(ep);
+            let resp = cli.post("/").body(body()).send().await;
+            resp.assert_status_is_ok();
+            resp.assert_text("hello").await;
+
+            let resp = cli.post("/").body(body()).send().await;
+            resp.assert_trailers([("x-checksum", "abc"), ("grpc-status", "0")])
+                .await;
+        }
+
+        let cli = TestClient::new(index.with(SizeLimit::new(4)));
+        cli.post("/")
+            .body(body())
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
@@ -0,0 +1,752 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use hyper::body::HttpBody;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::{
+    cache::{
+        cache_control::CacheControl, CacheHandle, CacheKey, CacheStore, CacheTags, CachedResponse,
+    },
+    error::ReadBodyError,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    Body, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+type KeyFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Middleware for caching responses.
+///
+/// Only `GET` requests without the `Authorization` header are cached, and a
+/// response is stored only if:
+///
+/// - its status code is cacheable by default (`200`, `203`, `204`, `300`,
+///   `301`, `308`, `404`, `405`, `410`, `414` or `501`).
+/// - it does not contain the `Set-Cookie` header.
+/// - its `Cache-Control` header does not contain `no-store`, `no-cache` or
+///   `private`.
+/// - its time-to-live is specified by `s-maxage` or `max-age` of the
+///   `Cache-Control` header, or by [`Cache::default_ttl`].
+///
+/// The `Vary` header of responses is honored, and the `stale-while-revalidate`
+/// directive allows a stale response to be returned while it is refreshed in
+/// the background. Concurrent requests for a missing entry are coalesced so
+/// that the inner endpoint is called only once.
+///
+/// Handlers can attach [`CacheTags`] and [`CacheKey`] to responses, and use
+/// the [`CacheHandle`] extractor to purge cached responses by tag or key.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     cache::{Cache, CacheHandle, CacheTags, MemoryCacheStore},
+///     get, handler,
+///     http::{header, StatusCode},
+///     post,
+///     test::TestClient,
+///     EndpointExt, Response, Result, Route,
+/// };
+///
+/// #[handler]
+/// fn get_users() -> Response {
+///     Response::builder()
+///         .header(header::CACHE_CONTROL, "max-age=60")
+///         .extension(CacheTags::new(["users"]))
+///         .body("users")
+/// }
+///
+/// #[handler]
+/// async fn create_user(cache: &CacheHandle) -> Result<()> {
+///     cache.purge_tag("users").await
+/// }
+///
+/// let app = Route::new()
+///     .at("/users", get(get_users).post(create_user))
+///     .with(Cache::new(MemoryCacheStore::new(1024)));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/users").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("users").await;
+///
+/// let resp = cli.get("/users").send().await;
+/// resp.assert_header_exist(header::AGE);
+///
+/// cli.post("/users").send().await.assert_status_is_ok();
+///
+/// let resp = cli.get("/users").send().await;
+/// resp.assert_header_is_not_exist(header::AGE);
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+pub struct Cache<T> {
+    store: Arc<T>,
+    default_ttl: Option<Duration>,
+    stale_while_revalidate: Duration,
+    max_body_size: usize,
+    key_fn: Option<KeyFn>,
+}
+
+impl<T: CacheStore + 'static> Cache<T> {
+    /// Create `Cache` middleware.
+    pub fn new(store: T) -> Self {
+        Self {
+            store: Arc::new(store),
+            default_ttl: None,
+            stale_while_revalidate: Duration::ZERO,
+            max_body_size: 1024 * 1024,
+            key_fn: None,
+        }
+    }
+
+    /// Sets the time-to-live of responses that do not specify `max-age` or
+    /// `s-maxage` in the `Cache-Control` header.
+    ///
+    /// Default is `None`, which means that such responses are not cached.
+    #[must_use]
+    pub fn default_ttl(self, ttl: impl Into<Option<Duration>>) -> Self {
+        Self {
+            default_ttl: ttl.into(),
+            ..self
+        }
+    }
+
+    /// Sets the time window during which a stale response can be returned
+    /// while it is revalidated in the background, if the response does not
+    /// specify `stale-while-revalidate` in the `Cache-Control` header.
+    ///
+    /// Default is zero.
+    #[must_use]
+    pub fn stale_while_revalidate(self, value: Duration) -> Self {
+        Self {
+            stale_while_revalidate: value,
+            ..self
+        }
+    }
+
+    /// Sets the maximum body size of responses that can be cached. Default
+    /// is `1MB`.
+    ///
+    /// A larger response is not buffered, it is passed through uncached.
+    #[must_use]
+    pub fn max_body_size(self, size: usize) -> Self {
+        Self {
+            max_body_size: size,
+            ..self
+        }
+    }
+
+    /// Sets a function to compute the cache key of a request, returning
+    /// `None` means that the request bypasses the cache.
+    ///
+    /// The default key consists of the `Host` header and the uri of the
+    /// request.
+    #[must_use]
+    pub fn key(self, f: impl Fn(&Request) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            key_fn: Some(Arc::new(f)),
+            ..self
+        }
+    }
+}
+
+impl<T, E> Middleware<E> for Cache<T>
+where
+    T: CacheStore + 'static,
+    E: Endpoint + 'static,
+{
+    type Output = CacheEndpoint<T, E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        CacheEndpoint {
+            inner: Arc::new(ep),
+            state: Arc::new(State {
+                store: self.store.clone(),
+                default_ttl: self.default_ttl,
+                stale_while_revalidate: self.stale_while_revalidate,
+                max_body_size: self.max_body_size,
+                inflight: Default::default(),
+            }),
+            key_fn: self.key_fn.clone(),
+        }
+    }
+}
+
+/// Endpoint for Cache middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+pub struct CacheEndpoint<T, E> {
+    inner: Arc<E>,
+    state: Arc<State<T>>,
+    key_fn: Option<KeyFn>,
+}
+
+struct State<T> {
+    store: Arc<T>,
+    default_ttl: Option<Duration>,
+    stale_while_revalidate: Duration,
+    max_body_size: usize,
+    inflight: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+/// Removes the key from the in-flight requests and wakes up the waiters when
+/// dropped.
+struct InflightGuard<T> {
+    state: Arc<State<T>>,
+    key: String,
+    notify: Arc<Notify>,
+}
+
+impl<T> Drop for InflightGuard<T> {
+    fn drop(&mut self) {
+        let mut inflight = self.state.inflight.lock();
+        if matches!(inflight.get(&self.key), Some(notify) if Arc::ptr_eq(notify, &self.notify)) {
+            inflight.remove(&self.key);
+        }
+        drop(inflight);
+        self.notify.notify_waiters();
+    }
+}
+
+enum Flight<T> {
+    Leader(InflightGuard<T>),
+    Follower(Arc<Notify>),
+}
+
+impl<T: CacheStore + 'static> State<T> {
+    fn begin(self: &Arc<Self>, key: &str) -> Flight<T> {
+        let mut inflight = self.inflight.lock();
+        match inflight.get(key) {
+            Some(notify) => Flight::Follower(notify.clone()),
+            None => {
+                let notify = Arc::new(Notify::new());
+                inflight.insert(key.to_string(), notify.clone());
+                Flight::Leader(InflightGuard {
+                    state: self.clone(),
+                    key: key.to_string(),
+                    notify,
+                })
+            }
+        }
+    }
+
+    fn is_inflight(&self, key: &str, notify: &Arc<Notify>) -> bool {
+        matches!(self.inflight.lock().get(key), Some(current) if Arc::ptr_eq(current, notify))
+    }
+
+    async fn lookup(&self, key: &str, headers: &HeaderMap) -> Result<Option<CachedResponse>> {
+        let (key, cached) = match self.store.get(key).await? {
+            Some(cached) => match cached.alias() {
+                Some(alias) => (alias.to_string(), self.store.get(alias).await?),
+                None => (key.to_string(), Some(cached)),
+            },
+            None => return Ok(None),
+        };
+        match cached {
+            Some(cached) if !cached.vary().is_empty() => {
+                self.store
+                    .get(&variant_key(&key, cached.vary(), headers))
+                    .await
+            }
+            cached => Ok(cached),
+        }
+    }
+
+    async fn fetch<E: Endpoint>(&self, ep: &E, key: &str, req: Request) -> Result<Response> {
+        let req_headers = req.headers().clone();
+        let resp = ep.call(req).await?.into_response();
+
+        if !is_cacheable_status(resp.status()) || resp.headers().contains_key(header::SET_COOKIE) {
+            return Ok(resp);
+        }
+
+        let cc = CacheControl::from_headers(resp.headers());
+        if cc.no_store || cc.no_cache || cc.private {
+            return Ok(resp);
+        }
+
+        let ttl = match cc.ttl().or(self.default_ttl) {
+            Some(ttl) if !ttl.is_zero() => ttl,
+            _ => return Ok(resp),
+        };
+        let stale_while_revalidate = cc
+            .stale_while_revalidate
+            .unwrap_or(self.stale_while_revalidate);
+
+        let vary = parse_vary(resp.headers());
+        if vary.iter().any(|name| name == "*") {
+            return Ok(resp);
+        }
+
+        let tags = resp
+            .extensions()
+            .get::<CacheTags>()
+            .map(|tags| tags.0.clone())
+            .unwrap_or_default();
+        let cache_key = resp.extensions().get::<CacheKey>().map(|key| key.0.clone());
+
+        let content_length = resp
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if matches!(content_length, Some(len) if len > self.max_body_size as u64) {
+            return Ok(resp);
+        }
+
+        let (parts, body) = resp.into_parts();
+        let data = match read_body(body, self.max_body_size).await? {
+            Ok(data) => data,
+            Err(body) => return Ok(Response::from_parts(parts, body)),
+        };
+
+        let cached = CachedResponse::new(
+            parts.status,
+            &parts.headers,
+            data,
+            ttl,
+            stale_while_revalidate,
+            tags,
+            vary,
+        );
+        let expires = cached.expires();
+
+        if let Some(cache_key) = cache_key.filter(|cache_key| cache_key != key) {
+            let alias = CachedResponse::alias_marker(
+                cache_key.clone(),
+                ttl,
+                stale_while_revalidate,
+                cached.tags().to_vec(),
+            );
+            self.store.set(key, &alias, expires).await?;
+            self.store_response(&cache_key, &cached, &req_headers)
+                .await?;
+        } else {
+            self.store_response(key, &cached, &req_headers).await?;
+        }
+
+        Ok(cached.to_response())
+    }
+
+    async fn store_response(
+        &self,
+        key: &str,
+        cached: &CachedResponse,
+        req_headers: &HeaderMap,
+    ) -> Result<()> {
+        let expires = cached.expires();
+        if cached.vary().is_empty() {
+            self.store.set(key, cached, expires).await?;
+        } else {
+            let marker = CachedResponse::vary_marker(
+                cached.ttl(),
+                cached.stale_while_revalidate(),
+                cached.tags().to_vec(),
+                cached.vary().to_vec(),
+            );
+            self.store
+                .set(
+                    &variant_key(key, cached.vary(), req_headers),
+                    cached,
+                    expires,
+                )
+                .await?;
+            self.store.set(key, &marker, expires).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T, E> Endpoint for CacheEndpoint<T, E>
+where
+    T: CacheStore + 'static,
+    E: Endpoint + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        req.extensions_mut()
+            .insert(CacheHandle::new(self.state.store.clone()));
+
+        let key = match &self.key_fn {
+            Some(key_fn) => key_fn(&req),
+            None => Some(default_key(&req)),
+        };
+        let key = match key {
+            Some(key)
+                if req.method() == Method::GET
+                    && !req.headers().contains_key(header::AUTHORIZATION) =>
+            {
+                key
+            }
+            _ => return self.inner.call(req).await.map(IntoResponse::into_response),
+        };
+
+        let req_cc = CacheControl::from_headers(req.headers());
+        if req_cc.no_store {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        if !req_cc.no_cache && req_cc.max_age != Some(Duration::ZERO) {
+            if let Some(cached) = self.state.lookup(&key, req.headers()).await? {
+                if cached.is_fresh() {
+                    return Ok(cached_response(&cached));
+                }
+
+                if cached.is_usable_stale() {
+                    if let Flight::Leader(guard) = self.state.begin(&key) {
+                        let inner = self.inner.clone();
+                        let state = self.state.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = state.fetch(&*inner, &key, req).await {
+                                tracing::error!(error = %err, "failed to revalidate the response");
+                            }
+                            drop(guard);
+                        });
+                    }
+                    return Ok(cached_response(&cached));
+                }
+            }
+        }
+
+        match self.state.begin(&key) {
+            Flight::Leader(_guard) => self.state.fetch(&*self.inner, &key, req).await,
+            Flight::Follower(notify) => {
+                let notified = notify.notified();
+                if self.state.is_inflight(&key, &notify) {
+                    notified.await;
+                }
+
+                if let Some(cached) = self.state.lookup(&key, req.headers()).await? {
+                    if cached.is_fresh() {
+                        return Ok(cached_response(&cached));
+                    }
+                }
+                self.inner.call(req).await.map(IntoResponse::into_response)
+            }
+        }
+    }
+}
+
+/// Reads the body if it is not larger than `limit`, otherwise returns a body
+/// with the same content without buffering the rest of it.
+async fn read_body(body: Body, limit: usize) -> Result<Result<Bytes, Body>> {
+    if HttpBody::size_hint(&body.0).lower() > limit as u64 {
+        return Ok(Err(body));
+    }
+
+    let mut stream = Box::pin(body.into_bytes_stream());
+    let mut data = BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(ReadBodyError::Io)?;
+        if data.len() + chunk.len() > limit {
+            let buffered = stream::iter([Ok(data.freeze()), Ok(chunk)]);
+            return Ok(Err(Body::from_bytes_stream(buffered.chain(stream))));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Ok(data.freeze()))
+}
+
+fn cached_response(cached: &CachedResponse) -> Response {
+    let mut resp = cached.to_response();
+    resp.headers_mut()
+        .insert(header::AGE, HeaderValue::from(cached.age().as_secs()));
+    resp
+}
+
+fn default_key(req: &Request) -> String {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    format!("{}{}", host, req.uri())
+}
+
+fn variant_key(key: &str, vary: &[String], headers: &HeaderMap) -> String {
+    let mut variant_key = key.to_string();
+    for name in vary {
+        variant_key.push('|');
+        variant_key.push_str(name);
+        variant_key.push('=');
+        for (idx, value) in headers.get_all(name.as_str()).iter().enumerate() {
+            if idx > 0 {
+                variant_key.push(',');
+            }
+            variant_key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+        }
+    }
+    variant_key
+}
+
+fn parse_vary(headers: &HeaderMap) -> Vec<String> {
+    let mut vary = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    vary.sort();
+    vary.dedup();
+    vary
+}
+
+fn is_cacheable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{cache::MemoryCacheStore, endpoint::make, handler, test::TestClient, EndpointExt};
+
+    fn counter_endpoint(
+        headers: &'static [(&'static str, &'static str)],
+    ) -> (Arc<AtomicUsize>, impl Endpoint<Output = Response>) {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let ep = make({
+            let counter = counter.clone();
+            move |req| {
+                let counter = counter.clone();
+                async move {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    let mut resp = Response::builder();
+                    for (name, value) in headers {
+                        resp = resp.header(*name, *value);
+                    }
+                    let lang = req
+                        .header("accept-language")
+                        .unwrap_or_default()
+                        .to_string();
+                    resp.body(format!("{}{}", lang, n))
+                }
+            }
+        });
+        (counter, ep)
+    }
+
+    #[tokio::test]
+    async fn cache_response() {
+        let (counter, ep) = counter_endpoint(&[("cache-control", "max-age=60")]);
+        let cli = TestClient::new(ep.with(Cache::new(MemoryCacheStore::default())));
+
+        let resp = cli.get("/").send().await;
+        resp.assert_header_is_not_exist(header::AGE);
+        resp.assert_text("1").await;
+
+        let resp = cli.get("/").send().await;
+        resp.assert_header(header::AGE, "0");
+        resp.assert_header(header::CACHE_CONTROL, "max-age=60");
+        resp.assert_text("1").await;
+
+        cli.get("/a").send().await.assert_text("2").await;
+        cli.post("/").send().await.assert_text("3").await;
+        cli.get("/")
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .send()
+            .await
+            .assert_text("4")
+            .await;
+        cli.get("/")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .send()
+            .await
+            .assert_text("5")
+            .await;
+        cli.get("/").send().await.assert_text("5").await;
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn not_cacheable() {
+        for headers in [
+            &[][..],
+            &[("cache-control", "max-age=60, no-store")][..],
+            &[("cache-control", "max-age=60, private")][..],
+            &[("cache-control", "max-age=60"), ("set-cookie", "a=1")][..],
+            &[("cache-control", "max-age=60"), ("vary", "*")][..],
+        ] {
+            let (counter, ep) = counter_endpoint(headers);
+            let cli = TestClient::new(ep.with(Cache::new(MemoryCacheStore::default())));
+            cli.get("/").send().await.assert_text("1").await;
+            cli.get("/").send().await.assert_text("2").await;
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn vary() {
+        let (counter, ep) =
+            counter_endpoint(&[("cache-control", "max-age=60"), ("vary", "Accept-Language")]);
+        let cli = TestClient::new(ep.with(Cache::new(MemoryCacheStore::default())));
+
+        for (lang, expected) in [("en", "en1"), ("fr", "fr2"), ("en", "en1"), ("fr", "fr2")] {
+            cli.get("/")
+                .header("accept-language", lang)
+                .send()
+                .await
+                .assert_text(expected)
+                .await;
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn purge_tag() {
+        #[handler(internal)]
+        async fn purge(cache: &CacheHandle) -> Result<()> {
+            cache.purge_tag("a").await
+        }
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let ep = crate::Route::new()
+            .at(
+                "/",
+                make({
+                    let counter = counter.clone();
+                    move |_| {
+                        let counter = counter.clone();
+                        async move {
+                            Response::builder()
+                                .header(header::CACHE_CONTROL, "max-age=60")
+                                .extension(CacheTags::new(["a"]))
+                                .body(counter.fetch_add(1, Ordering::SeqCst).to_string())
+                        }
+                    }
+                }),
+            )
+            .at("/purge", purge)
+            .with(Cache::new(MemoryCacheStore::default()));
+        let cli = TestClient::new(ep);
+
+        cli.get("/").send().await.assert_text("0").await;
+        cli.get("/").send().await.assert_text("0").await;
+        cli.post("/purge").send().await.assert_status_is_ok();
+        cli.get("/").send().await.assert_text("1").await;
+    }
+
+    #[tokio::test]
+    async fn single_flight() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let ep = make({
+            let counter = counter.clone();
+            move |_| {
+                let counter = counter.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Response::builder()
+                        .header(header::CACHE_CONTROL, "max-age=60")
+                        .body(counter.fetch_add(1, Ordering::SeqCst).to_string())
+                }
+            }
+        })
+        .with(Cache::new(MemoryCacheStore::default()));
+        let cli = TestClient::new(ep);
+
+        let resps = futures_util::future::join_all((0..5).map(|_| cli.get("/").send())).await;
+        for resp in resps {
+            resp.assert_text("0").await;
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stale_while_revalidate() {
+        let (counter, ep) = counter_endpoint(&[]);
+        let cli = TestClient::new(
+            ep.with(
+                Cache::new(MemoryCacheStore::default())
+                    .default_ttl(Duration::from_secs(1))
+                    .stale_while_revalidate(Duration::from_secs(60)),
+            ),
+        );
+
+        cli.get("/").send().await.assert_text("1").await;
+        tokio::time::advance(Duration::from_secs(2)).await;
+
+        cli.get("/").send().await.assert_text("1").await;
+        tokio::task::yield_now().await;
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        cli.get("/").send().await.assert_text("2").await;
+    }
+
+    #[tokio::test]
+    async fn cache_key() {
+        #[handler(internal)]
+        async fn purge(cache: &CacheHandle) -> Result<()> {
+            cache.purge_key("user:1").await
+        }
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let ep = crate::Route::new()
+            .at(
+                "/users/1",
+                make({
+                    let counter = counter.clone();
+                    move |_| {
+                        let counter = counter.clone();
+                        async move {
+                            Response::builder()
+                                .header(header::CACHE_CONTROL, "max-age=60")
+                                .extension(CacheKey::new("user:1"))
+                                .body(counter.fetch_add(1, Ordering::SeqCst).to_string())
+                        }
+                    }
+                }),
+            )
+            .at("/purge", purge)
+            .with(Cache::new(MemoryCacheStore::default()));
+        let cli = TestClient::new(ep);
+
+        cli.get("/users/1").send().await.assert_text("0").await;
+        cli.get("/users/1").send().await.assert_text("0").await;
+        cli.get("/users/1?a=1").send().await.assert_text("1").await;
+        cli.post("/purge").send().await.assert_status_is_ok();
+        cli.get("/users/1").send().await.assert_text("2").await;
+        cli.get("/users/1?a=1").send().await.assert_text("2").await;
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn max_body_size() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let ep = make({
+            let counter = counter.clone();
+            move |req| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let data = match req.uri().path() {
+                        "/stream" => Body::from_bytes_stream(stream::iter(
+                            ["123", "456", "789"].map(Ok::<_, std::io::Error>),
+                        )),
+                        _ => Body::from("123456789"),
+                    };
+                    Response::builder()
+                        .header(header::CACHE_CONTROL, "max-age=60")
+                        .body(data)
+                }
+            }
+        })
+        .with(Cache::new(MemoryCacheStore::default()).max_body_size(5));
+        let cli = TestClient::new(ep);
+
+        for path in ["/", "/", "/stream", "/stream"] {
+            cli.get(path).send().await.assert_text("123456789").await;
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use crate::http::{header, HeaderMap};
+
+/// The directives of the `Cache-Control` header that are used by the cache.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct CacheControl {
+    pub(crate) no_store: bool,
+    pub(crate) no_cache: bool,
+    pub(crate) private: bool,
+    pub(crate) public: bool,
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) s_maxage: Option<Duration>,
+    pub(crate) stale_while_revalidate: Option<Duration>,
+}
+
+impl CacheControl {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cc = CacheControl::default();
+
+        for value in headers.get_all(header::CACHE_CONTROL) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            for directive in value.split(',') {
+                let (name, value) = match directive.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                    None => (directive.trim(), None),
+                };
+                let seconds = || {
+                    value
+                        .and_then(|value| value.parse::<u64>().ok())
+                        .map(Duration::from_secs)
+                };
+
+                match name.to_ascii_lowercase().as_str() {
+                    "no-store" => cc.no_store = true,
+                    "no-cache" => cc.no_cache = true,
+                    "private" => cc.private = true,
+                    "public" => cc.public = true,
+                    "max-age" => cc.max_age = seconds(),
+                    "s-maxage" => cc.s_maxage = seconds(),
+                    "stale-while-revalidate" => cc.stale_while_revalidate = seconds(),
+                    _ => {}
+                }
+            }
+        }
+
+        cc
+    }
+
+    /// Returns the time-to-live for shared caches.
+    pub(crate) fn ttl(&self) -> Option<Duration> {
+        self.s_maxage.or(self.max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HeaderValue;
+
+    #[test]
+    fn parse() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        headers.append(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("s-maxage=\"120\", stale-while-revalidate=30"),
+        );
+
+        let cc = CacheControl::from_headers(&headers);
+        assert_eq!(
+            cc,
+            CacheControl {
+                public: true,
+                max_age: Some(Duration::from_secs(60)),
+                s_maxage: Some(Duration::from_secs(120)),
+                stale_while_revalidate: Some(Duration::from_secs(30)),
+                ..Default::default()
+            }
+        );
+        assert_eq!(cc.ttl(), Some(Duration::from_secs(120)));
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("No-Store, private"),
+        );
+        let cc = CacheControl::from_headers(&headers);
+        assert!(cc.no_store);
+        assert!(cc.private);
+        assert_eq!(cc.ttl(), None);
+    }
+}
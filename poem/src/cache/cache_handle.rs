@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use crate::{cache::CacheStore, FromRequest, Request, RequestBody, Result};
+
+/// Tags attached to a response, which can be used to purge the cached
+/// responses with [`CacheHandle::purge_tag`].
+///
+/// Insert it into the extensions of the response.
+///
+/// # Example
+///
+/// ```
+/// use poem::{cache::CacheTags, handler, Response};
+///
+/// #[handler]
+/// fn get_user() -> Response {
+///     Response::builder()
+///         .extension(CacheTags::new(["users", "user:1"]))
+///         .body("user 1")
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CacheTags(pub Vec<String>);
+
+impl CacheTags {
+    /// Create a `CacheTags` from tags.
+    pub fn new<I, T>(tags: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self(tags.into_iter().map(Into::into).collect())
+    }
+}
+
+/// The key used to store a response, which can be used to purge the cached
+/// response with [`CacheHandle::purge_key`].
+///
+/// Insert it into the extensions of the response. The response is stored
+/// with this key instead of the key computed from the request, and the key of
+/// the request refers to it, so that the handlers can use a key independent of
+/// the uri, such as the id of a resource.
+///
+/// # Example
+///
+/// ```
+/// use poem::{cache::CacheKey, handler, web::Path, Response};
+///
+/// #[handler]
+/// fn get_user(Path(id): Path<u64>) -> Response {
+///     Response::builder()
+///         .extension(CacheKey::new(format!("user:{}", id)))
+///         .body(format!("user {}", id))
+/// }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CacheKey(pub String);
+
+impl CacheKey {
+    /// Create a `CacheKey`.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+}
+
+/// A handle to the cache store used by the [`Cache`](super::Cache)
+/// middleware.
+#[derive(Clone)]
+pub struct CacheHandle {
+    store: Arc<dyn CacheStore>,
+}
+
+impl CacheHandle {
+    pub(crate) fn new(store: Arc<dyn CacheStore>) -> Self {
+        Self { store }
+    }
+
+    /// Remove the cached response with the specified key.
+    pub async fn purge_key(&self, key: &str) -> Result<()> {
+        self.store.remove(key).await
+    }
+
+    /// Remove all cached responses with the specified tag.
+    pub async fn purge_tag(&self, tag: &str) -> Result<()> {
+        self.store.purge_tag(tag).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for &'a CacheHandle {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<CacheHandle>()
+            .expect("To use the `CacheHandle` extractor, the `Cache` middleware is required."))
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    http::{header::HeaderName, HeaderMap, HeaderValue, StatusCode},
+    Response, Result,
+};
+
+/// A response stored in a [`CacheStore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    #[serde(with = "serde_body")]
+    body: Bytes,
+    created_at: u64,
+    ttl: u64,
+    stale_while_revalidate: u64,
+    tags: Vec<String>,
+    vary: Vec<String>,
+    #[serde(default)]
+    alias: Option<String>,
+}
+
+impl CachedResponse {
+    pub(crate) fn new(
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Bytes,
+        ttl: Duration,
+        stale_while_revalidate: Duration,
+        tags: Vec<String>,
+        vary: Vec<String>,
+    ) -> Self {
+        Self {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                .collect(),
+            body,
+            created_at: now_millis(),
+            ttl: ttl.as_millis() as u64,
+            stale_while_revalidate: stale_while_revalidate.as_millis() as u64,
+            tags,
+            vary,
+            alias: None,
+        }
+    }
+
+    /// Creates an entry that only records the `Vary` headers of a resource.
+    pub(crate) fn vary_marker(
+        ttl: Duration,
+        stale_while_revalidate: Duration,
+        tags: Vec<String>,
+        vary: Vec<String>,
+    ) -> Self {
+        Self::new(
+            StatusCode::OK,
+            &HeaderMap::new(),
+            Bytes::new(),
+            ttl,
+            stale_while_revalidate,
+            tags,
+            vary,
+        )
+    }
+
+    /// Creates an entry that refers to the response stored with the key
+    /// specified by [`CacheKey`](super::CacheKey).
+    pub(crate) fn alias_marker(
+        alias: String,
+        ttl: Duration,
+        stale_while_revalidate: Duration,
+        tags: Vec<String>,
+    ) -> Self {
+        Self {
+            alias: Some(alias),
+            ..Self::vary_marker(ttl, stale_while_revalidate, tags, Vec::new())
+        }
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK)
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the tags of the response.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Returns the request headers that the response varies on.
+    pub fn vary(&self) -> &[String] {
+        &self.vary
+    }
+
+    /// Returns the key of the response that this entry refers to.
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_deref()
+    }
+
+    /// Returns the age of the response.
+    pub fn age(&self) -> Duration {
+        Duration::from_millis(now_millis().saturating_sub(self.created_at))
+    }
+
+    /// Returns the time-to-live of the response.
+    pub fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl)
+    }
+
+    /// Returns the time window after the response becomes stale, during which
+    /// it can still be served while being revalidated in the background.
+    pub fn stale_while_revalidate(&self) -> Duration {
+        Duration::from_millis(self.stale_while_revalidate)
+    }
+
+    /// Returns the time for which the entry must be kept in the store.
+    pub fn expires(&self) -> Duration {
+        self.ttl() + self.stale_while_revalidate()
+    }
+
+    /// Returns `true` if the response is still fresh.
+    pub fn is_fresh(&self) -> bool {
+        self.age() < self.ttl()
+    }
+
+    /// Returns `true` if the response is stale, but can still be served while
+    /// being revalidated.
+    pub fn is_usable_stale(&self) -> bool {
+        self.age() < self.expires()
+    }
+
+    pub(crate) fn to_response(&self) -> Response {
+        let mut resp = Response::builder()
+            .status(self.status())
+            .body(self.body.clone());
+        let headers = resp.headers_mut();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_bytes(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        resp
+    }
+}
+
+fn now_millis() -> u64 {
+    crate::time::unix_time().as_millis() as u64
+}
+
+/// Serializes the body as a base64 string for the human-readable formats such
+/// as JSON, and as raw bytes for the binary formats.
+mod serde_body {
+    use bytes::Bytes;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::encode(body))
+        } else {
+            serializer.serialize_bytes(body)
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Bytes, D::Error> {
+        if deserializer.is_human_readable() {
+            let data = String::deserialize(deserializer)?;
+            base64::decode(data)
+                .map(Bytes::from)
+                .map_err(D::Error::custom)
+        } else {
+            serde_bytes::ByteBuf::deserialize(deserializer).map(|data| Bytes::from(data.into_vec()))
+        }
+    }
+}
+
+/// Represents a back-end cache storage.
+#[async_trait::async_trait]
+pub trait CacheStore: Send + Sync {
+    /// Load a cached response.
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>>;
+
+    /// Insert or update a cached response.
+    ///
+    /// The entry should be removed after `expires`.
+    async fn set(&self, key: &str, response: &CachedResponse, expires: Duration) -> Result<()>;
+
+    /// Remove a cached response by key.
+    async fn remove(&self, key: &str) -> Result<()>;
+
+    /// Remove all cached responses with the specified tag.
+    async fn purge_tag(&self, tag: &str) -> Result<()>;
+}
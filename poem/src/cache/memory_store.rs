@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    cache::{CacheStore, CachedResponse},
+    Result,
+};
+
+struct Entry {
+    response: CachedResponse,
+    expires_at: Instant,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct InnerStore {
+    entries: HashMap<String, Entry>,
+    lru: BTreeMap<u64, String>,
+    tags: HashMap<String, HashSet<String>>,
+    tick: u64,
+}
+
+impl InnerStore {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+            for tag in entry.response.tags() {
+                if let Some(keys) = self.tags.get_mut(tag) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.tags.remove(tag);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A cache store using memory, the least recently used entries are evicted
+/// when the capacity is reached.
+pub struct MemoryCacheStore {
+    capacity: usize,
+    inner: Mutex<InnerStore>,
+}
+
+impl Default for MemoryCacheStore {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl MemoryCacheStore {
+    /// Create a `MemoryCacheStore` with the maximum number of entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Default::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheStore for MemoryCacheStore {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut inner = self.inner.lock();
+        let tick = inner.next_tick();
+
+        match inner.entries.get_mut(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                let old_tick = std::mem::replace(&mut entry.tick, tick);
+                let response = entry.response.clone();
+                inner.lru.remove(&old_tick);
+                inner.lru.insert(tick, key.to_string());
+                Ok(Some(response))
+            }
+            Some(_) => {
+                inner.remove(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, response: &CachedResponse, expires: Duration) -> Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock();
+        inner.remove(key);
+
+        while inner.entries.len() >= self.capacity {
+            let oldest_key = match inner.lru.iter().next() {
+                Some((_, key)) => key.clone(),
+                None => break,
+            };
+            inner.remove(&oldest_key);
+        }
+
+        let tick = inner.next_tick();
+        for tag in response.tags() {
+            inner
+                .tags
+                .entry(tag.clone())
+                .or_default()
+                .insert(key.to_string());
+        }
+        inner.lru.insert(tick, key.to_string());
+        inner.entries.insert(
+            key.to_string(),
+            Entry {
+                response: response.clone(),
+                expires_at: Instant::now() + expires,
+                tick,
+            },
+        );
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        self.inner.lock().remove(key);
+        Ok(())
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if let Some(keys) = inner.tags.remove(tag) {
+            for key in keys {
+                inner.remove(&key);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::http::{HeaderMap, StatusCode};
+
+    fn response(tags: &[&str]) -> CachedResponse {
+        CachedResponse::new(
+            StatusCode::OK,
+            &HeaderMap::new(),
+            Bytes::from_static(b"hello"),
+            Duration::from_secs(60),
+            Duration::ZERO,
+            tags.iter().map(ToString::to_string).collect(),
+            Vec::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn lru() {
+        let store = MemoryCacheStore::new(2);
+        let expires = Duration::from_secs(60);
+
+        store.set("a", &response(&[]), expires).await.unwrap();
+        store.set("b", &response(&[]), expires).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_some());
+
+        store.set("c", &response(&[]), expires).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_some());
+        assert!(store.get("b").await.unwrap().is_none());
+        assert!(store.get("c").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn expires() {
+        let store = MemoryCacheStore::new(2);
+        store
+            .set("a", &response(&[]), Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(store.get("a").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(store.get("a").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn purge_tag() {
+        let store = MemoryCacheStore::new(10);
+        let expires = Duration::from_secs(60);
+
+        store.set("a", &response(&["x"]), expires).await.unwrap();
+        store
+            .set("b", &response(&["x", "y"]), expires)
+            .await
+            .unwrap();
+        store.set("c", &response(&["y"]), expires).await.unwrap();
+
+        store.purge_tag("x").await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        assert!(store.get("b").await.unwrap().is_none());
+        assert!(store.get("c").await.unwrap().is_some());
+
+        store.remove("c").await.unwrap();
+        assert!(store.get("c").await.unwrap().is_none());
+    }
+}
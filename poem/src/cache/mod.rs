@@ -0,0 +1,17 @@
+//! HTTP response caching.
+
+#[allow(clippy::module_inception)]
+mod cache;
+mod cache_control;
+mod cache_handle;
+mod cache_store;
+mod memory_store;
+#[cfg(feature = "redis-cache")]
+mod redis_store;
+
+pub use cache::{Cache, CacheEndpoint};
+pub use cache_handle::{CacheHandle, CacheKey, CacheTags};
+pub use cache_store::{CacheStore, CachedResponse};
+pub use memory_store::MemoryCacheStore;
+#[cfg(feature = "redis-cache")]
+pub use redis_store::RedisCacheStore;
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionLike, AsyncCommands, Cmd, Script};
+
+use crate::{
+    cache::{CacheStore, CachedResponse},
+    error::InternalServerError,
+    Result,
+};
+
+/// A cache store using redis.
+///
+/// Every tag is stored as a redis set containing the keys of the responses
+/// with that tag, which expires no earlier than the responses in it.
+///
+/// # Errors
+///
+/// - [`redis::RedisError`]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-cache")))]
+pub struct RedisCacheStore<T> {
+    connection: T,
+    prefix: String,
+}
+
+impl<T> RedisCacheStore<T> {
+    /// Create a `RedisCacheStore`.
+    pub fn new(connection: T) -> Self {
+        Self {
+            connection,
+            prefix: "poem-cache:".to_string(),
+        }
+    }
+
+    /// Sets the prefix of the keys in redis. Default is `poem-cache:`.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    fn entry_key(&self, key: &str) -> String {
+        format!("{}entry:{}", self.prefix, key)
+    }
+
+    fn tag_key(&self, tag: &str) -> String {
+        format!("{}tag:{}", self.prefix, tag)
+    }
+}
+
+/// Stores the entry, adds its key to the tag sets and extends the expiration
+/// of the tag sets to at least the expiration of the entry.
+///
+/// `KEYS[1]` is the entry key, the others are the tag keys. `ARGV` is the
+/// value, the expiration in milliseconds and the key.
+const SET_SCRIPT: &str = r"
+redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+local expires = tonumber(ARGV[2])
+for i = 2, #KEYS do
+    redis.call('SADD', KEYS[i], ARGV[3])
+    if redis.call('PTTL', KEYS[i]) < expires then
+        redis.call('PEXPIRE', KEYS[i], expires)
+    end
+end
+";
+
+fn decode(key: &str, data: &str) -> Option<CachedResponse> {
+    match serde_json::from_str(data) {
+        Ok(response) => Some(response),
+        Err(err) => {
+            tracing::warn!(key = key, error = %err, "corrupted cache entry");
+            None
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: ConnectionLike + Clone + Sync + Send> CacheStore for RedisCacheStore<T> {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let data: Option<String> = self
+            .connection
+            .clone()
+            .get(self.entry_key(key))
+            .await
+            .map_err(InternalServerError)?;
+        match data {
+            Some(data) => match decode(key, &data) {
+                Some(response) => Ok(Some(response)),
+                None => {
+                    // The corrupted entry is removed, so that it can be replaced.
+                    self.remove(key).await?;
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, response: &CachedResponse, expires: Duration) -> Result<()> {
+        let value = serde_json::to_string(response).map_err(InternalServerError)?;
+        let expires = expires.as_millis().max(1) as u64;
+        let script = Script::new(SET_SCRIPT);
+        let mut invocation = script.key(self.entry_key(key));
+        for tag in response.tags() {
+            invocation.key(self.tag_key(tag));
+        }
+        invocation
+            .arg(value)
+            .arg(expires)
+            .arg(key)
+            .invoke_async::<_, ()>(&mut self.connection.clone())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<()> {
+        let entry_key = self.entry_key(key);
+        let data: Option<String> = self
+            .connection
+            .clone()
+            .get(&entry_key)
+            .await
+            .map_err(InternalServerError)?;
+        let mut pipe = redis::pipe();
+        pipe.add_command(Cmd::del(entry_key)).ignore();
+        for tag in data
+            .and_then(|data| decode(key, &data))
+            .iter()
+            .flat_map(|response| response.tags())
+        {
+            pipe.add_command(Cmd::srem(self.tag_key(tag), key)).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.connection.clone())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<()> {
+        let tag_key = self.tag_key(tag);
+        let keys: Vec<String> = self
+            .connection
+            .clone()
+            .smembers(&tag_key)
+            .await
+            .map_err(InternalServerError)?;
+        let mut pipe = redis::pipe();
+        pipe.add_command(Cmd::del(&tag_key)).ignore();
+        if !keys.is_empty() {
+            let entry_keys = keys
+                .iter()
+                .map(|key| self.entry_key(key))
+                .collect::<Vec<_>>();
+            let data: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(&entry_keys)
+                .query_async(&mut self.connection.clone())
+                .await
+                .map_err(InternalServerError)?;
+            pipe.add_command(Cmd::del(entry_keys)).ignore();
+
+            // Remove the purged keys from the sets of the other tags.
+            for (key, data) in keys.iter().zip(data) {
+                for other_tag in data
+                    .and_then(|data| decode(key, &data))
+                    .iter()
+                    .flat_map(|response| response.tags())
+                    .filter(|other_tag| *other_tag != tag)
+                {
+                    pipe.add_command(Cmd::srem(self.tag_key(other_tag), key))
+                        .ignore();
+                }
+            }
+        }
+        pipe.query_async::<_, ()>(&mut self.connection.clone())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use redis::{aio::ConnectionManager, Client, ConnectionLike};
+
+    use super::*;
+    use crate::http::{HeaderMap, StatusCode};
+
+    #[tokio::test]
+    async fn redis_cache() {
+        let mut client = match Client::open("redis://127.0.0.1/") {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        if !client.check_connection() {
+            return;
+        }
+
+        let connection = ConnectionManager::new(client).await.unwrap();
+        let store = RedisCacheStore::new(connection.clone()).prefix("poem-cache-test:");
+        let response = CachedResponse::new(
+            StatusCode::OK,
+            &HeaderMap::new(),
+            Bytes::from_static(b"hello"),
+            Duration::from_secs(60),
+            Duration::ZERO,
+            vec!["a".to_string(), "b".to_string()],
+            Vec::new(),
+        );
+
+        store
+            .set("key", &response, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.get("key").await.unwrap().unwrap().body(), b"hello");
+        let ttl: i64 = connection
+            .clone()
+            .pttl("poem-cache-test:tag:b")
+            .await
+            .unwrap();
+        assert!(ttl > 0);
+
+        store.purge_tag("a").await.unwrap();
+        assert!(store.get("key").await.unwrap().is_none());
+        let members: Vec<String> = connection
+            .clone()
+            .smembers("poem-cache-test:tag:b")
+            .await
+            .unwrap();
+        assert!(members.is_empty());
+
+        let _: () = connection
+            .clone()
+            .set("poem-cache-test:entry:corrupted", "abc")
+            .await
+            .unwrap();
+        assert!(store.get("corrupted").await.unwrap().is_none());
+        let exists: bool = connection
+            .clone()
+            .exists("poem-cache-test:entry:corrupted")
+            .await
+            .unwrap();
+        assert!(!exists);
+    }
+}
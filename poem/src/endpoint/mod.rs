@@ -19,6 +19,8 @@ mod static_files;
 mod to_response;
 #[cfg(feature = "tower-compat")]
 mod tower_compat;
+#[cfg(feature = "tus")]
+pub(crate) mod tus;
 
 pub use after::After;
 pub use and_then::AndThen;
@@ -38,3 +40,5 @@ pub use static_files::{StaticFileEndpoint, StaticFilesEndpoint};
 pub use to_response::ToResponse;
 #[cfg(feature = "tower-compat")]
 pub use tower_compat::TowerCompatExt;
+#[cfg(feature = "tus")]
+pub use tus::{LocalTusStorage, TusEndpoint, TusStorage, TusUpload};
@@ -0,0 +1,150 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    path::PathBuf,
+};
+
+use bytes::Bytes;
+use futures_util::stream::{BoxStream, StreamExt};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use super::{TusStorage, TusUpload};
+
+/// A [`TusStorage`] which stores the uploads in a local directory.
+///
+/// The data of an upload is stored in the file named with the id of the
+/// upload, see [`LocalTusStorage::data_path`], and its state is stored in the
+/// `{id}.info` file. The offset of the upload is the size of the data file, so
+/// the bytes written before a broken connection are never lost.
+#[derive(Debug, Clone)]
+pub struct LocalTusStorage {
+    dir: PathBuf,
+}
+
+impl LocalTusStorage {
+    /// Create a `LocalTusStorage` which stores the uploads in the directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the path of the data file of the upload with the id, or `None`
+    /// if the id is invalid.
+    pub fn data_path(&self, id: &str) -> Option<PathBuf> {
+        let is_valid = !id.is_empty()
+            && id
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_');
+        if is_valid {
+            Some(self.dir.join(id))
+        } else {
+            None
+        }
+    }
+
+    fn paths(&self, id: &str) -> IoResult<(PathBuf, PathBuf)> {
+        let data_path = self.data_path(id).ok_or_else(|| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid upload id `{}`", id),
+            )
+        })?;
+        let info_path = data_path.with_extension("info");
+        Ok((data_path, info_path))
+    }
+}
+
+#[async_trait::async_trait]
+impl TusStorage for LocalTusStorage {
+    async fn create(&self, upload: &TusUpload) -> IoResult<()> {
+        let (data_path, info_path) = self.paths(&upload.id)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::File::create(&data_path).await?;
+        tokio::fs::write(&info_path, serde_json::to_vec(upload)?).await
+    }
+
+    async fn get(&self, id: &str) -> IoResult<Option<TusUpload>> {
+        let (data_path, info_path) = match self.paths(id) {
+            Ok(paths) => paths,
+            Err(_) => return Ok(None),
+        };
+        let info = match tokio::fs::read(&info_path).await {
+            Ok(info) => info,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let offset = match tokio::fs::metadata(&data_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let upload: TusUpload = serde_json::from_slice(&info)?;
+        Ok(Some(TusUpload { offset, ..upload }))
+    }
+
+    async fn append(
+        &self,
+        id: &str,
+        offset: u64,
+        mut data: BoxStream<'static, IoResult<Bytes>>,
+    ) -> IoResult<u64> {
+        let (data_path, _) = self.paths(id)?;
+        let mut file = OpenOptions::new().append(true).open(&data_path).await?;
+        if file.metadata().await?.len() != offset {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "the offset does not match the size of the upload",
+            ));
+        }
+
+        let mut offset = offset;
+        let res = loop {
+            match data.next().await {
+                Some(Ok(chunk)) => {
+                    if let Err(err) = file.write_all(&chunk).await {
+                        break Err(err);
+                    }
+                    offset += chunk.len() as u64;
+                }
+                Some(Err(err)) => break Err(err),
+                None => break Ok(offset),
+            }
+        };
+
+        // Keep the bytes written before an error.
+        file.flush().await?;
+        file.sync_data().await?;
+        res
+    }
+
+    async fn delete(&self, id: &str) -> IoResult<()> {
+        let (data_path, info_path) = self.paths(id)?;
+        for path in [info_path, data_path] {
+            match tokio::fs::remove_file(path).await {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> IoResult<Vec<TusUpload>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut uploads = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("info") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                if let Some(upload) = self.get(id).await? {
+                    uploads.push(upload);
+                }
+            }
+        }
+        Ok(uploads)
+    }
+}
@@ -0,0 +1,817 @@
+mod local;
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    future::Future,
+    io::Result as IoResult,
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use futures_util::{
+    future::BoxFuture,
+    stream::{BoxStream, StreamExt},
+};
+pub use local::LocalTusStorage;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::is_payload_too_large,
+    error::TusError,
+    http::{header, HeaderValue, Method, StatusCode},
+    Endpoint, IntoResponse, Request, Response, Result,
+};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_RESUMABLE: &str = "tus-resumable";
+const TUS_EXTENSION: &str = "tus-extension";
+const TUS_MAX_SIZE: &str = "tus-max-size";
+const UPLOAD_LENGTH: &str = "upload-length";
+const UPLOAD_OFFSET: &str = "upload-offset";
+const UPLOAD_METADATA: &str = "upload-metadata";
+const UPLOAD_EXPIRES: &str = "upload-expires";
+const METHOD_OVERRIDE: &str = "x-http-method-override";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// The headers sent by the tus clients, which must be allowed by
+/// [`Cors`](crate::middleware::Cors) for the browser clients.
+pub(crate) const TUS_REQUEST_HEADERS: &[&str] = &[
+    TUS_RESUMABLE,
+    UPLOAD_LENGTH,
+    UPLOAD_OFFSET,
+    UPLOAD_METADATA,
+    METHOD_OVERRIDE,
+    "upload-defer-length",
+    "upload-concat",
+    "content-type",
+];
+
+/// The headers of the tus responses, which must be exposed by
+/// [`Cors`](crate::middleware::Cors) for the browser clients.
+pub(crate) const TUS_RESPONSE_HEADERS: &[&str] = &[
+    TUS_RESUMABLE,
+    "tus-version",
+    TUS_EXTENSION,
+    TUS_MAX_SIZE,
+    UPLOAD_LENGTH,
+    UPLOAD_OFFSET,
+    UPLOAD_METADATA,
+    UPLOAD_EXPIRES,
+    "location",
+];
+
+/// The state of an upload of [`TusEndpoint`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TusUpload {
+    /// The id of the upload.
+    pub id: String,
+    /// The total size of the upload in bytes.
+    pub length: u64,
+    /// The number of bytes received.
+    pub offset: u64,
+    /// The raw value of the `Upload-Metadata` header, see
+    /// [`TusUpload::decode_metadata`].
+    pub metadata: Option<String>,
+    /// The time after which the incomplete upload expires.
+    pub expires: Option<SystemTime>,
+}
+
+impl TusUpload {
+    /// Returns `true` if all the bytes of the upload have been received.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.offset >= self.length
+    }
+
+    /// Returns `true` if the upload is incomplete and has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        !self.is_complete() && matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    /// Returns the decoded key-value pairs of the `Upload-Metadata` header.
+    ///
+    /// The value of a key without a value is empty.
+    pub fn decode_metadata(&self) -> HashMap<String, Vec<u8>> {
+        self.metadata
+            .as_deref()
+            .and_then(parse_metadata)
+            .unwrap_or_default()
+    }
+}
+
+fn parse_metadata(value: &str) -> Option<HashMap<String, Vec<u8>>> {
+    let mut metadata = HashMap::new();
+    for pair in value.split(',') {
+        let mut parts = pair.trim().split(' ');
+        let key = parts.next().filter(|key| !key.is_empty())?;
+        let value = match parts.next() {
+            Some(value) => base64::decode(value).ok()?,
+            None => Vec::new(),
+        };
+        if parts.next().is_some() || metadata.insert(key.to_string(), value).is_some() {
+            return None;
+        }
+    }
+    Some(metadata)
+}
+
+/// A storage of the uploads of [`TusEndpoint`].
+#[async_trait::async_trait]
+pub trait TusStorage: Send + Sync + 'static {
+    /// Creates an empty upload.
+    async fn create(&self, upload: &TusUpload) -> IoResult<()>;
+
+    /// Returns the upload with the id, or `None` if it does not exist.
+    async fn get(&self, id: &str) -> IoResult<Option<TusUpload>>;
+
+    /// Appends the data to the upload at `offset`, and returns the new offset.
+    ///
+    /// If an error occurs while reading the data, the bytes written before the
+    /// error must be kept, so that the client can resume from them.
+    async fn append(
+        &self,
+        id: &str,
+        offset: u64,
+        data: BoxStream<'static, IoResult<Bytes>>,
+    ) -> IoResult<u64>;
+
+    /// Deletes the upload with the id.
+    async fn delete(&self, id: &str) -> IoResult<()>;
+
+    /// Returns all the uploads.
+    async fn list(&self) -> IoResult<Vec<TusUpload>>;
+
+    /// Deletes the expired uploads, and returns the number of the deleted
+    /// uploads.
+    async fn remove_expired(&self) -> IoResult<usize> {
+        let now = SystemTime::now();
+        let mut count = 0;
+        for upload in self.list().await? {
+            if upload.is_expired(now) {
+                self.delete(&upload.id).await?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+type Hook = Box<dyn Fn(TusUpload) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// An endpoint which implements the [tus](https://tus.io/protocols/resumable-upload.html)
+/// resumable upload protocol `1.0.0`.
+///
+/// The `creation`, `termination` and `expiration` extensions are supported.
+/// The uploads are created by `POST` requests to the path of the endpoint,
+/// and the other requests are sent to the URL in the `Location` header of the
+/// creation response, so it should be nested with
+/// [`Route::nest`](crate::Route::nest).
+///
+/// The `X-HTTP-Method-Override` header is supported for the clients which can
+/// not send the `PATCH` and `DELETE` requests. For the browser clients, use
+/// [`Cors::allow_tus`](crate::middleware::Cors::allow_tus) to allow and
+/// expose the headers of the protocol.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{
+///     endpoint::{LocalTusStorage, TusEndpoint},
+///     Route,
+/// };
+///
+/// let app = Route::new().nest(
+///     "/files",
+///     TusEndpoint::new(LocalTusStorage::new("/tmp/uploads"))
+///         .max_size(1024 * 1024 * 1024)
+///         .expiration(Duration::from_secs(60 * 60 * 24)),
+/// );
+/// ```
+pub struct TusEndpoint<S> {
+    storage: S,
+    max_size: Option<u64>,
+    expiration: Option<Duration>,
+    on_create: Option<Hook>,
+    on_complete: Option<Hook>,
+    locks: Mutex<HashSet<String>>,
+}
+
+impl<S: TusStorage> TusEndpoint<S> {
+    /// Create a `TusEndpoint` which stores the uploads in the storage.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            max_size: None,
+            expiration: None,
+            on_create: None,
+            on_complete: None,
+            locks: Default::default(),
+        }
+    }
+
+    /// Sets the maximum size of an upload in bytes.
+    ///
+    /// Default is `None`, which means no limit.
+    #[must_use]
+    pub fn max_size(self, max_size: u64) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    /// Sets the duration after which the incomplete uploads expire.
+    ///
+    /// The expired uploads can be deleted by
+    /// [`TusStorage::remove_expired`]. Default is `None`, which means the
+    /// uploads never expire.
+    #[must_use]
+    pub fn expiration(self, expiration: Duration) -> Self {
+        Self {
+            expiration: Some(expiration),
+            ..self
+        }
+    }
+
+    /// Sets a hook which is called before an upload is created, the upload is
+    /// rejected if it returns an error.
+    #[must_use]
+    pub fn on_create<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(TusUpload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self {
+            on_create: Some(Box::new(move |upload| Box::pin(f(upload)))),
+            ..self
+        }
+    }
+
+    /// Sets a hook which is called after all the bytes of an upload have been
+    /// received.
+    ///
+    /// If it returns an error, the error is returned to the client, but the
+    /// upload remains completed.
+    #[must_use]
+    pub fn on_complete<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(TusUpload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self {
+            on_complete: Some(Box::new(move |upload| Box::pin(f(upload)))),
+            ..self
+        }
+    }
+
+    /// Returns the storage of the uploads.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    async fn handle(&self, mut req: Request) -> Result<Response> {
+        let method = match req.headers().get(METHOD_OVERRIDE) {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse::<Method>().ok())
+                .ok_or(TusError::InvalidHeader("X-HTTP-Method-Override"))?,
+            None => req.method().clone(),
+        };
+
+        if method == Method::OPTIONS {
+            return Ok(self.options());
+        }
+
+        if req.headers().get(TUS_RESUMABLE).map(HeaderValue::as_bytes)
+            != Some(TUS_VERSION.as_bytes())
+        {
+            return Err(TusError::UnsupportedVersion.into());
+        }
+
+        let id = req.uri().path().trim_matches('/').to_string();
+        if id.is_empty() {
+            if method == Method::POST {
+                self.create(&req).await
+            } else {
+                Err(TusError::MethodNotAllowed(method).into())
+            }
+        } else if id.contains('/') {
+            Err(TusError::NotFound.into())
+        } else if method == Method::HEAD {
+            self.head(&id).await
+        } else if method == Method::PATCH {
+            self.patch(&id, &mut req).await
+        } else if method == Method::DELETE {
+            self.delete(&id).await
+        } else {
+            Err(TusError::MethodNotAllowed(method).into())
+        }
+    }
+
+    fn options(&self) -> Response {
+        let mut extensions = "creation,termination".to_string();
+        if self.expiration.is_some() {
+            extensions.push_str(",expiration");
+        }
+
+        let mut resp = Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header("tus-version", TUS_VERSION)
+            .header(TUS_EXTENSION, extensions)
+            .finish();
+        if let Some(max_size) = self.max_size {
+            resp.headers_mut().insert(TUS_MAX_SIZE, max_size.into());
+        }
+        resp
+    }
+
+    async fn create(&self, req: &Request) -> Result<Response> {
+        let length = header_u64(req, UPLOAD_LENGTH, "Upload-Length")?;
+        if matches!(self.max_size, Some(max_size) if length > max_size) {
+            return Err(TusError::TooLarge.into());
+        }
+
+        let metadata = match req.headers().get(UPLOAD_METADATA) {
+            Some(value) => {
+                let value = value.to_str().map_err(|_| TusError::InvalidMetadata)?;
+                parse_metadata(value).ok_or(TusError::InvalidMetadata)?;
+                Some(value.to_string())
+            }
+            None => None,
+        };
+
+        let upload = TusUpload {
+            id: format!("{:032x}", rand::random::<u128>()),
+            length,
+            offset: 0,
+            metadata,
+            expires: self
+                .expiration
+                .map(|expiration| SystemTime::now() + expiration),
+        };
+
+        if let Some(on_create) = &self.on_create {
+            on_create(upload.clone()).await?;
+        }
+        self.storage.create(&upload).await.map_err(TusError::Io)?;
+
+        let location = format!(
+            "{}/{}",
+            req.original_uri().path().trim_end_matches('/'),
+            upload.id
+        );
+        let mut resp = Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::LOCATION, location)
+            .finish();
+        set_expires(&mut resp, &upload);
+
+        if upload.is_complete() {
+            self.complete(upload).await?;
+        }
+        Ok(resp)
+    }
+
+    async fn head(&self, id: &str) -> Result<Response> {
+        let upload = self.get_upload(id).await?;
+        let mut resp = Response::builder()
+            .header(UPLOAD_OFFSET, upload.offset)
+            .header(UPLOAD_LENGTH, upload.length)
+            .header(header::CACHE_CONTROL, "no-store")
+            .finish();
+        if let Some(metadata) = upload
+            .metadata
+            .as_deref()
+            .and_then(|metadata| HeaderValue::from_str(metadata).ok())
+        {
+            resp.headers_mut().insert(UPLOAD_METADATA, metadata);
+        }
+        set_expires(&mut resp, &upload);
+        Ok(resp)
+    }
+
+    async fn patch(&self, id: &str, req: &mut Request) -> Result<Response> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim);
+        if !matches!(content_type, Some(content_type) if content_type.eq_ignore_ascii_case(OFFSET_OCTET_STREAM))
+        {
+            return Err(TusError::InvalidContentType.into());
+        }
+        let offset = header_u64(req, UPLOAD_OFFSET, "Upload-Offset")?;
+
+        let _lock = self.lock(id)?;
+        let upload = self.get_upload(id).await?;
+        if offset != upload.offset {
+            return Err(TusError::OffsetMismatch {
+                expected: upload.offset,
+                actual: offset,
+            }
+            .into());
+        }
+
+        let remaining =
+            upload
+                .length
+                .checked_sub(upload.offset)
+                .ok_or(TusError::OffsetExceedsLength {
+                    offset: upload.offset,
+                    length: upload.length,
+                })?;
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if matches!(content_length, Some(content_length) if content_length > remaining) {
+            return Err(TusError::TooLarge.into());
+        }
+
+        let data = req
+            .take_body()
+            .limit(usize::try_from(remaining).unwrap_or(usize::MAX))
+            .into_bytes_stream()
+            .boxed();
+        let offset = match self.storage.append(id, offset, data).await {
+            Ok(offset) => offset,
+            Err(err) if is_payload_too_large(&err) => return Err(TusError::TooLarge.into()),
+            Err(err) => return Err(TusError::Io(err).into()),
+        };
+
+        let upload = TusUpload { offset, ..upload };
+        let mut resp = Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(UPLOAD_OFFSET, upload.offset)
+            .finish();
+        set_expires(&mut resp, &upload);
+
+        if upload.is_complete() {
+            self.complete(upload).await?;
+        }
+        Ok(resp)
+    }
+
+    async fn delete(&self, id: &str) -> Result<Response> {
+        let _lock = self.lock(id)?;
+        if self.storage.get(id).await.map_err(TusError::Io)?.is_none() {
+            return Err(TusError::NotFound.into());
+        }
+        self.storage.delete(id).await.map_err(TusError::Io)?;
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    async fn get_upload(&self, id: &str) -> Result<TusUpload> {
+        let upload = self
+            .storage
+            .get(id)
+            .await
+            .map_err(TusError::Io)?
+            .ok_or(TusError::NotFound)?;
+        if upload.is_expired(SystemTime::now()) {
+            return Err(TusError::Expired.into());
+        }
+        Ok(upload)
+    }
+
+    async fn complete(&self, upload: TusUpload) -> Result<()> {
+        match &self.on_complete {
+            Some(on_complete) => on_complete(upload).await,
+            None => Ok(()),
+        }
+    }
+
+    fn lock(&self, id: &str) -> Result<UploadLock<'_>> {
+        if !self.locks.lock().insert(id.to_string()) {
+            return Err(TusError::Locked.into());
+        }
+        Ok(UploadLock {
+            locks: &self.locks,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Prevents the concurrent writes of an upload.
+struct UploadLock<'a> {
+    locks: &'a Mutex<HashSet<String>>,
+    id: String,
+}
+
+impl<'a> Drop for UploadLock<'a> {
+    fn drop(&mut self) {
+        self.locks.lock().remove(&self.id);
+    }
+}
+
+fn header_u64(req: &Request, name: &str, display_name: &'static str) -> Result<u64, TusError> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or(TusError::InvalidHeader(display_name))
+}
+
+fn set_expires(resp: &mut Response, upload: &TusUpload) {
+    if let Some(expires) = upload.expires.filter(|_| !upload.is_complete()) {
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(expires)) {
+            resp.headers_mut().insert(UPLOAD_EXPIRES, value);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: TusStorage> Endpoint for TusEndpoint<S> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let mut resp = match self.handle(req).await {
+            Ok(resp) => resp,
+            Err(err) => err.as_response(),
+        };
+        resp.headers_mut()
+            .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::*;
+    use crate::{middleware::Cors, test::TestClient, EndpointExt, Route};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("poem-tus-{:016x}", rand::random::<u64>()))
+    }
+
+    /// Creates a `TestClient` which sets the original uri of the requests like
+    /// the server, so that the `Location` header contains the nested path.
+    fn client(ep: impl Endpoint + 'static) -> TestClient<impl Endpoint> {
+        TestClient::new(ep.before(|mut req| async move {
+            req.state_mut().original_uri = req.uri().clone();
+            Ok(req)
+        }))
+    }
+
+    #[test]
+    fn metadata() {
+        let upload = TusUpload {
+            id: "a".to_string(),
+            length: 0,
+            offset: 0,
+            metadata: Some(
+                "filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential".to_string(),
+            ),
+            expires: None,
+        };
+        let metadata = upload.decode_metadata();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["filename"], b"world_domination_plan.pdf");
+        assert_eq!(metadata["is_confidential"], b"");
+
+        for value in ["", "a b c", "a !!!", "a,a", ",a"] {
+            assert!(parse_metadata(value).is_none(), "{}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn protocol() {
+        let dir = temp_dir();
+        let completed = Arc::new(Mutex::new(Vec::new()));
+        let ep = TusEndpoint::new(LocalTusStorage::new(&dir))
+            .max_size(100)
+            .on_complete({
+                let completed = completed.clone();
+                move |upload| {
+                    completed.lock().push(upload.id);
+                    async { Ok(()) }
+                }
+            });
+        let cli = client(Route::new().nest("/files", ep));
+
+        let resp = cli.options("/files").send().await;
+        resp.assert_status(StatusCode::NO_CONTENT);
+        resp.assert_header("tus-resumable", "1.0.0");
+        resp.assert_header("tus-version", "1.0.0");
+        resp.assert_header("tus-extension", "creation,termination");
+        resp.assert_header("tus-max-size", "100");
+
+        let resp = cli.post("/files").header("upload-length", 10).send().await;
+        resp.assert_status(StatusCode::PRECONDITION_FAILED);
+        resp.assert_header("tus-version", "1.0.0");
+
+        let cli = cli.default_header("tus-resumable", "1.0.0");
+
+        cli.post("/files")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.post("/files")
+            .header("upload-length", 101)
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        cli.post("/files")
+            .header("upload-length", 10)
+            .header("upload-metadata", "filename !!!")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let resp = cli
+            .post("/files")
+            .header("upload-length", 10)
+            .header("upload-metadata", "filename YS50eHQ=")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::CREATED);
+        let location = resp.0.header("location").unwrap().to_string();
+        assert!(location.starts_with("/files/"));
+
+        let resp = cli.head(&location).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header("upload-offset", "0");
+        resp.assert_header("upload-length", "10");
+        resp.assert_header("upload-metadata", "filename YS50eHQ=");
+        resp.assert_header("cache-control", "no-store");
+
+        cli.patch(&location)
+            .header("upload-offset", 0)
+            .body("01234")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let resp = cli
+            .patch(&location)
+            .content_type(OFFSET_OCTET_STREAM)
+            .header("upload-offset", 0)
+            .body("01234")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NO_CONTENT);
+        resp.assert_header("upload-offset", "5");
+        assert!(completed.lock().is_empty());
+
+        cli.patch(&location)
+            .content_type(OFFSET_OCTET_STREAM)
+            .header("upload-offset", 0)
+            .body("01234")
+            .send()
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        cli.patch(&location)
+            .content_type(OFFSET_OCTET_STREAM)
+            .header("upload-offset", 5)
+            .body("567890")
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        // The method override.
+        let resp = cli
+            .post(&location)
+            .header("x-http-method-override", "PATCH")
+            .content_type(OFFSET_OCTET_STREAM)
+            .header("upload-offset", 5)
+            .body("56789")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NO_CONTENT);
+        resp.assert_header("upload-offset", "10");
+
+        let id = location.trim_start_matches("/files/");
+        assert_eq!(*completed.lock(), vec![id.to_string()]);
+        assert_eq!(
+            tokio::fs::read(LocalTusStorage::new(&dir).data_path(id).unwrap())
+                .await
+                .unwrap(),
+            b"0123456789"
+        );
+
+        cli.delete(&location)
+            .send()
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        cli.head(&location)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        cli.delete(&location)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn offset_exceeds_length() {
+        let dir = temp_dir();
+        let storage = LocalTusStorage::new(&dir);
+        let cli = client(Route::new().nest("/files", TusEndpoint::new(storage.clone())))
+            .default_header("tus-resumable", "1.0.0");
+
+        let resp = cli.post("/files").header("upload-length", 5).send().await;
+        resp.assert_status(StatusCode::CREATED);
+        let location = resp.0.header("location").unwrap().to_string();
+        let id = location.trim_start_matches("/files/");
+
+        // The storage holds more bytes than the declared length.
+        tokio::fs::write(storage.data_path(id).unwrap(), b"0123456789")
+            .await
+            .unwrap();
+        cli.patch(&location)
+            .content_type(OFFSET_OCTET_STREAM)
+            .header("upload-offset", 10)
+            .body("0")
+            .send()
+            .await
+            .assert_status(StatusCode::CONFLICT);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expiration() {
+        let dir = temp_dir();
+        let storage = LocalTusStorage::new(&dir);
+        let cli = client(Route::new().nest(
+            "/files",
+            TusEndpoint::new(storage.clone()).expiration(Duration::from_secs(0)),
+        ))
+        .default_header("tus-resumable", "1.0.0");
+
+        cli.options("/files")
+            .send()
+            .await
+            .assert_header("tus-extension", "creation,termination,expiration");
+
+        let resp = cli.post("/files").header("upload-length", 10).send().await;
+        resp.assert_status(StatusCode::CREATED);
+        resp.assert_header_exist("upload-expires");
+        let location = resp.0.header("location").unwrap().to_string();
+
+        // An empty upload is completed, and never expires.
+        cli.post("/files")
+            .header("upload-length", 0)
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+
+        cli.head(&location)
+            .send()
+            .await
+            .assert_status(StatusCode::GONE);
+        assert_eq!(storage.list().await.unwrap().len(), 2);
+        assert_eq!(storage.remove_expired().await.unwrap(), 1);
+        assert_eq!(storage.list().await.unwrap().len(), 1);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cors() {
+        let dir = temp_dir();
+        let cli = client(
+            Route::new()
+                .nest("/files", TusEndpoint::new(LocalTusStorage::new(&dir)))
+                .with(Cors::new().allow_origin("https://example.com").allow_tus()),
+        );
+
+        let resp = cli
+            .options("/files")
+            .header("origin", "https://example.com")
+            .header("access-control-request-method", "PATCH")
+            .header(
+                "access-control-request-headers",
+                "tus-resumable, upload-offset, content-type",
+            )
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+
+        let resp = cli
+            .post("/files")
+            .header("origin", "https://example.com")
+            .header("tus-resumable", "1.0.0")
+            .header("upload-length", 10)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::CREATED);
+        let exposed = resp.0.header("access-control-expose-headers").unwrap();
+        assert!(exposed.contains("location"));
+        assert!(exposed.contains("upload-offset"));
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
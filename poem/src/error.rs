@@ -10,7 +10,7 @@ use std::{
 use headers::{ContentRange, HeaderMapExt};
 use http::Method;
 
-use crate::{http::StatusCode, IntoResponse, Response};
+use crate::{body::is_payload_too_large, http::StatusCode, IntoResponse, Response};
 
 macro_rules! define_http_error {
     ($($(#[$docs:meta])* ($name:ident, $status:ident);)*) => {
@@ -533,6 +533,18 @@ define_simple_errors!(
 
     /// Error occurred in the `Cors` middleware.
     (CorsError, UNAUTHORIZED, "unauthorized");
+
+    /// Error occurred in the `IpFilter` middleware.
+    (IpFilterError, FORBIDDEN, "forbidden");
+
+    /// The session storage does not support the per-user session index.
+    (SessionIndexNotSupportedError, NOT_IMPLEMENTED, "session index is not supported");
+
+    /// The session data in the storage is corrupted.
+    (CorruptedSessionError, INTERNAL_SERVER_ERROR, "corrupted session");
+
+    /// The session does not exist in the storage.
+    (SessionNotFoundError, NOT_FOUND, "session not found");
 );
 
 /// A possible error value when reading the body.
@@ -560,6 +572,7 @@ impl ResponseError for ReadBodyError {
         match self {
             ReadBodyError::BodyHasBeenTaken => StatusCode::INTERNAL_SERVER_ERROR,
             ReadBodyError::Utf8(_) => StatusCode::BAD_REQUEST,
+            ReadBodyError::Io(err) if is_payload_too_large(err) => StatusCode::PAYLOAD_TOO_LARGE,
             ReadBodyError::Io(_) => StatusCode::BAD_REQUEST,
             ReadBodyError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
         }
@@ -685,6 +698,51 @@ pub enum ParseMultipartError {
     /// Io error
     #[error("io: {0}")]
     Io(#[from] std::io::Error),
+
+    /// The number of the fields exceeds the limit.
+    #[error("the number of the fields exceeds the limit `{0}`")]
+    TooManyFields(usize),
+
+    /// The number of the files exceeds the limit.
+    #[error("the number of the files exceeds the limit `{0}`")]
+    TooManyFiles(usize),
+
+    /// The content type of the file is not allowed.
+    #[error("the content type `{content_type}` of the file is not allowed")]
+    ContentTypeNotAllowed {
+        /// The name of the field.
+        field_name: Option<String>,
+        /// The content type of the file.
+        content_type: String,
+    },
+}
+
+#[cfg(feature = "multipart")]
+fn multer_error_status(err: &multer::Error) -> StatusCode {
+    match err {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            StatusCode::PAYLOAD_TOO_LARGE
+        }
+        multer::Error::StreamReadFailed(err) => match err.downcast_ref::<multer::Error>() {
+            Some(err) => multer_error_status(err),
+            None if is_payload_too_large(err.as_ref()) => StatusCode::PAYLOAD_TOO_LARGE,
+            None => StatusCode::BAD_REQUEST,
+        },
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(feature = "multipart")]
+fn multipart_io_error_status(err: &std::io::Error) -> StatusCode {
+    // The errors of reading the fields are wrapped in the io errors.
+    match err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<multer::Error>())
+    {
+        Some(err) => multer_error_status(err),
+        None if is_payload_too_large(err) => StatusCode::PAYLOAD_TOO_LARGE,
+        None => StatusCode::BAD_REQUEST,
+    }
 }
 
 #[cfg(feature = "multipart")]
@@ -693,13 +751,197 @@ impl ResponseError for ParseMultipartError {
         match self {
             ParseMultipartError::InvalidContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             ParseMultipartError::ContentTypeRequired => StatusCode::UNSUPPORTED_MEDIA_TYPE,
-            ParseMultipartError::Multipart(_) => StatusCode::BAD_REQUEST,
+            ParseMultipartError::Multipart(err) => multer_error_status(err),
             ParseMultipartError::Utf8(_) => StatusCode::BAD_REQUEST,
-            ParseMultipartError::Io(_) => StatusCode::BAD_REQUEST,
+            ParseMultipartError::Io(err) => multipart_io_error_status(err),
+            ParseMultipartError::TooManyFields(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ParseMultipartError::TooManyFiles(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ParseMultipartError::ContentTypeNotAllowed { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
         }
     }
 }
 
+/// A possible error value when streaming an upload to an
+/// [`UploadSink`](crate::web::upload::UploadSink).
+#[cfg(feature = "upload")]
+#[cfg_attr(docsrs, doc(cfg(feature = "upload")))]
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    /// The size of the upload exceeds the limit.
+    #[error("the size of the upload exceeds the limit `{0}`")]
+    TooLarge(u64),
+
+    /// The content type of the upload is not allowed.
+    #[error("the content type `{0}` of the upload is not allowed")]
+    ContentTypeNotAllowed(String),
+
+    /// Failed to read the upload.
+    #[error("read: {0}")]
+    Read(std::io::Error),
+
+    /// Failed to write the upload to the sink.
+    #[error("sink: {0}")]
+    Sink(std::io::Error),
+}
+
+#[cfg(feature = "upload")]
+impl ResponseError for UploadError {
+    fn status(&self) -> StatusCode {
+        match self {
+            UploadError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::ContentTypeNotAllowed(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            UploadError::Read(err) => multipart_io_error_status(err),
+            UploadError::Sink(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// A possible error value when handling the requests of
+/// [`TusEndpoint`](crate::endpoint::TusEndpoint).
+#[cfg(feature = "tus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+#[derive(Debug, thiserror::Error)]
+pub enum TusError {
+    /// The version in the `Tus-Resumable` header is not supported.
+    #[error("unsupported tus version")]
+    UnsupportedVersion,
+
+    /// Method not allowed.
+    #[error("method `{0}` not allowed")]
+    MethodNotAllowed(Method),
+
+    /// A required header is missing or invalid.
+    #[error("header `{0}` is missing or invalid")]
+    InvalidHeader(&'static str),
+
+    /// The `Upload-Metadata` header is invalid.
+    #[error("invalid upload metadata")]
+    InvalidMetadata,
+
+    /// The upload does not exist.
+    #[error("upload not found")]
+    NotFound,
+
+    /// The upload has expired.
+    #[error("upload expired")]
+    Expired,
+
+    /// The `Upload-Offset` header does not match the offset of the upload.
+    #[error("upload offset mismatch, expected `{expected}`, actual `{actual}`")]
+    OffsetMismatch {
+        /// The offset of the upload.
+        expected: u64,
+        /// The offset in the request.
+        actual: u64,
+    },
+
+    /// The offset of the upload exceeds its length, e.g. the storage holds
+    /// more bytes than declared.
+    #[error("upload offset `{offset}` exceeds the length `{length}`")]
+    OffsetExceedsLength {
+        /// The offset of the upload.
+        offset: u64,
+        /// The length of the upload.
+        length: u64,
+    },
+
+    /// The upload is being written by another request.
+    #[error("upload locked")]
+    Locked,
+
+    /// The content type of the `PATCH` request is not
+    /// `application/offset+octet-stream`.
+    #[error("invalid content type")]
+    InvalidContentType,
+
+    /// The size of the upload exceeds the limit.
+    #[error("the size of the upload exceeds the limit")]
+    TooLarge,
+
+    /// Io error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "tus")]
+impl ResponseError for TusError {
+    fn status(&self) -> StatusCode {
+        match self {
+            TusError::UnsupportedVersion => StatusCode::PRECONDITION_FAILED,
+            TusError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            TusError::InvalidHeader(_) | TusError::InvalidMetadata => StatusCode::BAD_REQUEST,
+            TusError::NotFound => StatusCode::NOT_FOUND,
+            TusError::Expired => StatusCode::GONE,
+            TusError::OffsetMismatch { .. } | TusError::OffsetExceedsLength { .. } => {
+                StatusCode::CONFLICT
+            }
+            TusError::Locked => StatusCode::LOCKED,
+            TusError::InvalidContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            TusError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            TusError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let mut resp = Response::builder()
+            .status(self.status())
+            .body(self.to_string());
+        if let TusError::UnsupportedVersion = self {
+            resp.headers_mut()
+                .insert("tus-version", http::HeaderValue::from_static("1.0.0"));
+        }
+        resp
+    }
+}
+
+/// A possible error value when negotiating the format of
+/// [`Negotiated`](crate::web::Negotiated).
+#[derive(Debug, thiserror::Error)]
+pub enum NegotiationError {
+    /// None of the formats is acceptable by the `Accept` header.
+    #[error("not acceptable")]
+    NotAcceptable,
+
+    /// The `Content-Type` header is required.
+    #[error("expect a `Content-Type` header")]
+    ContentTypeRequired,
+
+    /// The content type of the request is not supported.
+    #[error("unsupported content type `{0}`")]
+    UnsupportedMediaType(String),
+
+    /// Failed to deserialize the request body.
+    #[error("parse: {0}")]
+    Deserialize(String),
+
+    /// Failed to serialize the response body.
+    #[error("serialize: {0}")]
+    Serialize(String),
+}
+
+impl ResponseError for NegotiationError {
+    fn status(&self) -> StatusCode {
+        match self {
+            NegotiationError::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            NegotiationError::ContentTypeRequired | NegotiationError::UnsupportedMediaType(_) => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+            NegotiationError::Deserialize(_) => StatusCode::BAD_REQUEST,
+            NegotiationError::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let body = match self {
+            // The details of the serialization errors are not exposed to the
+            // clients.
+            NegotiationError::Serialize(_) => "failed to serialize the response".to_string(),
+            _ => self.to_string(),
+        };
+        Response::builder().status(self.status()).body(body)
+    }
+}
+
 /// A possible error value when parsing typed headers.
 #[derive(Debug, thiserror::Error)]
 pub enum ParseTypedHeaderError {
@@ -727,6 +969,10 @@ pub enum WebSocketError {
     #[error("invalid protocol")]
     InvalidProtocol,
 
+    /// The origin is not allowed
+    #[error("origin not allowed")]
+    OriginNotAllowed,
+
     /// Upgrade Error
     #[error(transparent)]
     UpgradeError(#[from] UpgradeError),
@@ -737,6 +983,7 @@ impl ResponseError for WebSocketError {
     fn status(&self) -> StatusCode {
         match self {
             WebSocketError::InvalidProtocol => StatusCode::BAD_REQUEST,
+            WebSocketError::OriginNotAllowed => StatusCode::FORBIDDEN,
             WebSocketError::UpgradeError(err) => err.status(),
         }
     }
@@ -823,6 +1070,123 @@ impl ResponseError for StaticFileError {
     }
 }
 
+/// A possible error value when parsing IP filter rules.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum ParseIpFilterError {
+    /// Invalid IP address.
+    #[error("invalid ip address: {0}")]
+    InvalidAddress(String),
+
+    /// Invalid prefix length.
+    #[error("invalid prefix length: {0}")]
+    InvalidPrefixLength(String),
+
+    /// Invalid rule.
+    #[error("invalid rule: {0}")]
+    InvalidRule(String),
+}
+
+impl ResponseError for ParseIpFilterError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value occurred in the `JwtAuth` middleware.
+#[cfg(feature = "jwt")]
+#[derive(Debug, thiserror::Error, Clone, Eq, PartialEq)]
+pub enum JwtAuthError {
+    /// Missing token
+    #[error("missing token")]
+    MissingToken,
+
+    /// The token is expired
+    #[error("token expired")]
+    ExpiredToken,
+
+    /// Invalid token
+    #[error("invalid token: {0}")]
+    InvalidToken(String),
+
+    /// Failed to load the JSON Web Key Set
+    #[error("failed to load jwks: {0}")]
+    LoadJwks(String),
+}
+
+#[cfg(feature = "jwt")]
+impl ResponseError for JwtAuthError {
+    fn status(&self) -> StatusCode {
+        match self {
+            JwtAuthError::LoadJwks(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let mut resp = Response::builder()
+            .status(self.status())
+            .body(self.to_string());
+        let challenge = match self {
+            JwtAuthError::MissingToken => Some("Bearer"),
+            JwtAuthError::ExpiredToken => Some(
+                "Bearer error=\"invalid_token\", error_description=\"The access token expired\"",
+            ),
+            JwtAuthError::InvalidToken(_) => Some("Bearer error=\"invalid_token\""),
+            JwtAuthError::LoadJwks(_) => None,
+        };
+        if let Some(challenge) = challenge {
+            resp.headers_mut().insert(
+                http::header::WWW_AUTHENTICATE,
+                http::HeaderValue::from_static(challenge),
+            );
+        }
+        resp
+    }
+}
+
+/// A possible error value occurred in the `EncryptedStorage`.
+#[cfg(feature = "session-encryption")]
+#[derive(Debug, thiserror::Error, Clone, Eq, PartialEq)]
+pub enum SessionEncryptionError {
+    /// Failed to encode the session entries
+    #[error("failed to encode session: {0}")]
+    Encode(String),
+
+    /// Failed to encrypt the session entries
+    #[error("failed to encrypt session")]
+    Encrypt,
+}
+
+#[cfg(feature = "session-encryption")]
+impl ResponseError for SessionEncryptionError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value occurred in the `ServerSession` middleware when the
+/// session security policies are enabled.
+///
+/// The status code is `401 Unauthorized`, the application can use
+/// [`EndpointExt::catch_error`](crate::EndpointExt::catch_error) to respond
+/// with a login page.
+#[derive(Debug, thiserror::Error, Copy, Clone, Eq, PartialEq)]
+pub enum SessionSecurityError {
+    /// The session is used by a client other than the one it is bound to.
+    #[error("the session is bound to another client")]
+    ClientMismatch,
+
+    /// The user has reached the maximum number of concurrent sessions.
+    #[error("too many concurrent sessions")]
+    TooManySessions,
+}
+
+impl ResponseError for SessionSecurityError {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
 /// A possible error value occurred in the `SizeLimit` middleware.
 #[derive(Debug, thiserror::Error, Eq, PartialEq)]
 pub enum SizedLimitError {
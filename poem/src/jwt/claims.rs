@@ -0,0 +1,40 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{error::JwtAuthError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor that can extract the validated claims of the JSON Web Token.
+///
+/// If the [`JwtAuth`](super::JwtAuth) middleware is
+/// [optional](super::JwtAuth::optional), extracting fails with the
+/// authentication error when the request does not contain a valid token.
+///
+/// # Errors
+///
+/// - [`JwtAuthError`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Claims<T>(pub T);
+
+impl<T> Deref for Claims<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Claims<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: Send + Sync + 'static> FromRequest<'a> for &'a Claims<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        req.extensions()
+            .get::<Result<Claims<T>, JwtAuthError>>()
+            .expect("To use the `Claims` extractor, the `JwtAuth` middleware is required.")
+            .as_ref()
+            .map_err(|err| err.clone().into())
+    }
+}
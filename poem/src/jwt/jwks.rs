@@ -0,0 +1,209 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    DecodingKey,
+};
+use parking_lot::RwLock;
+use tokio::sync::Mutex;
+
+use crate::error::JwtAuthError;
+
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+enum JwksSource {
+    File(PathBuf),
+    Url(hyper::Uri),
+}
+
+struct Keys {
+    keys: Vec<(Option<String>, DecodingKey)>,
+    loaded_at: Instant,
+}
+
+struct State {
+    keys: RwLock<Keys>,
+    refresh_lock: Mutex<()>,
+}
+
+/// A JSON Web Key Set (JWKS) used by the [`JwtAuth`](super::JwtAuth)
+/// middleware to verify tokens.
+///
+/// The keys are reloaded from the source every
+/// [`refresh_interval`](Jwks::refresh_interval), and also when a token
+/// references a key id that is not in the set, so that rotated keys are picked
+/// up without restarting the server. If reloading fails, the previous keys
+/// are kept.
+///
+/// The key set is shared by all clones.
+#[derive(Clone)]
+pub struct Jwks {
+    source: Arc<JwksSource>,
+    refresh_interval: Duration,
+    state: Arc<State>,
+}
+
+impl Jwks {
+    /// Load the key set from a JSON file.
+    pub async fn from_file(path: impl Into<PathBuf>) -> Result<Self, JwtAuthError> {
+        Self::load(JwksSource::File(path.into())).await
+    }
+
+    /// Load the key set from a HTTP endpoint, such as
+    /// `http://127.0.0.1:8080/.well-known/jwks.json`.
+    ///
+    /// Only plain HTTP is supported, so this is intended for endpoints served
+    /// by a local identity provider or sidecar.
+    pub async fn from_url(url: impl AsRef<str>) -> Result<Self, JwtAuthError> {
+        let uri = url
+            .as_ref()
+            .parse::<hyper::Uri>()
+            .map_err(|err| JwtAuthError::LoadJwks(err.to_string()))?;
+        Self::load(JwksSource::Url(uri)).await
+    }
+
+    async fn load(source: JwksSource) -> Result<Self, JwtAuthError> {
+        let keys = fetch(&source).await?;
+        Ok(Self {
+            source: Arc::new(source),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            state: Arc::new(State {
+                keys: RwLock::new(Keys {
+                    keys,
+                    loaded_at: Instant::now(),
+                }),
+                refresh_lock: Mutex::new(()),
+            }),
+        })
+    }
+
+    /// Sets the interval for reloading the key set. Default is `5 minutes`.
+    #[must_use]
+    pub fn refresh_interval(self, interval: Duration) -> Self {
+        Self {
+            refresh_interval: interval,
+            ..self
+        }
+    }
+
+    /// Reload the key set from the source immediately.
+    pub async fn refresh(&self) -> Result<(), JwtAuthError> {
+        let keys = fetch(&self.source).await?;
+        *self.state.keys.write() = Keys {
+            keys,
+            loaded_at: Instant::now(),
+        };
+        Ok(())
+    }
+
+    pub(crate) async fn find(&self, kid: Option<&str>) -> Option<DecodingKey> {
+        let loaded_at = self.state.keys.read().loaded_at;
+        if loaded_at.elapsed() >= self.refresh_interval {
+            self.refresh_if_unchanged(loaded_at).await;
+        }
+
+        if let Some(key) = self.lookup(kid) {
+            return Some(key);
+        }
+
+        // The key may have been rotated, reload the key set and try again.
+        let loaded_at = self.state.keys.read().loaded_at;
+        if loaded_at.elapsed() >= MIN_REFRESH_INTERVAL {
+            self.refresh_if_unchanged(loaded_at).await;
+            return self.lookup(kid);
+        }
+
+        None
+    }
+
+    async fn refresh_if_unchanged(&self, loaded_at: Instant) {
+        let _guard = self.state.refresh_lock.lock().await;
+        let current_loaded_at = self.state.keys.read().loaded_at;
+        if current_loaded_at != loaded_at {
+            // Already reloaded by another request.
+            return;
+        }
+
+        if let Err(err) = self.refresh().await {
+            tracing::warn!(error = %err, "failed to reload jwks");
+            self.state.keys.write().loaded_at = Instant::now();
+        }
+    }
+
+    fn lookup(&self, kid: Option<&str>) -> Option<DecodingKey> {
+        let keys = self.state.keys.read();
+        match kid {
+            Some(kid) => keys
+                .keys
+                .iter()
+                .find(|(id, _)| id.as_deref() == Some(kid))
+                .map(|(_, key)| key.clone()),
+            None if keys.keys.len() == 1 => Some(keys.keys[0].1.clone()),
+            None => None,
+        }
+    }
+}
+
+async fn fetch(source: &JwksSource) -> Result<Vec<(Option<String>, DecodingKey)>, JwtAuthError> {
+    let data = match source {
+        JwksSource::File(path) => tokio::fs::read(path)
+            .await
+            .map_err(|err| JwtAuthError::LoadJwks(err.to_string()))?,
+        JwksSource::Url(uri) => {
+            let resp = hyper::Client::new()
+                .get(uri.clone())
+                .await
+                .map_err(|err| JwtAuthError::LoadJwks(err.to_string()))?;
+            if !resp.status().is_success() {
+                return Err(JwtAuthError::LoadJwks(format!(
+                    "unexpected status code: {}",
+                    resp.status()
+                )));
+            }
+            hyper::body::to_bytes(resp.into_body())
+                .await
+                .map_err(|err| JwtAuthError::LoadJwks(err.to_string()))?
+                .to_vec()
+        }
+    };
+
+    let jwks = serde_json::from_slice::<JwkSet>(&data)
+        .map_err(|err| JwtAuthError::LoadJwks(err.to_string()))?;
+    Ok(jwks
+        .keys
+        .iter()
+        .filter_map(|jwk| match decoding_key(jwk) {
+            Ok(key) => Some((jwk.common.key_id.clone(), key)),
+            Err(err) => {
+                tracing::warn!(
+                    kid = ?jwk.common.key_id,
+                    error = %err,
+                    "unsupported key in jwks"
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+fn decoding_key(jwk: &Jwk) -> jsonwebtoken::errors::Result<DecodingKey> {
+    match &jwk.algorithm {
+        // `DecodingKey::from_jwk` decodes the `k` parameter with the standard
+        // base64 alphabet, but it is base64url encoded without padding.
+        AlgorithmParameters::OctetKey(params) => {
+            let secret = base64::decode_config(
+                params.value.trim_end_matches('='),
+                base64::URL_SAFE_NO_PAD,
+            )
+            .map_err(|_| ErrorKind::InvalidKeyFormat)?;
+            Ok(DecodingKey::from_secret(&secret))
+        }
+        _ => DecodingKey::from_jwk(jwk),
+    }
+}
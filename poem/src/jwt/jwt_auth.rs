@@ -0,0 +1,536 @@
+use std::{collections::HashSet, marker::PhantomData};
+
+use headers::HeaderMapExt;
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    error::JwtAuthError,
+    http::header,
+    jwt::{Claims, Jwks},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+#[derive(Clone)]
+enum Keys {
+    Static(DecodingKey),
+    Jwks(Jwks),
+}
+
+/// Middleware for authenticating requests with JSON Web Tokens (JWT).
+///
+/// The token is read from the `Authorization: Bearer <token>` header by
+/// default, and optionally from a cookie. The signature is verified with a
+/// static key or a [`Jwks`], and the `exp`, `nbf`, `aud` and `iss` claims are
+/// validated. The claims are deserialized into `T`, and can be extracted with
+/// the [`Claims`] extractor.
+///
+/// # Errors
+///
+/// - [`JwtAuthError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     jwt::{Algorithm, Claims, DecodingKey, JwtAuth},
+///     EndpointExt, Route,
+/// };
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct MyClaims {
+///     sub: String,
+/// }
+///
+/// #[handler]
+/// fn index(claims: &Claims<MyClaims>) -> String {
+///     format!("hello {}", claims.sub)
+/// }
+///
+/// let app = Route::new().at("/", get(index)).with(
+///     JwtAuth::<MyClaims>::new(DecodingKey::from_secret(b"secret"), [Algorithm::HS256])
+///         .audience(["my-service"])
+///         .cookie("access_token"),
+/// );
+/// ```
+pub struct JwtAuth<T> {
+    keys: Keys,
+    validation: Validation,
+    header: String,
+    cookie: Option<String>,
+    optional: bool,
+    _mark: PhantomData<fn() -> T>,
+}
+
+impl<T> JwtAuth<T> {
+    /// Create `JwtAuth` middleware that verifies tokens with a static key.
+    ///
+    /// Only tokens signed with one of the `algorithms` are accepted, all of
+    /// them must belong to the same family as the key.
+    pub fn new(key: DecodingKey, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        Self::with_keys(Keys::Static(key), algorithms)
+    }
+
+    /// Create `JwtAuth` middleware that verifies tokens with the keys in the
+    /// key set, selected by the `kid` header of the token.
+    pub fn with_jwks(jwks: Jwks, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        Self::with_keys(Keys::Jwks(jwks), algorithms)
+    }
+
+    fn with_keys(keys: Keys, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.algorithms = algorithms.into_iter().collect();
+        validation.validate_nbf = true;
+        Self {
+            keys,
+            validation,
+            header: header::AUTHORIZATION.to_string(),
+            cookie: None,
+            optional: false,
+            _mark: PhantomData,
+        }
+    }
+
+    /// Sets the name of the header that contains the token, the value may be
+    /// prefixed with the `Bearer` scheme. Default is `Authorization`.
+    #[must_use]
+    pub fn header(self, name: impl Into<String>) -> Self {
+        Self {
+            header: name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the name of the cookie that contains the token, which is used if
+    /// the header is not present.
+    #[must_use]
+    pub fn cookie(self, name: impl Into<String>) -> Self {
+        Self {
+            cookie: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the leeway in seconds for validating the `exp` and `nbf` claims.
+    /// Default is `60`.
+    #[must_use]
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.validation.leeway = leeway;
+        self
+    }
+
+    /// Sets the accepted values of the `aud` claim.
+    #[must_use]
+    pub fn audience<I, S>(mut self, audience: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.validation.aud = Some(audience.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the accepted values of the `iss` claim.
+    #[must_use]
+    pub fn issuer<I, S>(mut self, issuer: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.validation.iss = Some(issuer.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the claims that must be present in the token. Default is `["exp"]`.
+    #[must_use]
+    pub fn required_claims<I, S>(mut self, claims: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.validation.required_spec_claims = claims.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether to validate the `exp` claim. Default is `true`.
+    #[must_use]
+    pub fn validate_exp(mut self, validate: bool) -> Self {
+        self.validation.validate_exp = validate;
+        self
+    }
+
+    /// Sets whether to validate the `nbf` claim. Default is `true`.
+    #[must_use]
+    pub fn validate_nbf(mut self, validate: bool) -> Self {
+        self.validation.validate_nbf = validate;
+        self
+    }
+
+    /// If `true`, requests without a valid token are passed to the inner
+    /// endpoint, and the error is returned when extracting [`Claims`].
+    /// Default is `false`.
+    #[must_use]
+    pub fn optional(self, optional: bool) -> Self {
+        Self { optional, ..self }
+    }
+}
+
+impl<E, T> Middleware<E> for JwtAuth<T>
+where
+    E: Endpoint,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    type Output = JwtAuthEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        let algorithms = self
+            .validation
+            .algorithms
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        JwtAuthEndpoint {
+            inner: ep,
+            keys: self.keys.clone(),
+            validations: algorithms
+                .into_iter()
+                .map(|alg| {
+                    let mut validation = self.validation.clone();
+                    validation.algorithms = vec![alg];
+                    (alg, validation)
+                })
+                .collect(),
+            header: self.header.clone(),
+            cookie: self.cookie.clone(),
+            optional: self.optional,
+            _mark: PhantomData,
+        }
+    }
+}
+
+/// Endpoint for JwtAuth middleware.
+pub struct JwtAuthEndpoint<E, T> {
+    inner: E,
+    keys: Keys,
+    validations: Vec<(Algorithm, Validation)>,
+    header: String,
+    cookie: Option<String>,
+    optional: bool,
+    _mark: PhantomData<fn() -> T>,
+}
+
+impl<E, T: DeserializeOwned> JwtAuthEndpoint<E, T> {
+    fn extract_token(&self, req: &Request) -> Option<String> {
+        if let Some(value) = req
+            .headers()
+            .get(self.header.as_str())
+            .and_then(|value| value.to_str().ok())
+        {
+            match value.trim().split_once(' ') {
+                Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                    return Some(token.trim().to_string())
+                }
+                Some(_) => {}
+                None => return Some(value.trim().to_string()),
+            }
+        }
+
+        let cookie_name = self.cookie.as_deref()?;
+        req.headers()
+            .typed_get::<headers::Cookie>()
+            .and_then(|cookie| cookie.get(cookie_name).map(ToString::to_string))
+    }
+
+    async fn decode(&self, token: &str) -> Result<T, JwtAuthError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|err| JwtAuthError::InvalidToken(err.to_string()))?;
+        let validation = self
+            .validations
+            .iter()
+            .find(|(alg, _)| *alg == header.alg)
+            .map(|(_, validation)| validation)
+            .ok_or_else(|| {
+                JwtAuthError::InvalidToken(format!("algorithm `{:?}` is not allowed", header.alg))
+            })?;
+
+        let key = match &self.keys {
+            Keys::Static(key) => key.clone(),
+            Keys::Jwks(jwks) => jwks
+                .find(header.kid.as_deref())
+                .await
+                .ok_or_else(|| JwtAuthError::InvalidToken("unknown key".to_string()))?,
+        };
+
+        jsonwebtoken::decode::<T>(token, &key, validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => JwtAuthError::ExpiredToken,
+                _ => JwtAuthError::InvalidToken(err.to_string()),
+            })
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, T> Endpoint for JwtAuthEndpoint<E, T>
+where
+    E: Endpoint,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let res = match self.extract_token(&req) {
+            Some(token) => self.decode(&token).await.map(Claims),
+            None => Err(JwtAuthError::MissingToken),
+        };
+
+        match res {
+            Err(err) if !self.optional => return Err(err.into()),
+            res => {
+                req.extensions_mut().insert(res);
+            }
+        }
+
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, EndpointExt};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct MyClaims {
+        sub: String,
+        exp: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aud: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nbf: Option<u64>,
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn create_token(secret: &[u8], kid: Option<&str>, claims: &MyClaims) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(ToString::to_string);
+        jsonwebtoken::encode(&header, claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn claims(exp: u64, aud: Option<&str>) -> MyClaims {
+        MyClaims {
+            sub: "sunli".to_string(),
+            exp,
+            aud: aud.map(ToString::to_string),
+            nbf: None,
+        }
+    }
+
+    #[handler(internal)]
+    fn index(claims: &Claims<MyClaims>) -> String {
+        claims.sub.clone()
+    }
+
+    #[tokio::test]
+    async fn jwt_auth() {
+        let cli = TestClient::new(
+            index.with(
+                JwtAuth::<MyClaims>::new(DecodingKey::from_secret(b"secret"), [Algorithm::HS256])
+                    .audience(["poem"])
+                    .leeway(0)
+                    .cookie("token"),
+            ),
+        );
+
+        let token = create_token(b"secret", None, &claims(now() + 60, Some("poem")));
+        let resp = cli
+            .get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text("sunli").await;
+
+        cli.get("/")
+            .header(header::COOKIE, format!("token={}", token))
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_header(header::WWW_AUTHENTICATE, "Bearer");
+
+        let token = create_token(b"secret", None, &claims(now() - 60, Some("poem")));
+        let resp = cli
+            .get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_header(
+            header::WWW_AUTHENTICATE,
+            "Bearer error=\"invalid_token\", error_description=\"The access token expired\"",
+        );
+
+        for token in [
+            create_token(b"secret", None, &claims(now() + 60, Some("other"))),
+            create_token(b"other", None, &claims(now() + 60, Some("poem"))),
+            "abc".to_string(),
+        ] {
+            let resp = cli
+                .get("/")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .send()
+                .await;
+            resp.assert_status(StatusCode::UNAUTHORIZED);
+            resp.assert_header(header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"");
+        }
+    }
+
+    #[tokio::test]
+    async fn algorithm_not_allowed() {
+        let cli = TestClient::new(index.with(JwtAuth::<MyClaims>::new(
+            DecodingKey::from_secret(b"secret"),
+            [Algorithm::HS512],
+        )));
+        let token = create_token(b"secret", None, &claims(now() + 60, None));
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn optional() {
+        #[handler(internal)]
+        fn index(claims: Result<&Claims<MyClaims>>) -> String {
+            match claims {
+                Ok(claims) => claims.sub.clone(),
+                Err(_) => "anonymous".to_string(),
+            }
+        }
+
+        let cli = TestClient::new(
+            index.with(
+                JwtAuth::<MyClaims>::new(DecodingKey::from_secret(b"secret"), [Algorithm::HS256])
+                    .header("x-token")
+                    .optional(true),
+            ),
+        );
+
+        cli.get("/").send().await.assert_text("anonymous").await;
+
+        let token = create_token(b"secret", None, &claims(now() + 60, None));
+        cli.get("/")
+            .header("x-token", token)
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn not_before() {
+        let cli = TestClient::new(index.with(JwtAuth::<MyClaims>::new(
+            DecodingKey::from_secret(b"secret"),
+            [Algorithm::HS256],
+        )));
+
+        let token = create_token(
+            b"secret",
+            None,
+            &MyClaims {
+                nbf: Some(now() + 3600),
+                ..claims(now() + 7200, None)
+            },
+        );
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        let token = create_token(
+            b"secret",
+            None,
+            &MyClaims {
+                nbf: Some(now() + 30),
+                ..claims(now() + 7200, None)
+            },
+        );
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn jwks_rotation() {
+        fn jwks(kid: &str, k: &str) -> String {
+            serde_json::json!({
+                "keys": [{
+                    "kty": "oct",
+                    "alg": "HS256",
+                    "kid": kid,
+                    "k": k,
+                }]
+            })
+            .to_string()
+        }
+
+        let path = std::env::temp_dir().join(format!("poem-jwks-{}.json", now()));
+        tokio::fs::write(&path, jwks("key1", "c2VjcmV0MQ"))
+            .await
+            .unwrap();
+        let keys = Jwks::from_file(&path).await.unwrap();
+        let cli = TestClient::new(index.with(JwtAuth::<MyClaims>::with_jwks(
+            keys.clone(),
+            [Algorithm::HS256],
+        )));
+
+        let token1 = create_token(b"secret1", Some("key1"), &claims(now() + 60, None));
+        let token2 = create_token(b"secret2", Some("key2"), &claims(now() + 60, None));
+
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token1))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        tokio::fs::write(&path, jwks("key2", "c2VjcmV0Mg"))
+            .await
+            .unwrap();
+        keys.refresh().await.unwrap();
+
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token1))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        cli.get("/")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token2))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
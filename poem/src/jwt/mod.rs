@@ -0,0 +1,10 @@
+//! JSON Web Token (JWT) authentication.
+
+mod claims;
+mod jwks;
+mod jwt_auth;
+
+pub use claims::Claims;
+pub use jsonwebtoken::{Algorithm, DecodingKey};
+pub use jwks::Jwks;
+pub use jwt_auth::{JwtAuth, JwtAuthEndpoint};
@@ -230,20 +230,36 @@
 //!
 //! |Feature           |Description                     |
 //! |------------------|--------------------------------|
+//! |cache             | Support for HTTP response caching |
 //! |compression  | Support decompress request body and compress response body |
 //! |cookie            | Support for Cookie             |
 //! |csrf | Support for Cross-Site Request Forgery (CSRF) protection |
+//! |jwt               | Support for JSON Web Token (JWT) authentication |
 //! |multipart         | Support for Multipart          |
+//! |negotiated-xml    | Support for the XML format of `Negotiated` |
+//! |negotiated-msgpack | Support for the MessagePack format of `Negotiated` |
+//! |negotiated-cbor   | Support for the CBOR format of `Negotiated` |
+//! |negotiated-yaml   | Support for the YAML format of `Negotiated` |
 //! |native-tls        | Support for HTTP server over TLS with [`native-tls`](https://crates.io/crates/native-tls)  |
 //! |opentelemetry     | Support for opentelemetry    |
 //! |prometheus        | Support for Prometheus       |
 //! |redis-session     | Support for RedisSession     |
+//! |security-headers  | Support for security headers (HSTS, CSP, etc.) |
+//! |redis-cache       | Support for RedisCacheStore |
 //! |rustls            | Support for HTTP server over TLS with [`rustls`](https://crates.io/crates/rustls)  |
 //! |session           | Support for session    |
+//! |session-encryption | Support for encrypting sessions at rest with `EncryptedStorage` |
+//! |session-msgpack   | Support for the MessagePack codec of `EncryptedStorage` |
+//! |session-cbor      | Support for the CBOR codec of `EncryptedStorage` |
 //! |sse               | Support Server-Sent Events (SSE)       |
 //! |tempfile          | Support for [`tempfile`](https://crates.io/crates/tempfile) |
 //! |tower-compat      | Adapters for `tower::Layer` and `tower::Service`. |
+//! |tus               | Support for the [tus](https://tus.io) resumable upload protocol |
+//! |upload            | Support for streaming multipart uploads to the storages |
+//! |upload-s3         | Support for the S3-compatible upload sink `S3Sink` |
 //! |websocket         | Support for WebSocket          |
+//! |websocket-msgpack | Support for the MessagePack codec of `TypedWebSocketStream` |
+//! |websocket-cbor    | Support for the CBOR codec of `TypedWebSocketStream` |
 //! | anyhow        | Integrate with the [`anyhow`](https://crates.io/crates/anyhow) crate. |
 //! | eyre06        | Integrate with version 0.6.x of the [`eyre`](https://crates.io/crates/eyre) crate. |
 //! | i18n          | Support for internationalization |
@@ -257,11 +273,17 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 
+#[cfg(feature = "cache")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+pub mod cache;
 pub mod endpoint;
 pub mod error;
 #[cfg(feature = "i18n")]
 #[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
 pub mod i18n;
+#[cfg(feature = "jwt")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jwt")))]
+pub mod jwt;
 pub mod listener;
 pub mod middleware;
 #[cfg(feature = "session")]
@@ -281,10 +303,12 @@ mod request;
 mod response;
 mod route;
 mod server;
+#[cfg(any(feature = "cache", feature = "session"))]
+mod time;
 
 pub use addr::Addr;
 pub use async_trait::async_trait;
-pub use body::Body;
+pub use body::{Body, Trailers};
 pub use endpoint::{Endpoint, EndpointExt, IntoEndpoint};
 pub use error::{Error, Result};
 pub use middleware::Middleware;
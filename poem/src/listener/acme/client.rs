@@ -190,7 +190,7 @@ async fn get_directory(
         ));
     }
 
-    let directory = Body(resp.into_body())
+    let directory = Body::from(resp.into_body())
         .into_json::<Directory>()
         .await
         .map_err(|err| {
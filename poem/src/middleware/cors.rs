@@ -192,6 +192,31 @@ impl Cors {
         self.max_age = max_age;
         self
     }
+
+    /// Expose the headers of the tus protocol, and allow the methods and the
+    /// headers of the protocol if the allowed methods or headers are
+    /// restricted, which are required by the browser clients of
+    /// [`TusEndpoint`](crate::endpoint::TusEndpoint).
+    #[cfg(feature = "tus")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+    #[must_use]
+    pub fn allow_tus(mut self) -> Self {
+        use crate::endpoint::tus::{TUS_REQUEST_HEADERS, TUS_RESPONSE_HEADERS};
+
+        if !self.allow_methods.is_empty() {
+            self = self.allow_methods([
+                Method::POST,
+                Method::HEAD,
+                Method::PATCH,
+                Method::DELETE,
+                Method::OPTIONS,
+            ]);
+        }
+        if !self.allow_headers.is_empty() {
+            self = self.allow_headers(TUS_REQUEST_HEADERS.iter().copied());
+        }
+        self.expose_headers(TUS_RESPONSE_HEADERS.iter().copied())
+    }
 }
 
 impl<E: Endpoint> Middleware<E> for Cors {
@@ -0,0 +1,547 @@
+use std::{str::FromStr, sync::Arc};
+
+use crc::{Crc, CRC_64_XZ};
+use headers::{
+    HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince, LastModified,
+};
+use hyper::body::HttpBody;
+
+use crate::{
+    http::{header, HeaderValue, Method, StatusCode},
+    Body, Endpoint, Error, IntoResponse, Middleware, Request, Response, Result,
+};
+
+type HashFn = Arc<dyn Fn(&[u8]) -> String + Send + Sync>;
+
+/// Middleware for generating `ETag` headers and handling conditional
+/// requests.
+///
+/// For `GET` and `HEAD` requests, successful responses with a known body size
+/// up to [`ETag::max_body_size`] are buffered and an `ETag` header is computed
+/// from the body, unless the endpoint already set one. If the request
+/// contains a matching `If-None-Match` header (or `If-Modified-Since` when the
+/// response has a `Last-Modified` header), `304 Not Modified` is returned
+/// instead.
+///
+/// The `If-Match` and `If-Unmodified-Since` preconditions of the `PUT`,
+/// `PATCH` and `DELETE` requests should be checked by the handlers with the
+/// [`Preconditions`](crate::web::Preconditions) extractor, so that the check
+/// and the update of the resource are atomic. See
+/// [`ETag::check_preconditions`] for checking them in this middleware.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     http::{header, StatusCode},
+///     middleware::ETag,
+///     test::TestClient,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new().at("/", get(index)).with(ETag::new());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// resp.assert_status_is_ok();
+/// let etag = resp.0.headers().get(header::ETAG).unwrap().clone();
+///
+/// let resp = cli
+///     .get("/")
+///     .header(header::IF_NONE_MATCH, etag)
+///     .send()
+///     .await;
+/// resp.assert_status(StatusCode::NOT_MODIFIED);
+/// # });
+/// ```
+pub struct ETag {
+    weak: bool,
+    max_body_size: usize,
+    check_preconditions: bool,
+    hasher: HashFn,
+}
+
+impl Default for ETag {
+    fn default() -> Self {
+        Self {
+            weak: false,
+            max_body_size: 1024 * 1024,
+            check_preconditions: false,
+            hasher: Arc::new(default_hash),
+        }
+    }
+}
+
+impl ETag {
+    /// Create `ETag` middleware.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Generates weak validators (`W/"..."`). Default is `false`.
+    ///
+    /// Weak validators never match the `If-Match` header, except for `*`.
+    #[must_use]
+    pub fn weak(self, weak: bool) -> Self {
+        Self { weak, ..self }
+    }
+
+    /// Sets the maximum body size of responses for which an `ETag` is
+    /// computed. Default is `1MB`.
+    #[must_use]
+    pub fn max_body_size(self, size: usize) -> Self {
+        Self {
+            max_body_size: size,
+            ..self
+        }
+    }
+
+    /// Sets whether to check the `If-Match` and `If-Unmodified-Since`
+    /// preconditions for `PUT`, `PATCH` and `DELETE` requests. Default is
+    /// `false`.
+    ///
+    /// If enabled, the current representation of the resource is fetched with
+    /// a `GET` request to the inner endpoint, and `412 Precondition Failed` is
+    /// returned if the precondition does not pass. The preconditions are not
+    /// checked if the resource has no `GET` handler.
+    ///
+    /// Note that:
+    ///
+    /// - The `GET` handler is called for every conditional request.
+    /// - The `GET` request does not carry the extensions of the original
+    ///   request, so data required by the inner endpoint must be added inside
+    ///   this middleware.
+    /// - The check and the update are not atomic, so the concurrent updates
+    ///   with the same precondition can all pass. Use the
+    ///   [`Preconditions`](crate::web::Preconditions) extractor in the
+    ///   handlers for optimistic concurrency control.
+    #[must_use]
+    pub fn check_preconditions(self, value: bool) -> Self {
+        Self {
+            check_preconditions: value,
+            ..self
+        }
+    }
+
+    /// Sets the function to hash the response body, the returned string is
+    /// used as the opaque tag, and must only contain characters allowed in
+    /// an `ETag`.
+    ///
+    /// The default hasher is CRC-64/XZ, whose result is the same across
+    /// builds and instances.
+    #[must_use]
+    pub fn hasher(self, f: impl Fn(&[u8]) -> String + Send + Sync + 'static) -> Self {
+        Self {
+            hasher: Arc::new(f),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ETag {
+    type Output = ETagEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ETagEndpoint {
+            inner: ep,
+            weak: self.weak,
+            max_body_size: self.max_body_size,
+            check_preconditions: self.check_preconditions,
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+/// Endpoint for ETag middleware.
+pub struct ETagEndpoint<E> {
+    inner: E,
+    weak: bool,
+    max_body_size: usize,
+    check_preconditions: bool,
+    hasher: HashFn,
+}
+
+impl<E: Endpoint> ETagEndpoint<E> {
+    /// Calls the inner endpoint and adds the `ETag` header to the response if
+    /// possible.
+    async fn call_with_etag(&self, req: Request) -> Result<(Response, Option<headers::ETag>)> {
+        let resp = self.inner.call(req).await?.into_response();
+
+        if let Some(etag) = resp.headers().typed_get::<headers::ETag>() {
+            return Ok((resp, Some(etag)));
+        }
+
+        if !resp.status().is_success() || resp.status() == StatusCode::NO_CONTENT {
+            return Ok((resp, None));
+        }
+
+        let (mut parts, body) = resp.into_parts();
+        match body.0.size_hint().upper() {
+            Some(size) if size as usize <= self.max_body_size => {}
+            _ => return Ok((Response::from_parts(parts, body), None)),
+        }
+
+        let data = body.into_bytes().await?;
+        let tag = (self.hasher)(&data);
+        let value = if self.weak {
+            format!("W/\"{}\"", tag)
+        } else {
+            format!("\"{}\"", tag)
+        };
+
+        let etag = headers::ETag::from_str(&value).ok();
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            parts.headers.insert(header::ETAG, value);
+        }
+        Ok((Response::from_parts(parts, Body::from(data)), etag))
+    }
+
+    async fn check_preconditions(&self, req: &Request) -> Result<()> {
+        let if_match = req.headers().typed_get::<IfMatch>();
+        let if_unmodified_since = req.headers().typed_get::<IfUnmodifiedSince>();
+        if if_match.is_none() && if_unmodified_since.is_none() {
+            return Ok(());
+        }
+
+        let mut current = req.clone_head();
+        current.set_method(Method::GET);
+        for name in [
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            header::IF_UNMODIFIED_SINCE,
+            header::CONTENT_TYPE,
+            header::CONTENT_LENGTH,
+        ] {
+            current.headers_mut().remove(name);
+        }
+
+        let current = match self.call_with_etag(current).await {
+            Ok((resp, etag)) if resp.status().is_success() => Some((resp, etag)),
+            Ok((resp, _)) if resp.status() == StatusCode::METHOD_NOT_ALLOWED => return Ok(()),
+            Err(err) if err.as_response().status() == StatusCode::METHOD_NOT_ALLOWED => {
+                return Ok(())
+            }
+            _ => None,
+        };
+
+        let passes = match (if_match, &current) {
+            (Some(if_match), Some((_, etag))) => {
+                if_match == IfMatch::any()
+                    || matches!(etag, Some(etag) if if_match.precondition_passes(etag))
+            }
+            (Some(_), None) => false,
+            // `If-Unmodified-Since` is ignored if the resource has no
+            // modification date, see RFC 9110 section 13.1.4.
+            (None, current) => match (
+                if_unmodified_since,
+                current
+                    .as_ref()
+                    .and_then(|(resp, _)| resp.headers().typed_get::<LastModified>()),
+            ) {
+                (Some(if_unmodified_since), Some(last_modified)) => {
+                    if_unmodified_since.precondition_passes(last_modified.into())
+                }
+                _ => true,
+            },
+        };
+
+        if !passes {
+            return Err(Error::from_status(StatusCode::PRECONDITION_FAILED));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ETagEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        match *req.method() {
+            Method::GET | Method::HEAD => {
+                let if_none_match = req.headers().typed_get::<IfNoneMatch>();
+                let if_modified_since = req.headers().typed_get::<IfModifiedSince>();
+                let (resp, etag) = self.call_with_etag(req).await?;
+
+                if !resp.status().is_success() {
+                    return Ok(resp);
+                }
+
+                let modified = if let Some(if_none_match) = if_none_match {
+                    match &etag {
+                        Some(etag) => if_none_match.precondition_passes(etag),
+                        None => true,
+                    }
+                } else if let Some(if_modified_since) = if_modified_since {
+                    match resp.headers().typed_get::<LastModified>() {
+                        Some(last_modified) => if_modified_since.is_modified(last_modified.into()),
+                        None => true,
+                    }
+                } else {
+                    true
+                };
+
+                if modified {
+                    Ok(resp)
+                } else {
+                    Ok(not_modified(resp))
+                }
+            }
+            Method::PUT | Method::PATCH | Method::DELETE if self.check_preconditions => {
+                self.check_preconditions(&req).await?;
+                self.inner.call(req).await.map(IntoResponse::into_response)
+            }
+            _ => self.inner.call(req).await.map(IntoResponse::into_response),
+        }
+    }
+}
+
+fn not_modified(resp: Response) -> Response {
+    let (mut parts, _) = resp.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    for name in [
+        header::CONTENT_LENGTH,
+        header::CONTENT_TYPE,
+        header::TRANSFER_ENCODING,
+    ] {
+        parts.headers.remove(name);
+    }
+    Response::from_parts(parts, Body::empty())
+}
+
+const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_XZ);
+
+fn default_hash(data: &[u8]) -> String {
+    format!("{:x}-{:016x}", data.len(), CRC64.checksum(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::{
+        endpoint::make_sync, handler, http::StatusCode, test::TestClient, web::Data, EndpointExt,
+        Route,
+    };
+
+    async fn get_etag(cli: &TestClient<impl Endpoint>, uri: &str) -> String {
+        let resp = cli.get(uri).send().await;
+        resp.assert_status_is_ok();
+        resp.0
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn conditional_get() {
+        let cli = TestClient::new(make_sync(|_| "hello").with(ETag::new()));
+
+        let etag = get_etag(&cli, "/").await;
+        assert!(!etag.starts_with("W/"));
+
+        let resp = cli
+            .get("/")
+            .header(header::IF_NONE_MATCH, &etag)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NOT_MODIFIED);
+        resp.assert_header(header::ETAG, &etag);
+        resp.assert_header_is_not_exist(header::CONTENT_TYPE);
+        resp.assert_text("").await;
+
+        let resp = cli
+            .get("/")
+            .header(header::IF_NONE_MATCH, "\"abc\"")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text("hello").await;
+    }
+
+    #[test]
+    fn stable_default_hash() {
+        assert_eq!(default_hash(b"123456789"), "9-995dc9bbdf1939fa");
+    }
+
+    #[tokio::test]
+    async fn weak_etag_and_custom_hasher() {
+        let cli = TestClient::new(
+            make_sync(|_| "hello")
+                .with(ETag::new().weak(true).hasher(|data| data.len().to_string())),
+        );
+        assert_eq!(get_etag(&cli, "/").await, "W/\"5\"");
+
+        cli.get("/")
+            .header(header::IF_NONE_MATCH, "\"5\"")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn skip_large_body() {
+        let cli = TestClient::new(make_sync(|_| "hello").with(ETag::new().max_body_size(4)));
+        cli.get("/")
+            .send()
+            .await
+            .assert_header_is_not_exist(header::ETAG);
+    }
+
+    #[tokio::test]
+    async fn keep_existing_etag() {
+        let cli = TestClient::new(
+            make_sync(|_| {
+                Response::builder()
+                    .header(header::ETAG, "\"v1\"")
+                    .body("hello")
+            })
+            .with(ETag::new()),
+        );
+        assert_eq!(get_etag(&cli, "/").await, "\"v1\"");
+        cli.get("/")
+            .header(header::IF_NONE_MATCH, "\"v1\"")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn if_match() {
+        #[handler(internal)]
+        fn get(value: Data<&Arc<AtomicUsize>>) -> String {
+            value.load(Ordering::SeqCst).to_string()
+        }
+
+        #[handler(internal)]
+        fn put(value: Data<&Arc<AtomicUsize>>) {
+            value.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let value = Arc::new(AtomicUsize::new(0));
+        let app = Route::new()
+            .at("/", crate::get(get).put(put).delete(put))
+            .data(value.clone())
+            .with(ETag::new().check_preconditions(true));
+        let cli = TestClient::new(app);
+
+        let etag = get_etag(&cli, "/").await;
+
+        cli.put("/")
+            .header(header::IF_MATCH, "\"abc\"")
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+        assert_eq!(value.load(Ordering::SeqCst), 0);
+
+        cli.put("/")
+            .header(header::IF_MATCH, &etag)
+            .send()
+            .await
+            .assert_status_is_ok();
+        assert_eq!(value.load(Ordering::SeqCst), 1);
+
+        cli.delete("/")
+            .header(header::IF_MATCH, &etag)
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+        assert_eq!(value.load(Ordering::SeqCst), 1);
+
+        cli.delete("/")
+            .header(header::IF_MATCH, "*")
+            .send()
+            .await
+            .assert_status_is_ok();
+        assert_eq!(value.load(Ordering::SeqCst), 2);
+
+        cli.put("/").send().await.assert_status_is_ok();
+        assert_eq!(value.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn if_unmodified_since() {
+        let last_modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let app = Route::new()
+            .at(
+                "/",
+                crate::get(make_sync(move |_| {
+                    let mut resp = Response::builder().body("hello");
+                    resp.headers_mut()
+                        .typed_insert(LastModified::from(last_modified));
+                    resp
+                }))
+                .put(make_sync(|_| "updated")),
+            )
+            .at(
+                "/no_date",
+                crate::get(make_sync(|_| "hello")).put(make_sync(|_| "updated")),
+            )
+            .with(ETag::new().check_preconditions(true));
+        let cli = TestClient::new(app);
+
+        let before = IfUnmodifiedSince::from(last_modified - Duration::from_secs(60));
+        cli.put("/")
+            .typed_header(before)
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+        cli.put("/")
+            .typed_header(IfUnmodifiedSince::from(last_modified))
+            .send()
+            .await
+            .assert_text("updated")
+            .await;
+        cli.put("/no_date")
+            .typed_header(before)
+            .send()
+            .await
+            .assert_text("updated")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn preconditions_are_opt_in() {
+        let cli = TestClient::new(
+            Route::new()
+                .at("/", crate::put(make_sync(|_| "updated")))
+                .with(ETag::new()),
+        );
+        cli.put("/")
+            .header(header::IF_MATCH, "\"abc\"")
+            .send()
+            .await
+            .assert_text("updated")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn skip_preconditions_without_get() {
+        let cli = TestClient::new(
+            Route::new()
+                .at("/", crate::put(make_sync(|_| "updated")))
+                .with(ETag::new().check_preconditions(true)),
+        );
+        cli.put("/")
+            .header(header::IF_MATCH, "\"abc\"")
+            .send()
+            .await
+            .assert_text("updated")
+            .await;
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    sync::Arc,
+};
+
+use parking_lot::RwLock;
+
+use crate::{
+    error::{IpFilterError, ParseIpFilterError},
+    Addr, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// An IP network in CIDR notation, such as `192.168.0.0/16` or `fd00::/8`.
+///
+/// A plain IP address is parsed as a network that contains only that
+/// address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Create an `IpCidr` from an address and a prefix length, the host bits
+    /// of the address are cleared.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, ParseIpFilterError> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(ParseIpFilterError::InvalidPrefixLength(
+                prefix_len.to_string(),
+            ));
+        }
+
+        let addr = match addr {
+            IpAddr::V4(addr) => IpAddr::V4(Ipv4Addr::from(u32::from(addr) & v4_mask(prefix_len))),
+            IpAddr::V6(addr) => IpAddr::V6(Ipv6Addr::from(u128::from(addr) & v6_mask(prefix_len))),
+        };
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Returns the network address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the prefix length.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns `true` if the network contains the specified address.
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) are matched against
+    /// IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, normalize_ip(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & v4_mask(self.prefix_len) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & v6_mask(self.prefix_len) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpCidr {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { addr, prefix_len }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = ParseIpFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.split_once('/') {
+            Some((addr, prefix_len)) => {
+                let addr = addr
+                    .parse::<IpAddr>()
+                    .map_err(|_| ParseIpFilterError::InvalidAddress(addr.to_string()))?;
+                let prefix_len = prefix_len
+                    .parse::<u8>()
+                    .map_err(|_| ParseIpFilterError::InvalidPrefixLength(prefix_len.to_string()))?;
+                IpCidr::new(addr, prefix_len)
+            }
+            None => s
+                .parse::<IpAddr>()
+                .map(Into::into)
+                .map_err(|_| ParseIpFilterError::InvalidAddress(s.to_string())),
+        }
+    }
+}
+
+impl Display for IpCidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+fn v4_mask(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn v6_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+fn normalize_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, _, _] => IpAddr::V4(Ipv4Addr::from(u128::from(v6) as u32)),
+            _ => ip,
+        },
+        ip => ip,
+    }
+}
+
+#[derive(Clone, Default)]
+struct Rules {
+    allow: Vec<IpCidr>,
+    deny: Vec<IpCidr>,
+}
+
+/// The allow and deny lists used by the [`IpFilter`] middleware, which can be
+/// reloaded at runtime.
+///
+/// An address is allowed if it is not in the deny list, and either the allow
+/// list is empty or the address is in the allow list.
+///
+/// # Example
+///
+/// ```
+/// use poem::middleware::IpFilterRules;
+///
+/// let rules = IpFilterRules::new();
+/// rules
+///     .reload_from_str(
+///         r#"
+///         ## office
+///         allow 203.0.113.0/24
+///         ## vpn
+///         allow 10.8.0.0/16
+///         deny 10.8.1.1
+///         "#,
+///     )
+///     .unwrap();
+///
+/// assert!(rules.is_allowed("203.0.113.10".parse().unwrap()));
+/// assert!(!rules.is_allowed("10.8.1.1".parse().unwrap()));
+/// assert!(!rules.is_allowed("198.51.100.1".parse().unwrap()));
+/// ```
+#[derive(Clone, Default)]
+pub struct IpFilterRules {
+    inner: Arc<RwLock<Rules>>,
+}
+
+impl IpFilterRules {
+    /// Create an empty `IpFilterRules` that allows all addresses.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Replaces the allow list.
+    pub fn set_allow(&self, allow: impl IntoIterator<Item = IpCidr>) {
+        self.inner.write().allow = allow.into_iter().collect();
+    }
+
+    /// Replaces the deny list.
+    pub fn set_deny(&self, deny: impl IntoIterator<Item = IpCidr>) {
+        self.inner.write().deny = deny.into_iter().collect();
+    }
+
+    /// Replaces both lists with the rules parsed from a string.
+    ///
+    /// Each line contains a rule in the form of `allow <cidr>` or
+    /// `deny <cidr>`, empty lines and lines starting with `#` are ignored. If
+    /// any rule is invalid, the current lists are left unchanged.
+    pub fn reload_from_str(&self, s: &str) -> Result<(), ParseIpFilterError> {
+        let mut rules = Rules::default();
+
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(char::is_whitespace) {
+                Some(("allow", cidr)) => rules.allow.push(cidr.parse()?),
+                Some(("deny", cidr)) => rules.deny.push(cidr.parse()?),
+                _ => return Err(ParseIpFilterError::InvalidRule(line.to_string())),
+            }
+        }
+
+        *self.inner.write() = rules;
+        Ok(())
+    }
+
+    /// Returns `true` if the address is allowed.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        let rules = self.inner.read();
+        if rules.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return false;
+        }
+        rules.allow.is_empty() || rules.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+/// The policy for requests whose remote address is not an IP address, such as
+/// requests from Unix domain sockets.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NonIpPolicy {
+    /// Allow the request.
+    Allow,
+    /// Reject the request.
+    Deny,
+    /// Treat the peer as a trusted proxy, and check the client address in the
+    /// `X-Forwarded-For` header. The request is rejected if all the addresses
+    /// in the header are trusted proxies.
+    TrustForwarded,
+}
+
+type RejectFn = Arc<dyn Fn(&Request) -> Response + Send + Sync>;
+
+/// Middleware for restricting access by the IP address of the client.
+///
+/// If the remote address is in one of the
+/// [trusted proxies](IpFilter::trusted_proxies), the client address is
+/// resolved from the `X-Forwarded-For` header, using the rightmost address
+/// that is not a trusted proxy. If all the addresses are trusted proxies, the
+/// remote address is used, because the leftmost addresses can be spoofed by
+/// the client.
+///
+/// # Errors
+///
+/// - [`IpFilterError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     middleware::{IpFilter, NonIpPolicy},
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new().at("/", get(index)).with(
+///     IpFilter::new()
+///         .allow("10.0.0.0/8")
+///         .deny("10.0.0.1")
+///         .trusted_proxies(["127.0.0.1"])
+///         .non_ip_policy(NonIpPolicy::TrustForwarded),
+/// );
+/// ```
+pub struct IpFilter {
+    rules: IpFilterRules,
+    trusted_proxies: Vec<IpCidr>,
+    non_ip_policy: NonIpPolicy,
+    on_rejected: Option<RejectFn>,
+}
+
+impl Default for IpFilter {
+    fn default() -> Self {
+        Self::with_rules(IpFilterRules::new())
+    }
+}
+
+impl IpFilter {
+    /// Create `IpFilter` middleware.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create `IpFilter` middleware with the specified rules, which can be
+    /// reloaded later.
+    pub fn with_rules(rules: IpFilterRules) -> Self {
+        Self {
+            rules,
+            trusted_proxies: Vec::new(),
+            non_ip_policy: NonIpPolicy::Deny,
+            on_rejected: None,
+        }
+    }
+
+    /// Adds a network to the allow list.
+    ///
+    /// The rules passed to [`IpFilter::with_rules`] are copied instead of
+    /// modified, so the other middlewares sharing them are not affected, and
+    /// reloading them no longer affects this middleware.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid IP network.
+    #[must_use]
+    pub fn allow(self, cidr: impl AsRef<str>) -> Self {
+        let cidr = parse_cidr(cidr.as_ref());
+        self.update_rules(|rules| rules.allow.push(cidr))
+    }
+
+    /// Adds a network to the deny list.
+    ///
+    /// The rules are copied like [`IpFilter::allow`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid IP network.
+    #[must_use]
+    pub fn deny(self, cidr: impl AsRef<str>) -> Self {
+        let cidr = parse_cidr(cidr.as_ref());
+        self.update_rules(|rules| rules.deny.push(cidr))
+    }
+
+    fn update_rules(self, f: impl FnOnce(&mut Rules)) -> Self {
+        let mut rules = self.rules.inner.read().clone();
+        f(&mut rules);
+        Self {
+            rules: IpFilterRules {
+                inner: Arc::new(RwLock::new(rules)),
+            },
+            ..self
+        }
+    }
+
+    /// Sets the networks of the trusted proxies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any item is not a valid IP network.
+    #[must_use]
+    pub fn trusted_proxies<I, T>(self, proxies: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        Self {
+            trusted_proxies: proxies
+                .into_iter()
+                .map(|cidr| parse_cidr(cidr.as_ref()))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Sets the policy for requests whose remote address is not an IP
+    /// address. Default is [`NonIpPolicy::Deny`].
+    #[must_use]
+    pub fn non_ip_policy(self, policy: NonIpPolicy) -> Self {
+        Self {
+            non_ip_policy: policy,
+            ..self
+        }
+    }
+
+    /// Sets a function to create the response for rejected requests.
+    #[must_use]
+    pub fn on_rejected(self, f: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Self {
+            on_rejected: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Returns the rules used by this middleware.
+    pub fn rules(&self) -> IpFilterRules {
+        self.rules.clone()
+    }
+}
+
+fn parse_cidr(cidr: &str) -> IpCidr {
+    match cidr.parse() {
+        Ok(cidr) => cidr,
+        Err(err) => panic!("invalid ip network `{}`: {}", cidr, err),
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for IpFilter {
+    type Output = IpFilterEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        IpFilterEndpoint {
+            inner: ep,
+            rules: self.rules.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+            non_ip_policy: self.non_ip_policy,
+            on_rejected: self.on_rejected.clone(),
+        }
+    }
+}
+
+/// Endpoint for IpFilter middleware.
+pub struct IpFilterEndpoint<E> {
+    inner: E,
+    rules: IpFilterRules,
+    trusted_proxies: Vec<IpCidr>,
+    non_ip_policy: NonIpPolicy,
+    on_rejected: Option<RejectFn>,
+}
+
+impl<E> IpFilterEndpoint<E> {
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Returns the rightmost address in `X-Forwarded-For` that is not a
+    /// trusted proxy, or `peer` if there is no such address.
+    fn forwarded_client_ip(&self, req: &Request, peer: Option<IpAddr>) -> Option<IpAddr> {
+        let forwarded = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted_proxy(**ip))
+            .copied()
+            .or(peer)
+    }
+
+    fn is_allowed(&self, req: &Request) -> bool {
+        let client_ip = match &req.remote_addr().0 {
+            Addr::SocketAddr(addr) if self.is_trusted_proxy(addr.ip()) => {
+                if req.headers().contains_key("x-forwarded-for") {
+                    self.forwarded_client_ip(req, Some(addr.ip()))
+                } else {
+                    Some(addr.ip())
+                }
+            }
+            Addr::SocketAddr(addr) => Some(addr.ip()),
+            _ => match self.non_ip_policy {
+                NonIpPolicy::Allow => return true,
+                NonIpPolicy::Deny => return false,
+                NonIpPolicy::TrustForwarded => self.forwarded_client_ip(req, None),
+            },
+        };
+
+        match client_ip {
+            Some(ip) => self.rules.is_allowed(ip),
+            None => false,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for IpFilterEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if !self.is_allowed(&req) {
+            return match &self.on_rejected {
+                Some(on_rejected) => Ok(on_rejected(&req)),
+                None => Err(IpFilterError.into()),
+            };
+        }
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::{endpoint::make_sync, http::StatusCode, web::RemoteAddr, EndpointExt};
+
+    #[test]
+    fn parse_cidr() {
+        let cidr = "192.168.1.7/16".parse::<IpCidr>().unwrap();
+        assert_eq!(cidr.to_string(), "192.168.0.0/16");
+        assert!(cidr.contains("192.168.200.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:192.168.200.1".parse().unwrap()));
+        assert!(!cidr.contains("192.169.0.1".parse().unwrap()));
+
+        let cidr = "fd00::/8".parse::<IpCidr>().unwrap();
+        assert!(cidr.contains("fd12:3456::1".parse().unwrap()));
+        assert!(!cidr.contains("fe80::1".parse().unwrap()));
+
+        let cidr = "0.0.0.0/0".parse::<IpCidr>().unwrap();
+        assert!(cidr.contains("8.8.8.8".parse().unwrap()));
+
+        assert_eq!(
+            "10.0.0.1".parse::<IpCidr>().unwrap().to_string(),
+            "10.0.0.1/32"
+        );
+        assert_eq!(
+            "10.0.0.1/33".parse::<IpCidr>(),
+            Err(ParseIpFilterError::InvalidPrefixLength("33".to_string()))
+        );
+        assert_eq!(
+            "abc/8".parse::<IpCidr>(),
+            Err(ParseIpFilterError::InvalidAddress("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn reload_rules() {
+        let rules = IpFilterRules::new();
+        assert!(rules.is_allowed("1.2.3.4".parse().unwrap()));
+
+        rules.reload_from_str("deny 1.2.3.0/24").unwrap();
+        assert!(!rules.is_allowed("1.2.3.4".parse().unwrap()));
+        assert!(rules.is_allowed("1.2.4.4".parse().unwrap()));
+
+        assert_eq!(
+            rules.reload_from_str("permit 1.2.3.4"),
+            Err(ParseIpFilterError::InvalidRule(
+                "permit 1.2.3.4".to_string()
+            ))
+        );
+        assert!(!rules.is_allowed("1.2.3.4".parse().unwrap()));
+    }
+
+    async fn call_from(ep: &impl Endpoint, addr: Addr, forwarded_for: Option<&str>) -> StatusCode {
+        let mut req = Request::builder();
+        if let Some(forwarded_for) = forwarded_for {
+            req = req.header("x-forwarded-for", forwarded_for);
+        }
+        let mut req = req.finish();
+        req.state_mut().remote_addr = RemoteAddr(addr);
+        ep.get_response(req).await.status()
+    }
+
+    fn socket_addr(ip: &str) -> Addr {
+        Addr::SocketAddr(SocketAddr::new(ip.parse().unwrap(), 1234))
+    }
+
+    #[tokio::test]
+    async fn filter() {
+        let ep = make_sync(|_| ()).with(IpFilter::new().allow("10.0.0.0/8").deny("10.0.0.1"));
+
+        assert_eq!(
+            call_from(&ep, socket_addr("10.1.1.1"), None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_from(&ep, socket_addr("10.0.0.1"), None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.0.1"), Some("10.1.1.1")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(&ep, Addr::custom("test", "a"), None).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn trusted_proxies() {
+        let ep = make_sync(|_| ()).with(
+            IpFilter::new()
+                .allow("10.0.0.0/8")
+                .trusted_proxies(["192.168.0.0/16"])
+                .non_ip_policy(NonIpPolicy::TrustForwarded),
+        );
+
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.0.1"), Some("10.1.1.1")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_from(
+                &ep,
+                socket_addr("192.168.0.1"),
+                Some("10.1.1.1, 8.8.8.8, 192.168.0.2")
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(
+                &ep,
+                socket_addr("192.168.0.1"),
+                Some("8.8.8.8, 10.1.1.1, 192.168.0.2")
+            )
+            .await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.0.1"), Some("invalid")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.0.1"), None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(&ep, Addr::custom("test", "a"), Some("10.1.1.1")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_from(&ep, Addr::custom("test", "a"), None).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn all_forwarded_addresses_trusted() {
+        let ep = make_sync(|_| ()).with(
+            IpFilter::new()
+                .allow("192.168.1.0/24")
+                .trusted_proxies(["192.168.0.0/16"])
+                .non_ip_policy(NonIpPolicy::TrustForwarded),
+        );
+
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.1.1"), Some("192.168.0.2")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_from(&ep, socket_addr("192.168.0.1"), Some("192.168.1.2")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_from(&ep, Addr::custom("test", "a"), Some("192.168.1.2")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn builder_copies_shared_rules() {
+        let rules = IpFilterRules::new();
+        rules.reload_from_str("deny 1.2.3.4").unwrap();
+        let filter = IpFilter::with_rules(rules.clone()).deny("5.6.7.8");
+
+        assert!(rules.is_allowed("5.6.7.8".parse().unwrap()));
+        assert!(!filter.rules().is_allowed("5.6.7.8".parse().unwrap()));
+        assert!(!filter.rules().is_allowed("1.2.3.4".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn custom_rejection_and_reload() {
+        let filter = IpFilter::new()
+            .deny("10.0.0.1")
+            .non_ip_policy(NonIpPolicy::Allow)
+            .on_rejected(|_| StatusCode::NOT_FOUND.into());
+        let rules = filter.rules();
+        let ep = make_sync(|_| ()).with(filter);
+
+        assert_eq!(
+            call_from(&ep, socket_addr("10.0.0.1"), None).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            call_from(&ep, Addr::custom("test", "a"), None).await,
+            StatusCode::OK
+        );
+
+        rules.set_deny([]);
+        assert_eq!(
+            call_from(&ep, socket_addr("10.0.0.1"), None).await,
+            StatusCode::OK
+        );
+    }
+}
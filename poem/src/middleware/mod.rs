@@ -8,13 +8,17 @@ mod cookie_jar_manager;
 mod cors;
 #[cfg(feature = "csrf")]
 mod csrf;
+mod etag;
 mod force_https;
+mod ip_filter;
 mod normalize_path;
 #[cfg(feature = "opentelemetry")]
 mod opentelemetry_metrics;
 #[cfg(feature = "opentelemetry")]
 mod opentelemetry_tracing;
 mod propagate_header;
+#[cfg(feature = "security-headers")]
+mod security_headers;
 mod sensitive_header;
 mod set_header;
 mod size_limit;
@@ -37,12 +41,20 @@ pub use self::csrf::{Csrf, CsrfEndpoint};
 pub use self::opentelemetry_metrics::{OpenTelemetryMetrics, OpenTelemetryMetricsEndpoint};
 #[cfg(feature = "opentelemetry")]
 pub use self::opentelemetry_tracing::{OpenTelemetryTracing, OpenTelemetryTracingEndpoint};
+#[cfg(feature = "security-headers")]
+pub use self::security_headers::{
+    ContentSecurityPolicy, CrossOriginEmbedderPolicy, CrossOriginOpenerPolicy,
+    CrossOriginResourcePolicy, FrameOptions, Hsts, ReferrerPolicy, SecurityHeaders,
+    SecurityHeadersEndpoint,
+};
 #[cfg(feature = "tower-compat")]
 pub use self::tower_compat::TowerLayerCompatExt;
 pub use self::{
     add_data::{AddData, AddDataEndpoint},
     cors::{Cors, CorsEndpoint},
+    etag::{ETag, ETagEndpoint},
     force_https::ForceHttps,
+    ip_filter::{IpCidr, IpFilter, IpFilterEndpoint, IpFilterRules, NonIpPolicy},
     normalize_path::{NormalizePath, NormalizePathEndpoint, TrailingSlash},
     propagate_header::{PropagateHeader, PropagateHeaderEndpoint},
     sensitive_header::{SensitiveHeader, SensitiveHeaderEndpoint},
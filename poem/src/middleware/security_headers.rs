@@ -0,0 +1,771 @@
+use std::{fmt::Write, time::Duration};
+
+use http::header::{self, HeaderName};
+use rand::{thread_rng, Rng};
+
+use crate::{
+    http::HeaderValue, web::CspNonce, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// The `Strict-Transport-Security` header configuration.
+///
+/// Reference: <https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Strict-Transport-Security>
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Hsts {
+    max_age: Duration,
+    include_sub_domains: bool,
+    preload: bool,
+}
+
+impl Default for Hsts {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(365 * 24 * 60 * 60),
+            include_sub_domains: true,
+            preload: false,
+        }
+    }
+}
+
+impl Hsts {
+    /// Create a new `Hsts` configuration with a `max-age` of one year and
+    /// `includeSubDomains` enabled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the time that the browser should remember that the site is only
+    /// to be accessed using HTTPS.
+    #[must_use]
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self { max_age, ..self }
+    }
+
+    /// Sets whether the rule applies to all of the site's subdomains as well.
+    #[must_use]
+    pub fn include_sub_domains(self, value: bool) -> Self {
+        Self {
+            include_sub_domains: value,
+            ..self
+        }
+    }
+
+    /// Sets the `preload` directive.
+    #[must_use]
+    pub fn preload(self, value: bool) -> Self {
+        Self {
+            preload: value,
+            ..self
+        }
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let mut value = format!("max-age={}", self.max_age.as_secs());
+        if self.include_sub_domains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        HeaderValue::try_from(value).expect("valid header value")
+    }
+}
+
+/// The `Content-Security-Policy` header configuration.
+///
+/// Reference: <https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Security-Policy>
+///
+/// # Example
+///
+/// ```
+/// use poem::middleware::ContentSecurityPolicy;
+///
+/// let csp = ContentSecurityPolicy::new()
+///     .directive("default-src", ["'self'"])
+///     .directive("img-src", ["'self'", "https://example.com"])
+///     .nonce("script-src");
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(String, Vec<String>)>,
+    nonce_directives: Vec<String>,
+    report_only: bool,
+}
+
+impl ContentSecurityPolicy {
+    /// Create an empty `ContentSecurityPolicy`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends sources to a directive.
+    ///
+    /// If the directive already exists, the sources are appended to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or any of the sources is not a valid header value.
+    #[must_use]
+    pub fn directive<I, T>(mut self, name: impl Into<String>, sources: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let name = validate_csp_value(name.into());
+        let sources = sources
+            .into_iter()
+            .map(|source| validate_csp_value(source.into()));
+        match self.directives.iter_mut().find(|(n, _)| n == &name) {
+            Some((_, values)) => values.extend(sources),
+            None => self.directives.push((name, sources.collect())),
+        }
+        self
+    }
+
+    /// Adds a `'nonce-<value>'` source to the specified directive, the nonce
+    /// is generated for each request and can be extracted with
+    /// [`CspNonce`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is not a valid header value.
+    #[must_use]
+    pub fn nonce(mut self, name: impl Into<String>) -> Self {
+        let name = validate_csp_value(name.into());
+        if !self.directives.iter().any(|(n, _)| n == &name) {
+            self.directives.push((name.clone(), Vec::new()));
+        }
+        if !self.nonce_directives.contains(&name) {
+            self.nonce_directives.push(name);
+        }
+        self
+    }
+
+    /// Sends the policy in the `Content-Security-Policy-Report-Only` header
+    /// instead of the `Content-Security-Policy` header.
+    #[must_use]
+    pub fn report_only(self, value: bool) -> Self {
+        Self {
+            report_only: value,
+            ..self
+        }
+    }
+
+    fn header_name(&self) -> HeaderName {
+        if self.report_only {
+            header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            header::CONTENT_SECURITY_POLICY
+        }
+    }
+
+    fn has_nonce(&self) -> bool {
+        !self.nonce_directives.is_empty()
+    }
+
+    fn header_value(&self, nonce: Option<&str>) -> HeaderValue {
+        let mut value = String::new();
+
+        for (name, sources) in &self.directives {
+            if !value.is_empty() {
+                value.push_str("; ");
+            }
+            value.push_str(name);
+            for source in sources {
+                value.push(' ');
+                value.push_str(source);
+            }
+            if let Some(nonce) = nonce {
+                if self.nonce_directives.contains(name) {
+                    let _ = write!(value, " 'nonce-{}'", nonce);
+                }
+            }
+        }
+
+        // The directives and the sources are validated by the builder, and the
+        // nonce is encoded with base64.
+        HeaderValue::try_from(value).expect("valid header value")
+    }
+}
+
+fn validate_csp_value(value: String) -> String {
+    if HeaderValue::from_str(&value).is_err() {
+        panic!("invalid content security policy `{}`", value.escape_debug());
+    }
+    value
+}
+
+/// The `X-Frame-Options` header value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FrameOptions {
+    /// The page cannot be displayed in a frame.
+    Deny,
+    /// The page can only be displayed in a frame on the same origin as the
+    /// page itself.
+    SameOrigin,
+}
+
+impl FrameOptions {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FrameOptions::Deny => "DENY",
+            FrameOptions::SameOrigin => "SAMEORIGIN",
+        }
+    }
+}
+
+/// The `Referrer-Policy` header value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReferrerPolicy {
+    /// `no-referrer`
+    NoReferrer,
+    /// `no-referrer-when-downgrade`
+    NoReferrerWhenDowngrade,
+    /// `origin`
+    Origin,
+    /// `origin-when-cross-origin`
+    OriginWhenCrossOrigin,
+    /// `same-origin`
+    SameOrigin,
+    /// `strict-origin`
+    StrictOrigin,
+    /// `strict-origin-when-cross-origin`
+    StrictOriginWhenCrossOrigin,
+    /// `unsafe-url`
+    UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReferrerPolicy::NoReferrer => "no-referrer",
+            ReferrerPolicy::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            ReferrerPolicy::Origin => "origin",
+            ReferrerPolicy::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            ReferrerPolicy::SameOrigin => "same-origin",
+            ReferrerPolicy::StrictOrigin => "strict-origin",
+            ReferrerPolicy::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            ReferrerPolicy::UnsafeUrl => "unsafe-url",
+        }
+    }
+}
+
+/// The `Cross-Origin-Opener-Policy` header value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CrossOriginOpenerPolicy {
+    /// `unsafe-none`
+    UnsafeNone,
+    /// `same-origin-allow-popups`
+    SameOriginAllowPopups,
+    /// `same-origin`
+    SameOrigin,
+}
+
+impl CrossOriginOpenerPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CrossOriginOpenerPolicy::UnsafeNone => "unsafe-none",
+            CrossOriginOpenerPolicy::SameOriginAllowPopups => "same-origin-allow-popups",
+            CrossOriginOpenerPolicy::SameOrigin => "same-origin",
+        }
+    }
+}
+
+/// The `Cross-Origin-Embedder-Policy` header value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CrossOriginEmbedderPolicy {
+    /// `unsafe-none`
+    UnsafeNone,
+    /// `require-corp`
+    RequireCorp,
+    /// `credentialless`
+    Credentialless,
+}
+
+impl CrossOriginEmbedderPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CrossOriginEmbedderPolicy::UnsafeNone => "unsafe-none",
+            CrossOriginEmbedderPolicy::RequireCorp => "require-corp",
+            CrossOriginEmbedderPolicy::Credentialless => "credentialless",
+        }
+    }
+}
+
+/// The `Cross-Origin-Resource-Policy` header value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CrossOriginResourcePolicy {
+    /// `same-site`
+    SameSite,
+    /// `same-origin`
+    SameOrigin,
+    /// `cross-origin`
+    CrossOrigin,
+}
+
+impl CrossOriginResourcePolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CrossOriginResourcePolicy::SameSite => "same-site",
+            CrossOriginResourcePolicy::SameOrigin => "same-origin",
+            CrossOriginResourcePolicy::CrossOrigin => "cross-origin",
+        }
+    }
+}
+
+/// Middleware for setting security related headers to response.
+///
+/// By default, the following headers are sent:
+///
+/// - `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+/// - `X-Frame-Options: SAMEORIGIN`
+/// - `X-Content-Type-Options: nosniff`
+/// - `Referrer-Policy: strict-origin-when-cross-origin`
+///
+/// Headers that have already been set by the inner endpoint are not
+/// overridden.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     middleware::{ContentSecurityPolicy, FrameOptions, SecurityHeaders},
+///     test::TestClient,
+///     web::{CspNonce, Html},
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index(nonce: &CspNonce) -> Html<String> {
+///     Html(format!(
+///         r#"<script nonce="{}">console.log("hello")</script>"#,
+///         nonce.as_str()
+///     ))
+/// }
+///
+/// let app = Route::new().at("/", get(index)).with(
+///     SecurityHeaders::new()
+///         .frame_options(FrameOptions::Deny)
+///         .content_security_policy(
+///             ContentSecurityPolicy::new()
+///                 .directive("default-src", ["'self'"])
+///                 .nonce("script-src"),
+///         ),
+/// );
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app).get("/").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_header("x-frame-options", "DENY");
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "security-headers")))]
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    hsts: Option<Hsts>,
+    csp: Option<ContentSecurityPolicy>,
+    frame_options: Option<FrameOptions>,
+    content_type_options: bool,
+    referrer_policy: Option<ReferrerPolicy>,
+    permissions_policy: Option<HeaderValue>,
+    coop: Option<CrossOriginOpenerPolicy>,
+    coep: Option<CrossOriginEmbedderPolicy>,
+    corp: Option<CrossOriginResourcePolicy>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            hsts: Some(Hsts::default()),
+            csp: None,
+            frame_options: Some(FrameOptions::SameOrigin),
+            content_type_options: true,
+            referrer_policy: Some(ReferrerPolicy::StrictOriginWhenCrossOrigin),
+            permissions_policy: None,
+            coop: None,
+            coep: None,
+            corp: None,
+        }
+    }
+}
+
+impl SecurityHeaders {
+    /// Create new `SecurityHeaders` middleware.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the `Strict-Transport-Security` header, `None` means that the
+    /// header is not sent.
+    #[must_use]
+    pub fn hsts(self, hsts: impl Into<Option<Hsts>>) -> Self {
+        Self {
+            hsts: hsts.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Content-Security-Policy` header, `None` means that the
+    /// header is not sent.
+    #[must_use]
+    pub fn content_security_policy(self, csp: impl Into<Option<ContentSecurityPolicy>>) -> Self {
+        Self {
+            csp: csp.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `X-Frame-Options` header, `None` means that the header is
+    /// not sent.
+    #[must_use]
+    pub fn frame_options(self, value: impl Into<Option<FrameOptions>>) -> Self {
+        Self {
+            frame_options: value.into(),
+            ..self
+        }
+    }
+
+    /// Sets whether to send `X-Content-Type-Options: nosniff`. Default is
+    /// `true`.
+    #[must_use]
+    pub fn content_type_options(self, value: bool) -> Self {
+        Self {
+            content_type_options: value,
+            ..self
+        }
+    }
+
+    /// Sets the `Referrer-Policy` header, `None` means that the header is
+    /// not sent.
+    #[must_use]
+    pub fn referrer_policy(self, value: impl Into<Option<ReferrerPolicy>>) -> Self {
+        Self {
+            referrer_policy: value.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Permissions-Policy` header.
+    ///
+    /// For example: `geolocation=(), camera=(self)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not a valid header value.
+    #[must_use]
+    pub fn permissions_policy(self, value: impl AsRef<str>) -> Self {
+        let value = value.as_ref();
+        let permissions_policy = match HeaderValue::from_str(value) {
+            Ok(permissions_policy) => permissions_policy,
+            Err(_) => panic!("invalid permissions policy `{}`", value.escape_debug()),
+        };
+        Self {
+            permissions_policy: Some(permissions_policy),
+            ..self
+        }
+    }
+
+    /// Sets the `Cross-Origin-Opener-Policy` header.
+    #[must_use]
+    pub fn cross_origin_opener_policy(
+        self,
+        value: impl Into<Option<CrossOriginOpenerPolicy>>,
+    ) -> Self {
+        Self {
+            coop: value.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Cross-Origin-Embedder-Policy` header.
+    #[must_use]
+    pub fn cross_origin_embedder_policy(
+        self,
+        value: impl Into<Option<CrossOriginEmbedderPolicy>>,
+    ) -> Self {
+        Self {
+            coep: value.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Cross-Origin-Resource-Policy` header.
+    #[must_use]
+    pub fn cross_origin_resource_policy(
+        self,
+        value: impl Into<Option<CrossOriginResourcePolicy>>,
+    ) -> Self {
+        Self {
+            corp: value.into(),
+            ..self
+        }
+    }
+
+    fn static_headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = Vec::new();
+
+        if let Some(hsts) = &self.hsts {
+            headers.push((header::STRICT_TRANSPORT_SECURITY, hsts.header_value()));
+        }
+        if let Some(frame_options) = self.frame_options {
+            headers.push((
+                header::X_FRAME_OPTIONS,
+                HeaderValue::from_static(frame_options.as_str()),
+            ));
+        }
+        if self.content_type_options {
+            headers.push((
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ));
+        }
+        if let Some(referrer_policy) = self.referrer_policy {
+            headers.push((
+                header::REFERRER_POLICY,
+                HeaderValue::from_static(referrer_policy.as_str()),
+            ));
+        }
+        if let Some(permissions_policy) = &self.permissions_policy {
+            headers.push((
+                HeaderName::from_static("permissions-policy"),
+                permissions_policy.clone(),
+            ));
+        }
+        if let Some(coop) = self.coop {
+            headers.push((
+                HeaderName::from_static("cross-origin-opener-policy"),
+                HeaderValue::from_static(coop.as_str()),
+            ));
+        }
+        if let Some(coep) = self.coep {
+            headers.push((
+                HeaderName::from_static("cross-origin-embedder-policy"),
+                HeaderValue::from_static(coep.as_str()),
+            ));
+        }
+        if let Some(corp) = self.corp {
+            headers.push((
+                HeaderName::from_static("cross-origin-resource-policy"),
+                HeaderValue::from_static(corp.as_str()),
+            ));
+        }
+
+        if let Some(csp) = &self.csp {
+            if !csp.has_nonce() {
+                headers.push((csp.header_name(), csp.header_value(None)));
+            }
+        }
+
+        headers
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for SecurityHeaders {
+    type Output = SecurityHeadersEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        SecurityHeadersEndpoint {
+            inner: ep,
+            headers: self.static_headers(),
+            nonce_csp: self.csp.clone().filter(ContentSecurityPolicy::has_nonce),
+        }
+    }
+}
+
+/// Endpoint for SecurityHeaders middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "security-headers")))]
+pub struct SecurityHeadersEndpoint<E> {
+    inner: E,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    nonce_csp: Option<ContentSecurityPolicy>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for SecurityHeadersEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let nonce = match &self.nonce_csp {
+            Some(_) => {
+                let nonce = generate_nonce();
+                req.extensions_mut().insert(CspNonce(nonce.clone()));
+                Some(nonce)
+            }
+            None => None,
+        };
+
+        // The error responses, such as `404 Not Found`, also carry the headers.
+        let mut resp = match self.inner.call(req).await {
+            Ok(resp) => resp.into_response(),
+            Err(err) => err.as_response(),
+        };
+        let headers = resp.headers_mut();
+
+        for (name, value) in &self.headers {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        if let (Some(csp), Some(nonce)) = (&self.nonce_csp, nonce) {
+            let name = csp.header_name();
+            if !headers.contains_key(&name) {
+                headers.insert(name, csp.header_value(Some(&nonce)));
+            }
+        }
+
+        Ok(resp)
+    }
+}
+
+fn generate_nonce() -> String {
+    let nonce: [u8; 16] = thread_rng().gen();
+    base64::encode(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, EndpointExt, Route};
+
+    #[tokio::test]
+    async fn default_headers() {
+        #[handler(internal)]
+        fn index() {}
+
+        let cli = TestClient::new(index.with(SecurityHeaders::new()));
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header(
+            header::STRICT_TRANSPORT_SECURITY,
+            "max-age=31536000; includeSubDomains",
+        );
+        resp.assert_header(header::X_FRAME_OPTIONS, "SAMEORIGIN");
+        resp.assert_header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+        resp.assert_header(header::REFERRER_POLICY, "strict-origin-when-cross-origin");
+        resp.assert_header_is_not_exist(header::CONTENT_SECURITY_POLICY);
+    }
+
+    #[tokio::test]
+    async fn custom_headers() {
+        #[handler(internal)]
+        fn index() {}
+
+        let cli = TestClient::new(
+            index.with(
+                SecurityHeaders::new()
+                    .hsts(
+                        Hsts::new()
+                            .max_age(Duration::from_secs(60))
+                            .include_sub_domains(false)
+                            .preload(true),
+                    )
+                    .frame_options(None)
+                    .content_type_options(false)
+                    .referrer_policy(ReferrerPolicy::NoReferrer)
+                    .permissions_policy("geolocation=()")
+                    .cross_origin_opener_policy(CrossOriginOpenerPolicy::SameOrigin)
+                    .cross_origin_embedder_policy(CrossOriginEmbedderPolicy::RequireCorp)
+                    .cross_origin_resource_policy(CrossOriginResourcePolicy::SameSite)
+                    .content_security_policy(
+                        ContentSecurityPolicy::new()
+                            .directive("default-src", ["'self'"])
+                            .directive("img-src", ["'self'"])
+                            .directive("img-src", ["https://example.com"]),
+                    ),
+            ),
+        );
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header(header::STRICT_TRANSPORT_SECURITY, "max-age=60; preload");
+        resp.assert_header_is_not_exist(header::X_FRAME_OPTIONS);
+        resp.assert_header_is_not_exist(header::X_CONTENT_TYPE_OPTIONS);
+        resp.assert_header(header::REFERRER_POLICY, "no-referrer");
+        resp.assert_header("permissions-policy", "geolocation=()");
+        resp.assert_header("cross-origin-opener-policy", "same-origin");
+        resp.assert_header("cross-origin-embedder-policy", "require-corp");
+        resp.assert_header("cross-origin-resource-policy", "same-site");
+        resp.assert_header(
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'self'; img-src 'self' https://example.com",
+        );
+    }
+
+    #[tokio::test]
+    async fn csp_nonce() {
+        #[handler(internal)]
+        fn index(nonce: &CspNonce) -> String {
+            nonce.to_string()
+        }
+
+        let cli = TestClient::new(
+            index.with(
+                SecurityHeaders::new().content_security_policy(
+                    ContentSecurityPolicy::new()
+                        .directive("default-src", ["'self'"])
+                        .nonce("script-src")
+                        .report_only(true),
+                ),
+            ),
+        );
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        let csp = resp
+            .0
+            .headers()
+            .get(header::CONTENT_SECURITY_POLICY_REPORT_ONLY)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let nonce = resp.0.into_body().into_string().await.unwrap();
+        assert_eq!(
+            csp,
+            format!("default-src 'self'; script-src 'nonce-{}'", nonce)
+        );
+
+        let resp = cli.get("/").send().await;
+        let nonce2 = resp.0.into_body().into_string().await.unwrap();
+        assert_ne!(nonce, nonce2);
+    }
+
+    #[tokio::test]
+    async fn do_not_override() {
+        #[handler(internal)]
+        fn index() -> Response {
+            Response::builder()
+                .header(header::X_FRAME_OPTIONS, "DENY")
+                .finish()
+        }
+
+        let cli = TestClient::new(index.with(SecurityHeaders::new()));
+        let resp = cli.get("/").send().await;
+        resp.assert_header(header::X_FRAME_OPTIONS, "DENY");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid permissions policy")]
+    fn invalid_permissions_policy() {
+        let _ = SecurityHeaders::new().permissions_policy("camera=()\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid content security policy")]
+    fn invalid_content_security_policy() {
+        let _ = ContentSecurityPolicy::new().directive("img-src", ["https://example.com\n"]);
+    }
+
+    #[tokio::test]
+    async fn error_response() {
+        #[handler(internal)]
+        fn index() {}
+
+        let cli = TestClient::new(Route::new().at("/", index).with(SecurityHeaders::new()));
+        let resp = cli.get("/a").send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+        resp.assert_header(header::X_FRAME_OPTIONS, "SAMEORIGIN");
+        resp.assert_header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    }
+}
@@ -4,20 +4,39 @@ use crate::{
 
 /// Middleware for limit the request payload size.
 ///
-/// If the incoming request does not contain the `Content-Length` header, it
-/// will return `BAD_REQUEST` status code.
+/// If the `Content-Length` header of the incoming request exceeds the limit,
+/// it will return `PAYLOAD_TOO_LARGE` status code immediately. Otherwise the
+/// bytes of the body are counted as they are read, so the chunked bodies
+/// without the `Content-Length` header are also limited, and reading more
+/// than the limit fails with
+/// [`ReadBodyError::PayloadTooLarge`](crate::error::ReadBodyError::PayloadTooLarge).
 ///
 /// # Errors
 ///
 /// - [`SizedLimitError`]
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
 pub struct SizeLimit {
     max_size: usize,
+    require_content_length: bool,
 }
 
 impl SizeLimit {
     /// Create `SizeLimit` middleware.
     pub fn new(max_size: usize) -> Self {
-        Self { max_size }
+        Self {
+            max_size,
+            require_content_length: false,
+        }
+    }
+
+    /// Sets whether to reject the requests without the `Content-Length`
+    /// header with `BAD_REQUEST` status code. Default is `false`.
+    #[must_use]
+    pub fn require_content_length(self, enable: bool) -> Self {
+        Self {
+            require_content_length: enable,
+            ..self
+        }
     }
 }
 
@@ -28,6 +47,7 @@ impl<E: Endpoint> Middleware<E> for SizeLimit {
         SizeLimitEndpoint {
             inner: ep,
             max_size: self.max_size,
+            require_content_length: self.require_content_length,
         }
     }
 }
@@ -36,45 +56,53 @@ impl<E: Endpoint> Middleware<E> for SizeLimit {
 pub struct SizeLimitEndpoint<E> {
     inner: E,
     max_size: usize,
+    require_content_length: bool,
 }
 
 #[async_trait::async_trait]
 impl<E: Endpoint> Endpoint for SizeLimitEndpoint<E> {
     type Output = E::Output;
 
-    async fn call(&self, req: Request) -> Result<Self::Output> {
-        let content_length = req
-            .headers()
-            .typed_get::<headers::ContentLength>()
-            .ok_or(SizedLimitError::MissingContentLength)?;
-
-        if content_length.0 as usize > self.max_size {
-            return Err(SizedLimitError::PayloadTooLarge.into());
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        match req.headers().typed_get::<headers::ContentLength>() {
+            Some(content_length) if content_length.0 > self.max_size as u64 => {
+                return Err(SizedLimitError::PayloadTooLarge.into());
+            }
+            None if self.require_content_length => {
+                return Err(SizedLimitError::MissingContentLength.into());
+            }
+            _ => {}
         }
 
+        let body = req.take_body().limit(self.max_size);
+        req.set_body(body);
         self.inner.call(req).await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use http::StatusCode;
+    use futures_util::TryStreamExt;
+    use http::{HeaderMap, StatusCode};
+    use hyper::body::HttpBody;
 
     use super::*;
     use crate::{
         endpoint::{make_sync, EndpointExt},
+        error::ReadBodyError,
+        handler,
         test::TestClient,
+        Body,
     };
 
     #[tokio::test]
     async fn size_limit() {
-        let ep = make_sync(|_| ()).with(SizeLimit::new(5));
-        let cli = TestClient::new(ep);
+        #[handler(internal)]
+        async fn index(data: Vec<u8>) -> String {
+            data.len().to_string()
+        }
 
-        cli.post("/")
-            .send()
-            .await
-            .assert_status(StatusCode::BAD_REQUEST);
+        let cli = TestClient::new(index.with(SizeLimit::new(5)));
 
         cli.post("/")
             .header("content-length", 6)
@@ -96,5 +124,89 @@ mod tests {
             .send()
             .await
             .assert_status_is_ok();
+
+        // The chunked bodies without `Content-Length`.
+        let chunked = |chunks: Vec<&'static str>| {
+            Body::from_bytes_stream(futures_util::stream::iter(
+                chunks.into_iter().map(Ok::<_, std::io::Error>),
+            ))
+        };
+
+        cli.post("/")
+            .body(chunked(vec!["123", "456"]))
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = cli.post("/").body(chunked(vec!["12", "345"])).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("5").await;
+
+        cli.post("/").send().await.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn keep_trailers() {
+        #[handler(internal)]
+        async fn index(body: Body) -> crate::Result<String> {
+            let (stream, trailers) = body.into_bytes_stream_with_trailers();
+            let data = stream
+                .try_collect::<Vec<_>>()
+                .await
+                .map_err(ReadBodyError::Io)?
+                .concat();
+            let trailers = trailers.await?.unwrap_or_default();
+            Ok(format!(
+                "{} {:?}",
+                String::from_utf8(data).unwrap(),
+                trailers.get("x-checksum")
+            ))
+        }
+
+        let body = |chunks: Vec<&'static str>| {
+            Body::from_stream_with_trailers(
+                futures_util::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>)),
+                async {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("x-checksum", "abc".parse().unwrap());
+                    trailers
+                },
+            )
+        };
+
+        let cli = TestClient::new(index.with(SizeLimit::new(5)));
+        let resp = cli.post("/").body(body(vec!["12", "345"])).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("12345 Some(\"abc\")").await;
+
+        cli.post("/")
+            .body(body(vec!["123", "456"]))
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        // The size hint of the body is kept.
+        let body = Body::from("123").limit(5);
+        assert_eq!(HttpBody::size_hint(&body.0).exact(), Some(3));
+        assert!(!body.is_empty());
+        assert!(Body::empty().limit(5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn require_content_length() {
+        let ep = make_sync(|_| ()).with(SizeLimit::new(5).require_content_length(true));
+        let cli = TestClient::new(ep);
+
+        cli.post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        cli.post("/")
+            .header("content-length", 5)
+            .body(&b"12345"[..])
+            .send()
+            .await
+            .assert_status_is_ok();
     }
 }
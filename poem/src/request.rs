@@ -131,7 +131,7 @@ impl From<(http::Request<hyper::Body>, LocalAddr, RemoteAddr, Scheme)> for Reque
             version: parts.version,
             headers: parts.headers,
             extensions: parts.extensions,
-            body: Body(body),
+            body: body.into(),
             state: RequestState {
                 local_addr,
                 remote_addr,
@@ -446,6 +446,29 @@ impl Request {
         )
     }
 
+    /// Creates a request with a copy of the head of this request and an empty
+    /// body. Extensions are not copied.
+    pub(crate) fn clone_head(&self) -> Request {
+        Request {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            version: self.version,
+            headers: self.headers.clone(),
+            extensions: Default::default(),
+            body: Body::empty(),
+            state: RequestState {
+                local_addr: self.state.local_addr.clone(),
+                remote_addr: self.state.remote_addr.clone(),
+                scheme: self.state.scheme.clone(),
+                original_uri: self.state.original_uri.clone(),
+                match_params: self.state.match_params.clone(),
+                #[cfg(feature = "cookie")]
+                cookie_jar: self.state.cookie_jar.clone(),
+                on_upgrade: Default::default(),
+            },
+        }
+    }
+
     /// Upgrade the connection and return a stream.
     pub fn take_upgrade(&self) -> Result<OnUpgrade, UpgradeError> {
         self.state
@@ -88,6 +88,17 @@ impl From<Response> for hyper::Response<hyper::Body> {
     }
 }
 
+impl From<Response> for hyper::Response<Body> {
+    fn from(resp: Response) -> Self {
+        let mut hyper_resp = hyper::Response::new(resp.body);
+        *hyper_resp.status_mut() = resp.status;
+        *hyper_resp.version_mut() = resp.version;
+        *hyper_resp.headers_mut() = resp.headers;
+        *hyper_resp.extensions_mut() = resp.extensions;
+        hyper_resp
+    }
+}
+
 impl From<hyper::Response<hyper::Body>> for Response {
     fn from(hyper_resp: hyper::Response<hyper::Body>) -> Self {
         let (parts, body) = hyper_resp.into_parts();
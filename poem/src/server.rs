@@ -18,7 +18,7 @@ use tokio::{
 use crate::{
     listener::{Acceptor, AcceptorExt, Listener},
     web::{LocalAddr, RemoteAddr},
-    Endpoint, EndpointExt, IntoEndpoint, Response,
+    Body, Endpoint, EndpointExt, IntoEndpoint, Response,
 };
 
 enum Either<L, A> {
@@ -180,7 +180,7 @@ async fn serve_connection(
             let remote_addr = remote_addr.clone();
             let scheme = scheme.clone();
             async move {
-                Ok::<http::Response<_>, Infallible>(
+                Ok::<http::Response<Body>, Infallible>(
                     ep.get_response((req, local_addr, remote_addr, scheme).into())
                         .await
                         .into(),
@@ -0,0 +1,190 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{http::header, Addr, Request};
+
+/// The action when a session is used by a client other than the one it is
+/// bound to, see [`ClientBinding`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BindingMismatchPolicy {
+    /// Rejects the request with
+    /// [`SessionSecurityError::ClientMismatch`](crate::error::SessionSecurityError::ClientMismatch),
+    /// and the session is kept.
+    Reject,
+
+    /// Removes the session, and handles the request with a new empty session.
+    Purge,
+}
+
+/// Binds the server-side sessions to the characteristics of the client,
+/// which makes a stolen session id useless from another client.
+///
+/// By default, the session is bound to the `User-Agent` header, and
+/// [`BindingMismatchPolicy::Purge`] is used.
+///
+/// See also [`ServerSession::bind_to_client`](crate::session::ServerSession::bind_to_client).
+#[derive(Debug, Clone)]
+pub struct ClientBinding {
+    user_agent: bool,
+    ip_prefix: Option<(u8, u8)>,
+    policy: BindingMismatchPolicy,
+}
+
+impl Default for ClientBinding {
+    fn default() -> Self {
+        Self {
+            user_agent: true,
+            ip_prefix: None,
+            policy: BindingMismatchPolicy::Purge,
+        }
+    }
+}
+
+impl ClientBinding {
+    /// Create a `ClientBinding`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets whether to bind the session to the `User-Agent` header. Default
+    /// is `true`.
+    #[must_use]
+    pub fn user_agent(self, enable: bool) -> Self {
+        Self {
+            user_agent: enable,
+            ..self
+        }
+    }
+
+    /// Binds the session to the network prefix of the remote address, with
+    /// the prefix lengths of IPv4 and IPv6 addresses.
+    ///
+    /// Binding to the prefix instead of the full address tolerates clients
+    /// whose address changes within the same network, e.g. `24` and `64`.
+    #[must_use]
+    pub fn ip_prefix(self, ipv4_len: u8, ipv6_len: u8) -> Self {
+        Self {
+            ip_prefix: Some((ipv4_len.min(32), ipv6_len.min(128))),
+            ..self
+        }
+    }
+
+    /// Sets the action when the session is used by another client. Default is
+    /// [`BindingMismatchPolicy::Purge`].
+    #[must_use]
+    pub fn on_mismatch(self, policy: BindingMismatchPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    #[inline]
+    pub(crate) fn policy(&self) -> BindingMismatchPolicy {
+        self.policy
+    }
+
+    pub(crate) fn fingerprint(&self, req: &Request) -> ClientFingerprint {
+        ClientFingerprint {
+            user_agent: if self.user_agent {
+                req.headers()
+                    .get(header::USER_AGENT)
+                    .and_then(|value| value.to_str().ok())
+                    .map(ToString::to_string)
+            } else {
+                None
+            },
+            ip: match (self.ip_prefix, &req.remote_addr().0) {
+                (Some((ipv4_len, ipv6_len)), Addr::SocketAddr(addr)) => {
+                    Some(ip_prefix(addr.ip(), ipv4_len, ipv6_len))
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+fn ip_prefix(ip: IpAddr, ipv4_len: u8, ipv6_len: u8) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - ipv4_len as u32).unwrap_or(0);
+            format!("{}/{}", Ipv4Addr::from(u32::from(ip) & mask), ipv4_len)
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - ipv6_len as u32).unwrap_or(0);
+            format!("{}/{}", Ipv6Addr::from(u128::from(ip) & mask), ipv6_len)
+        }
+    }
+}
+
+/// The characteristics of the client which a session is bound to.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ClientFingerprint {
+    #[serde(default, rename = "ua", skip_serializing_if = "Option::is_none")]
+    user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ip: Option<String>,
+}
+
+impl ClientFingerprint {
+    /// Returns `false` if any characteristic recorded in the session differs.
+    ///
+    /// The characteristics which are not recorded yet are ignored, so that
+    /// enabling more characteristics does not invalidate existing sessions.
+    pub(crate) fn matches(&self, stored: &ClientFingerprint) -> bool {
+        fn matches(current: &Option<String>, stored: &Option<String>) -> bool {
+            match (current, stored) {
+                (Some(current), Some(stored)) => current == stored,
+                _ => true,
+            }
+        }
+
+        matches(&self.user_agent, &stored.user_agent) && matches(&self.ip, &stored.ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix() {
+        let ip = "192.168.12.34".parse().unwrap();
+        assert_eq!(ip_prefix(ip, 24, 64), "192.168.12.0/24");
+        assert_eq!(ip_prefix(ip, 32, 64), "192.168.12.34/32");
+        assert_eq!(ip_prefix(ip, 0, 64), "0.0.0.0/0");
+
+        let ip = "2001:db8:1:2:3:4:5:6".parse().unwrap();
+        assert_eq!(ip_prefix(ip, 24, 64), "2001:db8:1:2::/64");
+        assert_eq!(ip_prefix(ip, 24, 48), "2001:db8:1::/48");
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut req = Request::builder()
+            .header(header::USER_AGENT, "test")
+            .finish();
+        req.state_mut().remote_addr.0 = Addr::SocketAddr("10.0.0.1:1234".parse().unwrap());
+
+        let binding = ClientBinding::new().ip_prefix(24, 64);
+        let fingerprint = binding.fingerprint(&req);
+        assert_eq!(
+            fingerprint,
+            ClientFingerprint {
+                user_agent: Some("test".to_string()),
+                ip: Some("10.0.0.0/24".to_string()),
+            }
+        );
+
+        let other = ClientFingerprint {
+            user_agent: Some("test".to_string()),
+            ip: Some("10.0.1.0/24".to_string()),
+        };
+        assert!(!fingerprint.matches(&other));
+        assert!(fingerprint.matches(&ClientFingerprint {
+            user_agent: Some("test".to_string()),
+            ip: None,
+        }));
+
+        let fingerprint = ClientBinding::new().user_agent(false).fingerprint(&req);
+        assert_eq!(fingerprint, ClientFingerprint::default());
+    }
+}
@@ -0,0 +1,581 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use aes_gcm::{
+    aead::{Aead, NewAead, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::XChaCha20Poly1305;
+use rand::RngCore;
+use serde_json::Value;
+
+use crate::{
+    error::{SessionEncryptionError, SessionNotFoundError},
+    session::{SessionMetadata, SessionStorage},
+    Result,
+};
+
+/// The session entry used to store the encrypted session entries.
+const SEALED_KEY: &str = "__poem_sealed";
+
+const FORMAT_VERSION: u8 = 1;
+
+/// The cipher used by [`EncryptedStorage`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SessionCipher {
+    /// AES-256-GCM with a 96-bit random nonce.
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 with a 192-bit random nonce.
+    XChaCha20Poly1305,
+}
+
+impl SessionCipher {
+    fn id(self) -> u8 {
+        match self {
+            SessionCipher::Aes256Gcm => 1,
+            SessionCipher::XChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(SessionCipher::Aes256Gcm),
+            2 => Some(SessionCipher::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    fn nonce_len(self) -> usize {
+        match self {
+            SessionCipher::Aes256Gcm => 12,
+            SessionCipher::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    fn encrypt(self, key: &[u8; 32], nonce: &[u8], payload: Payload<'_, '_>) -> Option<Vec<u8>> {
+        match self {
+            SessionCipher::Aes256Gcm => Aes256Gcm::new(key.into())
+                .encrypt(nonce.into(), payload)
+                .ok(),
+            SessionCipher::XChaCha20Poly1305 => XChaCha20Poly1305::new(key.into())
+                .encrypt(nonce.into(), payload)
+                .ok(),
+        }
+    }
+
+    fn decrypt(self, key: &[u8; 32], nonce: &[u8], payload: Payload<'_, '_>) -> Option<Vec<u8>> {
+        match self {
+            SessionCipher::Aes256Gcm => Aes256Gcm::new(key.into())
+                .decrypt(nonce.into(), payload)
+                .ok(),
+            SessionCipher::XChaCha20Poly1305 => XChaCha20Poly1305::new(key.into())
+                .decrypt(nonce.into(), payload)
+                .ok(),
+        }
+    }
+}
+
+/// The codec used by [`EncryptedStorage`] to serialize the session entries.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SessionCodec {
+    /// JSON
+    Json,
+    /// MessagePack
+    #[cfg(feature = "session-msgpack")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "session-msgpack")))]
+    MessagePack,
+    /// CBOR
+    #[cfg(feature = "session-cbor")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "session-cbor")))]
+    Cbor,
+}
+
+impl SessionCodec {
+    fn id(self) -> u8 {
+        match self {
+            SessionCodec::Json => 1,
+            #[cfg(feature = "session-msgpack")]
+            SessionCodec::MessagePack => 2,
+            #[cfg(feature = "session-cbor")]
+            SessionCodec::Cbor => 3,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(SessionCodec::Json),
+            #[cfg(feature = "session-msgpack")]
+            2 => Some(SessionCodec::MessagePack),
+            #[cfg(feature = "session-cbor")]
+            3 => Some(SessionCodec::Cbor),
+            _ => None,
+        }
+    }
+
+    fn encode(self, entries: &BTreeMap<String, Value>) -> Result<Vec<u8>, SessionEncryptionError> {
+        match self {
+            SessionCodec::Json => serde_json::to_vec(entries)
+                .map_err(|err| SessionEncryptionError::Encode(err.to_string())),
+            #[cfg(feature = "session-msgpack")]
+            SessionCodec::MessagePack => rmp_serde::to_vec(entries)
+                .map_err(|err| SessionEncryptionError::Encode(err.to_string())),
+            #[cfg(feature = "session-cbor")]
+            SessionCodec::Cbor => {
+                let mut data = Vec::new();
+                ciborium::ser::into_writer(entries, &mut data)
+                    .map_err(|err| SessionEncryptionError::Encode(err.to_string()))?;
+                Ok(data)
+            }
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> Option<BTreeMap<String, Value>> {
+        match self {
+            SessionCodec::Json => serde_json::from_slice(data).ok(),
+            #[cfg(feature = "session-msgpack")]
+            SessionCodec::MessagePack => rmp_serde::from_slice(data).ok(),
+            #[cfg(feature = "session-cbor")]
+            SessionCodec::Cbor => ciborium::de::from_reader(data).ok(),
+        }
+    }
+}
+
+/// A session storage wrapper that encrypts and authenticates the session
+/// entries before they are passed to the inner storage.
+///
+/// The entries are serialized with the [`SessionCodec`], encrypted with the
+/// [`SessionCipher`] using the first key, and the id of the key is stored
+/// along with the encrypted data. The session id is used as associated data,
+/// so encrypted entries cannot be moved to another session.
+///
+/// To rotate keys, create the storage with the new key and add the old keys
+/// with [`EncryptedStorage::decryption_key`]. Sessions encrypted with the old
+/// keys can still be loaded, and they are encrypted with the new key on the
+/// next update.
+///
+/// Sessions that cannot be decrypted are treated as missing. The per-user
+/// session index is passed to the inner storage unencrypted.
+///
+/// The entries of a session are sealed as a single value, so the changes are
+/// written with the atomic `patch_session` of the inner storage, which does
+/// not recreate removed sessions, but concurrent requests which change
+/// different entries of the same session may overwrite each other's changes.
+///
+/// # Example
+///
+/// ```
+/// use poem::session::{EncryptedStorage, MemoryStorage, SessionCipher, SessionCodec};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let storage = EncryptedStorage::new(MemoryStorage::new(), "2022-03", [1; 32])
+///     .decryption_key("2021-12", [0; 32])
+///     .cipher(SessionCipher::XChaCha20Poly1305)
+///     .codec(SessionCodec::Json);
+/// # });
+/// ```
+pub struct EncryptedStorage<T> {
+    inner: T,
+    keys: Vec<(String, [u8; 32])>,
+    cipher: SessionCipher,
+    codec: SessionCodec,
+    accept_plaintext: bool,
+}
+
+impl<T: SessionStorage> EncryptedStorage<T> {
+    /// Create an `EncryptedStorage` which encrypts the sessions with the
+    /// specified 256-bit key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key id is longer than 255 bytes.
+    pub fn new(inner: T, key_id: impl Into<String>, key: [u8; 32]) -> Self {
+        Self {
+            inner,
+            keys: vec![(check_key_id(key_id.into()), key)],
+            cipher: SessionCipher::Aes256Gcm,
+            codec: SessionCodec::Json,
+            accept_plaintext: false,
+        }
+    }
+
+    /// Adds a key which is only used to decrypt the sessions, such as a key
+    /// which has been rotated out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key id is longer than 255 bytes.
+    #[must_use]
+    pub fn decryption_key(mut self, key_id: impl Into<String>, key: [u8; 32]) -> Self {
+        self.keys.push((check_key_id(key_id.into()), key));
+        self
+    }
+
+    /// Sets the cipher used to encrypt the sessions. Default is
+    /// [`SessionCipher::Aes256Gcm`].
+    ///
+    /// Sessions encrypted with other ciphers can still be loaded.
+    #[must_use]
+    pub fn cipher(self, cipher: SessionCipher) -> Self {
+        Self { cipher, ..self }
+    }
+
+    /// Sets the codec used to serialize the sessions. Default is
+    /// [`SessionCodec::Json`].
+    ///
+    /// Sessions serialized with other enabled codecs can still be loaded.
+    #[must_use]
+    pub fn codec(self, codec: SessionCodec) -> Self {
+        Self { codec, ..self }
+    }
+
+    /// If `true`, unencrypted sessions in the inner storage are loaded
+    /// as-is, which is useful when migrating an existing storage. Default is
+    /// `false`.
+    #[must_use]
+    pub fn accept_plaintext(self, accept: bool) -> Self {
+        Self {
+            accept_plaintext: accept,
+            ..self
+        }
+    }
+
+    fn seal(
+        &self,
+        session_id: &str,
+        entries: &BTreeMap<String, Value>,
+    ) -> Result<String, SessionEncryptionError> {
+        let (key_id, key) = &self.keys[0];
+        let data = self.codec.encode(entries)?;
+
+        let mut sealed = vec![
+            FORMAT_VERSION,
+            self.cipher.id(),
+            self.codec.id(),
+            key_id.len() as u8,
+        ];
+        sealed.extend_from_slice(key_id.as_bytes());
+
+        let mut nonce = vec![0; self.cipher.nonce_len()];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let aad = [&sealed[..], session_id.as_bytes()].concat();
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                key,
+                &nonce,
+                Payload {
+                    msg: &data,
+                    aad: &aad,
+                },
+            )
+            .ok_or(SessionEncryptionError::Encrypt)?;
+
+        sealed.reserve(nonce.len() + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+
+        Ok(base64::encode_config(sealed, base64::URL_SAFE_NO_PAD))
+    }
+
+    fn open(&self, session_id: &str, sealed: &str) -> Option<BTreeMap<String, Value>> {
+        let sealed = base64::decode_config(sealed, base64::URL_SAFE_NO_PAD).ok()?;
+        let (&version, rest) = sealed.split_first()?;
+        if version != FORMAT_VERSION || rest.len() < 3 {
+            return None;
+        }
+
+        let cipher = SessionCipher::from_id(rest[0])?;
+        let codec = SessionCodec::from_id(rest[1])?;
+        let key_id_len = rest[2] as usize;
+        let rest = &rest[3..];
+        if rest.len() < key_id_len + cipher.nonce_len() {
+            return None;
+        }
+        let (key_id, rest) = rest.split_at(key_id_len);
+        let (nonce, ciphertext) = rest.split_at(cipher.nonce_len());
+
+        let key = self
+            .keys
+            .iter()
+            .find(|(id, _)| id.as_bytes() == key_id)
+            .map(|(_, key)| key)?;
+        let header_len = 4 + key_id_len;
+        let aad = [&sealed[..header_len], session_id.as_bytes()].concat();
+        let data = cipher.decrypt(
+            key,
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )?;
+        codec.decode(&data)
+    }
+
+    /// Decrypts the entries loaded from the inner storage.
+    fn open_entries(
+        &self,
+        session_id: &str,
+        entries: BTreeMap<String, Value>,
+    ) -> Option<BTreeMap<String, Value>> {
+        match entries.get(SEALED_KEY) {
+            Some(Value::String(sealed)) if entries.len() == 1 => {
+                let entries = self.open(session_id, sealed);
+                if entries.is_none() {
+                    tracing::warn!(session_id = session_id, "failed to decrypt session");
+                }
+                entries
+            }
+            _ if self.accept_plaintext => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+fn check_key_id(key_id: String) -> String {
+    assert!(
+        key_id.len() <= u8::MAX as usize,
+        "the key id must not be longer than 255 bytes"
+    );
+    key_id
+}
+
+#[async_trait::async_trait]
+impl<T: SessionStorage> SessionStorage for EncryptedStorage<T> {
+    async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
+        Ok(self
+            .inner
+            .load_session(session_id)
+            .await?
+            .and_then(|entries| self.open_entries(session_id, entries)))
+    }
+
+    async fn update_session(
+        &self,
+        session_id: &str,
+        entries: &BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let sealed = self.seal(session_id, entries)?;
+        let mut sealed_entries = BTreeMap::new();
+        sealed_entries.insert(SEALED_KEY.to_string(), Value::String(sealed));
+        self.inner
+            .update_session(session_id, &sealed_entries, expires)
+            .await
+    }
+
+    /// Seals the whole session again and writes it with the `patch_session`
+    /// of the inner storage, see the documentation of [`EncryptedStorage`].
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let raw_entries = self
+            .inner
+            .load_session(session_id)
+            .await?
+            .ok_or(SessionNotFoundError)?;
+        // The plaintext entries are replaced with the sealed entry.
+        let raw_removed = raw_entries
+            .keys()
+            .filter(|name| name.as_str() != SEALED_KEY)
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut entries = self
+            .open_entries(session_id, raw_entries)
+            .ok_or(SessionNotFoundError)?;
+        for name in removed {
+            entries.remove(name);
+        }
+        entries.extend(
+            changed
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        let mut sealed_entries = BTreeMap::new();
+        sealed_entries.insert(
+            SEALED_KEY.to_string(),
+            Value::String(self.seal(session_id, &entries)?),
+        );
+        self.inner
+            .patch_session(session_id, &sealed_entries, &raw_removed, expires)
+            .await
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<()> {
+        self.inner.remove_session(session_id).await
+    }
+
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()> {
+        self.inner.touch_session(session_id, expires).await
+    }
+
+    async fn index_session(
+        &self,
+        user: &str,
+        metadata: &SessionMetadata,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        self.inner.index_session(user, metadata, expires).await
+    }
+
+    async fn list_sessions_for(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        self.inner.list_sessions_for(user).await
+    }
+
+    async fn revoke_all_for(&self, user: &str) -> Result<()> {
+        self.inner.revoke_all_for(user).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        session::{
+            test_harness::{index, TestClient},
+            CookieConfig, MemoryStorage, ServerSession,
+        },
+        EndpointExt, Route,
+    };
+
+    fn entries() -> BTreeMap<String, Value> {
+        let mut entries = BTreeMap::new();
+        entries.insert("user".to_string(), "secret-user".into());
+        entries.insert("count".to_string(), 10.into());
+        entries
+    }
+
+    #[tokio::test]
+    async fn encrypted_session() {
+        let app = Route::new().at("/:action", index).with(ServerSession::new(
+            CookieConfig::default(),
+            EncryptedStorage::new(MemoryStorage::new(), "k1", [1; 32]),
+        ));
+        let mut client = TestClient::default();
+
+        client.call(&app, 0).await;
+        client.assert_cookies(vec![]);
+
+        client.call(&app, 1).await;
+        client.call(&app, 2).await;
+        client.call(&app, 7).await;
+        client.call(&app, 6).await;
+        client.call(&app, 3).await;
+        client.call(&app, 4).await;
+        client.call(&app, 5).await;
+        client.assert_cookies(vec![]);
+    }
+
+    #[tokio::test]
+    async fn ciphers_and_codecs() {
+        let mut codecs = vec![SessionCodec::Json];
+        #[cfg(feature = "session-msgpack")]
+        codecs.push(SessionCodec::MessagePack);
+        #[cfg(feature = "session-cbor")]
+        codecs.push(SessionCodec::Cbor);
+
+        for cipher in [SessionCipher::Aes256Gcm, SessionCipher::XChaCha20Poly1305] {
+            for codec in codecs.iter().copied() {
+                let inner = MemoryStorage::new();
+                let storage = EncryptedStorage::new(inner.clone(), "k1", [1; 32])
+                    .cipher(cipher)
+                    .codec(codec);
+                storage.update_session("a", &entries(), None).await.unwrap();
+
+                let raw = inner.load_session("a").await.unwrap().unwrap();
+                assert_eq!(raw.len(), 1);
+                assert!(!raw[SEALED_KEY].as_str().unwrap().contains("secret"));
+                assert_eq!(storage.load_session("a").await.unwrap(), Some(entries()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn key_rotation() {
+        let inner = MemoryStorage::new();
+        let old = EncryptedStorage::new(inner.clone(), "k1", [1; 32]);
+        old.update_session("a", &entries(), None).await.unwrap();
+
+        let new = EncryptedStorage::new(inner.clone(), "k2", [2; 32])
+            .cipher(SessionCipher::XChaCha20Poly1305)
+            .decryption_key("k1", [1; 32]);
+        assert_eq!(new.load_session("a").await.unwrap(), Some(entries()));
+        new.update_session("a", &entries(), None).await.unwrap();
+        assert_eq!(old.load_session("a").await.unwrap(), None);
+
+        let wrong_key = EncryptedStorage::new(inner.clone(), "k2", [3; 32]);
+        assert_eq!(wrong_key.load_session("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn tampered() {
+        let inner = MemoryStorage::new();
+        let storage = EncryptedStorage::new(inner.clone(), "k1", [1; 32]);
+        storage.update_session("a", &entries(), None).await.unwrap();
+
+        // Moved to another session.
+        let raw = inner.load_session("a").await.unwrap().unwrap();
+        inner.update_session("b", &raw, None).await.unwrap();
+        assert_eq!(storage.load_session("b").await.unwrap(), None);
+
+        // Modified ciphertext.
+        let mut sealed =
+            base64::decode_config(raw[SEALED_KEY].as_str().unwrap(), base64::URL_SAFE_NO_PAD)
+                .unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        let mut raw = BTreeMap::new();
+        raw.insert(
+            SEALED_KEY.to_string(),
+            base64::encode_config(sealed, base64::URL_SAFE_NO_PAD).into(),
+        );
+        inner.update_session("a", &raw, None).await.unwrap();
+        assert_eq!(storage.load_session("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn plaintext() {
+        let inner = MemoryStorage::new();
+        inner.update_session("a", &entries(), None).await.unwrap();
+
+        let storage = EncryptedStorage::new(inner.clone(), "k1", [1; 32]);
+        assert_eq!(storage.load_session("a").await.unwrap(), None);
+
+        let storage = storage.accept_plaintext(true);
+        assert_eq!(storage.load_session("a").await.unwrap(), Some(entries()));
+    }
+
+    #[tokio::test]
+    async fn patch_session() {
+        let inner = MemoryStorage::new();
+        inner.update_session("a", &entries(), None).await.unwrap();
+        let storage = EncryptedStorage::new(inner.clone(), "k1", [1; 32]).accept_plaintext(true);
+
+        let mut changed = BTreeMap::new();
+        changed.insert("count".to_string(), 11.into());
+        storage
+            .patch_session("a", &changed, &["user".to_string()], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.load_session("a").await.unwrap(),
+            Some(changed.clone())
+        );
+        // The plaintext entries are replaced with the sealed entry.
+        let raw_entries = inner.load_session("a").await.unwrap().unwrap();
+        assert_eq!(raw_entries.keys().collect::<Vec<_>>(), vec![SEALED_KEY]);
+
+        // The removed session is not recreated.
+        storage.remove_session("a").await.unwrap();
+        let err = storage
+            .patch_session("a", &changed, &[], None)
+            .await
+            .unwrap_err();
+        assert!(err.is::<SessionNotFoundError>());
+        assert_eq!(inner.load_session("a").await.unwrap(), None);
+    }
+}
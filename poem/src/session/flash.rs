@@ -0,0 +1,549 @@
+use std::{fmt::Display, mem, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    middleware::{CookieJarManager, CookieJarManagerEndpoint},
+    session::{CookieConfig, Session},
+    Endpoint, FromRequest, IntoResponse, Middleware, Request, RequestBody, Response, Result,
+};
+
+/// The session entry used to store the flash messages.
+const FLASH_KEY: &str = "__poem_flash";
+
+/// The level of a [`FlashMessage`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashLevel {
+    /// Informational message.
+    Info,
+    /// Success message.
+    Success,
+    /// Warning message.
+    Warning,
+    /// Error message.
+    Error,
+}
+
+impl FlashLevel {
+    /// Returns the lowercase name of the level, such as `info`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlashLevel::Info => "info",
+            FlashLevel::Success => "success",
+            FlashLevel::Warning => "warning",
+            FlashLevel::Error => "error",
+        }
+    }
+}
+
+impl Display for FlashLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A one-shot message which is displayed on the next request.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    /// The level of the message.
+    pub level: FlashLevel,
+    /// The text of the message.
+    pub message: String,
+    /// A custom payload of the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+impl FlashMessage {
+    /// Create a flash message with the specified level.
+    pub fn new(level: FlashLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            payload: None,
+        }
+    }
+
+    /// Create an `info` flash message.
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(FlashLevel::Info, message)
+    }
+
+    /// Create a `success` flash message.
+    pub fn success(message: impl Into<String>) -> Self {
+        Self::new(FlashLevel::Success, message)
+    }
+
+    /// Create a `warning` flash message.
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(FlashLevel::Warning, message)
+    }
+
+    /// Create an `error` flash message.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(FlashLevel::Error, message)
+    }
+
+    /// Sets the custom payload of the message.
+    #[must_use]
+    pub fn with_payload(self, payload: impl Serialize) -> Self {
+        Self {
+            payload: serde_json::to_value(payload).ok(),
+            ..self
+        }
+    }
+
+    /// Deserializes the custom payload of the message.
+    pub fn payload<T: DeserializeOwned>(&self) -> Option<T> {
+        self.payload
+            .clone()
+            .and_then(|payload| serde_json::from_value(payload).ok())
+    }
+}
+
+/// A response helper for adding flash messages.
+///
+/// The messages are saved by the [`FlashManager`] middleware, and
+/// [`Redirect`](crate::web::Redirect) supports them with
+/// [`Redirect::flash`](crate::web::Redirect::flash).
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     session::{FlashMessage, FlashMessages},
+///     web::Redirect,
+///     Response,
+/// };
+///
+/// #[handler]
+/// fn save() -> Response {
+///     FlashMessages::new()
+///         .success("The article has been saved.")
+///         .push(FlashMessage::info("Reviewers are notified.").with_payload(3))
+///         .into_response_with(Redirect::see_other("/articles"))
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct FlashMessages(Vec<FlashMessage>);
+
+impl FlashMessages {
+    /// Create an empty `FlashMessages`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a message.
+    #[must_use]
+    pub fn push(mut self, message: FlashMessage) -> Self {
+        self.0.push(message);
+        self
+    }
+
+    /// Appends an `info` message.
+    #[must_use]
+    pub fn info(self, message: impl Into<String>) -> Self {
+        self.push(FlashMessage::info(message))
+    }
+
+    /// Appends a `success` message.
+    #[must_use]
+    pub fn success(self, message: impl Into<String>) -> Self {
+        self.push(FlashMessage::success(message))
+    }
+
+    /// Appends a `warning` message.
+    #[must_use]
+    pub fn warning(self, message: impl Into<String>) -> Self {
+        self.push(FlashMessage::warning(message))
+    }
+
+    /// Appends an `error` message.
+    #[must_use]
+    pub fn error(self, message: impl Into<String>) -> Self {
+        self.push(FlashMessage::error(message))
+    }
+
+    /// Returns `true` if there are no messages.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the messages.
+    #[inline]
+    pub fn messages(&self) -> &[FlashMessage] {
+        &self.0
+    }
+
+    /// Attach the messages to the response.
+    pub fn into_response_with(self, resp: impl IntoResponse) -> Response {
+        let mut resp = resp.into_response();
+        if !self.is_empty() {
+            match resp.extensions_mut().get_mut::<FlashMessages>() {
+                Some(messages) => messages.0.extend(self.0),
+                None => {
+                    resp.extensions_mut().insert(self);
+                }
+            }
+        }
+        resp
+    }
+}
+
+impl From<Vec<FlashMessage>> for FlashMessages {
+    fn from(messages: Vec<FlashMessage>) -> Self {
+        Self(messages)
+    }
+}
+
+impl IntoIterator for FlashMessages {
+    type Item = FlashMessage;
+    type IntoIter = std::vec::IntoIter<FlashMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl IntoResponse for FlashMessages {
+    fn into_response(self) -> Response {
+        self.into_response_with(())
+    }
+}
+
+struct FlashState {
+    incoming: Vec<FlashMessage>,
+    consumed: bool,
+    outgoing: Vec<FlashMessage>,
+}
+
+/// An extractor for reading and adding flash messages.
+///
+/// The messages added by the current request are available to the next
+/// request, and the messages are removed once they are read with
+/// [`Flash::messages`].
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     session::{CookieConfig, CookieSession, Flash, FlashManager},
+///     web::Redirect,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn login(flash: Flash) -> Redirect {
+///     flash.error("Invalid username or password.");
+///     Redirect::see_other("/")
+/// }
+///
+/// #[handler]
+/// fn index(flash: Flash) -> String {
+///     flash
+///         .messages()
+///         .into_iter()
+///         .map(|msg| format!("{}: {}", msg.level, msg.message))
+///         .collect::<Vec<_>>()
+///         .join("\n")
+/// }
+///
+/// let app = Route::new()
+///     .at("/", get(index))
+///     .at("/login", get(login))
+///     .with(FlashManager::new())
+///     .with(CookieSession::new(CookieConfig::default()));
+/// ```
+#[derive(Clone)]
+pub struct Flash {
+    state: Arc<Mutex<FlashState>>,
+}
+
+impl Flash {
+    fn new(incoming: Vec<FlashMessage>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FlashState {
+                incoming,
+                consumed: false,
+                outgoing: Vec::new(),
+            })),
+        }
+    }
+
+    /// Takes the messages added by the previous requests.
+    ///
+    /// The messages are removed from the storage after this request.
+    pub fn messages(&self) -> Vec<FlashMessage> {
+        let mut state = self.state.lock();
+        state.consumed = true;
+        mem::take(&mut state.incoming)
+    }
+
+    /// Returns `true` if there are no unread messages.
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().incoming.is_empty()
+    }
+
+    /// Adds a message for the next request.
+    pub fn push(&self, message: FlashMessage) {
+        self.state.lock().outgoing.push(message);
+    }
+
+    /// Adds an `info` message for the next request.
+    pub fn info(&self, message: impl Into<String>) {
+        self.push(FlashMessage::info(message));
+    }
+
+    /// Adds a `success` message for the next request.
+    pub fn success(&self, message: impl Into<String>) {
+        self.push(FlashMessage::success(message));
+    }
+
+    /// Adds a `warning` message for the next request.
+    pub fn warning(&self, message: impl Into<String>) {
+        self.push(FlashMessage::warning(message));
+    }
+
+    /// Adds an `error` message for the next request.
+    pub fn error(&self, message: impl Into<String>) {
+        self.push(FlashMessage::error(message));
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Flash {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<Flash>()
+            .cloned()
+            .expect("To use the `Flash` extractor, the `FlashManager` middleware is required."))
+    }
+}
+
+enum FlashStore {
+    Session,
+    Cookie(CookieConfig),
+}
+
+/// Middleware for flash messages.
+///
+/// By default, the messages are stored in the session, so the
+/// [`CookieSession`](crate::session::CookieSession) or
+/// [`ServerSession`](crate::session::ServerSession) middleware must be applied
+/// outside of it. Use [`FlashManager::cookie`] to store them in a dedicated
+/// cookie instead.
+pub struct FlashManager {
+    store: Arc<FlashStore>,
+}
+
+impl Default for FlashManager {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(FlashStore::Session),
+        }
+    }
+}
+
+impl FlashManager {
+    /// Create a `FlashManager` middleware which stores the messages in the
+    /// session.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a `FlashManager` middleware which stores the messages in a
+    /// dedicated cookie.
+    ///
+    /// The default cookie name of [`CookieConfig`] is used by the session, so
+    /// give it another name, such as `CookieConfig::signed(key).name("flash")`.
+    pub fn cookie(config: CookieConfig) -> Self {
+        Self {
+            store: Arc::new(FlashStore::Cookie(config)),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for FlashManager {
+    type Output = CookieJarManagerEndpoint<FlashManagerEndpoint<E>>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        CookieJarManager::new().transform(FlashManagerEndpoint {
+            inner: ep,
+            store: self.store.clone(),
+        })
+    }
+}
+
+/// Endpoint for `FlashManager` middleware.
+pub struct FlashManagerEndpoint<E> {
+    inner: E,
+    store: Arc<FlashStore>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for FlashManagerEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let cookie_jar = req.cookie().clone();
+        let session = match &*self.store {
+            FlashStore::Session => Some(req.extensions().get::<Session>().cloned().expect(
+                "To use the `FlashManager` middleware, the `CookieSession` or `ServerSession` \
+                 middleware is required.",
+            )),
+            FlashStore::Cookie(_) => None,
+        };
+
+        let incoming = match &*self.store {
+            FlashStore::Cookie(config) => config
+                .get_cookie_value(&cookie_jar)
+                .and_then(|value| serde_json::from_str::<Vec<FlashMessage>>(&value).ok()),
+            FlashStore::Session => session
+                .as_ref()
+                .and_then(|session| session.get::<Vec<FlashMessage>>(FLASH_KEY)),
+        }
+        .unwrap_or_default();
+        let incoming_len = incoming.len();
+
+        let flash = Flash::new(incoming);
+        req.extensions_mut().insert(flash.clone());
+        let mut resp = self.inner.call(req).await?.into_response();
+
+        let (consumed, mut messages) = {
+            let mut state = flash.state.lock();
+            let mut messages = mem::take(&mut state.incoming);
+            messages.append(&mut state.outgoing);
+            (state.consumed, messages)
+        };
+        if let Some(added) = resp.extensions_mut().remove::<FlashMessages>() {
+            messages.extend(added);
+        }
+
+        let changed = messages.len() != incoming_len || (consumed && incoming_len > 0);
+        if !changed {
+            return Ok(resp);
+        }
+
+        match &*self.store {
+            FlashStore::Cookie(config) => {
+                if messages.is_empty() {
+                    config.remove_cookie(&cookie_jar);
+                } else {
+                    config.set_cookie_value(
+                        &cookie_jar,
+                        &serde_json::to_string(&messages).unwrap_or_default(),
+                    );
+                }
+            }
+            FlashStore::Session => {
+                if let Some(session) = session {
+                    if messages.is_empty() {
+                        session.remove(FLASH_KEY);
+                    } else {
+                        session.set(FLASH_KEY, messages);
+                    }
+                }
+            }
+        }
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        get, handler,
+        http::StatusCode,
+        session::{test_harness::TestClient, CookieSession},
+        web::Redirect,
+        EndpointExt, Route,
+    };
+
+    #[handler(internal)]
+    fn add(flash: Flash) -> Redirect {
+        flash.info("a");
+        Redirect::see_other("/").flash(FlashMessage::error("b").with_payload(1))
+    }
+
+    #[handler(internal)]
+    fn read(flash: Flash) -> String {
+        flash
+            .messages()
+            .into_iter()
+            .map(|msg| format!("{}:{}:{:?}", msg.level, msg.message, msg.payload::<i32>()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[handler(internal)]
+    fn other() -> FlashMessages {
+        FlashMessages::new().warning("c")
+    }
+
+    #[handler(internal)]
+    fn ignore(_flash: Flash) {}
+
+    async fn text(cli: &mut TestClient, ep: &impl Endpoint, uri: &str) -> String {
+        cli.send(ep, uri)
+            .await
+            .into_body()
+            .into_string()
+            .await
+            .unwrap()
+    }
+
+    async fn check(ep: impl Endpoint) {
+        let mut cli = TestClient::default();
+
+        assert_eq!(text(&mut cli, &ep, "/").await, "");
+
+        let resp = cli.send(&ep, "/add").await;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        cli.send(&ep, "/ignore").await;
+        assert_eq!(text(&mut cli, &ep, "/").await, "info:a:None,error:b:Some(1)");
+        assert_eq!(text(&mut cli, &ep, "/").await, "");
+
+        cli.send(&ep, "/other").await;
+        cli.send(&ep, "/add").await;
+        assert_eq!(
+            text(&mut cli, &ep, "/").await,
+            "warning:c:None,info:a:None,error:b:Some(1)"
+        );
+        assert_eq!(text(&mut cli, &ep, "/").await, "");
+    }
+
+    fn routes() -> Route {
+        Route::new()
+            .at("/", get(read))
+            .at("/add", get(add))
+            .at("/other", get(other))
+            .at("/ignore", get(ignore))
+    }
+
+    #[tokio::test]
+    async fn session_store() {
+        check(
+            routes()
+                .with(FlashManager::new())
+                .with(CookieSession::new(CookieConfig::default())),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn cookie_store() {
+        check(routes().with(FlashManager::cookie(
+            CookieConfig::signed(crate::web::cookie::CookieKey::generate()).name("flash"),
+        )))
+        .await;
+    }
+}
@@ -1,22 +1,46 @@
 use std::{
     cmp::Reverse,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
-    time::{Duration, Instant},
+    time::Duration,
 };
 
 use parking_lot::Mutex;
 use priority_queue::PriorityQueue;
 use serde_json::Value;
+use tokio::time::Instant;
 
-use crate::{session::SessionStorage, Result};
+use crate::{
+    error::SessionNotFoundError,
+    session::{SessionMetadata, SessionStorage},
+    Result,
+};
 
 struct InnerStorage {
     sessions: HashMap<String, BTreeMap<String, Value>>,
     timeout_queue: PriorityQueue<String, Reverse<Instant>>,
+    user_sessions: HashMap<String, HashSet<String>>,
+    metadata: HashMap<String, (String, SessionMetadata)>,
 }
 
 impl InnerStorage {
+    fn remove(&mut self, session_id: &str) {
+        self.sessions.remove(session_id);
+        self.timeout_queue.remove(session_id);
+        self.unindex(session_id);
+    }
+
+    fn unindex(&mut self, session_id: &str) {
+        if let Some((user, _)) = self.metadata.remove(session_id) {
+            if let Some(sessions) = self.user_sessions.get_mut(&user) {
+                sessions.remove(session_id);
+                if sessions.is_empty() {
+                    self.user_sessions.remove(&user);
+                }
+            }
+        }
+    }
+
     fn cleanup(&mut self) {
         loop {
             let now = Instant::now();
@@ -25,7 +49,7 @@ impl InnerStorage {
                     break;
                 }
                 if let Some((session_id, _)) = self.timeout_queue.pop() {
-                    self.sessions.remove(&session_id);
+                    self.remove(&session_id);
                 }
             } else {
                 break;
@@ -35,6 +59,10 @@ impl InnerStorage {
 }
 
 /// A session storage using memory.
+///
+/// It supports the per-user session index, and the clones share the same
+/// sessions.
+#[derive(Clone)]
 pub struct MemoryStorage {
     inner: Arc<Mutex<InnerStorage>>,
 }
@@ -44,6 +72,8 @@ impl Default for MemoryStorage {
         let inner = Arc::new(Mutex::new(InnerStorage {
             sessions: HashMap::new(),
             timeout_queue: PriorityQueue::new(),
+            user_sessions: HashMap::new(),
+            metadata: HashMap::new(),
         }));
         tokio::spawn({
             let inner = Arc::downgrade(&inner);
@@ -95,9 +125,93 @@ impl SessionStorage for MemoryStorage {
     }
 
     async fn remove_session(&self, session_id: &str) -> Result<()> {
+        self.inner.lock().remove(session_id);
+        Ok(())
+    }
+
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
         let mut inner = self.inner.lock();
-        inner.sessions.remove(session_id);
+        let entries = inner
+            .sessions
+            .get_mut(session_id)
+            .ok_or(SessionNotFoundError)?;
+        for name in removed {
+            entries.remove(name);
+        }
+        entries.extend(
+            changed
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
         inner.timeout_queue.remove(session_id);
+        if let Some(expires) = expires {
+            inner
+                .timeout_queue
+                .push(session_id.to_string(), Reverse(Instant::now() + expires));
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if !inner.sessions.contains_key(session_id) {
+            return Ok(());
+        }
+        inner.timeout_queue.remove(session_id);
+        if let Some(expires) = expires {
+            inner
+                .timeout_queue
+                .push(session_id.to_string(), Reverse(Instant::now() + expires));
+        }
+        Ok(())
+    }
+
+    async fn index_session(
+        &self,
+        user: &str,
+        metadata: &SessionMetadata,
+        _expires: Option<Duration>,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if !inner.sessions.contains_key(&metadata.session_id) {
+            return Ok(());
+        }
+        inner.unindex(&metadata.session_id);
+        inner
+            .user_sessions
+            .entry(user.to_string())
+            .or_default()
+            .insert(metadata.session_id.clone());
+        inner.metadata.insert(
+            metadata.session_id.clone(),
+            (user.to_string(), metadata.clone()),
+        );
+        Ok(())
+    }
+
+    async fn list_sessions_for(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        let inner = self.inner.lock();
+        Ok(inner
+            .user_sessions
+            .get(user)
+            .into_iter()
+            .flatten()
+            .filter_map(|session_id| inner.metadata.get(session_id))
+            .map(|(_, metadata)| metadata.clone())
+            .collect())
+    }
+
+    async fn revoke_all_for(&self, user: &str) -> Result<()> {
+        let mut inner = self.inner.lock();
+        for session_id in inner.user_sessions.remove(user).unwrap_or_default() {
+            inner.remove(&session_id);
+        }
         Ok(())
     }
 }
@@ -134,6 +248,53 @@ mod tests {
         client.assert_cookies(vec![]);
     }
 
+    #[tokio::test]
+    async fn patch_removed_session() {
+        let storage = MemoryStorage::new();
+        let mut values = BTreeMap::new();
+        values.insert("value".to_string(), "1".into());
+
+        let err = storage
+            .patch_session("a", &values, &[], None)
+            .await
+            .unwrap_err();
+        assert!(err.is::<SessionNotFoundError>());
+        assert_eq!(storage.load_session("a").await.unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_remove_and_patch() {
+        let storage = MemoryStorage::new();
+        let mut values = BTreeMap::new();
+        values.insert("value".to_string(), "1".into());
+
+        for i in 0..100 {
+            let session_id = i.to_string();
+            storage
+                .update_session(&session_id, &values, None)
+                .await
+                .unwrap();
+
+            let remove = tokio::spawn({
+                let storage = storage.clone();
+                let session_id = session_id.clone();
+                async move { storage.remove_session(&session_id).await.unwrap() }
+            });
+            let patch = tokio::spawn({
+                let storage = storage.clone();
+                let session_id = session_id.clone();
+                let values = values.clone();
+                async move {
+                    let _ = storage.patch_session(&session_id, &values, &[], None).await;
+                }
+            });
+            remove.await.unwrap();
+            patch.await.unwrap();
+
+            assert_eq!(storage.load_session(&session_id).await.unwrap(), None);
+        }
+    }
+
     #[tokio::test]
     async fn timeout() {
         let storage = MemoryStorage::new();
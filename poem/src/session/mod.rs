@@ -1,9 +1,15 @@
 //! Session management.
 
+mod client_binding;
 mod cookie_config;
 mod cookie_session;
+#[cfg(feature = "session-encryption")]
+mod encrypted_storage;
+mod flash;
 mod memory_storage;
 #[cfg(feature = "redis-session")]
+mod redis_sentinel;
+#[cfg(feature = "redis-session")]
 mod redis_storage;
 mod server_session;
 #[allow(clippy::module_inception)]
@@ -11,12 +17,24 @@ mod session;
 mod session_storage;
 #[cfg(test)]
 pub(crate) mod test_harness;
+mod transport;
+mod typed_session;
 
+pub use client_binding::{BindingMismatchPolicy, ClientBinding};
 pub use cookie_config::{CookieConfig, CookieSecurity};
 pub use cookie_session::{CookieSession, CookieSessionEndpoint};
+#[cfg(feature = "session-encryption")]
+pub use encrypted_storage::{EncryptedStorage, SessionCipher, SessionCodec};
+pub use flash::{
+    Flash, FlashLevel, FlashManager, FlashManagerEndpoint, FlashMessage, FlashMessages,
+};
 pub use memory_storage::MemoryStorage;
 #[cfg(feature = "redis-session")]
+pub use redis_sentinel::SentinelConnection;
+#[cfg(feature = "redis-session")]
 pub use redis_storage::RedisStorage;
-pub use server_session::{ServerSession, ServerSessionEndpoint};
+pub use server_session::{ServerSession, ServerSessionEndpoint, SessionLimitPolicy};
 pub use session::{Session, SessionStatus};
-pub use session_storage::SessionStorage;
+pub use session_storage::{SessionMetadata, SessionStorage};
+pub use transport::{HeaderTransport, SessionTransport};
+pub use typed_session::{SessionState, TypedSession};
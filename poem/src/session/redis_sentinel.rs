@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use futures_util::FutureExt;
+use redis::{
+    aio::{ConnectionLike, MultiplexedConnection},
+    sentinel::{Sentinel, SentinelNodeConnectionInfo},
+    Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value,
+};
+use tokio::sync::Mutex;
+
+struct State {
+    sentinel: Sentinel,
+    service_name: String,
+    node_connection_info: SentinelNodeConnectionInfo,
+    connection: Option<MultiplexedConnection>,
+    generation: u64,
+}
+
+/// A connection to the master of a Redis Sentinel deployment, which can be
+/// used with [`RedisStorage`](crate::session::RedisStorage).
+///
+/// The address of the master is resolved with the sentinels. It is resolved
+/// again when the connection fails or the server is no longer the master
+/// after a failover, and the failed command is retried once on the new
+/// master. The clones share the same connection.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::session::{RedisStorage, SentinelConnection};
+/// use redis::sentinel::{Sentinel, SentinelNodeConnectionInfo};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let sentinel = Sentinel::build(vec!["redis://127.0.0.1:26379/"]).unwrap();
+/// let connection =
+///     SentinelConnection::new(sentinel, "mymaster", SentinelNodeConnectionInfo::default())
+///         .await
+///         .unwrap();
+/// let storage = RedisStorage::new(connection);
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-session")))]
+#[derive(Clone)]
+pub struct SentinelConnection {
+    state: Arc<Mutex<State>>,
+    db: i64,
+}
+
+impl SentinelConnection {
+    /// Connects to the master with the service name.
+    pub async fn new(
+        sentinel: Sentinel,
+        service_name: impl Into<String>,
+        node_connection_info: SentinelNodeConnectionInfo,
+    ) -> RedisResult<Self> {
+        let db = node_connection_info
+            .redis_connection_info
+            .as_ref()
+            .map_or(0, |info| info.db);
+        let connection = Self {
+            state: Arc::new(Mutex::new(State {
+                sentinel,
+                service_name: service_name.into(),
+                node_connection_info,
+                connection: None,
+                generation: 0,
+            })),
+            db,
+        };
+        connection.connection().await?;
+        Ok(connection)
+    }
+
+    /// Returns the connection to the master and its generation, and resolves
+    /// the master if there is no connection.
+    async fn connection(&self) -> RedisResult<(MultiplexedConnection, u64)> {
+        let mut state = self.state.lock().await;
+        if let Some(connection) = &state.connection {
+            return Ok((connection.clone(), state.generation));
+        }
+
+        let State {
+            sentinel,
+            service_name,
+            node_connection_info,
+            ..
+        } = &mut *state;
+        let client = sentinel
+            .async_master_for(service_name, Some(node_connection_info))
+            .await?;
+        let connection = client.get_multiplexed_tokio_connection().await?;
+        state.connection = Some(connection.clone());
+        state.generation += 1;
+        Ok((connection, state.generation))
+    }
+
+    /// Drops the connection, unless it has been replaced by another request.
+    async fn reset(&self, generation: u64) {
+        let mut state = self.state.lock().await;
+        if state.generation == generation {
+            state.connection = None;
+        }
+    }
+}
+
+/// Returns `true` if the master may have changed.
+fn is_failover(err: &RedisError) -> bool {
+    err.is_io_error() || err.kind() == ErrorKind::ReadOnly
+}
+
+impl ConnectionLike for SentinelConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        async move {
+            let (mut connection, generation) = self.connection().await?;
+            match connection.req_packed_command(cmd).await {
+                Err(err) if is_failover(&err) => {
+                    self.reset(generation).await;
+                    self.connection().await?.0.req_packed_command(cmd).await
+                }
+                res => res,
+            }
+        }
+        .boxed()
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        async move {
+            let (mut connection, generation) = self.connection().await?;
+            match connection.req_packed_commands(cmd, offset, count).await {
+                Err(err) if is_failover(&err) => {
+                    self.reset(generation).await;
+                    self.connection()
+                        .await?
+                        .0
+                        .req_packed_commands(cmd, offset, count)
+                        .await
+                }
+                res => res,
+            }
+        }
+        .boxed()
+    }
+
+    fn get_db(&self) -> i64 {
+        self.db
+    }
+}
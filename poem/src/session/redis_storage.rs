@@ -1,43 +1,213 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
-use redis::{aio::ConnectionLike, AsyncCommands, Cmd};
+use futures_util::future::try_join_all;
+use redis::{aio::ConnectionLike, AsyncCommands, Cmd, Script};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::{error::InternalServerError, session::session_storage::SessionStorage, Result};
+use crate::{
+    error::{CorruptedSessionError, InternalServerError, SessionNotFoundError},
+    session::{session_storage::SessionStorage, SessionMetadata},
+    Result,
+};
+
+const DEFAULT_PREFIX: &str = "poem-session:";
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    user: String,
+    metadata: SessionMetadata,
+}
 
 /// A session storage using redis.
 ///
+/// Each session is stored in a hash with the key `{prefix}{{session_id}}`,
+/// and every entry is stored in a separate field as a JSON string, so
+/// concurrent requests that change different entries of a session do not
+/// overwrite each other's changes. The default prefix is `poem-session:`.
+///
+/// It supports the per-user session index, the session ids of a user are
+/// stored in a set with the key `{prefix}user:{user}`, and the metadata of a
+/// session is stored with the key `{prefix}{{session_id}}:meta`. Expired
+/// members of the set are removed when listing the sessions.
+///
+/// The sessions stored by the previous versions in string keys named with
+/// the session ids are loaded and moved to the new format when they are
+/// accessed, see [`RedisStorage::migrate_legacy_sessions`].
+///
+/// It works with Redis Cluster through
+/// [`redis::cluster_async::ClusterConnection`], because the keys of a session
+/// share the `{session_id}` hash tag and no command uses the keys of
+/// different sessions, and with Redis Sentinel through
+/// [`SentinelConnection`](crate::session::SentinelConnection).
+///
 /// # Errors
 ///
 /// - [`redis::RedisError`]
+/// - [`CorruptedSessionError`] if the stored session cannot be deserialized,
+///   unless [`RedisStorage::discard_corrupted`] is enabled.
 #[cfg_attr(docsrs, doc(cfg(feature = "redis-session")))]
+#[derive(Clone)]
 pub struct RedisStorage<T> {
     connection: T,
+    prefix: String,
+    discard_corrupted: bool,
+    migrate_legacy: bool,
 }
 
 impl<T> RedisStorage<T> {
     /// Create a `RedisStorage`.
     pub fn new(connection: T) -> Self {
-        Self { connection }
+        Self {
+            connection,
+            prefix: DEFAULT_PREFIX.to_string(),
+            discard_corrupted: false,
+            migrate_legacy: true,
+        }
+    }
+
+    /// Sets the prefix of the keys. Default is `poem-session:`.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    /// If `true`, sessions that cannot be deserialized are logged and treated
+    /// as missing instead of returning [`CorruptedSessionError`]. Default is
+    /// `false`.
+    #[must_use]
+    pub fn discard_corrupted(self, discard: bool) -> Self {
+        Self {
+            discard_corrupted: discard,
+            ..self
+        }
+    }
+
+    /// If `true`, a session which is not found is loaded from the string key
+    /// named with the session id, the format used by the previous versions,
+    /// and moved to the new format. Default is `true`, so that the sessions
+    /// created before upgrading remain valid.
+    ///
+    /// Only the ids generated by [`ServerSession`](crate::session::ServerSession)
+    /// are looked up, but other string keys of the database with such names
+    /// may still be read and removed, so it can be disabled once the legacy
+    /// sessions have expired.
+    #[must_use]
+    pub fn migrate_legacy_sessions(self, migrate: bool) -> Self {
+        Self {
+            migrate_legacy: migrate,
+            ..self
+        }
+    }
+
+    fn session_key(&self, session_id: &str) -> String {
+        format!("{}{{{}}}", self.prefix, session_id)
+    }
+
+    fn metadata_key(&self, session_id: &str) -> String {
+        format!("{}{{{}}}:meta", self.prefix, session_id)
+    }
+
+    fn user_key(&self, user: &str) -> String {
+        format!("{}user:{}", self.prefix, user)
+    }
+}
+
+impl<T: ConnectionLike + Clone + Sync + Send> RedisStorage<T> {
+    /// Loads the session from the string key used by the previous versions,
+    /// and moves it to the hash.
+    async fn migrate_legacy_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<BTreeMap<String, Value>>> {
+        // The session ids generated by the previous versions.
+        if session_id.len() != 32 || !session_id.bytes().all(|c| c.is_ascii_alphanumeric()) {
+            return Ok(None);
+        }
+
+        let mut connection = self.connection.clone();
+        let key_type: String = redis::cmd("TYPE")
+            .arg(session_id)
+            .query_async(&mut connection)
+            .await
+            .map_err(InternalServerError)?;
+        if key_type != "string" {
+            return Ok(None);
+        }
+        let (data, ttl): (Option<String>, i64) = redis::pipe()
+            .get(session_id)
+            .pttl(session_id)
+            .query_async(&mut connection)
+            .await
+            .map_err(InternalServerError)?;
+        let entries = match data
+            .as_deref()
+            .and_then(|data| serde_json::from_str::<BTreeMap<String, Value>>(data).ok())
+        {
+            Some(entries) if !entries.is_empty() => entries,
+            _ => return Ok(None),
+        };
+
+        // The legacy key and the session key are in the same hash slot.
+        let key = self.session_key(session_id);
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset_multiple(&key, &encode_entries(&entries))
+            .ignore();
+        if ttl > 0 {
+            pipe.pexpire(&key, ttl as usize).ignore();
+        }
+        pipe.del(session_id).ignore();
+        pipe.query_async::<_, ()>(&mut connection)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Some(entries))
     }
 }
 
 #[async_trait::async_trait]
 impl<T: ConnectionLike + Clone + Sync + Send> SessionStorage for RedisStorage<T> {
     async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
-        let data: Option<String> = self
+        let data: HashMap<String, String> = self
             .connection
             .clone()
-            .get(session_id)
+            .hgetall(self.session_key(session_id))
             .await
             .map_err(InternalServerError)?;
-        match data {
-            Some(data) => match serde_json::from_str::<BTreeMap<String, Value>>(&data) {
-                Ok(entries) => Ok(Some(entries)),
-                Err(_) => Ok(None),
-            },
-            None => Ok(None),
+        if data.is_empty() {
+            if self.migrate_legacy {
+                return self.migrate_legacy_session(session_id).await;
+            }
+            return Ok(None);
         }
+
+        let mut entries = BTreeMap::new();
+        for (name, value) in data {
+            match serde_json::from_str::<Value>(&value) {
+                Ok(value) => {
+                    entries.insert(name, value);
+                }
+                Err(err) => {
+                    tracing::error!(
+                        session_id = session_id,
+                        entry = %name,
+                        error = %err,
+                        "corrupted session"
+                    );
+                    if self.discard_corrupted {
+                        return Ok(None);
+                    }
+                    return Err(CorruptedSessionError.into());
+                }
+            }
+        }
+        Ok(Some(entries))
     }
 
     async fn update_session(
@@ -46,24 +216,215 @@ impl<T: ConnectionLike + Clone + Sync + Send> SessionStorage for RedisStorage<T>
         entries: &BTreeMap<String, Value>,
         expires: Option<Duration>,
     ) -> Result<()> {
-        let value = serde_json::to_string(entries).unwrap_or_default();
-        let cmd = match expires {
-            Some(expires) => Cmd::set_ex(&session_id, value, expires.as_secs() as usize),
-            None => Cmd::set(&session_id, value),
-        };
-        cmd.query_async(&mut self.connection.clone())
+        let key = self.session_key(session_id);
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&key).ignore();
+        if !entries.is_empty() {
+            pipe.hset_multiple(&key, &encode_entries(entries)).ignore();
+            if let Some(expires) = expires {
+                pipe.pexpire(&key, expire_millis(expires)).ignore();
+            }
+        }
+        pipe.query_async::<_, ()>(&mut self.connection.clone())
             .await
             .map_err(InternalServerError)?;
         Ok(())
     }
 
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let script = Script::new(PATCH_SCRIPT);
+        let mut invocation = script.key(self.session_key(session_id));
+        invocation
+            .arg(expires.map(expire_millis).unwrap_or_default())
+            .arg(changed.len());
+        for (name, value) in encode_entries(changed) {
+            invocation.arg(name).arg(value);
+        }
+        let exists: bool = invocation
+            .arg(removed)
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .map_err(InternalServerError)?;
+        if !exists {
+            return Err(SessionNotFoundError.into());
+        }
+        Ok(())
+    }
+
     async fn remove_session(&self, session_id: &str) -> Result<()> {
-        Cmd::del(session_id)
-            .query_async(&mut self.connection.clone())
+        let mut connection = self.connection.clone();
+        let metadata_key = self.metadata_key(session_id);
+        let entry: Option<String> = connection
+            .get(&metadata_key)
+            .await
+            .map_err(InternalServerError)?;
+
+        connection
+            .del::<_, ()>(&[self.session_key(session_id), metadata_key])
+            .await
+            .map_err(InternalServerError)?;
+        if let Some(entry) = entry.and_then(|entry| serde_json::from_str::<IndexEntry>(&entry).ok())
+        {
+            connection
+                .srem::<_, _, ()>(self.user_key(&entry.user), session_id)
+                .await
+                .map_err(InternalServerError)?;
+        }
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()> {
+        let mut pipe = redis::pipe();
+        for key in [self.session_key(session_id), self.metadata_key(session_id)] {
+            match expires {
+                Some(expires) => pipe.pexpire(key, expire_millis(expires)),
+                None => pipe.persist(key),
+            };
+            pipe.ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.connection.clone())
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+
+    async fn index_session(
+        &self,
+        user: &str,
+        metadata: &SessionMetadata,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let entry = serde_json::to_string(&IndexEntry {
+            user: user.to_string(),
+            metadata: metadata.clone(),
+        })
+        .unwrap_or_default();
+        let metadata_key = self.metadata_key(&metadata.session_id);
+
+        match expires {
+            Some(expires) => {
+                connection
+                    .pset_ex::<_, _, ()>(&metadata_key, entry, expire_millis(expires))
+                    .await
+            }
+            None => connection.set::<_, _, ()>(&metadata_key, entry).await,
+        }
+        .map_err(InternalServerError)?;
+        connection
+            .sadd::<_, _, ()>(self.user_key(user), &metadata.session_id)
             .await
             .map_err(InternalServerError)?;
         Ok(())
     }
+
+    async fn list_sessions_for(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        let mut connection = self.connection.clone();
+        let user_key = self.user_key(user);
+        let session_ids: Vec<String> = connection
+            .smembers(&user_key)
+            .await
+            .map_err(InternalServerError)?;
+        if session_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The metadata keys may be in different hash slots, so they are read
+        // one by one instead of with `MGET`.
+        let entries = try_join_all(session_ids.iter().map(|session_id| {
+            let mut connection = self.connection.clone();
+            let metadata_key = self.metadata_key(session_id);
+            async move { connection.get::<_, Option<String>>(metadata_key).await }
+        }))
+        .await
+        .map_err(InternalServerError)?;
+
+        let mut sessions = Vec::new();
+        let mut expired = Vec::new();
+        for (session_id, entry) in session_ids.into_iter().zip(entries) {
+            match entry.and_then(|entry| serde_json::from_str::<IndexEntry>(&entry).ok()) {
+                Some(entry) if entry.user == user => sessions.push(entry.metadata),
+                _ => expired.push(session_id),
+            }
+        }
+
+        if !expired.is_empty() {
+            Cmd::srem(&user_key, expired)
+                .query_async::<_, ()>(&mut connection)
+                .await
+                .map_err(InternalServerError)?;
+        }
+
+        Ok(sessions)
+    }
+
+    async fn revoke_all_for(&self, user: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let user_key = self.user_key(user);
+        let session_ids: Vec<String> = connection
+            .smembers(&user_key)
+            .await
+            .map_err(InternalServerError)?;
+
+        try_join_all(session_ids.iter().map(|session_id| {
+            let mut connection = self.connection.clone();
+            let keys = [self.session_key(session_id), self.metadata_key(session_id)];
+            async move { connection.del::<_, ()>(&keys).await }
+        }))
+        .await
+        .map_err(InternalServerError)?;
+        connection
+            .del::<_, ()>(&user_key)
+            .await
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+}
+
+/// Updates the entries of a session if it exists, and returns `1` if it
+/// exists.
+///
+/// `KEYS[1]` is the session key. `ARGV[1]` is the TTL in milliseconds, `0`
+/// means no expiration, `ARGV[2]` is the number of the changed entries,
+/// followed by the names and values of the changed entries, and the names of
+/// the removed entries.
+const PATCH_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+local changed = tonumber(ARGV[2])
+for i = 1, changed do
+    redis.call('HSET', KEYS[1], ARGV[1 + i * 2], ARGV[2 + i * 2])
+end
+for i = 3 + changed * 2, #ARGV do
+    redis.call('HDEL', KEYS[1], ARGV[i])
+end
+local ttl = tonumber(ARGV[1])
+if ttl > 0 then
+    redis.call('PEXPIRE', KEYS[1], ttl)
+else
+    redis.call('PERSIST', KEYS[1])
+end
+return 1
+";
+
+/// Returns the TTL in milliseconds, which is at least `1`, because
+/// `PEXPIRE 0` deletes the key immediately.
+fn expire_millis(expires: Duration) -> usize {
+    expires.as_millis().max(1) as usize
+}
+
+fn encode_entries(entries: &BTreeMap<String, Value>) -> Vec<(&str, String)> {
+    entries
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.to_string()))
+        .collect()
 }
 
 #[cfg(test)]
@@ -107,4 +468,183 @@ mod tests {
         client.call(&app, 5).await;
         client.assert_cookies(vec![]);
     }
+
+    #[tokio::test]
+    async fn cluster() {
+        let client = match redis::cluster::ClusterClient::new(vec!["redis://127.0.0.1:7000/"]) {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        let connection = match client.get_async_connection().await {
+            Ok(connection) => connection,
+            Err(_) => return,
+        };
+
+        let storage = RedisStorage::new(connection).prefix("poem-test-session:");
+        let mut entries = BTreeMap::new();
+        entries.insert("a".to_string(), Value::from(1));
+        storage
+            .update_session("cluster", &entries, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        storage
+            .index_session(
+                "sunli",
+                &SessionMetadata {
+                    session_id: "cluster".to_string(),
+                    created_at: std::time::SystemTime::now(),
+                    last_seen: std::time::SystemTime::now(),
+                    ip: None,
+                    user_agent: None,
+                },
+                Some(Duration::from_secs(60)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.load_session("cluster").await.unwrap(),
+            Some(entries)
+        );
+        assert_eq!(storage.list_sessions_for("sunli").await.unwrap().len(), 1);
+        storage.revoke_all_for("sunli").await.unwrap();
+        assert_eq!(storage.load_session("cluster").await.unwrap(), None);
+    }
+
+    #[test]
+    fn keys() {
+        let storage = RedisStorage::new(()).prefix("app:");
+        assert_eq!(storage.session_key("abc"), "app:{abc}");
+        assert_eq!(storage.metadata_key("abc"), "app:{abc}:meta");
+        assert_eq!(storage.user_key("sunli"), "app:user:sunli");
+    }
+
+    #[tokio::test]
+    async fn patch_session() {
+        let client = match Client::open("redis://127.0.0.1/") {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        let mut conn = match client.get_connection() {
+            Ok(conn) => conn,
+            Err(_) => return,
+        };
+        if !conn.check_connection() {
+            return;
+        }
+
+        let storage = RedisStorage::new(ConnectionManager::new(client).await.unwrap())
+            .prefix("poem-test-session:");
+        let mut entries = BTreeMap::new();
+        entries.insert("a".to_string(), Value::from(1));
+        entries.insert("b".to_string(), Value::from(2));
+        storage
+            .update_session("patch", &entries, None)
+            .await
+            .unwrap();
+
+        // Two requests change different entries of the same session.
+        let mut changed = BTreeMap::new();
+        changed.insert("a".to_string(), Value::from(10));
+        storage
+            .patch_session("patch", &changed, &[], None)
+            .await
+            .unwrap();
+        let mut changed = BTreeMap::new();
+        changed.insert("c".to_string(), Value::from(3));
+        storage
+            .patch_session("patch", &changed, &["b".to_string()], None)
+            .await
+            .unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), Value::from(10));
+        expected.insert("c".to_string(), Value::from(3));
+        assert_eq!(storage.load_session("patch").await.unwrap(), Some(expected));
+
+        redis::cmd("HSET")
+            .arg("poem-test-session:{patch}")
+            .arg("d")
+            .arg("{invalid")
+            .query::<()>(&mut conn)
+            .unwrap();
+        assert!(storage.load_session("patch").await.is_err());
+        assert_eq!(
+            storage
+                .clone()
+                .discard_corrupted(true)
+                .load_session("patch")
+                .await
+                .unwrap(),
+            None
+        );
+        storage.remove_session("patch").await.unwrap();
+
+        // The removed session is not recreated.
+        let err = storage
+            .patch_session("patch", &changed, &[], None)
+            .await
+            .unwrap_err();
+        assert!(err.is::<SessionNotFoundError>());
+        assert_eq!(storage.load_session("patch").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn migrate_legacy_sessions() {
+        let client = match Client::open("redis://127.0.0.1/") {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        let mut conn = match client.get_connection() {
+            Ok(conn) => conn,
+            Err(_) => return,
+        };
+        if !conn.check_connection() {
+            return;
+        }
+
+        let session_id = "poemTestLegacySession00000000000";
+        redis::cmd("SET")
+            .arg(session_id)
+            .arg(r#"{"a":1,"b":"x"}"#)
+            .arg("EX")
+            .arg(60)
+            .query::<()>(&mut conn)
+            .unwrap();
+
+        let storage = RedisStorage::new(ConnectionManager::new(client).await.unwrap())
+            .prefix("poem-test-session:");
+        assert_eq!(
+            storage
+                .clone()
+                .migrate_legacy_sessions(false)
+                .load_session(session_id)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), Value::from(1));
+        expected.insert("b".to_string(), Value::from("x"));
+        assert_eq!(
+            storage.load_session(session_id).await.unwrap(),
+            Some(expected.clone())
+        );
+
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(session_id)
+            .query(&mut conn)
+            .unwrap();
+        assert!(!exists);
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(format!("poem-test-session:{{{}}}", session_id))
+            .query(&mut conn)
+            .unwrap();
+        assert!(ttl > 0);
+        assert_eq!(
+            storage.load_session(session_id).await.unwrap(),
+            Some(expected)
+        );
+        storage.remove_session(session_id).await.unwrap();
+    }
 }
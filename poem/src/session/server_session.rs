@@ -1,37 +1,277 @@
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use serde_json::Value;
 
 use crate::{
+    error::{SessionNotFoundError, SessionSecurityError},
+    http::header,
     middleware::{CookieJarManager, CookieJarManagerEndpoint},
-    session::{session_storage::SessionStorage, CookieConfig, Session, SessionStatus},
-    Endpoint, Middleware, Request, Result,
+    session::{
+        client_binding::ClientFingerprint, session::SessionLimitCheck,
+        session_storage::SessionStorage, BindingMismatchPolicy, ClientBinding, CookieConfig,
+        Session, SessionMetadata, SessionStatus, SessionTransport,
+    },
+    web::cookie::CookieJar,
+    Addr, Endpoint, IntoResponse, Middleware, Request, Response, Result,
 };
 
+/// The entry used to store the creation time of the session when
+/// [`ServerSession::absolute_timeout`] or [`ServerSession::index_by`] is set.
+const CREATED_AT_KEY: &str = "__poem_session_created_at";
+
+/// The entry used to store the time when the session id was last rotated
+/// when [`ServerSession::rotation_interval`] is set.
+const ROTATED_AT_KEY: &str = "__poem_session_rotated_at";
+
+/// The entry used to store the client characteristics when
+/// [`ServerSession::bind_to_client`] is set.
+const CLIENT_KEY: &str = "__poem_session_client";
+
+/// The entry used to store the time when the session was last recorded in
+/// the per-user session index when [`ServerSession::index_by`] is set.
+const LAST_SEEN_KEY: &str = "__poem_session_last_seen";
+
+/// The minimum interval between the updates of the last seen time of an
+/// unchanged session in the per-user session index.
+const LAST_SEEN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The action when a user reaches the maximum number of concurrent sessions,
+/// see [`ServerSession::max_sessions_per_user`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SessionLimitPolicy {
+    /// Rejects the new session with
+    /// [`SessionSecurityError::TooManySessions`], and the changes to the
+    /// session in the request are discarded.
+    ///
+    /// The limit is checked after the inner endpoint has handled the request,
+    /// so the side effects of the handler are not rolled back and only its
+    /// response is replaced with the error. A handler can call
+    /// [`Session::check_session_limit`] to check the limit before performing
+    /// any side effects.
+    Reject,
+
+    /// Removes the least recently used sessions of the user.
+    EvictOldest,
+}
+
 /// Middleware for server-side session.
 pub struct ServerSession<T> {
-    config: Arc<CookieConfig>,
+    transport: Arc<dyn SessionTransport>,
     storage: Arc<T>,
+    idle_timeout: Option<Duration>,
+    absolute_timeout: Option<Duration>,
+    index_by: Option<String>,
+    binding: Option<ClientBinding>,
+    rotate_on_change: Vec<String>,
+    rotation_interval: Option<Duration>,
+    max_sessions: Option<(usize, SessionLimitPolicy)>,
 }
 
 impl<T> ServerSession<T> {
-    /// Create a `ServerSession` middleware.
+    /// Create a `ServerSession` middleware which carries the session id in a
+    /// cookie.
     pub fn new(config: CookieConfig, storage: T) -> Self {
+        Self::with_transport(config, storage)
+    }
+
+    /// Create a `ServerSession` middleware with the specified
+    /// [`SessionTransport`], e.g. [`HeaderTransport`](crate::session::HeaderTransport)
+    /// for the clients which do not support cookies.
+    pub fn with_transport(transport: impl SessionTransport, storage: T) -> Self {
         Self {
-            config: Arc::new(config),
+            transport: Arc::new(transport),
             storage: Arc::new(storage),
+            idle_timeout: None,
+            absolute_timeout: None,
+            index_by: None,
+            binding: None,
+            rotate_on_change: Vec::new(),
+            rotation_interval: None,
+            max_sessions: None,
+        }
+    }
+
+    /// Sets the idle timeout of the session.
+    ///
+    /// If set, the session expires after it has not been accessed for the
+    /// specified duration, and every request with an unchanged session resets
+    /// the TTL(time-to-live) in the storage (sliding expiration). Otherwise the
+    /// session expires after the `MaxAge` of the [`CookieConfig`] (or the
+    /// [`SessionTransport::ttl`]) since it was last changed.
+    #[must_use]
+    pub fn idle_timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            idle_timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Sets the absolute timeout of the session.
+    ///
+    /// If set, the session expires after the specified duration since it was
+    /// created or renewed, regardless of activity.
+    #[must_use]
+    pub fn absolute_timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            absolute_timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Records the sessions in the per-user session index of the storage.
+    ///
+    /// The user identifier is read from the session entry with the specified
+    /// key, which is usually set when the user logs in. The metadata (creation
+    /// time, last seen time, IP address and user agent) of the session is
+    /// updated when the session is changed, and at most once a minute if it
+    /// is not changed, see also
+    /// [`SessionStorage::list_sessions_for`] and
+    /// [`SessionStorage::revoke_all_for`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{
+    ///     handler,
+    ///     session::{CookieConfig, MemoryStorage, ServerSession, Session, SessionStorage},
+    ///     web::Data,
+    ///     EndpointExt, Result, Route,
+    /// };
+    ///
+    /// #[handler]
+    /// async fn logout_everywhere(session: &Session, storage: Data<&MemoryStorage>) -> Result<()> {
+    ///     if let Some(user_id) = session.get::<String>("user_id") {
+    ///         storage.revoke_all_for(&user_id).await?;
+    ///     }
+    ///     Ok(())
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let storage = MemoryStorage::new();
+    /// let app = Route::new()
+    ///     .at("/logout_everywhere", logout_everywhere)
+    ///     .with(ServerSession::new(CookieConfig::default(), storage.clone()).index_by("user_id"))
+    ///     .data(storage);
+    /// # });
+    /// ```
+    #[must_use]
+    pub fn index_by(self, key: impl Into<String>) -> Self {
+        Self {
+            index_by: Some(key.into()),
+            ..self
+        }
+    }
+
+    /// Binds the sessions to the characteristics of the client.
+    ///
+    /// The characteristics are recorded when the session is created, and the
+    /// [`BindingMismatchPolicy`] is applied if the session is used by another
+    /// client later.
+    #[must_use]
+    pub fn bind_to_client(self, binding: ClientBinding) -> Self {
+        Self {
+            binding: Some(binding),
+            ..self
+        }
+    }
+
+    /// Rotates the session id when any of the session entries with the
+    /// specified keys is changed, which prevents the session fixation attack.
+    ///
+    /// It is usually used with the entries which represent the privilege of
+    /// the session, such as the user identifier and the roles, so that the
+    /// session id is changed when the user logs in or the privilege is
+    /// elevated. The session entries are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{
+    ///     handler,
+    ///     session::{CookieConfig, MemoryStorage, ServerSession, Session},
+    ///     EndpointExt,
+    /// };
+    ///
+    /// #[handler]
+    /// fn login(session: &Session) {
+    ///     // The session id is rotated automatically.
+    ///     session.set("user_id", "sunli");
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let app = login.with(
+    ///     ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+    ///         .rotate_on_change(["user_id", "roles"]),
+    /// );
+    /// # });
+    /// ```
+    #[must_use]
+    pub fn rotate_on_change<I, K>(self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        Self {
+            rotate_on_change: keys.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Rotates the session id periodically, the session entries are kept.
+    ///
+    /// The old session id is invalid immediately after the rotation, so a
+    /// concurrent request with the old session id starts a new session.
+    #[must_use]
+    pub fn rotation_interval(self, interval: impl Into<Option<Duration>>) -> Self {
+        Self {
+            rotation_interval: interval.into(),
+            ..self
+        }
+    }
+
+    /// Limits the number of concurrent sessions of each user.
+    ///
+    /// The limit is checked when a user identifier is stored in the session,
+    /// so it requires [`ServerSession::index_by`] and a storage supporting the
+    /// per-user session index.
+    ///
+    /// # Panics
+    ///
+    /// The middleware panics when it is applied to an endpoint if
+    /// [`ServerSession::index_by`] is not set.
+    #[must_use]
+    pub fn max_sessions_per_user(self, max_sessions: usize, policy: SessionLimitPolicy) -> Self {
+        Self {
+            max_sessions: Some((max_sessions.max(1), policy)),
+            ..self
         }
     }
 }
 
-impl<T: SessionStorage, E: Endpoint> Middleware<E> for ServerSession<T> {
+impl<T: SessionStorage + 'static, E: Endpoint> Middleware<E> for ServerSession<T> {
     type Output = CookieJarManagerEndpoint<ServerSessionEndpoint<T, E>>;
 
     fn transform(&self, ep: E) -> Self::Output {
+        assert!(
+            self.max_sessions.is_none() || self.index_by.is_some(),
+            "`ServerSession::max_sessions_per_user` requires `ServerSession::index_by`"
+        );
         CookieJarManager::new().transform(ServerSessionEndpoint {
             inner: ep,
-            config: self.config.clone(),
+            transport: self.transport.clone(),
             storage: self.storage.clone(),
+            idle_timeout: self.idle_timeout,
+            absolute_timeout: self.absolute_timeout,
+            index_by: self.index_by.clone(),
+            binding: self.binding.clone(),
+            rotate_on_change: self.rotate_on_change.clone(),
+            rotation_interval: self.rotation_interval,
+            max_sessions: self.max_sessions,
         })
     }
 }
@@ -44,73 +284,1130 @@ fn generate_session_id() -> String {
     String::from_utf8(value).unwrap_or_default()
 }
 
+fn now() -> u64 {
+    crate::time::unix_time().as_secs()
+}
+
+fn user_id(value: Value) -> String {
+    match value {
+        Value::String(user) => user,
+        user => user.to_string(),
+    }
+}
+
+/// The times recorded in the session.
+#[derive(Copy, Clone)]
+struct Timestamps {
+    created_at: u64,
+    rotated_at: u64,
+    last_seen: u64,
+}
+
+impl Timestamps {
+    fn new() -> Self {
+        let now = now();
+        Self {
+            created_at: now,
+            rotated_at: now,
+            last_seen: now,
+        }
+    }
+
+    /// Removes the times from the session entries.
+    fn take_from(entries: &mut BTreeMap<String, Value>) -> Self {
+        let mut timestamps = Self::new();
+        if let Some(created_at) = entries.remove(CREATED_AT_KEY).and_then(|v| v.as_u64()) {
+            timestamps.created_at = created_at;
+        }
+        timestamps.rotated_at = entries
+            .remove(ROTATED_AT_KEY)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(timestamps.created_at);
+        timestamps.last_seen = entries
+            .remove(LAST_SEEN_KEY)
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default();
+        timestamps
+    }
+}
+
+/// The client information recorded in the per-user session index.
+#[derive(Default)]
+struct ClientMetadata {
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl ClientMetadata {
+    fn new(req: &Request) -> Self {
+        Self {
+            ip: match &req.remote_addr().0 {
+                Addr::SocketAddr(addr) => Some(addr.ip().to_string()),
+                _ => None,
+            },
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+        }
+    }
+}
+
+/// The session of a request, as loaded from the storage.
+struct LoadedSession {
+    session_id: Option<String>,
+    session: Session,
+    timestamps: Timestamps,
+    /// The entries in the storage, including the internal entries.
+    entries: Option<BTreeMap<String, Value>>,
+    /// The characteristics of the client are not recorded yet.
+    rebind: bool,
+    /// The client sent the id of a session which has expired or has been
+    /// removed.
+    discarded: bool,
+}
+
+impl LoadedSession {
+    fn empty(discarded: bool) -> Self {
+        Self {
+            session_id: None,
+            session: Session::default(),
+            timestamps: Timestamps::new(),
+            entries: None,
+            rebind: false,
+            discarded,
+        }
+    }
+}
+
+/// Checks the concurrent session limit of a request, see
+/// [`ServerSession::max_sessions_per_user`].
+struct SessionLimit<T> {
+    storage: Arc<T>,
+    max_sessions: usize,
+    policy: SessionLimitPolicy,
+    session_id: Option<String>,
+    /// The user stored in the session before the request.
+    loaded_user: Option<String>,
+}
+
+impl<T: SessionStorage> SessionLimit<T> {
+    /// Returns the least recently used sessions of the user, which have to
+    /// be removed to store the user in the current session.
+    async fn exceeding(&self, user: &str) -> Result<Vec<SessionMetadata>> {
+        if self.loaded_user.as_deref() == Some(user) {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions = self
+            .storage
+            .list_sessions_for(user)
+            .await?
+            .into_iter()
+            .filter(|metadata| Some(&metadata.session_id) != self.session_id.as_ref())
+            .collect::<Vec<_>>();
+        if sessions.len() < self.max_sessions {
+            return Ok(Vec::new());
+        }
+        sessions.sort_by_key(|metadata| metadata.last_seen);
+        sessions.truncate(sessions.len() + 1 - self.max_sessions);
+        Ok(sessions)
+    }
+
+    /// Applies the [`SessionLimitPolicy`] when the user is stored in the
+    /// session.
+    async fn apply(&self, user: &str) -> Result<()> {
+        let sessions = self.exceeding(user).await?;
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        match self.policy {
+            SessionLimitPolicy::Reject => Err(SessionSecurityError::TooManySessions.into()),
+            SessionLimitPolicy::EvictOldest => {
+                for metadata in &sessions {
+                    self.storage.remove_session(&metadata.session_id).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: SessionStorage> SessionLimitCheck for SessionLimit<T> {
+    async fn check(&self, user: &str) -> Result<()> {
+        match self.policy {
+            SessionLimitPolicy::Reject if !self.exceeding(user).await?.is_empty() => {
+                Err(SessionSecurityError::TooManySessions.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Endpoint for `ServerSession` middleware.
 pub struct ServerSessionEndpoint<T, E> {
     inner: E,
-    config: Arc<CookieConfig>,
+    transport: Arc<dyn SessionTransport>,
     storage: Arc<T>,
+    idle_timeout: Option<Duration>,
+    absolute_timeout: Option<Duration>,
+    index_by: Option<String>,
+    binding: Option<ClientBinding>,
+    rotate_on_change: Vec<String>,
+    rotation_interval: Option<Duration>,
+    max_sessions: Option<(usize, SessionLimitPolicy)>,
+}
+
+impl<T, E> ServerSessionEndpoint<T, E> {
+    fn ttl(&self, created_at: u64) -> Option<Duration> {
+        let ttl = self.idle_timeout.or_else(|| self.transport.ttl());
+        match self.absolute_timeout {
+            Some(absolute_timeout) => {
+                let remaining = absolute_timeout
+                    .saturating_sub(Duration::from_secs(now().saturating_sub(created_at)));
+                Some(ttl.map_or(remaining, |ttl| ttl.min(remaining)))
+            }
+            None => ttl,
+        }
+    }
+
+    fn entries(
+        &self,
+        session: &Session,
+        timestamps: Timestamps,
+        client: Option<&ClientFingerprint>,
+    ) -> BTreeMap<String, Value> {
+        let mut entries = session.entries();
+        if self.absolute_timeout.is_some() || self.index_by.is_some() {
+            entries.insert(CREATED_AT_KEY.to_string(), timestamps.created_at.into());
+        }
+        if self.rotation_interval.is_some() {
+            entries.insert(ROTATED_AT_KEY.to_string(), timestamps.rotated_at.into());
+        }
+        if self.index_by.is_some() {
+            entries.insert(LAST_SEEN_KEY.to_string(), timestamps.last_seen.into());
+        }
+        if let Some(client) = client.and_then(|client| serde_json::to_value(client).ok()) {
+            entries.insert(CLIENT_KEY.to_string(), client);
+        }
+        entries
+    }
+
+    /// Returns the user stored in the session entries.
+    fn user(&self, entries: &BTreeMap<String, Value>) -> Option<String> {
+        self.index_by
+            .as_deref()
+            .and_then(|key| entries.get(key).cloned())
+            .map(user_id)
+    }
+
+    /// Returns `true` if the session id should be rotated automatically.
+    fn should_rotate(&self, loaded: &BTreeMap<String, Value>, session: &Session) -> bool {
+        let changed = self
+            .rotate_on_change
+            .iter()
+            .any(|key| loaded.get(key) != session.get::<Value>(key).as_ref());
+        let expired = match self.rotation_interval {
+            Some(interval) => {
+                let rotated_at = loaded
+                    .get(ROTATED_AT_KEY)
+                    .or_else(|| loaded.get(CREATED_AT_KEY))
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default();
+                now().saturating_sub(rotated_at) >= interval.as_secs()
+            }
+            None => false,
+        };
+        changed || expired
+    }
+}
+
+impl<T: SessionStorage, E> ServerSessionEndpoint<T, E> {
+    /// Loads the session, and discards it if it has expired or it is used by
+    /// another client.
+    async fn load(
+        &self,
+        session_id: Option<String>,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<LoadedSession> {
+        let session_id = match session_id {
+            Some(session_id) => session_id,
+            None => return Ok(LoadedSession::empty(false)),
+        };
+        let mut entries = match self.storage.load_session(&session_id).await? {
+            Some(entries) => entries,
+            None => return Ok(LoadedSession::empty(true)),
+        };
+
+        let raw_entries = entries.clone();
+        let timestamps = Timestamps::take_from(&mut entries);
+        let stored_client = entries
+            .remove(CLIENT_KEY)
+            .and_then(|value| serde_json::from_value::<ClientFingerprint>(value).ok());
+        let mismatch = match (client, &stored_client) {
+            (Some(client), Some(stored_client)) => !client.matches(stored_client),
+            _ => false,
+        };
+        let expired = matches!(
+            self.absolute_timeout,
+            Some(absolute_timeout)
+                if now().saturating_sub(timestamps.created_at) >= absolute_timeout.as_secs()
+        );
+
+        if !expired
+            && mismatch
+            && self.binding.as_ref().map(ClientBinding::policy)
+                == Some(BindingMismatchPolicy::Reject)
+        {
+            return Err(SessionSecurityError::ClientMismatch.into());
+        }
+        if expired || mismatch {
+            self.storage.remove_session(&session_id).await?;
+            return Ok(LoadedSession::empty(true));
+        }
+
+        Ok(LoadedSession {
+            session_id: Some(session_id),
+            session: Session::new(entries),
+            timestamps,
+            entries: Some(raw_entries),
+            rebind: client.is_some() && stored_client.as_ref() != client,
+            discarded: false,
+        })
+    }
+
+    fn session_limit(&self, loaded: &LoadedSession) -> Option<SessionLimit<T>> {
+        let (max_sessions, policy) = self.max_sessions?;
+        Some(SessionLimit {
+            storage: self.storage.clone(),
+            max_sessions,
+            policy,
+            session_id: loaded.session_id.clone(),
+            loaded_user: loaded
+                .entries
+                .as_ref()
+                .and_then(|entries| self.user(entries)),
+        })
+    }
+
+    /// Updates some entries of the session, and returns `false` if the
+    /// session does not exist.
+    async fn patch(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<bool> {
+        match self
+            .storage
+            .patch_session(session_id, changed, removed, expires)
+            .await
+        {
+            Ok(()) => Ok(true),
+            Err(err) if err.is::<SessionNotFoundError>() => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Stores the session with a new session id.
+    async fn create(
+        &self,
+        cookie_jar: &CookieJar,
+        resp: &mut Response,
+        session: &Session,
+        timestamps: Timestamps,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<String> {
+        let session_id = generate_session_id();
+        self.transport.set_session_id(cookie_jar, resp, &session_id);
+        self.storage
+            .update_session(
+                &session_id,
+                &self.entries(session, timestamps, client),
+                self.ttl(timestamps.created_at),
+            )
+            .await?;
+        Ok(session_id)
+    }
+
+    /// Moves the session to a new session id.
+    async fn rotate(
+        &self,
+        cookie_jar: &CookieJar,
+        resp: &mut Response,
+        session_id: Option<String>,
+        session: &Session,
+        timestamps: Timestamps,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<String> {
+        if let Some(session_id) = session_id {
+            self.storage.remove_session(&session_id).await?;
+        }
+        self.create(cookie_jar, resp, session, timestamps, client)
+            .await
+    }
+
+    /// Writes the changed entries of the session, and returns `None` if the
+    /// session has been removed in the meantime.
+    async fn update(
+        &self,
+        cookie_jar: &CookieJar,
+        resp: &mut Response,
+        loaded: LoadedSession,
+        timestamps: Timestamps,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<Option<String>> {
+        let entries = self.entries(&loaded.session, timestamps, client);
+        let (session_id, loaded_entries) = match (loaded.session_id, loaded.entries) {
+            (Some(session_id), Some(loaded_entries)) => (session_id, loaded_entries),
+            _ => {
+                return self
+                    .create(cookie_jar, resp, &loaded.session, timestamps, client)
+                    .await
+                    .map(Some)
+            }
+        };
+
+        let changed = entries
+            .iter()
+            .filter(|(name, value)| loaded_entries.get(*name) != Some(value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let removed = loaded_entries
+            .keys()
+            .filter(|name| !entries.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let exists = self
+            .patch(
+                &session_id,
+                &changed,
+                &removed,
+                self.ttl(timestamps.created_at),
+            )
+            .await?;
+        Ok(exists.then_some(session_id))
+    }
+
+    /// Updates the last seen time and extends the expiration of an unchanged
+    /// session, returns the session id and whether the last seen time is
+    /// updated, or `None` if the session has been removed in the meantime.
+    async fn refresh(
+        &self,
+        cookie_jar: &CookieJar,
+        resp: &mut Response,
+        session_id: String,
+        user: Option<&str>,
+        timestamps: &mut Timestamps,
+    ) -> Result<Option<(String, bool)>> {
+        let mut seen = false;
+        if user.is_some()
+            && now().saturating_sub(timestamps.last_seen) >= LAST_SEEN_INTERVAL.as_secs()
+        {
+            timestamps.last_seen = now();
+            let mut changed = BTreeMap::new();
+            changed.insert(LAST_SEEN_KEY.to_string(), timestamps.last_seen.into());
+            if !self
+                .patch(&session_id, &changed, &[], self.ttl(timestamps.created_at))
+                .await?
+            {
+                return Ok(None);
+            }
+            seen = true;
+        }
+
+        if self.idle_timeout.is_some() {
+            if !seen {
+                self.storage
+                    .touch_session(&session_id, self.ttl(timestamps.created_at))
+                    .await?;
+            }
+            if self.transport.ttl().is_some() {
+                // Also extend the expiration of the session id on the client.
+                self.transport.set_session_id(cookie_jar, resp, &session_id);
+            }
+        }
+        Ok(Some((session_id, seen)))
+    }
+
+    /// Writes the session to the storage after the request is handled.
+    async fn save(
+        &self,
+        cookie_jar: &CookieJar,
+        resp: &mut Response,
+        loaded: LoadedSession,
+        client: Option<&ClientFingerprint>,
+        client_metadata: ClientMetadata,
+    ) -> Result<()> {
+        let session = loaded.session.clone();
+        let mut timestamps = loaded.timestamps;
+        let discarded = loaded.discarded;
+        let had_session_id = loaded.session_id.is_some();
+
+        let mut status = session.status();
+        if status == SessionStatus::Unchanged && loaded.rebind {
+            // Records the characteristics which are not recorded yet.
+            status = SessionStatus::Changed;
+        }
+        let rotate = matches!(status, SessionStatus::Changed | SessionStatus::Unchanged)
+            && matches!(&loaded.entries, Some(entries) if self.should_rotate(entries, &session));
+
+        let user = match status {
+            SessionStatus::Purged => None,
+            _ => self.user(&session.entries()),
+        };
+        if let (Some(user), Some(limit)) = (&user, self.session_limit(&loaded)) {
+            limit.apply(user).await?;
+        }
+
+        // The session is recorded in the per-user session index when it is
+        // written to the storage.
+        let mut index = rotate || matches!(status, SessionStatus::Changed | SessionStatus::Renewed);
+        if index {
+            timestamps.last_seen = now();
+        }
+
+        let session_id = match status {
+            _ if rotate => {
+                timestamps.rotated_at = now();
+                Some(
+                    self.rotate(
+                        cookie_jar,
+                        resp,
+                        loaded.session_id,
+                        &session,
+                        timestamps,
+                        client,
+                    )
+                    .await?,
+                )
+            }
+            SessionStatus::Changed => {
+                self.update(cookie_jar, resp, loaded, timestamps, client)
+                    .await?
+            }
+            SessionStatus::Renewed => {
+                timestamps = Timestamps::new();
+                Some(
+                    self.rotate(
+                        cookie_jar,
+                        resp,
+                        loaded.session_id,
+                        &session,
+                        timestamps,
+                        client,
+                    )
+                    .await?,
+                )
+            }
+            SessionStatus::Purged => {
+                if let Some(session_id) = loaded.session_id {
+                    self.storage.remove_session(&session_id).await?;
+                    self.transport.remove_session_id(cookie_jar, resp);
+                }
+                None
+            }
+            SessionStatus::Unchanged => match loaded.session_id {
+                Some(session_id) => self
+                    .refresh(
+                        cookie_jar,
+                        resp,
+                        session_id,
+                        user.as_deref(),
+                        &mut timestamps,
+                    )
+                    .await?
+                    .map(|(session_id, seen)| {
+                        index = seen;
+                        session_id
+                    }),
+                None => None,
+            },
+        };
+
+        if session_id.is_none()
+            && (discarded || (had_session_id && status != SessionStatus::Purged))
+        {
+            // The client still holds the id of the expired or removed session.
+            self.transport.remove_session_id(cookie_jar, resp);
+        }
+
+        if let (true, Some(session_id), Some(user)) = (index, session_id, user) {
+            let metadata = SessionMetadata {
+                session_id,
+                created_at: UNIX_EPOCH + Duration::from_secs(timestamps.created_at),
+                last_seen: SystemTime::now(),
+                ip: client_metadata.ip,
+                user_agent: client_metadata.user_agent,
+            };
+            self.storage
+                .index_session(&user, &metadata, self.ttl(timestamps.created_at))
+                .await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl<T, E> Endpoint for ServerSessionEndpoint<T, E>
 where
-    T: SessionStorage,
+    T: SessionStorage + 'static,
     E: Endpoint,
 {
-    type Output = E::Output;
+    type Output = Response;
 
     async fn call(&self, mut req: Request) -> Result<Self::Output> {
         let cookie_jar = req.cookie().clone();
-        let mut session_id = self.config.get_cookie_value(&cookie_jar);
-        let session = match &session_id {
-            Some(id) => match self.storage.load_session(id).await? {
-                Some(entries) => Session::new(entries),
-                None => {
-                    session_id = None;
-                    Session::default()
-                }
-            },
-            None => Session::default(),
+        let client_metadata = match &self.index_by {
+            Some(_) => ClientMetadata::new(&req),
+            None => ClientMetadata::default(),
         };
+        let client = self
+            .binding
+            .as_ref()
+            .map(|binding| binding.fingerprint(&req));
 
-        req.extensions_mut().insert(session.clone());
-        let resp = self.inner.call(req).await?;
+        let mut loaded = self
+            .load(self.transport.get_session_id(&req), client.as_ref())
+            .await?;
+        if let Some(limit) = self.session_limit(&loaded) {
+            loaded.session = loaded.session.with_limit_check(Arc::new(limit));
+        }
 
-        match session.status() {
-            SessionStatus::Changed => match session_id {
-                Some(session_id) => {
-                    self.storage
-                        .update_session(&session_id, &session.entries(), self.config.ttl())
-                        .await?;
-                }
-                None => {
-                    let session_id = generate_session_id();
-                    self.config.set_cookie_value(&cookie_jar, &session_id);
-                    self.storage
-                        .update_session(&session_id, &session.entries(), self.config.ttl())
-                        .await?;
-                }
-            },
-            SessionStatus::Renewed => {
-                if let Some(session_id) = session_id {
-                    self.storage.remove_session(&session_id).await?;
-                }
+        req.extensions_mut().insert(loaded.session.clone());
+        let mut resp = self.inner.call(req).await?.into_response();
+        self.save(
+            &cookie_jar,
+            &mut resp,
+            loaded,
+            client.as_ref(),
+            client_metadata,
+        )
+        .await?;
+        Ok(resp)
+    }
+}
 
-                let session_id = generate_session_id();
-                self.config.set_cookie_value(&cookie_jar, &session_id);
-                self.storage
-                    .update_session(&session_id, &session.entries(), self.config.ttl())
-                    .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler,
+        http::StatusCode,
+        session::{
+            test_harness::{index, TestClient},
+            HeaderTransport, MemoryStorage,
+        },
+        test::TestResponse,
+        web::Data,
+        EndpointExt, Route,
+    };
+
+    #[handler(internal)]
+    fn login(session: &Session) {
+        session.set("user_id", "sunli");
+    }
+
+    #[handler(internal)]
+    fn check(session: &Session) -> String {
+        session.get::<String>("user_id").unwrap_or_default()
+    }
+
+    fn session_cookie(resp: &TestResponse) -> Option<String> {
+        resp.0
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(ToString::to_string)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout() {
+        let app = Route::new().at("/:action", index).with(
+            ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                .idle_timeout(Duration::from_secs(2)),
+        );
+        let mut client = TestClient::default();
+
+        client.call(&app, 1).await;
+        client.call(&app, 2).await;
+        for _ in 0..3 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            tokio::task::yield_now().await;
+            client.call(&app, 7).await;
+        }
+
+        tokio::time::advance(Duration::from_secs(3)).await;
+        tokio::task::yield_now().await;
+        client.call(&app, 5).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn absolute_timeout() {
+        let app = Route::new().at("/:action", index).with(
+            ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                .idle_timeout(Duration::from_secs(10))
+                .absolute_timeout(Duration::from_secs(2)),
+        );
+        let mut client = TestClient::default();
+
+        // The creation time is not visible in the session.
+        client.call(&app, 1).await;
+        client.call(&app, 2).await;
+        client.call(&app, 3).await;
+        client.call(&app, 4).await;
+        client.call(&app, 5).await;
+        client.assert_cookies(vec![]);
+
+        client.call(&app, 1).await;
+        client.call(&app, 2).await;
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        client.call(&app, 7).await;
+        tokio::time::advance(Duration::from_secs(2)).await;
+        tokio::task::yield_now().await;
+        client.call(&app, 5).await;
+        client.assert_cookies(vec![]);
+
+        // The session id of the expired session is removed from the client.
+        client.call(&app, 1).await;
+        tokio::time::advance(Duration::from_secs(2)).await;
+        tokio::task::yield_now().await;
+        client.call(&app, 0).await;
+        client.assert_cookies(vec![]);
+    }
+
+    #[tokio::test]
+    async fn do_not_recreate_removed_session() {
+        #[handler(internal)]
+        async fn logout_and_change(session: &Session, storage: Data<&MemoryStorage>) {
+            // A concurrent request removes the session.
+            for metadata in storage.list_sessions_for("sunli").await.unwrap() {
+                storage.remove_session(&metadata.session_id).await.unwrap();
             }
-            SessionStatus::Purged => {
-                if let Some(session_id) = session_id {
-                    self.storage.remove_session(&session_id).await?;
-                    self.config.remove_cookie(&cookie_jar);
+            session.set("value", 1);
+        }
+
+        let storage = MemoryStorage::new();
+        let app = Route::new()
+            .at("/login", login)
+            .at("/change", logout_and_change)
+            .with(ServerSession::new(CookieConfig::default(), storage.clone()).index_by("user_id"))
+            .data(storage.clone());
+        let cli = crate::test::TestClient::new(&app);
+
+        let resp = cli.get("/login").send().await;
+        let cookie = session_cookie(&resp).unwrap();
+        let session_id = cookie.split('=').nth(1).unwrap().to_string();
+
+        let resp = cli
+            .get("/change")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        assert_eq!(session_cookie(&resp).as_deref(), Some("poem-session="));
+        assert_eq!(storage.load_session(&session_id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn throttle_last_seen() {
+        let storage = MemoryStorage::new();
+        let app = Route::new()
+            .at("/login", login)
+            .at("/check", check)
+            .with(ServerSession::new(CookieConfig::default(), storage.clone()).index_by("user_id"));
+        let cli = crate::test::TestClient::new(&app);
+
+        let resp = cli.get("/login").send().await;
+        resp.assert_status_is_ok();
+        let cookie = session_cookie(&resp).unwrap();
+        let session_id = cookie.split('=').nth(1).unwrap().to_string();
+        let last_seen = storage.list_sessions_for("sunli").await.unwrap()[0].last_seen;
+
+        // The index is not updated for the unchanged session.
+        cli.get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+        let sessions = storage.list_sessions_for("sunli").await.unwrap();
+        assert_eq!(sessions[0].last_seen, last_seen);
+
+        // The index is updated when the last seen time is out of date.
+        let mut entries = storage.load_session(&session_id).await.unwrap().unwrap();
+        entries.insert(LAST_SEEN_KEY.to_string(), 0.into());
+        storage
+            .update_session(&session_id, &entries, None)
+            .await
+            .unwrap();
+        cli.get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+        let sessions = storage.list_sessions_for("sunli").await.unwrap();
+        assert!(sessions[0].last_seen > last_seen);
+        let entries = storage.load_session(&session_id).await.unwrap().unwrap();
+        assert_ne!(entries.get(LAST_SEEN_KEY), Some(&Value::from(0)));
+    }
+
+    #[tokio::test]
+    async fn session_index() {
+        let storage = MemoryStorage::new();
+        let app = Route::new()
+            .at("/login", login)
+            .at("/check", check)
+            .with(ServerSession::new(CookieConfig::default(), storage.clone()).index_by("user_id"));
+        let cli1 = crate::test::TestClient::new(&app);
+        let cli2 = crate::test::TestClient::new(&app);
+
+        let mut cookies = Vec::new();
+        for cli in [&cli1, &cli2] {
+            let resp = cli
+                .get("/login")
+                .header(header::USER_AGENT, "test")
+                .send()
+                .await;
+            resp.assert_status_is_ok();
+            cookies.push(session_cookie(&resp).unwrap());
+        }
+
+        let sessions = storage.list_sessions_for("sunli").await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions
+            .iter()
+            .all(|metadata| metadata.user_agent.as_deref() == Some("test")));
+
+        cli1.get("/check")
+            .header(header::COOKIE, &cookies[0])
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+
+        storage.revoke_all_for("sunli").await.unwrap();
+        assert!(storage.list_sessions_for("sunli").await.unwrap().is_empty());
+        for cookie in &cookies {
+            cli1.get("/check")
+                .header(header::COOKIE, cookie)
+                .send()
+                .await
+                .assert_text("")
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn client_binding() {
+        let storage = MemoryStorage::new();
+        for policy in [BindingMismatchPolicy::Purge, BindingMismatchPolicy::Reject] {
+            let app = Route::new().at("/login", login).at("/check", check).with(
+                ServerSession::new(CookieConfig::default(), storage.clone())
+                    .bind_to_client(ClientBinding::new().on_mismatch(policy)),
+            );
+            let cli = crate::test::TestClient::new(&app);
+
+            let resp = cli
+                .get("/login")
+                .header(header::USER_AGENT, "a")
+                .send()
+                .await;
+            let cookie = session_cookie(&resp).unwrap();
+            cli.get("/check")
+                .header(header::COOKIE, &cookie)
+                .header(header::USER_AGENT, "a")
+                .send()
+                .await
+                .assert_text("sunli")
+                .await;
+
+            let resp = cli
+                .get("/check")
+                .header(header::COOKIE, &cookie)
+                .header(header::USER_AGENT, "b")
+                .send()
+                .await;
+            match policy {
+                BindingMismatchPolicy::Purge => resp.assert_text("").await,
+                BindingMismatchPolicy::Reject => resp.assert_status(StatusCode::UNAUTHORIZED),
+            }
+
+            let resp = cli
+                .get("/check")
+                .header(header::COOKIE, &cookie)
+                .header(header::USER_AGENT, "a")
+                .send()
+                .await;
+            match policy {
+                BindingMismatchPolicy::Purge => resp.assert_text("").await,
+                BindingMismatchPolicy::Reject => resp.assert_text("sunli").await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rotate_on_change() {
+        #[handler(internal)]
+        fn visit(session: &Session) {
+            session.set("visited", true);
+        }
+
+        let app = Route::new()
+            .at("/visit", visit)
+            .at("/login", login)
+            .at("/check", check)
+            .with(
+                ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                    .rotate_on_change(["user_id"]),
+            );
+        let cli = crate::test::TestClient::new(&app);
+
+        let anonymous = session_cookie(&cli.get("/visit").send().await).unwrap();
+        let resp = cli
+            .get("/login")
+            .header(header::COOKIE, &anonymous)
+            .send()
+            .await;
+        let cookie = session_cookie(&resp).unwrap();
+        assert_ne!(cookie, anonymous);
+
+        cli.get("/check")
+            .header(header::COOKIE, &anonymous)
+            .send()
+            .await
+            .assert_text("")
+            .await;
+        cli.get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+
+        // The session id is kept if the user is not changed.
+        let resp = cli
+            .get("/login")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await;
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+        let resp = cli
+            .get("/visit")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await;
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+    }
+
+    #[tokio::test]
+    async fn rotation_interval() {
+        let app = Route::new().at("/login", login).at("/check", check).with(
+            ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                .rotation_interval(Duration::from_secs(1)),
+        );
+        let cli = crate::test::TestClient::new(&app);
+
+        let cookie = session_cookie(&cli.get("/login").send().await).unwrap();
+        let resp = cli
+            .get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await;
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let resp = cli
+            .get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await;
+        let new_cookie = session_cookie(&resp).unwrap();
+        assert_ne!(new_cookie, cookie);
+        resp.assert_text("sunli").await;
+
+        cli.get("/check")
+            .header(header::COOKIE, &cookie)
+            .send()
+            .await
+            .assert_text("")
+            .await;
+        cli.get("/check")
+            .header(header::COOKIE, &new_cookie)
+            .send()
+            .await
+            .assert_text("sunli")
+            .await;
+    }
+
+    #[test]
+    #[should_panic]
+    fn max_sessions_per_user_requires_index() {
+        let _ = Route::new().at("/login", login).with(
+            ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                .max_sessions_per_user(2, SessionLimitPolicy::Reject),
+        );
+    }
+
+    #[tokio::test]
+    async fn max_sessions_per_user() {
+        for policy in [SessionLimitPolicy::Reject, SessionLimitPolicy::EvictOldest] {
+            let storage = MemoryStorage::new();
+            let app = Route::new().at("/login", login).at("/check", check).with(
+                ServerSession::new(CookieConfig::default(), storage.clone())
+                    .index_by("user_id")
+                    .max_sessions_per_user(2, policy),
+            );
+            let cli = crate::test::TestClient::new(&app);
+
+            let mut cookies = Vec::new();
+            for _ in 0..2 {
+                cookies.push(session_cookie(&cli.get("/login").send().await).unwrap());
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let resp = cli.get("/login").send().await;
+            match policy {
+                SessionLimitPolicy::Reject => {
+                    resp.assert_status(StatusCode::UNAUTHORIZED);
+                    resp.assert_header_is_not_exist(header::SET_COOKIE);
+                }
+                SessionLimitPolicy::EvictOldest => {
+                    resp.assert_status_is_ok();
+                    cookies.push(session_cookie(&resp).unwrap());
                 }
             }
-            SessionStatus::Unchanged => {}
-        };
+            assert_eq!(storage.list_sessions_for("sunli").await.unwrap().len(), 2);
 
-        Ok(resp)
+            let expected = match policy {
+                SessionLimitPolicy::Reject => vec!["sunli", "sunli"],
+                SessionLimitPolicy::EvictOldest => vec!["", "sunli", "sunli"],
+            };
+            for (cookie, expected) in cookies.iter().zip(expected) {
+                cli.get("/check")
+                    .header(header::COOKIE, cookie)
+                    .send()
+                    .await
+                    .assert_text(expected)
+                    .await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_replaces_response() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[handler(internal)]
+        fn login(session: &Session, logins: Data<&Arc<AtomicUsize>>) -> &'static str {
+            logins.fetch_add(1, Ordering::SeqCst);
+            session.set("user_id", "sunli");
+            "logged in"
+        }
+
+        #[handler(internal)]
+        async fn checked_login(
+            session: &Session,
+            logins: Data<&Arc<AtomicUsize>>,
+        ) -> Result<&'static str> {
+            session.check_session_limit("sunli").await?;
+            logins.fetch_add(1, Ordering::SeqCst);
+            session.set("user_id", "sunli");
+            Ok("logged in")
+        }
+
+        let logins = Arc::new(AtomicUsize::new(0));
+        let app = Route::new()
+            .at("/login", login)
+            .at("/checked_login", checked_login)
+            .data(logins.clone())
+            .with(
+                ServerSession::new(CookieConfig::default(), MemoryStorage::new())
+                    .index_by("user_id")
+                    .max_sessions_per_user(1, SessionLimitPolicy::Reject),
+            );
+        let cli = crate::test::TestClient::new(&app);
+
+        let resp = cli.get("/checked_login").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("logged in").await;
+        assert_eq!(logins.load(Ordering::SeqCst), 1);
+
+        // The handler runs, but its response is replaced with the error.
+        let resp = cli.get("/login").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+        assert_ne!(resp.0.into_body().into_string().await.unwrap(), "logged in");
+        assert_eq!(logins.load(Ordering::SeqCst), 2);
+
+        // The handler rejects the login before performing any side effects.
+        let resp = cli.get("/checked_login").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+        assert_eq!(logins.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn header_transport() {
+        #[handler(internal)]
+        fn logout(session: &Session) {
+            session.purge();
+        }
+
+        let app = Route::new()
+            .at("/login", login)
+            .at("/check", check)
+            .at("/logout", logout)
+            .with(ServerSession::with_transport(
+                HeaderTransport::bearer(),
+                MemoryStorage::new(),
+            ));
+        let cli = crate::test::TestClient::new(&app);
+
+        let resp = cli.get("/login").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+        let token = resp
+            .0
+            .headers()
+            .get("x-session-token")
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+            .unwrap();
+
+        let resp = cli
+            .get("/check")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await;
+        resp.assert_header_is_not_exist("x-session-token");
+        resp.assert_text("sunli").await;
+
+        // The cookie is ignored.
+        cli.get("/check")
+            .header(header::COOKIE, format!("poem-session={}", token))
+            .send()
+            .await
+            .assert_text("")
+            .await;
+
+        let resp = cli
+            .get("/logout")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await;
+        resp.assert_header("x-session-token", "");
+        cli.get("/check")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .send()
+            .await
+            .assert_text("")
+            .await;
     }
 }
@@ -26,6 +26,13 @@ pub enum SessionStatus {
     Unchanged,
 }
 
+/// Checks whether a user can be stored in the session, see
+/// [`Session::check_session_limit`].
+#[async_trait::async_trait]
+pub(crate) trait SessionLimitCheck: Send + Sync {
+    async fn check(&self, user: &str) -> Result<()>;
+}
+
 struct SessionInner {
     status: SessionStatus,
     entries: BTreeMap<String, Value>,
@@ -35,6 +42,7 @@ struct SessionInner {
 #[derive(Clone)]
 pub struct Session {
     inner: Arc<RwLock<SessionInner>>,
+    limit: Option<Arc<dyn SessionLimitCheck>>,
 }
 
 impl Debug for Session {
@@ -63,6 +71,14 @@ impl Session {
                 status: SessionStatus::Unchanged,
                 entries,
             })),
+            limit: None,
+        }
+    }
+
+    pub(crate) fn with_limit_check(self, limit: Arc<dyn SessionLimitCheck>) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
         }
     }
 
@@ -145,6 +161,24 @@ impl Session {
         let inner = self.inner.read();
         inner.status
     }
+
+    /// Checks whether the user can be stored in this session without
+    /// exceeding [`ServerSession::max_sessions_per_user`].
+    ///
+    /// Returns [`SessionSecurityError::TooManySessions`] if the user has
+    /// reached the limit and the policy is [`SessionLimitPolicy::Reject`], so
+    /// that a handler can reject a login before performing any side effects.
+    /// Always returns `Ok` for other sessions.
+    ///
+    /// [`ServerSession::max_sessions_per_user`]: crate::session::ServerSession::max_sessions_per_user
+    /// [`SessionSecurityError::TooManySessions`]: crate::error::SessionSecurityError::TooManySessions
+    /// [`SessionLimitPolicy::Reject`]: crate::session::SessionLimitPolicy::Reject
+    pub async fn check_session_limit(&self, user: &str) -> Result<()> {
+        match &self.limit {
+            Some(limit) => limit.check(user).await,
+            None => Ok(()),
+        }
+    }
 }
 
 #[async_trait::async_trait]
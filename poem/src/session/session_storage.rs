@@ -1,8 +1,32 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::Result;
+use crate::{
+    error::{SessionIndexNotSupportedError, SessionNotFoundError},
+    Result,
+};
+
+/// Metadata of a session recorded in the per-user session index.
+///
+/// See also [`ServerSession::index_by`](crate::session::ServerSession::index_by).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    /// The session id.
+    pub session_id: String,
+    /// The time when the session was created.
+    pub created_at: SystemTime,
+    /// The time of the last request with this session.
+    pub last_seen: SystemTime,
+    /// The IP address of the client.
+    pub ip: Option<String>,
+    /// The `User-Agent` header of the last request.
+    pub user_agent: Option<String>,
+}
 
 /// Represents a back-end session storage.
 #[async_trait::async_trait]
@@ -20,4 +44,76 @@ pub trait SessionStorage: Send + Sync {
 
     /// Remove a session by session id.
     async fn remove_session(&self, session_id: &str) -> Result<()>;
+
+    /// Updates some entries of an existing session.
+    ///
+    /// `changed` contains the inserted or modified entries, and `removed`
+    /// contains the names of the removed entries. If the session does not
+    /// exist, for example it has been removed by a concurrent request or has
+    /// expired, it must not be recreated and [`SessionNotFoundError`] is
+    /// returned.
+    ///
+    /// The default implementation loads the session, applies the changes and
+    /// writes it back, storages that can update individual entries should
+    /// override it so that concurrent requests do not overwrite each other's
+    /// changes.
+    async fn patch_session(
+        &self,
+        session_id: &str,
+        changed: &BTreeMap<String, Value>,
+        removed: &[String],
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let mut entries = self
+            .load_session(session_id)
+            .await?
+            .ok_or(SessionNotFoundError)?;
+        for name in removed {
+            entries.remove(name);
+        }
+        entries.extend(
+            changed
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        self.update_session(session_id, &entries, expires).await
+    }
+
+    /// Reset the TTL(time-to-live) of a session without changing its entries.
+    ///
+    /// Storages supporting the per-user session index also reset the TTL of
+    /// the index entry of the session, see [`SessionStorage::index_session`].
+    ///
+    /// If the session does not exist, it must not be recreated and nothing is
+    /// done.
+    async fn touch_session(&self, session_id: &str, expires: Option<Duration>) -> Result<()>;
+
+    /// Records a session of the user in the per-user session index.
+    ///
+    /// The index entry expires together with the session, and removing the
+    /// session also removes it from the index. The default implementation
+    /// does nothing.
+    async fn index_session(
+        &self,
+        _user: &str,
+        _metadata: &SessionMetadata,
+        _expires: Option<Duration>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns all sessions of the user.
+    ///
+    /// The default implementation returns [`SessionIndexNotSupportedError`].
+    async fn list_sessions_for(&self, _user: &str) -> Result<Vec<SessionMetadata>> {
+        Err(SessionIndexNotSupportedError.into())
+    }
+
+    /// Removes all sessions of the user, such as when the user logs out
+    /// everywhere.
+    ///
+    /// The default implementation returns [`SessionIndexNotSupportedError`].
+    async fn revoke_all_for(&self, _user: &str) -> Result<()> {
+        Err(SessionIndexNotSupportedError.into())
+    }
 }
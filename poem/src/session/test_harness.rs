@@ -5,7 +5,7 @@ use crate::{
     http::{header, HeaderValue},
     session::Session,
     web::{cookie::Cookie, Path},
-    Endpoint, IntoResponse, Request,
+    Endpoint, IntoResponse, Request, Response,
 };
 
 #[derive(Default)]
@@ -15,9 +15,11 @@ pub(crate) struct TestClient {
 
 impl TestClient {
     pub(crate) async fn call(&mut self, ep: impl Endpoint, action: i32) {
-        let mut req = Request::builder()
-            .uri(format!("/{}", action).parse().unwrap())
-            .finish();
+        self.send(ep, &format!("/{}", action)).await;
+    }
+
+    pub(crate) async fn send(&mut self, ep: impl Endpoint, uri: &str) -> Response {
+        let mut req = Request::builder().uri(uri.parse().unwrap()).finish();
 
         let mut cookie = String::new();
         for (name, value) in &self.cookies {
@@ -41,6 +43,7 @@ impl TestClient {
                 }
             }
         }
+        resp
     }
 
     pub(crate) fn assert_cookies<'a>(&self, cookies: impl IntoIterator<Item = (&'a str, &'a str)>) {
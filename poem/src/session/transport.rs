@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use crate::{
+    http::{header, HeaderName, HeaderValue},
+    session::CookieConfig,
+    web::cookie::CookieJar,
+    Request, Response,
+};
+
+/// Represents how the session id is carried between the client and the
+/// server, see [`ServerSession::with_transport`](crate::session::ServerSession::with_transport).
+///
+/// It is implemented for [`CookieConfig`], which carries the session id in a
+/// cookie, and [`HeaderTransport`], which carries the session id in a header.
+pub trait SessionTransport: Send + Sync + 'static {
+    /// Reads the session id from the request.
+    fn get_session_id(&self, req: &Request) -> Option<String>;
+
+    /// Sends the session id to the client, which is called when the session is
+    /// created or renewed.
+    ///
+    /// `cookie_jar` is the [`CookieJar`] of the request, whose changes are
+    /// added to the response as `Set-Cookie` headers.
+    fn set_session_id(&self, cookie_jar: &CookieJar, resp: &mut Response, session_id: &str);
+
+    /// Tells the client to discard the session id, which is called when the
+    /// session is purged.
+    fn remove_session_id(&self, cookie_jar: &CookieJar, resp: &mut Response);
+
+    /// Returns how long the client keeps the session id, which is used as the
+    /// TTL(time-to-live) of the session in the storage by default.
+    ///
+    /// If it returns `Some`, the session id is sent again when the TTL of the
+    /// session is extended. Default is `None`.
+    fn ttl(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl SessionTransport for CookieConfig {
+    fn get_session_id(&self, req: &Request) -> Option<String> {
+        self.get_cookie_value(req.cookie())
+    }
+
+    fn set_session_id(&self, cookie_jar: &CookieJar, _resp: &mut Response, session_id: &str) {
+        self.set_cookie_value(cookie_jar, session_id);
+    }
+
+    fn remove_session_id(&self, cookie_jar: &CookieJar, _resp: &mut Response) {
+        self.remove_cookie(cookie_jar);
+    }
+
+    fn ttl(&self) -> Option<Duration> {
+        CookieConfig::ttl(self)
+    }
+}
+
+/// A [`SessionTransport`] which carries the session id in a request header,
+/// such as `X-Session-Id` or `Authorization: Bearer <session id>`, for the
+/// clients which do not support cookies.
+///
+/// When the session is created or renewed, the new session id is sent in the
+/// response header, and the client should send it in the request header of
+/// the subsequent requests. When the session is purged, the response header
+/// is sent with an empty value.
+///
+/// NOTE: If the application is accessed across origins, the response header
+/// should be exposed with
+/// [`Cors::expose_header`](crate::middleware::Cors::expose_header).
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{
+///     handler,
+///     session::{HeaderTransport, MemoryStorage, ServerSession, Session},
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(session: &Session) {
+///     session.set("visited", true);
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// // Reads the session id from `Authorization: Bearer <session id>`, and sends
+/// // the new session id in `X-Session-Token`.
+/// let app = index.with(ServerSession::with_transport(
+///     HeaderTransport::bearer().ttl(Duration::from_secs(3600)),
+///     MemoryStorage::new(),
+/// ));
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderTransport {
+    request_header: HeaderName,
+    bearer: bool,
+    response_header: HeaderName,
+    ttl: Option<Duration>,
+}
+
+impl HeaderTransport {
+    /// Create a `HeaderTransport` which carries the session id in the
+    /// specified header of both the request and the response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        let name = HeaderName::try_from(name.as_ref()).expect("valid header name");
+        Self {
+            request_header: name.clone(),
+            bearer: false,
+            response_header: name,
+            ttl: None,
+        }
+    }
+
+    /// Create a `HeaderTransport` which reads the session id from the
+    /// `Authorization` header with the `Bearer` scheme, and sends the new
+    /// session id in the `X-Session-Token` response header.
+    pub fn bearer() -> Self {
+        Self {
+            request_header: header::AUTHORIZATION,
+            bearer: true,
+            response_header: HeaderName::from_static("x-session-token"),
+            ttl: None,
+        }
+    }
+
+    /// Sets the response header used to send the new session id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    #[must_use]
+    pub fn response_header(self, name: impl AsRef<str>) -> Self {
+        Self {
+            response_header: HeaderName::try_from(name.as_ref()).expect("valid header name"),
+            ..self
+        }
+    }
+
+    /// Sets how long the client keeps the session id, see
+    /// [`SessionTransport::ttl`]. Default is `None`.
+    #[must_use]
+    pub fn ttl(self, ttl: impl Into<Option<Duration>>) -> Self {
+        Self {
+            ttl: ttl.into(),
+            ..self
+        }
+    }
+}
+
+impl SessionTransport for HeaderTransport {
+    fn get_session_id(&self, req: &Request) -> Option<String> {
+        let value = req.headers().get(&self.request_header)?.to_str().ok()?;
+        let value = if self.bearer {
+            let (scheme, token) = value.split_once(' ')?;
+            if !scheme.eq_ignore_ascii_case("bearer") {
+                return None;
+            }
+            token
+        } else {
+            value
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    }
+
+    fn set_session_id(&self, _cookie_jar: &CookieJar, resp: &mut Response, session_id: &str) {
+        if let Ok(value) = HeaderValue::from_str(session_id) {
+            resp.headers_mut()
+                .insert(self.response_header.clone(), value);
+        }
+    }
+
+    fn remove_session_id(&self, _cookie_jar: &CookieJar, resp: &mut Response) {
+        resp.headers_mut()
+            .insert(self.response_header.clone(), HeaderValue::from_static(""));
+    }
+
+    fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_transport() {
+        let config = CookieConfig::default();
+        let cookie_jar = CookieJar::default();
+        let mut resp = Response::default();
+
+        config.set_session_id(&cookie_jar, &mut resp, "abc");
+        assert_eq!(config.get_cookie_value(&cookie_jar).as_deref(), Some("abc"));
+        config.remove_session_id(&cookie_jar, &mut resp);
+        assert_eq!(config.get_cookie_value(&cookie_jar), None);
+    }
+
+    #[test]
+    fn get_session_id() {
+        let transport = HeaderTransport::new("x-session-id");
+        let req = Request::builder().header("x-session-id", "abc").finish();
+        assert_eq!(transport.get_session_id(&req).as_deref(), Some("abc"));
+        let req = Request::builder().header("x-session-id", "").finish();
+        assert_eq!(transport.get_session_id(&req), None);
+
+        let transport = HeaderTransport::bearer();
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .finish();
+        assert_eq!(transport.get_session_id(&req).as_deref(), Some("abc"));
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, "bearer  abc ")
+            .finish();
+        assert_eq!(transport.get_session_id(&req).as_deref(), Some("abc"));
+        let req = Request::builder()
+            .header(header::AUTHORIZATION, "Basic abc")
+            .finish();
+        assert_eq!(transport.get_session_id(&req), None);
+        assert_eq!(transport.get_session_id(&Request::default()), None);
+    }
+}
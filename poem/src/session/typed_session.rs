@@ -0,0 +1,332 @@
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{session::Session, FromRequest, Request, RequestBody, Result};
+
+/// The session entry used to store the state of [`TypedSession`].
+const STATE_KEY: &str = "__poem_typed_session";
+
+#[derive(Serialize, Deserialize)]
+struct VersionedState {
+    version: u32,
+    data: Value,
+}
+
+/// Represents the state stored in a [`TypedSession`].
+///
+/// # Example
+///
+/// ```
+/// use poem::session::SessionState;
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::Value;
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct MyState {
+///     user_id: Option<i64>,
+///     theme: String,
+/// }
+///
+/// impl SessionState for MyState {
+///     const VERSION: u32 = 2;
+///
+///     fn migrate(version: u32, data: Value) -> Option<Self> {
+///         match version {
+///             // Version 1 stored the user id as a string.
+///             1 => Some(MyState {
+///                 user_id: data.get("user_id")?.as_str()?.parse().ok(),
+///                 ..Default::default()
+///             }),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait SessionState: Serialize + DeserializeOwned + Default + Send + Sync + 'static {
+    /// The schema version of the state, which should be increased when the
+    /// state type is changed incompatibly. Default is `0`.
+    const VERSION: u32 = 0;
+
+    /// Converts the stored data to the current state type, called when the
+    /// stored data has a different version or cannot be deserialized.
+    ///
+    /// If it returns `None`, the default state is used. The default
+    /// implementation always returns `None`.
+    fn migrate(version: u32, data: Value) -> Option<Self> {
+        let _ = (version, data);
+        None
+    }
+}
+
+/// An extractor for the session whose whole state is a single type.
+///
+/// It works with both [`CookieSession`](crate::session::CookieSession) and
+/// [`ServerSession`](crate::session::ServerSession). The state is stored with
+/// its [version](SessionState::VERSION), and the session is only marked as
+/// changed if the serialized state is actually modified.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     session::{CookieConfig, CookieSession, SessionState, TypedSession},
+///     EndpointExt,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Counter {
+///     count: i32,
+/// }
+///
+/// impl SessionState for Counter {}
+///
+/// #[handler]
+/// fn index(session: TypedSession<Counter>) -> String {
+///     let count = session.update(|counter| {
+///         counter.count += 1;
+///         counter.count
+///     });
+///     format!("count: {}", count)
+/// }
+///
+/// let app = index.with(CookieSession::new(CookieConfig::default()));
+/// ```
+pub struct TypedSession<T> {
+    session: Session,
+    _mark: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for TypedSession<T> {
+    fn clone(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            _mark: PhantomData,
+        }
+    }
+}
+
+impl<T: SessionState> TypedSession<T> {
+    /// Creates a `TypedSession` from the session.
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            _mark: PhantomData,
+        }
+    }
+
+    /// Returns the underlying session.
+    #[inline]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Returns the state of the session.
+    ///
+    /// If the stored state is migrated by [`SessionState::migrate`], the
+    /// migrated state is written back to the session.
+    pub fn get(&self) -> T {
+        let state = match self.session.get::<VersionedState>(STATE_KEY) {
+            Some(state) => state,
+            None => return T::default(),
+        };
+
+        if state.version == T::VERSION {
+            if let Ok(value) = serde_json::from_value(state.data.clone()) {
+                return value;
+            }
+        }
+
+        match T::migrate(state.version, state.data) {
+            Some(value) => {
+                self.set(&value);
+                value
+            }
+            None => T::default(),
+        }
+    }
+
+    /// Sets the state of the session.
+    pub fn set(&self, value: &T) {
+        let data = match serde_json::to_value(value) {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let state = VersionedState {
+            version: T::VERSION,
+            data,
+        };
+        let state = match serde_json::to_value(&state) {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        if self.session.get::<Value>(STATE_KEY).as_ref() != Some(&state) {
+            self.session.set(STATE_KEY, state);
+        }
+    }
+
+    /// Modifies the state of the session with a function.
+    ///
+    /// The session is only changed if the function modifies the state.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.get();
+        let before = serde_json::to_value(&value).ok();
+        let res = f(&mut value);
+        if serde_json::to_value(&value).ok() != before {
+            self.set(&value);
+        }
+        res
+    }
+
+    /// Removes the state from the session.
+    pub fn clear(&self) {
+        if self.session.get::<Value>(STATE_KEY).is_some() {
+            self.session.remove(STATE_KEY);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: SessionState> FromRequest<'a> for TypedSession<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(TypedSession::new(
+            req.extensions().get::<Session>().cloned().expect(
+                "To use the `TypedSession` extractor, the `CookieSession` or `ServerSession` \
+                 middleware is required.",
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        handler,
+        http::{header, HeaderValue},
+        session::{CookieConfig, CookieSession, MemoryStorage, ServerSession, SessionStatus},
+        web::cookie::Cookie,
+        Endpoint, EndpointExt, IntoResponse,
+    };
+
+    #[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+    struct MyState {
+        user_id: Option<i64>,
+        count: i32,
+    }
+
+    impl SessionState for MyState {
+        const VERSION: u32 = 2;
+
+        fn migrate(version: u32, data: Value) -> Option<Self> {
+            match version {
+                1 => Some(MyState {
+                    user_id: data.get("user_id")?.as_str()?.parse().ok(),
+                    count: 0,
+                }),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn dirty_tracking() {
+        let session = TypedSession::<MyState>::new(Session::default());
+        assert_eq!(session.get(), MyState::default());
+
+        session.update(|_| {});
+        assert!(session.session().is_empty());
+
+        session.update(|state| state.count += 1);
+        assert_eq!(session.session().status(), SessionStatus::Changed);
+        assert_eq!(session.get().count, 1);
+
+        let session = TypedSession::<MyState>::new(Session::new(session.session().entries()));
+        session.set(&MyState {
+            user_id: None,
+            count: 1,
+        });
+        assert_eq!(session.session().status(), SessionStatus::Unchanged);
+
+        session.clear();
+        assert_eq!(session.session().status(), SessionStatus::Changed);
+        assert_eq!(session.get(), MyState::default());
+    }
+
+    #[test]
+    fn migrate() {
+        let session = Session::default();
+        session.set(
+            STATE_KEY,
+            json!({ "version": 1, "data": { "user_id": "100" } }),
+        );
+        let session = TypedSession::<MyState>::new(session);
+        assert_eq!(
+            session.get(),
+            MyState {
+                user_id: Some(100),
+                count: 0
+            }
+        );
+        assert_eq!(
+            session.session().get::<Value>(STATE_KEY),
+            Some(json!({ "version": 2, "data": { "user_id": 100, "count": 0 } }))
+        );
+
+        let session = Session::default();
+        session.set(
+            STATE_KEY,
+            json!({ "version": 2, "data": { "count": "abc" } }),
+        );
+        let session = TypedSession::<MyState>::new(session);
+        assert_eq!(session.get(), MyState::default());
+    }
+
+    #[handler(internal)]
+    fn index(session: TypedSession<MyState>) -> String {
+        session
+            .update(|state| {
+                state.count += 1;
+                state.count
+            })
+            .to_string()
+    }
+
+    async fn check_counter(ep: impl Endpoint) {
+        let mut cookie: Option<String> = None;
+
+        for expected in ["1", "2", "3"] {
+            let mut req = Request::builder().finish();
+            if let Some(cookie) = &cookie {
+                req.headers_mut()
+                    .insert(header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+            }
+            let resp = ep.call(req).await.unwrap().into_response();
+            if let Some(value) = resp.headers().get(header::SET_COOKIE) {
+                let value = Cookie::parse(value.to_str().unwrap()).unwrap();
+                cookie = Some(format!("{}={}", value.name(), value.value_str()));
+            }
+            assert_eq!(resp.into_body().into_string().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn cookie_session() {
+        check_counter(index.with(CookieSession::new(CookieConfig::default()))).await;
+    }
+
+    #[tokio::test]
+    async fn server_session() {
+        check_counter(index.with(ServerSession::new(
+            CookieConfig::default(),
+            MemoryStorage::new(),
+        )))
+        .await;
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use futures_util::{Stream, StreamExt};
-use http::{header, header::HeaderName, HeaderValue, StatusCode};
+use http::{header, header::HeaderName, HeaderMap, HeaderValue, StatusCode};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use tokio_util::compat::TokioAsyncReadCompatExt;
@@ -158,6 +158,48 @@ impl TestResponse {
         );
     }
 
+    /// Consumes this object and return the trailers of the response body.
+    pub async fn trailers(self) -> HeaderMap {
+        let (stream, trailers) = self.0.into_body().into_bytes_stream_with_trailers();
+        stream
+            .for_each(|res| async move {
+                res.expect("expect body");
+            })
+            .await;
+        trailers.await.expect("expect trailers").unwrap_or_default()
+    }
+
+    /// Asserts that the response body has a trailer `key` which equals to
+    /// `value`.
+    pub async fn assert_trailer<K, V>(self, key: K, value: V)
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        self.assert_trailers([(key, value)]).await;
+    }
+
+    /// Asserts that the response body has the trailers `key`-`value` pairs.
+    pub async fn assert_trailers<K, V, I>(self, trailers: I)
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let actual = self.trailers().await;
+        for (key, value) in trailers {
+            let key = key.try_into().map_err(|_| ()).expect("valid header name");
+            let value = value
+                .try_into()
+                .map_err(|_| ())
+                .expect("valid header value");
+            let value2 = actual
+                .get(&key)
+                .unwrap_or_else(|| panic!("expect trailer `{}`", key));
+            assert_eq!(value2, value);
+        }
+    }
+
     /// Consumes this object and return the [`TestJson`].
     pub async fn json(self) -> TestJson {
         self.0
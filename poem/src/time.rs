@@ -0,0 +1,17 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Returns the duration since the Unix epoch.
+///
+/// The time follows the clock of tokio, which is the system clock unless it
+/// is paused with `tokio::time::pause`, so that the expiration logic can be
+/// tested by advancing the clock.
+pub(crate) fn unix_time() -> Duration {
+    let std_now = std::time::Instant::now();
+    let tokio_now = tokio::time::Instant::now().into_std();
+    let now = if tokio_now >= std_now {
+        SystemTime::now() + (tokio_now - std_now)
+    } else {
+        SystemTime::now() - (std_now - tokio_now)
+    };
+    now.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
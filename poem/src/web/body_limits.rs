@@ -0,0 +1,268 @@
+use bytes::Bytes;
+
+use crate::{error::ReadBodyError, Body, Request};
+
+/// The default size limit of the request bodies read into the memory.
+const DEFAULT_LIMIT: usize = 2 * 1024 * 1024;
+
+/// The size limits of the request bodies read by the extractors.
+///
+/// The bytes of the body are counted as they are read, so the chunked bodies
+/// without the `Content-Length` header are also limited, and exceeding the
+/// limit fails with
+/// [`ReadBodyError::PayloadTooLarge`](crate::error::ReadBodyError::PayloadTooLarge).
+///
+/// It is used by the extractors when it is added to the request with
+/// [`EndpointExt::data`](crate::EndpointExt::data), otherwise the default
+/// limits are used.
+///
+/// | Extractor                                      | Default   |
+/// |------------------------------------------------|-----------|
+/// | [`Json`](crate::web::Json)                     | 2 MiB     |
+/// | [`Form`](crate::web::Form)                     | 2 MiB     |
+/// | [`Bytes`], [`Vec<u8>`] and [`String`]          | 2 MiB     |
+/// | [`Multipart`](crate::web::Multipart)           | Unlimited |
+///
+/// The [`Body`] extractor is never limited, and the limits of multipart
+/// requests can also be specified by
+/// [`MultipartConfig`](crate::web::MultipartConfig).
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     web::{BodyLimits, Json},
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(data: Json<serde_json::Value>) {}
+///
+/// let app = index.data(BodyLimits::new().json(16 * 1024 * 1024));
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BodyLimits {
+    pub(crate) json: Option<usize>,
+    pub(crate) form: Option<usize>,
+    pub(crate) bytes: Option<usize>,
+    pub(crate) multipart: Option<usize>,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            json: Some(DEFAULT_LIMIT),
+            form: Some(DEFAULT_LIMIT),
+            bytes: Some(DEFAULT_LIMIT),
+            multipart: None,
+        }
+    }
+}
+
+impl BodyLimits {
+    /// Create a `BodyLimits` with the default limits.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a `BodyLimits` without any limits.
+    pub fn unlimited() -> Self {
+        Self {
+            json: None,
+            form: None,
+            bytes: None,
+            multipart: None,
+        }
+    }
+
+    /// Sets the size limit of the [`Json`](crate::web::Json) extractor.
+    ///
+    /// `None` means no limit. Default is `2 MiB`.
+    #[must_use]
+    pub fn json(self, limit: impl Into<Option<usize>>) -> Self {
+        Self {
+            json: limit.into(),
+            ..self
+        }
+    }
+
+    /// Sets the size limit of the [`Form`](crate::web::Form) extractor.
+    ///
+    /// `None` means no limit. Default is `2 MiB`.
+    #[must_use]
+    pub fn form(self, limit: impl Into<Option<usize>>) -> Self {
+        Self {
+            form: limit.into(),
+            ..self
+        }
+    }
+
+    /// Sets the size limit of the [`Bytes`], [`Vec<u8>`] and [`String`]
+    /// extractors.
+    ///
+    /// `None` means no limit. Default is `2 MiB`.
+    #[must_use]
+    pub fn bytes(self, limit: impl Into<Option<usize>>) -> Self {
+        Self {
+            bytes: limit.into(),
+            ..self
+        }
+    }
+
+    /// Sets the size limit of the [`Multipart`](crate::web::Multipart)
+    /// extractor.
+    ///
+    /// `None` means no limit. Default is `None`.
+    #[must_use]
+    pub fn multipart(self, limit: impl Into<Option<usize>>) -> Self {
+        Self {
+            multipart: limit.into(),
+            ..self
+        }
+    }
+
+    /// Returns the limits in the request data, or the default limits.
+    pub(crate) fn get(req: &Request) -> Self {
+        req.extensions().get::<Self>().copied().unwrap_or_default()
+    }
+}
+
+/// Reads the whole body with the optional size limit.
+pub(crate) async fn read_body(body: Body, limit: Option<usize>) -> Result<Bytes, ReadBodyError> {
+    match limit {
+        Some(limit) => body.into_bytes_limit(limit).await,
+        None => body.into_bytes().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler,
+        http::StatusCode,
+        test::TestClient,
+        web::{Form, Json},
+        EndpointExt,
+    };
+
+    fn chunked(size: usize) -> Body {
+        let chunks = vec![0; size]
+            .chunks(1024)
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        Body::from_bytes_stream(futures_util::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn bytes() {
+        #[handler(internal)]
+        fn index(data: Bytes) -> String {
+            data.len().to_string()
+        }
+
+        let cli = TestClient::new(index);
+        cli.post("/")
+            .body(chunked(DEFAULT_LIMIT))
+            .send()
+            .await
+            .assert_text(DEFAULT_LIMIT.to_string())
+            .await;
+        cli.post("/")
+            .body(chunked(DEFAULT_LIMIT + 10))
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let cli = TestClient::new(index.data(BodyLimits::new().bytes(100)));
+        cli.post("/")
+            .body(chunked(110))
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let cli = TestClient::new(index.data(BodyLimits::unlimited()));
+        cli.post("/")
+            .body(chunked(DEFAULT_LIMIT + 10))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn json_and_form() {
+        #[handler(internal)]
+        fn json(data: Json<String>) -> String {
+            data.0
+        }
+
+        #[handler(internal)]
+        fn form(data: Form<std::collections::HashMap<String, String>>) -> String {
+            data.0["a"].clone()
+        }
+
+        let limits = BodyLimits::new().json(8).form(8);
+
+        let cli = TestClient::new(json.data(limits));
+        cli.post("/")
+            .content_type("application/json")
+            .body("\"abcdef\"")
+            .send()
+            .await
+            .assert_text("abcdef")
+            .await;
+        cli.post("/")
+            .content_type("application/json")
+            .body("\"abcdefg\"")
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let cli = TestClient::new(form.data(limits));
+        cli.post("/")
+            .content_type("application/x-www-form-urlencoded")
+            .body("a=abcdef")
+            .send()
+            .await
+            .assert_text("abcdef")
+            .await;
+        cli.post("/")
+            .content_type("application/x-www-form-urlencoded")
+            .body("a=abcdefg")
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[cfg(feature = "multipart")]
+    #[tokio::test]
+    async fn multipart() {
+        use crate::{web::Multipart, Result};
+
+        #[handler(internal)]
+        async fn index(mut multipart: Multipart) -> Result<()> {
+            while let Some(field) = multipart.next_field().await? {
+                field.bytes().await?;
+            }
+            Ok(())
+        }
+
+        let data = "--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nabcd\r\n--X-BOUNDARY--\r\n";
+        let cli = TestClient::new(index.data(BodyLimits::new().multipart(data.len())));
+        cli.post("/")
+            .content_type("multipart/form-data; boundary=X-BOUNDARY")
+            .body(data)
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let cli = TestClient::new(index.data(BodyLimits::new().multipart(data.len() - 1)));
+        cli.post("/")
+            .content_type("multipart/form-data; boundary=X-BOUNDARY")
+            .body(data)
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
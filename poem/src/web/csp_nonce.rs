@@ -0,0 +1,46 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Deref,
+};
+
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// A `Content-Security-Policy` nonce generated for the current request.
+///
+/// See also [`SecurityHeaders`](crate::middleware::SecurityHeaders) and
+/// [`ContentSecurityPolicy::nonce`](crate::middleware::ContentSecurityPolicy::nonce).
+#[cfg_attr(docsrs, doc(cfg(feature = "security-headers")))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CspNonce(pub String);
+
+impl CspNonce {
+    /// Returns the nonce as a string slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for CspNonce {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for CspNonce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for &'a CspNonce {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req.extensions().get::<CspNonce>().expect(
+            "To use the `CspNonce` extractor, the `SecurityHeaders` middleware with a nonce \
+             directive is required.",
+        ))
+    }
+}
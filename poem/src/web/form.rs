@@ -8,7 +8,7 @@ use crate::{
         header::{self, HeaderValue},
         Method,
     },
-    web::RequestBody,
+    web::{read_body, BodyLimits, RequestBody},
     FromRequest, Request, Result,
 };
 
@@ -109,8 +109,10 @@ impl<'a, T: DeserializeOwned> FromRequest<'a> for Form<T> {
             }
 
             Ok(Self(
-                serde_urlencoded::from_bytes(&body.take()?.into_vec().await?)
-                    .map_err(ParseFormError::UrlDecode)?,
+                serde_urlencoded::from_bytes(
+                    &read_body(body.take()?, BodyLimits::get(req).form).await?,
+                )
+                .map_err(ParseFormError::UrlDecode)?,
             ))
         }
     }
@@ -6,7 +6,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use crate::{
     error::{MissingJsonContentTypeError, ParseJsonError},
     http::header,
-    web::RequestBody,
+    web::{read_body, BodyLimits, RequestBody},
     FromRequest, IntoResponse, Request, Response, Result,
 };
 
@@ -109,7 +109,7 @@ impl<T> DerefMut for Json<T> {
 impl<'a, T: DeserializeOwned> FromRequest<'a> for Json<T> {
     async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
         if is_json_content_type(req) {
-            let data = body.take()?.into_bytes().await?;
+            let data = read_body(body.take()?, BodyLimits::get(req).json).await?;
             Ok(Self(serde_json::from_slice(&data).map_err(ParseJsonError)?))
         } else {
             Err(MissingJsonContentTypeError.into())
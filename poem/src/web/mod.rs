@@ -1,17 +1,22 @@
 //! Commonly used as the type of extractor or response.
 
 mod addr;
+mod body_limits;
 #[cfg(feature = "compression")]
 mod compress;
 #[cfg(feature = "cookie")]
 #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
 pub mod cookie;
+#[cfg(feature = "security-headers")]
+mod csp_nonce;
 mod data;
 mod form;
 mod json;
 #[cfg(feature = "multipart")]
 mod multipart;
+mod negotiated;
 mod path;
+mod preconditions;
 mod query;
 mod redirect;
 #[cfg(feature = "sse")]
@@ -21,6 +26,9 @@ pub mod sse;
 mod static_file;
 #[cfg(feature = "tempfile")]
 mod tempfile;
+#[cfg(feature = "upload")]
+#[cfg_attr(docsrs, doc(cfg(feature = "upload")))]
+pub mod upload;
 #[doc(inline)]
 pub use headers;
 #[cfg(feature = "csrf")]
@@ -35,12 +43,15 @@ use std::{convert::Infallible, fmt::Debug};
 use bytes::Bytes;
 use http::header;
 
+pub(crate) use self::body_limits::read_body;
 #[cfg(feature = "compression")]
 pub use self::compress::{Compress, CompressionAlgo};
+#[cfg(feature = "security-headers")]
+pub use self::csp_nonce::CspNonce;
 #[cfg(feature = "csrf")]
 pub use self::csrf::{CsrfToken, CsrfVerifier};
 #[cfg(feature = "multipart")]
-pub use self::multipart::{Field, Multipart};
+pub use self::multipart::{Field, Multipart, MultipartConfig};
 pub(crate) use self::path::PathDeserializer;
 #[cfg(feature = "static-files")]
 pub use self::static_file::{StaticFileRequest, StaticFileResponse};
@@ -48,10 +59,13 @@ pub use self::static_file::{StaticFileRequest, StaticFileResponse};
 pub use self::tempfile::TempFile;
 pub use self::{
     addr::{LocalAddr, RemoteAddr},
+    body_limits::BodyLimits,
     data::Data,
     form::Form,
     json::Json,
+    negotiated::{AcceptFormat, Format, Negotiated, NegotiatedFormats},
     path::Path,
+    preconditions::Preconditions,
     query::Query,
     redirect::Redirect,
     typed_header::TypedHeader,
@@ -228,6 +242,12 @@ impl RequestBody {
 ///    Extracts the [`Locale`](crate::i18n::Locale) from the incoming
 /// request.
 ///
+/// - **&CspNonce**
+///
+///    Extracts the [`CspNonce`] from the incoming request.
+///
+///    _Requires `SecurityHeaders` middleware with a nonce directive._
+///
 /// - **StaticFileRequest**
 ///
 ///     Ready to accept a static file request
@@ -733,23 +753,25 @@ impl<'a> FromRequest<'a> for Body {
 
 #[async_trait::async_trait]
 impl<'a> FromRequest<'a> for String {
-    async fn from_request(_req: &'a Request, body: &mut RequestBody) -> Result<Self> {
-        let data = body.take()?.into_bytes().await?;
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let data = read_body(body.take()?, BodyLimits::get(req).bytes).await?;
         Ok(String::from_utf8(data.to_vec()).map_err(ReadBodyError::Utf8)?)
     }
 }
 
 #[async_trait::async_trait]
 impl<'a> FromRequest<'a> for Bytes {
-    async fn from_request(_req: &'a Request, body: &mut RequestBody) -> Result<Self> {
-        Ok(body.take()?.into_bytes().await?)
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        Ok(read_body(body.take()?, BodyLimits::get(req).bytes).await?)
     }
 }
 
 #[async_trait::async_trait]
 impl<'a> FromRequest<'a> for Vec<u8> {
-    async fn from_request(_req: &'a Request, body: &mut RequestBody) -> Result<Self> {
-        Ok(body.take()?.into_vec().await?)
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        Ok(read_body(body.take()?, BodyLimits::get(req).bytes)
+            .await?
+            .to_vec())
     }
 }
 
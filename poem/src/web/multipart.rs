@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::{self, Debug, Formatter},
     str::FromStr,
 };
@@ -11,7 +12,10 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 #[cfg(feature = "tempfile")]
 use tokio::io::{AsyncSeekExt, SeekFrom};
 
-use crate::{error::ParseMultipartError, http::header, FromRequest, Request, RequestBody, Result};
+use crate::{
+    error::ParseMultipartError, http::header, web::BodyLimits, FromRequest, Request, RequestBody,
+    Result,
+};
 
 /// A single field in a multipart stream.
 #[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
@@ -90,18 +94,217 @@ impl Field {
         Ok(file)
     }
 
+    /// Streams the field to the [`UploadSink`](crate::web::upload::UploadSink)
+    /// with the key, see [`upload_reader`](crate::web::upload::upload_reader).
+    #[cfg(feature = "upload")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "upload")))]
+    pub async fn upload<S: crate::web::upload::UploadSink + ?Sized>(
+        self,
+        sink: &S,
+        key: &str,
+        limits: &crate::web::upload::UploadLimits,
+    ) -> Result<crate::web::upload::UploadedFile, crate::error::UploadError> {
+        let file_name = self.file_name().map(ToString::to_string);
+        let content_type = self.content_type().map(ToString::to_string);
+        let mut file = crate::web::upload::upload_reader(
+            sink,
+            key,
+            self.into_async_read(),
+            content_type.as_deref(),
+            limits,
+        )
+        .await?;
+        file.file_name = file_name;
+        Ok(file)
+    }
+
     /// Consume this field to return a reader.
     pub fn into_async_read(self) -> impl AsyncRead + Send {
-        tokio_util::io::StreamReader::new(
-            self.0
-                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string())),
-        )
+        tokio_util::io::StreamReader::new(self.0.map_err(std::io::Error::other))
+    }
+}
+
+/// The limits of a `multipart/form-data` request, which are checked while
+/// the request is being parsed.
+///
+/// It is used by the [`Multipart`] extractor when it is added to the
+/// request with [`EndpointExt::data`](crate::EndpointExt::data), or with
+/// [`Multipart::from_request_with_config`].
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     web::{Multipart, MultipartConfig},
+///     EndpointExt, Result,
+/// };
+///
+/// #[handler]
+/// async fn upload(mut multipart: Multipart) -> Result<()> {
+///     while let Some(field) = multipart.next_field().await? {
+///         // ...
+///     }
+///     Ok(())
+/// }
+///
+/// let app = upload.data(
+///     MultipartConfig::new()
+///         .max_size(10 * 1024 * 1024)
+///         .max_files(3)
+///         .allowed_content_types(["image/*"]),
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
+#[derive(Debug, Default, Clone)]
+pub struct MultipartConfig {
+    max_size: Option<u64>,
+    max_field_size: Option<u64>,
+    field_size_limits: HashMap<String, u64>,
+    allowed_fields: Option<Vec<String>>,
+    max_fields: Option<usize>,
+    max_files: Option<usize>,
+    allowed_content_types: Option<Vec<String>>,
+}
+
+impl MultipartConfig {
+    /// Create a `MultipartConfig` without any limits.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum size of the whole request body in bytes.
+    #[must_use]
+    pub fn max_size(self, size: u64) -> Self {
+        Self {
+            max_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of each field in bytes.
+    #[must_use]
+    pub fn max_field_size(self, size: u64) -> Self {
+        Self {
+            max_field_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of the fields with the specified name in bytes,
+    /// which overrides [`MultipartConfig::max_field_size`].
+    #[must_use]
+    pub fn field_size_limit(mut self, name: impl Into<String>, size: u64) -> Self {
+        self.field_size_limits.insert(name.into(), size);
+        self
+    }
+
+    /// Sets the allowed field names, the request is rejected if it contains
+    /// any other field.
+    #[must_use]
+    pub fn allowed_fields<I, T>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            allowed_fields: Some(names.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of the fields, including the files.
+    #[must_use]
+    pub fn max_fields(self, count: usize) -> Self {
+        Self {
+            max_fields: Some(count),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of the files, which are the fields with a file
+    /// name.
+    #[must_use]
+    pub fn max_files(self, count: usize) -> Self {
+        Self {
+            max_files: Some(count),
+            ..self
+        }
+    }
+
+    /// Sets the allowed content types of the files, such as `image/png` or
+    /// `image/*`.
+    ///
+    /// The files without the `Content-Type` header are treated as
+    /// `application/octet-stream`.
+    #[must_use]
+    pub fn allowed_content_types<I, T>(self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            allowed_content_types: Some(
+                content_types
+                    .into_iter()
+                    .map(|content_type| content_type.into().to_ascii_lowercase())
+                    .collect(),
+            ),
+            ..self
+        }
+    }
+
+    fn constraints(&self) -> multer::Constraints {
+        let mut size_limit = multer::SizeLimit::new();
+        if let Some(max_size) = self.max_size {
+            size_limit = size_limit.whole_stream(max_size);
+        }
+        if let Some(max_field_size) = self.max_field_size {
+            size_limit = size_limit.per_field(max_field_size);
+        }
+        for (name, limit) in &self.field_size_limits {
+            size_limit = size_limit.for_field(name.clone(), *limit);
+        }
+
+        let constraints = multer::Constraints::new().size_limit(size_limit);
+        match &self.allowed_fields {
+            Some(allowed_fields) => constraints.allowed_fields(allowed_fields.clone()),
+            None => constraints,
+        }
     }
+
+    fn is_allowed_content_type(&self, content_type: &str) -> bool {
+        match &self.allowed_content_types {
+            Some(allowed_content_types) => {
+                content_type_matches(allowed_content_types, content_type)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Returns whether the content type matches any of the lowercase patterns,
+/// such as `image/png`, `image/*` or `*/*`.
+pub(crate) fn content_type_matches(patterns: &[String], content_type: &str) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix("/*") {
+            Some("*") => true,
+            Some(ty) => content_type
+                .split_once('/')
+                .map(|(content_type, _)| content_type == ty)
+                .unwrap_or_default(),
+            None => *pattern == content_type,
+        })
 }
 
 /// An extractor that parses `multipart/form-data` requests commonly used with
 /// file uploads.
 ///
+/// The limits are specified by the [`MultipartConfig`] in the request data,
+/// otherwise there are no limits.
+///
 /// # Errors
 ///
 /// - [`ReadBodyError`](crate::error::ReadBodyError)
@@ -127,11 +330,31 @@ impl Field {
 #[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
 pub struct Multipart {
     inner: multer::Multipart<'static>,
+    config: MultipartConfig,
+    fields: usize,
+    files: usize,
 }
 
 #[async_trait::async_trait]
 impl<'a> FromRequest<'a> for Multipart {
     async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let config = req
+            .extensions()
+            .get::<MultipartConfig>()
+            .cloned()
+            .unwrap_or_default();
+        Self::from_request_with_config(req, body, config).await
+    }
+}
+
+impl Multipart {
+    /// Parses the `multipart/form-data` request with the specified
+    /// [`MultipartConfig`].
+    pub async fn from_request_with_config(
+        req: &Request,
+        body: &mut RequestBody,
+        config: MultipartConfig,
+    ) -> Result<Self> {
         let content_type = req
             .headers()
             .get(header::CONTENT_TYPE)
@@ -148,22 +371,54 @@ impl<'a> FromRequest<'a> for Multipart {
 
         let boundary = multer::parse_boundary(content_type.as_ref())
             .map_err(ParseMultipartError::Multipart)?;
+        let body = match BodyLimits::get(req).multipart {
+            Some(limit) => body.take()?.limit(limit),
+            None => body.take()?,
+        };
         Ok(Self {
-            inner: multer::Multipart::new(
-                tokio_util::io::ReaderStream::new(body.take()?.into_async_read()),
+            inner: multer::Multipart::with_constraints(
+                tokio_util::io::ReaderStream::new(body.into_async_read()),
                 boundary,
+                config.constraints(),
             ),
+            config,
+            fields: 0,
+            files: 0,
         })
     }
-}
 
-impl Multipart {
     /// Yields the next [`Field`] if available.
     pub async fn next_field(&mut self) -> Result<Option<Field>, ParseMultipartError> {
-        match self.inner.next_field().await? {
-            Some(field) => Ok(Some(Field(field))),
-            None => Ok(None),
+        let field = match self.inner.next_field().await? {
+            Some(field) => Field(field),
+            None => return Ok(None),
+        };
+
+        self.fields += 1;
+        if let Some(max_fields) = self.config.max_fields {
+            if self.fields > max_fields {
+                return Err(ParseMultipartError::TooManyFields(max_fields));
+            }
+        }
+
+        if field.file_name().is_some() {
+            self.files += 1;
+            if let Some(max_files) = self.config.max_files {
+                if self.files > max_files {
+                    return Err(ParseMultipartError::TooManyFiles(max_files));
+                }
+            }
+
+            let content_type = field.content_type().unwrap_or("application/octet-stream");
+            if !self.config.is_allowed_content_type(content_type) {
+                return Err(ParseMultipartError::ContentTypeNotAllowed {
+                    field_name: field.name().map(ToString::to_string),
+                    content_type: content_type.to_string(),
+                });
+            }
         }
+
+        Ok(Some(field))
     }
 }
 
@@ -218,4 +473,86 @@ mod tests {
             .await;
         resp.assert_status_is_ok();
     }
+
+    #[tokio::test]
+    async fn test_multipart_config() {
+        #[handler(internal)]
+        async fn index(mut multipart: Multipart) -> Result<()> {
+            while let Some(field) = multipart.next_field().await? {
+                field.bytes().await?;
+            }
+            Ok(())
+        }
+
+        async fn check(config: MultipartConfig, status: StatusCode) {
+            let data = "--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nabcd\r\n--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\nContent-Type: image/png\r\n\r\n0123456789\r\n--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"b.txt\"\r\nContent-Type: text/plain\r\n\r\n01234\r\n--X-BOUNDARY--\r\n";
+            let cli = TestClient::new(crate::EndpointExt::data(index, config));
+            cli.post("/")
+                .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+                .body(data)
+                .send()
+                .await
+                .assert_status(status);
+        }
+
+        check(MultipartConfig::new(), StatusCode::OK).await;
+
+        check(
+            MultipartConfig::new().max_size(32),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .await;
+        check(
+            MultipartConfig::new().max_field_size(8),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .await;
+        check(
+            MultipartConfig::new()
+                .max_field_size(8)
+                .field_size_limit("file", 10),
+            StatusCode::OK,
+        )
+        .await;
+
+        check(MultipartConfig::new().max_fields(3), StatusCode::OK).await;
+        check(
+            MultipartConfig::new().max_fields(2),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .await;
+        check(MultipartConfig::new().max_files(2), StatusCode::OK).await;
+        check(
+            MultipartConfig::new().max_files(1),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )
+        .await;
+
+        check(
+            MultipartConfig::new().allowed_fields(["name", "file"]),
+            StatusCode::OK,
+        )
+        .await;
+        check(
+            MultipartConfig::new().allowed_fields(["file"]),
+            StatusCode::BAD_REQUEST,
+        )
+        .await;
+
+        check(
+            MultipartConfig::new().allowed_content_types(["image/*", "Text/Plain"]),
+            StatusCode::OK,
+        )
+        .await;
+        check(
+            MultipartConfig::new().allowed_content_types(["*/*"]),
+            StatusCode::OK,
+        )
+        .await;
+        check(
+            MultipartConfig::new().allowed_content_types(["image/png"]),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        )
+        .await;
+    }
 }
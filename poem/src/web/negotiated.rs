@@ -0,0 +1,668 @@
+use std::ops::{Deref, DerefMut};
+
+use mime::Mime;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::NegotiationError,
+    http::header,
+    web::{read_body, BodyLimits, RequestBody},
+    Error, FromRequest, IntoResponse, Request, Response, Result,
+};
+
+/// A serialization format of [`Negotiated`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Format {
+    /// JSON, `application/json`.
+    Json,
+    /// XML, `application/xml`.
+    #[cfg(feature = "negotiated-xml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "negotiated-xml")))]
+    Xml,
+    /// MessagePack, `application/msgpack`.
+    #[cfg(feature = "negotiated-msgpack")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "negotiated-msgpack")))]
+    MessagePack,
+    /// CBOR, `application/cbor`.
+    #[cfg(feature = "negotiated-cbor")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "negotiated-cbor")))]
+    Cbor,
+    /// YAML, `application/yaml`.
+    #[cfg(feature = "negotiated-yaml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "negotiated-yaml")))]
+    Yaml,
+}
+
+impl Format {
+    /// Returns all the enabled formats.
+    pub fn all() -> Vec<Format> {
+        #[allow(unused_mut)]
+        let mut formats = vec![Format::Json];
+        #[cfg(feature = "negotiated-xml")]
+        formats.push(Format::Xml);
+        #[cfg(feature = "negotiated-msgpack")]
+        formats.push(Format::MessagePack);
+        #[cfg(feature = "negotiated-cbor")]
+        formats.push(Format::Cbor);
+        #[cfg(feature = "negotiated-yaml")]
+        formats.push(Format::Yaml);
+        formats
+    }
+
+    /// Returns the content type of the responses in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json; charset=utf-8",
+            #[cfg(feature = "negotiated-xml")]
+            Format::Xml => "application/xml; charset=utf-8",
+            #[cfg(feature = "negotiated-msgpack")]
+            Format::MessagePack => "application/msgpack",
+            #[cfg(feature = "negotiated-cbor")]
+            Format::Cbor => "application/cbor",
+            #[cfg(feature = "negotiated-yaml")]
+            Format::Yaml => "application/yaml; charset=utf-8",
+        }
+    }
+
+    /// The media types of this format, the first one is the preferred.
+    fn media_types(&self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &["application/json"],
+            #[cfg(feature = "negotiated-xml")]
+            Format::Xml => &["application/xml", "text/xml"],
+            #[cfg(feature = "negotiated-msgpack")]
+            Format::MessagePack => &[
+                "application/msgpack",
+                "application/x-msgpack",
+                "application/vnd.msgpack",
+            ],
+            #[cfg(feature = "negotiated-cbor")]
+            Format::Cbor => &["application/cbor"],
+            #[cfg(feature = "negotiated-yaml")]
+            Format::Yaml => &[
+                "application/yaml",
+                "application/x-yaml",
+                "text/yaml",
+                "text/x-yaml",
+            ],
+        }
+    }
+
+    /// The structured syntax suffix of this format, such as `+json`.
+    fn suffix(&self) -> Option<&'static str> {
+        match self {
+            Format::Json => Some("json"),
+            #[cfg(feature = "negotiated-xml")]
+            Format::Xml => Some("xml"),
+            #[cfg(feature = "negotiated-msgpack")]
+            Format::MessagePack => None,
+            #[cfg(feature = "negotiated-cbor")]
+            Format::Cbor => Some("cbor"),
+            #[cfg(feature = "negotiated-yaml")]
+            Format::Yaml => Some("yaml"),
+        }
+    }
+
+    fn matches(&self, mime: &Mime) -> bool {
+        self.media_types()
+            .iter()
+            .any(|media_type| mime.essence_str().eq_ignore_ascii_case(media_type))
+            || matches!((self.suffix(), mime.suffix()), (Some(a), Some(b)) if b.as_str().eq_ignore_ascii_case(a))
+    }
+
+    /// Serializes the value in this format.
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, NegotiationError> {
+        match self {
+            Format::Json => serde_json::to_vec(value)
+                .map_err(|err| NegotiationError::Serialize(err.to_string())),
+            #[cfg(feature = "negotiated-xml")]
+            Format::Xml => quick_xml::se::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|err| NegotiationError::Serialize(err.to_string())),
+            #[cfg(feature = "negotiated-msgpack")]
+            Format::MessagePack => rmp_serde::to_vec_named(value)
+                .map_err(|err| NegotiationError::Serialize(err.to_string())),
+            #[cfg(feature = "negotiated-cbor")]
+            Format::Cbor => {
+                let mut data = Vec::new();
+                ciborium::ser::into_writer(value, &mut data)
+                    .map_err(|err| NegotiationError::Serialize(err.to_string()))?;
+                Ok(data)
+            }
+            #[cfg(feature = "negotiated-yaml")]
+            Format::Yaml => serde_yaml::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|err| NegotiationError::Serialize(err.to_string())),
+        }
+    }
+
+    /// Deserializes the data in this format.
+    pub fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, NegotiationError> {
+        match self {
+            Format::Json => serde_json::from_slice(data)
+                .map_err(|err| NegotiationError::Deserialize(err.to_string())),
+            #[cfg(feature = "negotiated-xml")]
+            Format::Xml => quick_xml::de::from_reader(data)
+                .map_err(|err| NegotiationError::Deserialize(err.to_string())),
+            #[cfg(feature = "negotiated-msgpack")]
+            Format::MessagePack => rmp_serde::from_slice(data)
+                .map_err(|err| NegotiationError::Deserialize(err.to_string())),
+            #[cfg(feature = "negotiated-cbor")]
+            Format::Cbor => ciborium::de::from_reader(data)
+                .map_err(|err| NegotiationError::Deserialize(err.to_string())),
+            #[cfg(feature = "negotiated-yaml")]
+            Format::Yaml => serde_yaml::from_slice(data)
+                .map_err(|err| NegotiationError::Deserialize(err.to_string())),
+        }
+    }
+}
+
+/// The formats accepted and produced by [`Negotiated`] and [`AcceptFormat`],
+/// in the order of preference.
+///
+/// It is used when it is added to the request with
+/// [`EndpointExt::data`](crate::EndpointExt::data), otherwise all the enabled
+/// formats are used, and JSON is preferred.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     web::{AcceptFormat, Format, Negotiated, NegotiatedFormats},
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(
+///     AcceptFormat(format): AcceptFormat,
+///     data: Negotiated<serde_json::Value>,
+/// ) -> Negotiated<serde_json::Value> {
+///     Negotiated::new(format, data.value)
+/// }
+///
+/// let app = index.data(NegotiatedFormats::new([Format::Json]));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NegotiatedFormats(Vec<Format>);
+
+impl Default for NegotiatedFormats {
+    fn default() -> Self {
+        Self(Format::all())
+    }
+}
+
+impl NegotiatedFormats {
+    /// Create a `NegotiatedFormats` with the formats in the order of
+    /// preference.
+    pub fn new(formats: impl IntoIterator<Item = Format>) -> Self {
+        Self(formats.into_iter().collect())
+    }
+
+    /// Returns the formats in the order of preference.
+    pub fn formats(&self) -> &[Format] {
+        &self.0
+    }
+
+    /// Selects the format of the response by the value of the `Accept`
+    /// header, returns `None` if no format is acceptable.
+    ///
+    /// The format with the highest quality value wins, and the preferred one
+    /// wins if they have the same quality value. If the header is missing,
+    /// the most preferred format is selected.
+    pub fn negotiate(&self, accept: Option<&str>) -> Option<Format> {
+        let ranges = match accept.map(parse_accept) {
+            Some(ranges) if !ranges.is_empty() => ranges,
+            _ => return self.0.first().copied(),
+        };
+
+        let mut selected = None;
+        let mut selected_quality = 0.0;
+        for format in &self.0 {
+            let quality = format
+                .media_types()
+                .iter()
+                .map(|media_type| media_type_quality(&ranges, media_type))
+                .fold(0.0, f32::max);
+            if quality > selected_quality {
+                selected = Some(*format);
+                selected_quality = quality;
+            }
+        }
+        selected
+    }
+
+    fn find(&self, mime: &Mime) -> Option<Format> {
+        self.0.iter().find(|format| format.matches(mime)).copied()
+    }
+
+    fn get(req: &Request) -> Self {
+        req.extensions().get::<Self>().cloned().unwrap_or_default()
+    }
+}
+
+/// Parses the media ranges and their quality values of the `Accept` header.
+fn parse_accept(accept: &str) -> Vec<(String, f32)> {
+    accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let range = parts.next()?.trim().to_ascii_lowercase();
+            if range.is_empty() {
+                return None;
+            }
+            let mut quality = 1.0;
+            for param in parts {
+                if let Some((name, value)) = param.split_once('=') {
+                    if name.trim().eq_ignore_ascii_case("q") {
+                        quality = value.trim().parse::<f32>().ok()?;
+                    }
+                }
+            }
+            Some((range, quality))
+        })
+        .collect()
+}
+
+/// Returns the quality value of the most specific media range that matches
+/// the media type.
+fn media_type_quality(ranges: &[(String, f32)], media_type: &str) -> f32 {
+    let ty = media_type.split('/').next().unwrap_or_default();
+    let mut specificity = 0;
+    let mut quality = 0.0;
+    for (range, range_quality) in ranges {
+        let range_specificity = if range == media_type {
+            3
+        } else if range.strip_suffix("/*") == Some(ty) {
+            2
+        } else if range == "*/*" {
+            1
+        } else {
+            continue;
+        };
+        if range_specificity > specificity
+            || (range_specificity == specificity && *range_quality > quality)
+        {
+            specificity = range_specificity;
+            quality = *range_quality;
+        }
+    }
+    quality
+}
+
+/// An extractor that selects the format of the response by the `Accept`
+/// header, see [`NegotiatedFormats::negotiate`].
+///
+/// # Errors
+///
+/// - [`NegotiationError::NotAcceptable`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     http::{header, StatusCode},
+///     test::TestClient,
+///     web::{AcceptFormat, Negotiated},
+///     Route,
+/// };
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// fn index(AcceptFormat(format): AcceptFormat) -> Negotiated<User> {
+///     Negotiated::new(
+///         format,
+///         User {
+///             name: "sunli".to_string(),
+///         },
+///     )
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .get("/")
+///     .header(header::ACCEPT, "text/html, application/json;q=0.9")
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_json(serde_json::json!({ "name": "sunli" })).await;
+///
+/// let resp = cli.get("/").header(header::ACCEPT, "text/html").send().await;
+/// resp.assert_status(StatusCode::NOT_ACCEPTABLE);
+/// # });
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AcceptFormat(pub Format);
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for AcceptFormat {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        let accept = req
+            .headers()
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+        let accept = if accept.is_empty() {
+            None
+        } else {
+            Some(accept.join(","))
+        };
+
+        NegotiatedFormats::get(req)
+            .negotiate(accept.as_deref())
+            .map(AcceptFormat)
+            .ok_or_else(|| NegotiationError::NotAcceptable.into())
+    }
+}
+
+/// A value serialized in one of the [`NegotiatedFormats`].
+///
+/// # Extractor
+///
+/// Deserializes the request body in the format selected by the
+/// `Content-Type` header, the size limit of [`Json`](crate::web::Json) in
+/// [`BodyLimits`] applies.
+///
+/// # Errors
+///
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+/// - [`NegotiationError`]
+///
+/// # Response
+///
+/// Serializes the value in [`Negotiated::format`], and sets the
+/// `Content-Type` and `Vary: Accept` headers.
+///
+/// The response does not read the `Accept` header, so the format must be
+/// selected with the [`AcceptFormat`] extractor. An extracted value which is
+/// returned as is keeps the format of the request body, even if the client
+/// does not accept it.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     http::{header, StatusCode},
+///     post,
+///     test::TestClient,
+///     web::{AcceptFormat, Negotiated},
+///     Route,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// fn index(AcceptFormat(format): AcceptFormat, user: Negotiated<User>) -> Negotiated<User> {
+///     Negotiated::new(format, user.value)
+/// }
+///
+/// let app = Route::new().at("/", post(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .post("/")
+///     .content_type("application/json")
+///     .body(r#"{"name": "sunli"}"#)
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_content_type("application/json; charset=utf-8");
+///
+/// let resp = cli
+///     .post("/")
+///     .content_type("text/plain")
+///     .body("sunli")
+///     .send()
+///     .await;
+/// resp.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Negotiated<T> {
+    /// The format of the value.
+    pub format: Format,
+    /// The value.
+    pub value: T,
+}
+
+impl<T> Negotiated<T> {
+    /// Create a `Negotiated` which is serialized in the format.
+    pub fn new(format: Format, value: T) -> Self {
+        Self { format, value }
+    }
+}
+
+impl<T> Deref for Negotiated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Negotiated<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for Negotiated<T> {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(NegotiationError::ContentTypeRequired)?;
+        let format = content_type
+            .parse::<Mime>()
+            .ok()
+            .and_then(|mime| NegotiatedFormats::get(req).find(&mime))
+            .ok_or_else(|| NegotiationError::UnsupportedMediaType(content_type.to_string()))?;
+
+        let data = read_body(body.take()?, BodyLimits::get(req).json).await?;
+        Ok(Self {
+            format,
+            value: format.deserialize(&data)?,
+        })
+    }
+}
+
+impl<T: Serialize + Send> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let data = match self.format.serialize(&self.value) {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to serialize the negotiated response");
+                return Error::from(err).as_response();
+            }
+        };
+        Response::builder()
+            .header(header::CONTENT_TYPE, self.format.content_type())
+            .header(header::VARY, "Accept")
+            .body(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient};
+
+    #[test]
+    fn negotiate() {
+        let formats = NegotiatedFormats::new([Format::Json]);
+        assert_eq!(formats.negotiate(None), Some(Format::Json));
+        assert_eq!(formats.negotiate(Some("")), Some(Format::Json));
+        assert_eq!(formats.negotiate(Some("*/*")), Some(Format::Json));
+        assert_eq!(
+            formats.negotiate(Some("application/*;q=0.5")),
+            Some(Format::Json)
+        );
+        assert_eq!(
+            formats.negotiate(Some("text/html, application/json;q=0.1")),
+            Some(Format::Json)
+        );
+        assert_eq!(formats.negotiate(Some("text/html")), None);
+        assert_eq!(formats.negotiate(Some("*/*, application/json;q=0")), None);
+        assert_eq!(NegotiatedFormats::new([]).negotiate(None), None);
+    }
+
+    #[cfg(all(feature = "negotiated-msgpack", feature = "negotiated-cbor"))]
+    #[test]
+    fn negotiate_quality() {
+        let formats = NegotiatedFormats::new([Format::Json, Format::MessagePack, Format::Cbor]);
+        assert_eq!(
+            formats.negotiate(Some("application/cbor, application/json;q=0.9")),
+            Some(Format::Cbor)
+        );
+        assert_eq!(
+            formats.negotiate(Some("application/x-msgpack;q=0.8, */*;q=0.5")),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(
+            formats.negotiate(Some("application/*, application/json;q=0.2")),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(
+            formats.negotiate(Some("application/cbor;q=0.5, application/msgpack;q=0.5")),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(
+            formats.negotiate(Some("application/cbor;q=invalid")),
+            Some(Format::Json)
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+    struct User {
+        name: String,
+        age: i32,
+    }
+
+    #[handler(internal)]
+    fn index(AcceptFormat(format): AcceptFormat, user: Negotiated<User>) -> Negotiated<User> {
+        Negotiated::new(format, user.value)
+    }
+
+    #[tokio::test]
+    async fn negotiated() {
+        let cli = TestClient::new(index);
+
+        let resp = cli
+            .post("/")
+            .content_type("application/vnd.api+json")
+            .body(r#"{"name": "sunli", "age": 18}"#)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("application/json; charset=utf-8");
+        resp.assert_header("vary", "Accept");
+        resp.assert_json(User {
+            name: "sunli".to_string(),
+            age: 18,
+        })
+        .await;
+
+        cli.post("/")
+            .body(r#"{"name": "sunli", "age": 18}"#)
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        cli.post("/")
+            .content_type("text/plain")
+            .body("sunli")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        cli.post("/")
+            .content_type("application/json")
+            .body("sunli")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.post("/")
+            .header("accept", "text/html")
+            .content_type("application/json")
+            .body(r#"{"name": "sunli", "age": 18}"#)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[tokio::test]
+    async fn serialize_error() {
+        use std::collections::HashMap;
+
+        #[handler(internal)]
+        fn index() -> Negotiated<HashMap<(i32, i32), i32>> {
+            Negotiated::new(Format::Json, [((1, 2), 3)].into_iter().collect())
+        }
+
+        let resp = TestClient::new(index).get("/").send().await;
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        resp.assert_text("failed to serialize the response").await;
+    }
+
+    #[cfg(all(feature = "negotiated-msgpack", feature = "negotiated-cbor"))]
+    #[tokio::test]
+    async fn negotiated_formats() {
+        use crate::EndpointExt;
+
+        let user = User {
+            name: "sunli".to_string(),
+            age: 18,
+        };
+        let cli = TestClient::new(index);
+
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            for accept in [Format::Json, Format::MessagePack, Format::Cbor] {
+                let resp = cli
+                    .post("/")
+                    .header("accept", accept.media_types()[0])
+                    .content_type(format.media_types()[0])
+                    .body(format.serialize(&user).unwrap())
+                    .send()
+                    .await;
+                resp.assert_status_is_ok();
+                resp.assert_content_type(accept.content_type());
+                let data = resp.0.into_body().into_vec().await.unwrap();
+                assert_eq!(accept.deserialize::<User>(&data).unwrap(), user);
+            }
+        }
+
+        // The formats which are not registered are unsupported.
+        let cli = TestClient::new(index.data(NegotiatedFormats::new([Format::Json])));
+        cli.post("/")
+            .content_type("application/cbor")
+            .body(Format::Cbor.serialize(&user).unwrap())
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        cli.post("/")
+            .header("accept", "application/cbor")
+            .content_type("application/json")
+            .body(Format::Json.serialize(&user).unwrap())
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_ACCEPTABLE);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{str::FromStr, time::SystemTime};
+
+use headers::{ETag, HeaderMapExt, IfMatch, IfUnmodifiedSince};
+
+use crate::{
+    error::ParseTypedHeaderError, http::StatusCode, Error, FromRequest, Request, RequestBody,
+    Result,
+};
+
+/// An extractor for the `If-Match` and `If-Unmodified-Since` preconditions of
+/// the request.
+///
+/// The handler compares the preconditions with the current version of the
+/// resource with [`Preconditions::check`], so the check and the update can be
+/// done atomically, for example while holding a lock or in a database
+/// transaction.
+///
+/// # Errors
+///
+/// - [`ParseTypedHeaderError`]
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use poem::{
+///     handler,
+///     http::{header, StatusCode},
+///     put,
+///     test::TestClient,
+///     web::{Data, Preconditions},
+///     EndpointExt, Result, Route,
+/// };
+/// use tokio::sync::Mutex;
+///
+/// #[handler]
+/// async fn update(
+///     preconditions: Preconditions,
+///     version: Data<&Arc<Mutex<u32>>>,
+/// ) -> Result<String> {
+///     let mut version = version.lock().await;
+///     preconditions.check(Some(&format!("\"{}\"", *version)), None)?;
+///     *version += 1;
+///     Ok(version.to_string())
+/// }
+///
+/// let app = Route::new().at("/", put(update)).data(Arc::new(Mutex::new(1u32)));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.put("/").header(header::IF_MATCH, "\"1\"").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("2").await;
+///
+/// let resp = cli.put("/").header(header::IF_MATCH, "\"1\"").send().await;
+/// resp.assert_status(StatusCode::PRECONDITION_FAILED);
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Preconditions {
+    if_match: Option<IfMatch>,
+    if_unmodified_since: Option<IfUnmodifiedSince>,
+}
+
+impl Preconditions {
+    /// Returns `true` if the request has neither the `If-Match` nor the
+    /// `If-Unmodified-Since` header.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none() && self.if_unmodified_since.is_none()
+    }
+
+    /// Returns the `If-Match` header of the request.
+    #[inline]
+    pub fn if_match(&self) -> Option<&IfMatch> {
+        self.if_match.as_ref()
+    }
+
+    /// Returns the `If-Unmodified-Since` header of the request.
+    #[inline]
+    pub fn if_unmodified_since(&self) -> Option<&IfUnmodifiedSince> {
+        self.if_unmodified_since.as_ref()
+    }
+
+    /// Returns `true` if the preconditions pass for the current version of
+    /// the resource.
+    ///
+    /// `etag` is the current entity tag of the resource including the quotes,
+    /// such as `"v1"`, and is `None` if the resource does not exist.
+    /// `last_modified` is the modification time of the resource, the
+    /// `If-Unmodified-Since` header is ignored if it is `None` or the request
+    /// has the `If-Match` header.
+    pub fn passes(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        if let Some(if_match) = &self.if_match {
+            return match etag {
+                Some(_) if *if_match == IfMatch::any() => true,
+                Some(etag) => matches!(
+                    ETag::from_str(etag),
+                    Ok(etag) if if_match.precondition_passes(&etag)
+                ),
+                None => false,
+            };
+        }
+
+        match (&self.if_unmodified_since, last_modified) {
+            (Some(if_unmodified_since), Some(last_modified)) => {
+                if_unmodified_since.precondition_passes(last_modified)
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns `412 Precondition Failed` error if the preconditions do not
+    /// pass for the current version of the resource, see
+    /// [`Preconditions::passes`].
+    pub fn check(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> Result<()> {
+        if self.passes(etag, last_modified) {
+            Ok(())
+        } else {
+            Err(Error::from_status(StatusCode::PRECONDITION_FAILED))
+        }
+    }
+
+    fn internal_from_request(req: &Request) -> Result<Self, ParseTypedHeaderError> {
+        Ok(Self {
+            if_match: req.headers().typed_try_get::<IfMatch>()?,
+            if_unmodified_since: req.headers().typed_try_get::<IfUnmodifiedSince>()?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Preconditions {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Self::internal_from_request(req).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::http::header;
+
+    async fn extract(headers: &[(header::HeaderName, &str)]) -> Preconditions {
+        let mut req = Request::builder();
+        for (name, value) in headers {
+            req = req.header(name.clone(), *value);
+        }
+        let (req, mut body) = req.finish().split();
+        Preconditions::from_request(&req, &mut body).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn if_match() {
+        let preconditions = extract(&[]).await;
+        assert!(preconditions.is_empty());
+        assert!(preconditions.passes(None, None));
+
+        let preconditions = extract(&[(header::IF_MATCH, "\"a\", \"b\"")]).await;
+        assert!(!preconditions.is_empty());
+        assert!(preconditions.passes(Some("\"b\""), None));
+        assert!(!preconditions.passes(Some("\"c\""), None));
+        assert!(!preconditions.passes(Some("W/\"b\""), None));
+        assert!(!preconditions.passes(None, None));
+        assert_eq!(
+            preconditions
+                .check(Some("\"c\""), None)
+                .unwrap_err()
+                .as_response()
+                .status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        let preconditions = extract(&[(header::IF_MATCH, "*")]).await;
+        assert!(preconditions.passes(Some("\"c\""), None));
+        assert!(!preconditions.passes(None, None));
+    }
+
+    #[tokio::test]
+    async fn if_unmodified_since() {
+        let preconditions = extract(&[(
+            header::IF_UNMODIFIED_SINCE,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        )])
+        .await;
+        let since = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert!(preconditions.passes(None, Some(since)));
+        assert!(!preconditions.passes(None, Some(since + Duration::from_secs(1))));
+        assert!(preconditions.passes(None, None));
+    }
+
+    #[tokio::test]
+    async fn invalid_header() {
+        let (req, mut body) = Request::builder()
+            .header(header::IF_UNMODIFIED_SINCE, "abc")
+            .finish()
+            .split();
+        assert!(Preconditions::from_request(&req, &mut body).await.is_err());
+    }
+}
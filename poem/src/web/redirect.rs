@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+#[cfg(feature = "session")]
+use crate::session::{FlashMessage, FlashMessages};
 use crate::{
     http::{header, StatusCode},
     IntoResponse, Response,
@@ -34,6 +36,8 @@ use crate::{
 pub struct Redirect {
     status: StatusCode,
     uri: String,
+    #[cfg(feature = "session")]
+    flash: FlashMessages,
 }
 
 impl Redirect {
@@ -42,6 +46,8 @@ impl Redirect {
         Self {
             status: StatusCode::PERMANENT_REDIRECT,
             uri: uri.to_string(),
+            #[cfg(feature = "session")]
+            flash: FlashMessages::default(),
         }
     }
 
@@ -50,6 +56,8 @@ impl Redirect {
         Self {
             status: StatusCode::MOVED_PERMANENTLY,
             uri: uri.to_string(),
+            #[cfg(feature = "session")]
+            flash: FlashMessages::default(),
         }
     }
 
@@ -58,6 +66,8 @@ impl Redirect {
         Self {
             status: StatusCode::SEE_OTHER,
             uri: uri.to_string(),
+            #[cfg(feature = "session")]
+            flash: FlashMessages::default(),
         }
     }
 
@@ -66,15 +76,35 @@ impl Redirect {
         Self {
             status: StatusCode::TEMPORARY_REDIRECT,
             uri: uri.to_string(),
+            #[cfg(feature = "session")]
+            flash: FlashMessages::default(),
+        }
+    }
+
+    /// Adds a flash message, which is saved by the
+    /// [`FlashManager`](crate::session::FlashManager) middleware.
+    #[cfg(feature = "session")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "session")))]
+    #[must_use]
+    pub fn flash(self, message: FlashMessage) -> Self {
+        Self {
+            flash: self.flash.push(message),
+            ..self
         }
     }
 }
 
 impl IntoResponse for Redirect {
     fn into_response(self) -> Response {
-        self.status
+        let resp = self
+            .status
             .with_header(header::LOCATION, self.uri)
-            .into_response()
+            .into_response();
+
+        #[cfg(feature = "session")]
+        let resp = self.flash.into_response_with(resp);
+
+        resp
     }
 }
 
@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{Stream, StreamExt};
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+use super::{Event, SSE};
+
+struct Channel<T> {
+    buffer: VecDeque<(String, T)>,
+    sender: broadcast::Sender<(String, T)>,
+}
+
+impl<T> Channel<T> {
+    fn is_unused(&self) -> bool {
+        self.buffer.is_empty() && self.sender.receiver_count() == 0
+    }
+}
+
+struct State<T> {
+    next_id: u64,
+    channels: HashMap<String, Channel<T>>,
+}
+
+impl<T> Default for State<T> {
+    fn default() -> Self {
+        Self {
+            next_id: 1,
+            channels: HashMap::new(),
+        }
+    }
+}
+
+impl<T> State<T> {
+    /// Removes the channel if it has neither subscribers nor buffered events.
+    fn remove_unused(&mut self, name: &str) {
+        if matches!(self.channels.get(name), Some(channel) if channel.is_unused()) {
+            self.channels.remove(name);
+        }
+    }
+}
+
+/// The receiver of a subscriber, which removes the unused channel when it is
+/// dropped.
+struct Subscription<T> {
+    name: String,
+    receiver: Option<broadcast::Receiver<(String, T)>>,
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        self.receiver.take();
+        state.remove_unused(&self.name);
+    }
+}
+
+/// A broadcaster which fans the server-sent events out to the subscribers of
+/// the named channels, and resumes the reconnecting clients from the
+/// [`LastEventId`](super::LastEventId).
+///
+/// Each channel keeps the latest events in a bounded replay buffer. When a
+/// client reconnects with the id of the last event it received, the events
+/// after it are replayed before the new events. If the id is not in the
+/// buffer, for example the client has been disconnected for too long, the
+/// whole buffer is replayed.
+///
+/// The ids of the events are assigned by the broadcaster, and the ids set by
+/// [`Event::id`] are ignored.
+///
+/// A channel is created by the first subscriber or event, and removed when it
+/// has neither subscribers nor buffered events.
+///
+/// If a subscriber falls behind more than [`SseBroadcaster::capacity`]
+/// events, its stream ends, see [`SseBroadcaster::subscribe`].
+///
+/// The type parameter `T` is the type of the events, [`Event`] by default.
+/// Other types can be used with [`SseBroadcaster::subscribe`], which yields
+/// the id and the event.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     web::{
+///         sse::{Event, LastEventId, SseBroadcaster, SSE},
+///         Data,
+///     },
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn events(broadcaster: Data<&SseBroadcaster>, last_event_id: LastEventId) -> SSE {
+///     broadcaster.sse("news", last_event_id.as_str())
+/// }
+///
+/// #[handler]
+/// fn publish(broadcaster: Data<&SseBroadcaster>, body: String) {
+///     broadcaster.send("news", Event::message(body));
+/// }
+///
+/// let app = Route::new()
+///     .at("/events", get(events))
+///     .at("/publish", publish)
+///     .data(SseBroadcaster::<Event>::new());
+/// ```
+pub struct SseBroadcaster<T = Event> {
+    replay_buffer_size: usize,
+    capacity: usize,
+    epoch: u64,
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Clone for SseBroadcaster<T> {
+    fn clone(&self) -> Self {
+        Self {
+            replay_buffer_size: self.replay_buffer_size,
+            capacity: self.capacity,
+            epoch: self.epoch,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> Default for SseBroadcaster<T> {
+    fn default() -> Self {
+        Self {
+            replay_buffer_size: 128,
+            capacity: 128,
+            epoch: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> SseBroadcaster<T> {
+    /// Create a `SseBroadcaster`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the number of the events kept in the replay buffer of each
+    /// channel. Default is `128`.
+    #[must_use]
+    pub fn replay_buffer_size(self, size: usize) -> Self {
+        Self {
+            replay_buffer_size: size,
+            ..self
+        }
+    }
+
+    /// Sets the number of the events which can be queued for each
+    /// subscriber. Default is `128`.
+    ///
+    /// If a subscriber falls behind more than this, its stream ends and the
+    /// client is expected to reconnect, then the missed events are replayed
+    /// if they are still in the replay buffer.
+    #[must_use]
+    pub fn capacity(self, capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ..self
+        }
+    }
+
+    /// Sends an event to the channel, and returns the id assigned to it.
+    pub fn send(&self, name: &str, event: T) -> String {
+        let mut state = self.state.lock();
+        let id = format!("{}-{}", self.epoch, state.next_id);
+        state.next_id += 1;
+
+        let channel = self.channel(&mut state, name);
+        if self.replay_buffer_size > 0 {
+            if channel.buffer.len() == self.replay_buffer_size {
+                channel.buffer.pop_front();
+            }
+            channel.buffer.push_back((id.clone(), event.clone()));
+        }
+        let _ = channel.sender.send((id.clone(), event));
+        state.remove_unused(name);
+        id
+    }
+
+    /// Subscribes to the channel, and returns a stream of the ids and the
+    /// events.
+    ///
+    /// If `last_event_id` is specified, the events after it in the replay
+    /// buffer are yielded first.
+    ///
+    /// If the subscriber falls behind more than [`SseBroadcaster::capacity`]
+    /// events, the stream ends without an error. The subscriber can subscribe
+    /// again with the id of the last event it received, and the missed
+    /// events are replayed if they are still in the replay buffer.
+    pub fn subscribe(
+        &self,
+        name: &str,
+        last_event_id: Option<&str>,
+    ) -> impl Stream<Item = (String, T)> + Send + 'static {
+        let (replay, receiver) = {
+            let mut state = self.state.lock();
+            let channel = self.channel(&mut state, name);
+            let replay = match last_event_id {
+                Some(last_event_id) => {
+                    let skip = channel
+                        .buffer
+                        .iter()
+                        .position(|(id, _)| id == last_event_id)
+                        .map(|pos| pos + 1)
+                        .unwrap_or_default();
+                    channel.buffer.iter().skip(skip).cloned().collect()
+                }
+                None => Vec::new(),
+            };
+            (replay, channel.sender.subscribe())
+        };
+
+        let subscription = Subscription {
+            name: name.to_string(),
+            receiver: Some(receiver),
+            state: self.state.clone(),
+        };
+        futures_util::stream::iter(replay).chain(futures_util::stream::unfold(
+            subscription,
+            |mut subscription| async move {
+                // Ends the stream if the subscriber lags behind.
+                let item = subscription.receiver.as_mut()?.recv().await.ok()?;
+                Some((item, subscription))
+            },
+        ))
+    }
+
+    /// Returns the number of the subscribers of the channel.
+    pub fn subscriber_count(&self, channel: &str) -> usize {
+        self.state
+            .lock()
+            .channels
+            .get(channel)
+            .map(|channel| channel.sender.receiver_count())
+            .unwrap_or_default()
+    }
+
+    /// Removes the channel and its replay buffer, the streams of its
+    /// subscribers end.
+    pub fn remove_channel(&self, channel: &str) {
+        self.state.lock().channels.remove(channel);
+    }
+
+    fn channel<'a>(&self, state: &'a mut State<T>, name: &str) -> &'a mut Channel<T> {
+        let channels = &mut state.channels;
+        if !channels.contains_key(name) {
+            channels.insert(
+                name.to_string(),
+                Channel {
+                    buffer: VecDeque::new(),
+                    sender: broadcast::channel(self.capacity).0,
+                },
+            );
+        }
+        channels.get_mut(name).unwrap()
+    }
+}
+
+impl SseBroadcaster<Event> {
+    /// Subscribes to the channel, and returns a [`SSE`] response.
+    ///
+    /// If `last_event_id` is specified, the events after it in the replay
+    /// buffer are sent first.
+    ///
+    /// If the subscriber falls behind, the response ends and the browsers
+    /// reconnect with the `Last-Event-ID` header, see
+    /// [`SseBroadcaster::subscribe`].
+    pub fn sse(&self, channel: &str, last_event_id: Option<&str>) -> SSE {
+        SSE::new(
+            self.subscribe(channel, last_event_id)
+                .map(|(id, event)| event.id(id)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{
+        handler,
+        test::TestClient,
+        web::{sse::LastEventId, Data},
+        EndpointExt, IntoResponse,
+    };
+
+    #[tokio::test]
+    async fn replay() {
+        let broadcaster = SseBroadcaster::<i32>::new().replay_buffer_size(3);
+        let ids = (1..=5)
+            .map(|value| broadcaster.send("a", value))
+            .collect::<Vec<_>>();
+        broadcaster.send("b", 100);
+
+        // New subscribers receive new events only.
+        let mut stream = Box::pin(broadcaster.subscribe("a", None));
+        assert_eq!(broadcaster.subscriber_count("a"), 1);
+        let id = broadcaster.send("a", 6);
+        assert_eq!(stream.next().await, Some((id, 6)));
+
+        let values = |last_event_id: &str, count: usize| {
+            broadcaster
+                .subscribe("a", Some(last_event_id))
+                .map(|(_, value)| value)
+                .take(count)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&ids[3], 2).await, vec![5, 6]);
+        // The id is not in the replay buffer.
+        assert_eq!(values(&ids[0], 3).await, vec![4, 5, 6]);
+        assert_eq!(values("unknown", 3).await, vec![4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn lagged() {
+        let broadcaster = SseBroadcaster::<i32>::new().capacity(2);
+        let stream = broadcaster.subscribe("a", None);
+        for value in 0..5 {
+            broadcaster.send("a", value);
+        }
+        assert!(stream.collect::<Vec<_>>().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_unused_channels() {
+        let broadcaster = SseBroadcaster::<i32>::new();
+        let stream = broadcaster.subscribe("a", None);
+        let stream2 = broadcaster.subscribe("a", None);
+        assert_eq!(broadcaster.state.lock().channels.len(), 1);
+        drop(stream);
+        assert_eq!(broadcaster.subscriber_count("a"), 1);
+        drop(stream2);
+        assert!(broadcaster.state.lock().channels.is_empty());
+
+        // Without the replay buffer, the events sent to the channels without
+        // subscribers are dropped.
+        let broadcaster = SseBroadcaster::<i32>::new().replay_buffer_size(0);
+        broadcaster.send("a", 1);
+        assert!(broadcaster.state.lock().channels.is_empty());
+
+        // The channels with the buffered events are kept.
+        let broadcaster = SseBroadcaster::<i32>::new();
+        broadcaster.send("a", 1);
+        drop(broadcaster.subscribe("a", None));
+        assert_eq!(broadcaster.state.lock().channels.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn remove_channel() {
+        let broadcaster = SseBroadcaster::<i32>::new();
+        let stream = broadcaster.subscribe("a", None);
+        broadcaster.send("a", 1);
+        broadcaster.remove_channel("a");
+        assert_eq!(
+            stream.map(|(_, value)| value).collect::<Vec<_>>().await,
+            vec![1]
+        );
+        assert_eq!(broadcaster.subscriber_count("a"), 0);
+    }
+
+    #[tokio::test]
+    async fn sse() {
+        #[handler(internal)]
+        fn index(broadcaster: Data<&SseBroadcaster>, last_event_id: LastEventId) -> SSE {
+            broadcaster.sse("a", last_event_id.as_str())
+        }
+
+        let broadcaster = SseBroadcaster::new();
+        let id = broadcaster.send("a", Event::message("1").id("ignored"));
+        broadcaster.send("a", Event::message("2").event_type("tt"));
+
+        let app = index.data(broadcaster.clone());
+        let cli = TestClient::new(app);
+        let resp = cli.get("/").header("Last-Event-ID", &id).send().await;
+        resp.assert_status_is_ok();
+
+        let mut body = resp.0.into_body().into_async_read();
+        let mut data = vec![0; 256];
+        let len = body.read(&mut data).await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&data[..len]),
+            format!("id: {}-2\nevent: tt\ndata: 2\n\n", broadcaster.epoch)
+        );
+
+        broadcaster.send("a", Event::message("3"));
+        let len = body.read(&mut data).await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&data[..len]),
+            format!("id: {}-3\ndata: 3\n\n", broadcaster.epoch)
+        );
+
+        // The response can be created without the `Last-Event-ID` header.
+        let _ = broadcaster.sse("a", None).into_response();
+    }
+}
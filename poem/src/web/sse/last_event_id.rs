@@ -0,0 +1,41 @@
+use std::ops::Deref;
+
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// The `Last-Event-ID` header sent by the client when it reconnects to an
+/// event stream, which is the id of the last event it received.
+///
+/// See also the [Server-Sent Events spec](https://html.spec.whatwg.org/multipage/server-sent-events.html#the-last-event-id-header)
+/// and [`SseBroadcaster`](super::SseBroadcaster).
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct LastEventId(pub Option<String>);
+
+impl LastEventId {
+    /// Returns the id as a string slice, or `None` if the client did not send
+    /// the header.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl Deref for LastEventId {
+    type Target = Option<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for LastEventId {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(LastEventId(
+            req.headers()
+                .get("last-event-id")
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string),
+        ))
+    }
+}
@@ -1,9 +1,13 @@
 //! Server-Sent Events (SSE) types.
 
+mod broadcaster;
 mod event;
+mod last_event_id;
 mod response;
 
+pub use broadcaster::SseBroadcaster;
 pub use event::Event;
+pub use last_event_id::LastEventId;
 pub use response::SSE;
 
 #[cfg(test)]
@@ -0,0 +1,162 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    path::{Component, Path, PathBuf},
+};
+
+use bytes::Bytes;
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use super::{UploadSink, UploadWriter};
+
+/// An [`UploadSink`] which stores the uploads in a local directory.
+///
+/// The key is the relative path of the file in the directory, and the
+/// intermediate directories are created as needed. The upload is written to a
+/// temporary file in the same directory, and renamed to the final path when it
+/// is completed, so the readers never see a partial file.
+#[derive(Debug, Clone)]
+pub struct LocalDirSink {
+    dir: PathBuf,
+}
+
+impl LocalDirSink {
+    /// Create a `LocalDirSink` which stores the uploads in the directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the path of the file with the key.
+    ///
+    /// Returns an error if the key is not a relative path without `..`.
+    pub fn path(&self, key: &str) -> IoResult<PathBuf> {
+        let relative = Path::new(key);
+        let is_valid = relative.file_name().is_some()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_valid {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid upload key `{}`", key),
+            ));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait::async_trait]
+impl UploadSink for LocalDirSink {
+    type Writer = LocalDirWriter;
+
+    async fn create(&self, key: &str, _content_type: &str) -> IoResult<Self::Writer> {
+        let path = self.path(key)?;
+        let dir = path.parent().unwrap_or(&self.dir);
+        tokio::fs::create_dir_all(dir).await?;
+
+        let tmp_path = dir.join(format!(
+            ".{}.{:016x}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            rand::random::<u64>()
+        ));
+        let file = File::create(&tmp_path).await?;
+
+        Ok(LocalDirWriter {
+            file: Some(file),
+            tmp_path,
+            path,
+        })
+    }
+}
+
+/// The writer of [`LocalDirSink`].
+///
+/// If it is dropped without calling [`UploadWriter::finish`], the temporary
+/// file is removed.
+pub struct LocalDirWriter {
+    file: Option<File>,
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl UploadWriter for LocalDirWriter {
+    async fn write(&mut self, data: Bytes) -> IoResult<()> {
+        match &mut self.file {
+            Some(file) => file.write_all(&data).await,
+            None => Err(IoError::other("the upload is closed")),
+        }
+    }
+
+    async fn finish(mut self) -> IoResult<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush().await?;
+            file.sync_all().await?;
+        }
+        tokio::fs::rename(&self.tmp_path, &self.path).await?;
+        // The temporary file has been renamed.
+        self.tmp_path = PathBuf::new();
+        Ok(())
+    }
+
+    async fn abort(mut self) -> IoResult<()> {
+        self.file.take();
+        let tmp_path = std::mem::take(&mut self.tmp_path);
+        tokio::fs::remove_file(tmp_path).await
+    }
+}
+
+impl Drop for LocalDirWriter {
+    fn drop(&mut self) {
+        if !self.tmp_path.as_os_str().is_empty() {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path() {
+        let sink = LocalDirSink::new("/uploads");
+        assert_eq!(sink.path("a.txt").unwrap(), Path::new("/uploads/a.txt"));
+        assert_eq!(sink.path("a/b.txt").unwrap(), Path::new("/uploads/a/b.txt"));
+        for key in ["", "/a.txt", "../a.txt", "a/../../b.txt", "./a.txt", "a/.."] {
+            assert!(sink.path(key).is_err(), "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn write() {
+        let dir = std::env::temp_dir().join(format!("poem-upload-{:016x}", rand::random::<u64>()));
+        let sink = LocalDirSink::new(&dir);
+
+        let mut writer = sink.create("a/b.txt", "text/plain").await.unwrap();
+        writer.write(Bytes::from_static(b"hello ")).await.unwrap();
+        writer.write(Bytes::from_static(b"world")).await.unwrap();
+        assert!(!dir.join("a/b.txt").exists());
+        writer.finish().await.unwrap();
+        assert_eq!(
+            tokio::fs::read(dir.join("a/b.txt")).await.unwrap(),
+            b"hello world"
+        );
+
+        let mut writer = sink.create("a/c.txt", "text/plain").await.unwrap();
+        writer.write(Bytes::from_static(b"hello")).await.unwrap();
+        writer.abort().await.unwrap();
+
+        let writer = sink.create("a/d.txt", "text/plain").await.unwrap();
+        drop(writer);
+
+        // Only the completed file is left.
+        let mut entries = tokio::fs::read_dir(dir.join("a")).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names, vec!["b.txt"]);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use std::{collections::HashMap, io::Result as IoResult, sync::Arc};
+
+use bytes::{Bytes, BytesMut};
+use parking_lot::Mutex;
+
+use super::{UploadSink, UploadWriter};
+
+/// An [`UploadSink`] which stores the uploads in the memory, which is mainly
+/// used for testing.
+#[derive(Debug, Default, Clone)]
+pub struct MemorySink {
+    files: Arc<Mutex<HashMap<String, Bytes>>>,
+}
+
+impl MemorySink {
+    /// Create a `MemorySink`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the data of the upload with the key.
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        self.files.lock().get(key).cloned()
+    }
+
+    /// Removes the upload with the key, and returns its data.
+    pub fn remove(&self, key: &str) -> Option<Bytes> {
+        self.files.lock().remove(key)
+    }
+
+    /// Returns the keys of the uploads.
+    pub fn keys(&self) -> Vec<String> {
+        self.files.lock().keys().cloned().collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl UploadSink for MemorySink {
+    type Writer = MemoryWriter;
+
+    async fn create(&self, key: &str, _content_type: &str) -> IoResult<Self::Writer> {
+        Ok(MemoryWriter {
+            files: self.files.clone(),
+            key: key.to_string(),
+            data: BytesMut::new(),
+        })
+    }
+}
+
+/// The writer of [`MemorySink`].
+pub struct MemoryWriter {
+    files: Arc<Mutex<HashMap<String, Bytes>>>,
+    key: String,
+    data: BytesMut,
+}
+
+#[async_trait::async_trait]
+impl UploadWriter for MemoryWriter {
+    async fn write(&mut self, data: Bytes) -> IoResult<()> {
+        self.data.extend_from_slice(&data);
+        Ok(())
+    }
+
+    async fn finish(self) -> IoResult<()> {
+        self.files.lock().insert(self.key, self.data.freeze());
+        Ok(())
+    }
+
+    async fn abort(self) -> IoResult<()> {
+        Ok(())
+    }
+}
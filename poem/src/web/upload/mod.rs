@@ -0,0 +1,407 @@
+//! Streaming uploads to the storages.
+//!
+//! An [`UploadSink`] is a destination of the uploads, such as a local
+//! directory ([`LocalDirSink`]), the memory ([`MemorySink`]) or a
+//! S3-compatible object storage ([`S3Sink`], requires the `upload-s3`
+//! feature).
+//!
+//! [`Field::upload`](crate::web::Field::upload) streams a multipart field to
+//! a sink while computing the size, the SHA-256 digest and the sniffed content
+//! type, and enforces the [`UploadLimits`] before the whole field is received.
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     handler,
+//!     web::{
+//!         upload::{LocalDirSink, UploadLimits},
+//!         Data, Json, Multipart,
+//!     },
+//!     EndpointExt, Result,
+//! };
+//!
+//! #[handler]
+//! async fn upload(
+//!     mut multipart: Multipart,
+//!     sink: Data<&LocalDirSink>,
+//! ) -> Result<Json<Vec<String>>> {
+//!     let limits = UploadLimits::new()
+//!         .max_size(10 * 1024 * 1024)
+//!         .allowed_content_types(["image/*"]);
+//!     let mut digests = Vec::new();
+//!     let mut n = 0;
+//!     while let Some(field) = multipart.next_field().await? {
+//!         n += 1;
+//!         let file = field.upload(*sink, &format!("{}.bin", n), &limits).await?;
+//!         digests.push(file.sha256);
+//!     }
+//!     Ok(Json(digests))
+//! }
+//!
+//! let app = upload.data(LocalDirSink::new("./uploads"));
+//! ```
+
+mod local;
+mod memory;
+#[cfg(feature = "upload-s3")]
+mod s3;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[cfg(feature = "upload-s3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "upload-s3")))]
+pub use self::s3::{S3Sink, S3Writer};
+pub use self::{
+    local::{LocalDirSink, LocalDirWriter},
+    memory::{MemorySink, MemoryWriter},
+};
+use crate::{error::UploadError, web::multipart::content_type_matches};
+
+/// The number of the leading bytes used to sniff the content type.
+const SNIFF_LEN: usize = 8192;
+
+/// The content type of the uploads which cannot be sniffed and have no
+/// declared content type.
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// A destination of the uploads.
+#[async_trait::async_trait]
+pub trait UploadSink: Send + Sync {
+    /// The writer of an upload.
+    type Writer: UploadWriter;
+
+    /// Starts to write an upload with the key.
+    async fn create(&self, key: &str, content_type: &str) -> std::io::Result<Self::Writer>;
+}
+
+/// The writer of an upload, which is created by [`UploadSink::create`].
+///
+/// The upload must not be visible to the readers of the sink until
+/// [`UploadWriter::finish`] is called.
+#[async_trait::async_trait]
+pub trait UploadWriter: Send + Sized {
+    /// Writes a chunk of the upload.
+    async fn write(&mut self, data: Bytes) -> std::io::Result<()>;
+
+    /// Completes the upload.
+    async fn finish(self) -> std::io::Result<()>;
+
+    /// Discards the upload.
+    async fn abort(self) -> std::io::Result<()>;
+}
+
+/// The limits of an upload, which are checked while the upload is being
+/// streamed.
+#[derive(Debug, Default, Clone)]
+pub struct UploadLimits {
+    max_size: Option<u64>,
+    allowed_content_types: Option<Vec<String>>,
+}
+
+impl UploadLimits {
+    /// Create an `UploadLimits` without any limits.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum size of the upload in bytes.
+    #[must_use]
+    pub fn max_size(self, size: u64) -> Self {
+        Self {
+            max_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Sets the allowed content types of the upload, such as `image/png` or
+    /// `image/*`.
+    ///
+    /// The content type is sniffed from the leading bytes of the upload, and
+    /// the declared content type is used only if it cannot be sniffed.
+    #[must_use]
+    pub fn allowed_content_types<I, T>(self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            allowed_content_types: Some(
+                content_types
+                    .into_iter()
+                    .map(|content_type| content_type.into().to_ascii_lowercase())
+                    .collect(),
+            ),
+            ..self
+        }
+    }
+}
+
+/// The information of a completed upload.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UploadedFile {
+    /// The key of the upload in the sink.
+    pub key: String,
+    /// The file name found in the `Content-Disposition` header of the
+    /// multipart field.
+    pub file_name: Option<String>,
+    /// The content type, which is sniffed from the leading bytes or declared
+    /// by the client.
+    pub content_type: String,
+    /// The size in bytes.
+    pub size: u64,
+    /// The hex-encoded SHA-256 digest.
+    pub sha256: String,
+}
+
+/// Streams the data from the reader to the [`UploadSink`] with the key.
+///
+/// The leading bytes are buffered to sniff the content type before the upload
+/// is created in the sink. If any limit is exceeded or the reader fails, the
+/// upload is aborted and nothing is left in the sink.
+pub async fn upload_reader<S, R>(
+    sink: &S,
+    key: &str,
+    reader: R,
+    content_type: Option<&str>,
+    limits: &UploadLimits,
+) -> Result<UploadedFile, UploadError>
+where
+    S: UploadSink + ?Sized,
+    R: AsyncRead + Send,
+{
+    tokio::pin!(reader);
+
+    let mut size = 0u64;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    // Reads the leading bytes to sniff the content type.
+    let mut head = Vec::new();
+    let mut eof = false;
+    while head.len() < SNIFF_LEN {
+        let n = read_chunk(&mut reader, &mut buf, &mut size, limits).await?;
+        if n == 0 {
+            eof = true;
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let content_type = infer::get(&head)
+        .map(|ty| ty.mime_type())
+        .or(content_type)
+        .unwrap_or(DEFAULT_CONTENT_TYPE)
+        .to_string();
+    if let Some(allowed_content_types) = &limits.allowed_content_types {
+        if !content_type_matches(allowed_content_types, &content_type) {
+            return Err(UploadError::ContentTypeNotAllowed(content_type));
+        }
+    }
+
+    let mut writer = sink
+        .create(key, &content_type)
+        .await
+        .map_err(UploadError::Sink)?;
+    let res = async {
+        hasher.update(&head);
+        writer
+            .write(Bytes::from(head))
+            .await
+            .map_err(UploadError::Sink)?;
+
+        if !eof {
+            loop {
+                let n = read_chunk(&mut reader, &mut buf, &mut size, limits).await?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                writer
+                    .write(Bytes::copy_from_slice(&buf[..n]))
+                    .await
+                    .map_err(UploadError::Sink)?;
+            }
+        }
+
+        Ok::<_, UploadError>(())
+    }
+    .await;
+
+    match res {
+        Ok(()) => writer.finish().await.map_err(UploadError::Sink)?,
+        Err(err) => {
+            let _ = writer.abort().await;
+            return Err(err);
+        }
+    }
+
+    Ok(UploadedFile {
+        key: key.to_string(),
+        file_name: None,
+        content_type,
+        size,
+        sha256: hex::encode(hasher.finalize()),
+    })
+}
+
+async fn read_chunk<R: AsyncRead>(
+    reader: &mut std::pin::Pin<&mut R>,
+    buf: &mut [u8],
+    size: &mut u64,
+    limits: &UploadLimits,
+) -> Result<usize, UploadError> {
+    let n = reader.read(buf).await.map_err(UploadError::Read)?;
+    *size += n as u64;
+    if let Some(max_size) = limits.max_size {
+        if *size > max_size {
+            return Err(UploadError::TooLarge(max_size));
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler,
+        http::StatusCode,
+        test::TestClient,
+        web::{Data, Json, Multipart},
+        EndpointExt, Result,
+    };
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[tokio::test]
+    async fn upload() {
+        let sink = MemorySink::new();
+        let file = upload_reader(&sink, "a", &b"hello"[..], None, &UploadLimits::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            file,
+            UploadedFile {
+                key: "a".to_string(),
+                file_name: None,
+                content_type: "application/octet-stream".to_string(),
+                size: 5,
+                sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    .to_string(),
+            }
+        );
+        assert_eq!(sink.get("a").unwrap(), Bytes::from_static(b"hello"));
+
+        // The data larger than the sniffing buffer.
+        let data = vec![1; SNIFF_LEN * 10 + 1];
+        let file = upload_reader(
+            &sink,
+            "b",
+            &data[..],
+            Some("text/plain"),
+            &UploadLimits::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.size, data.len() as u64);
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.sha256, hex::encode(Sha256::digest(&data)));
+        assert_eq!(sink.get("b").unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let sink = MemorySink::new();
+
+        // The limit is exceeded after the upload is created in the sink.
+        let reader = tokio_util::io::StreamReader::new(futures_util::stream::iter(
+            (0..10).map(|_| Ok::<_, std::io::Error>(Bytes::from(vec![0; SNIFF_LEN]))),
+        ));
+        let err = upload_reader(
+            &sink,
+            "a",
+            reader,
+            None,
+            &UploadLimits::new().max_size(SNIFF_LEN as u64 * 5),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, UploadError::TooLarge(_)));
+        assert!(sink.get("a").is_none());
+
+        // The sniffed content type is used instead of the declared one.
+        let limits = UploadLimits::new().allowed_content_types(["image/*"]);
+        let file = upload_reader(&sink, "a", PNG, Some("text/plain"), &limits)
+            .await
+            .unwrap();
+        assert_eq!(file.content_type, "image/png");
+
+        let err = upload_reader(&sink, "b", &b"hello"[..], Some("text/plain"), &limits)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UploadError::ContentTypeNotAllowed(_)));
+        let err = upload_reader(
+            &sink,
+            "b",
+            &b"%PDF-1.5 hello"[..],
+            Some("image/png"),
+            &limits,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(err, UploadError::ContentTypeNotAllowed(content_type) if content_type == "application/pdf")
+        );
+        assert!(sink.get("b").is_none());
+    }
+
+    #[tokio::test]
+    async fn upload_field() {
+        #[handler(internal)]
+        async fn index(
+            mut multipart: Multipart,
+            sink: Data<&MemorySink>,
+        ) -> Result<Json<Vec<UploadedFile>>> {
+            let limits = UploadLimits::new().max_size(16);
+            let mut files = Vec::new();
+            while let Some(field) = multipart.next_field().await? {
+                let key = field.name().unwrap().to_string();
+                files.push(field.upload(*sink, &key, &limits).await?);
+            }
+            Ok(Json(files))
+        }
+
+        let sink = MemorySink::new();
+        let cli = TestClient::new(index.data(sink.clone()));
+
+        let resp = cli
+            .post("/")
+            .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+            .body("--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"a\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--X-BOUNDARY--\r\n")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_json(&[serde_json::json!({
+            "key": "a",
+            "file_name": "a.txt",
+            "content_type": "text/plain",
+            "size": 5,
+            "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        })])
+        .await;
+        assert_eq!(sink.get("a").unwrap(), Bytes::from_static(b"hello"));
+
+        let resp = cli
+            .post("/")
+            .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+            .body("--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"b\"\r\n\r\n0123456789abcdefg\r\n--X-BOUNDARY--\r\n")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(sink.get("b").is_none());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig as TungsteniteConfig;
+
+use super::{
+    deflate::{Deflate, DeflateParams},
+    io::WebSocketIo,
+};
+use crate::{http::HeaderValue, Upgraded};
+
+/// The configuration of the websocket connections, see
+/// [`WebSocket::config`](crate::web::websocket::WebSocket::config).
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     web::websocket::{WebSocket, WebSocketConfig},
+///     IntoResponse, Route,
+/// };
+///
+/// #[handler]
+/// async fn index(ws: WebSocket) -> impl IntoResponse {
+///     ws.config(
+///         WebSocketConfig::new()
+///             .max_message_size(1024 * 1024)
+///             .permessage_deflate(true)
+///             .allow_origin("https://example.com"),
+///     )
+///     .on_upgrade(|socket| async move {
+///         // ...
+///     })
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebSocketConfig {
+    max_send_queue: Option<usize>,
+    write_buffer_size: usize,
+    max_message_size: Option<usize>,
+    max_frame_size: Option<usize>,
+    accept_unmasked_frames: bool,
+    permessage_deflate: bool,
+    allow_origins: HashSet<HeaderValue>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        let config = TungsteniteConfig::default();
+        Self {
+            max_send_queue: config.max_send_queue,
+            write_buffer_size: 128 * 1024,
+            max_message_size: config.max_message_size,
+            max_frame_size: config.max_frame_size,
+            accept_unmasked_frames: config.accept_unmasked_frames,
+            permessage_deflate: false,
+            allow_origins: HashSet::new(),
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Create a `WebSocketConfig`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum number of the messages waiting to be written to the
+    /// connection, and sending more messages returns an error until the
+    /// queue is flushed.
+    ///
+    /// `None` means no limit. Default is `None`.
+    #[must_use]
+    pub fn max_send_queue(self, size: impl Into<Option<usize>>) -> Self {
+        Self {
+            max_send_queue: size.into(),
+            ..self
+        }
+    }
+
+    /// Sets the size of the buffer for the outgoing frames, they are written
+    /// to the connection when the buffer is full or the stream is flushed.
+    ///
+    /// `0` means the frames are always written immediately. Default is
+    /// `128 KiB`.
+    #[must_use]
+    pub fn write_buffer_size(self, size: usize) -> Self {
+        Self {
+            write_buffer_size: size,
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of a message, the connection is closed if the
+    /// client sends a larger message.
+    ///
+    /// `None` means no limit. Default is `64 MiB`.
+    #[must_use]
+    pub fn max_message_size(self, size: impl Into<Option<usize>>) -> Self {
+        Self {
+            max_message_size: size.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum payload size of a single frame, the connection is
+    /// closed if the client sends a larger frame.
+    ///
+    /// `None` means no limit. Default is `16 MiB`.
+    #[must_use]
+    pub fn max_frame_size(self, size: impl Into<Option<usize>>) -> Self {
+        Self {
+            max_frame_size: size.into(),
+            ..self
+        }
+    }
+
+    /// Sets whether to accept the unmasked frames from the client, which is
+    /// rejected by [RFC 6455](https://datatracker.ietf.org/doc/html/rfc6455#section-5.1).
+    ///
+    /// Default is `false`.
+    #[must_use]
+    pub fn accept_unmasked_frames(self, accept: bool) -> Self {
+        Self {
+            accept_unmasked_frames: accept,
+            ..self
+        }
+    }
+
+    /// Sets whether to enable the `permessage-deflate` extension, see
+    /// [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+    ///
+    /// If it is enabled and the client offers the extension, the messages are
+    /// compressed. The offers which limit the window size of the server are
+    /// declined, and the size limits apply to the decompressed messages.
+    ///
+    /// Default is `false`.
+    #[must_use]
+    pub fn permessage_deflate(self, enable: bool) -> Self {
+        Self {
+            permessage_deflate: enable,
+            ..self
+        }
+    }
+
+    /// Add an allowed origin.
+    ///
+    /// If any origin is allowed, the upgrade requests with an `Origin` header
+    /// which is not allowed are rejected with
+    /// [`WebSocketError::OriginNotAllowed`](crate::error::WebSocketError::OriginNotAllowed).
+    /// The requests without the `Origin` header, which are not sent by the
+    /// browsers, are accepted.
+    ///
+    /// NOTE: Default is allow any origin.
+    #[must_use]
+    pub fn allow_origin<T>(mut self, origin: T) -> Self
+    where
+        HeaderValue: TryFrom<T>,
+    {
+        let origin = match <HeaderValue as TryFrom<T>>::try_from(origin) {
+            Ok(origin) => origin,
+            Err(_) => panic!("illegal origin"),
+        };
+        self.allow_origins.insert(origin);
+        self
+    }
+
+    /// Add many allowed origins.
+    #[must_use]
+    pub fn allow_origins<I, T>(self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        HeaderValue: TryFrom<T>,
+    {
+        origins
+            .into_iter()
+            .fold(self, |config, origin| config.allow_origin(origin))
+    }
+
+    pub(crate) fn is_allowed_origin(&self, origin: Option<&HeaderValue>) -> bool {
+        match origin {
+            Some(origin) if !self.allow_origins.is_empty() => self.allow_origins.contains(origin),
+            _ => true,
+        }
+    }
+
+    pub(crate) fn is_permessage_deflate(&self) -> bool {
+        self.permessage_deflate
+    }
+
+    pub(crate) fn create_io(
+        &self,
+        upgraded: Upgraded,
+        deflate: Option<DeflateParams>,
+    ) -> WebSocketIo {
+        WebSocketIo::new(
+            upgraded,
+            deflate.map(Deflate::new),
+            self.write_buffer_size,
+            self.max_frame_size,
+            self.max_message_size,
+        )
+    }
+
+    pub(crate) fn protocol_config(&self) -> TungsteniteConfig {
+        TungsteniteConfig {
+            max_send_queue: self.max_send_queue,
+            max_message_size: self.max_message_size,
+            max_frame_size: self.max_frame_size,
+            accept_unmasked_frames: self.accept_unmasked_frames,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::http::HeaderValue;
+
+const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// The trailing bytes of a deflate block flushed with `Z_SYNC_FLUSH`, which
+/// are removed from the end of the compressed messages.
+const SYNC_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The parameters of the `permessage-deflate` extension accepted by the
+/// server, see [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DeflateParams {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    server_max_window_bits: bool,
+}
+
+impl DeflateParams {
+    /// Returns the first acceptable `permessage-deflate` offer in the
+    /// `Sec-WebSocket-Extensions` headers of the request.
+    ///
+    /// The offers which limit the LZ77 window of the server are declined,
+    /// because the compressor always uses the 32KiB window.
+    pub(crate) fn negotiate<'a>(headers: impl Iterator<Item = &'a HeaderValue>) -> Option<Self> {
+        headers
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(Self::parse_offer)
+    }
+
+    fn parse_offer(offer: &str) -> Option<Self> {
+        let mut items = offer.split(';').map(str::trim);
+        if !items.next()?.eq_ignore_ascii_case(EXTENSION_NAME) {
+            return None;
+        }
+
+        let mut params = Self::default();
+        let mut client_max_window_bits = false;
+        for item in items {
+            let (name, value) = match item.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (item, None),
+            };
+            let (seen, valid) = match name {
+                "server_no_context_takeover" => {
+                    (&mut params.server_no_context_takeover, value.is_none())
+                }
+                "client_no_context_takeover" => {
+                    (&mut params.client_no_context_takeover, value.is_none())
+                }
+                "server_max_window_bits" => {
+                    (&mut params.server_max_window_bits, value == Some("15"))
+                }
+                "client_max_window_bits" => (
+                    &mut client_max_window_bits,
+                    match value {
+                        Some(value) => {
+                            matches!(value.parse::<u8>(), Ok(bits) if (8..=15).contains(&bits))
+                        }
+                        None => true,
+                    },
+                ),
+                _ => return None,
+            };
+            if *seen || !valid {
+                return None;
+            }
+            *seen = true;
+        }
+        Some(params)
+    }
+
+    /// Returns the value of the `Sec-WebSocket-Extensions` header of the
+    /// response.
+    pub(crate) fn to_header_value(self) -> HeaderValue {
+        let mut value = EXTENSION_NAME.to_string();
+        if self.server_no_context_takeover {
+            value.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; client_no_context_takeover");
+        }
+        if self.server_max_window_bits {
+            value.push_str("; server_max_window_bits=15");
+        }
+        HeaderValue::from_str(&value).unwrap()
+    }
+}
+
+/// The compressor and decompressor of a connection.
+pub(crate) struct Deflate {
+    params: DeflateParams,
+    compress: Compress,
+    decompress: Decompress,
+}
+
+impl Deflate {
+    pub(crate) fn new(params: DeflateParams) -> Self {
+        Self {
+            params,
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+        }
+    }
+
+    /// Compresses the payload of a frame, `is_final` is `true` if it is the
+    /// last frame of the message.
+    pub(crate) fn compress(&mut self, mut input: &[u8], is_final: bool) -> IoResult<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() / 2 + 64);
+        loop {
+            let total_in = self.compress.total_in();
+            output.reserve(4096);
+            self.compress
+                .compress_vec(input, &mut output, FlushCompress::Sync)
+                .map_err(IoError::other)?;
+            input = &input[(self.compress.total_in() - total_in) as usize..];
+            if input.is_empty() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if is_final {
+            if output.ends_with(&SYNC_TAIL) {
+                output.truncate(output.len() - SYNC_TAIL.len());
+            }
+            if self.params.server_no_context_takeover {
+                self.compress.reset();
+            }
+        }
+        Ok(output)
+    }
+
+    /// Decompresses the payload of a frame, `is_final` is `true` if it is the
+    /// last frame of the message.
+    ///
+    /// Returns an error if the decompressed payload is larger than `limit`.
+    pub(crate) fn decompress(
+        &mut self,
+        input: &[u8],
+        is_final: bool,
+        limit: usize,
+    ) -> IoResult<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut buf = [0; 8192];
+        let inputs: &[&[u8]] = if is_final {
+            &[input, &SYNC_TAIL]
+        } else {
+            &[input]
+        };
+
+        'inputs: for mut input in inputs.iter().copied() {
+            loop {
+                let (total_in, total_out) =
+                    (self.decompress.total_in(), self.decompress.total_out());
+                let status = self
+                    .decompress
+                    .decompress(input, &mut buf, FlushDecompress::Sync)
+                    .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+                let consumed = (self.decompress.total_in() - total_in) as usize;
+                let produced = (self.decompress.total_out() - total_out) as usize;
+                input = &input[consumed..];
+
+                if output.len() + produced > limit {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        "decompressed message is too large",
+                    ));
+                }
+                output.extend_from_slice(&buf[..produced]);
+
+                if status == Status::StreamEnd {
+                    // The sender finished the deflate stream, the following
+                    // messages start a new one.
+                    self.decompress.reset(false);
+                    break 'inputs;
+                }
+                if input.is_empty() && produced < buf.len() {
+                    break;
+                }
+                if consumed == 0 && produced == 0 {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        "invalid compressed message",
+                    ));
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiate(value: &'static str) -> Option<DeflateParams> {
+        DeflateParams::negotiate([HeaderValue::from_static(value)].iter())
+    }
+
+    #[test]
+    fn negotiate_offers() {
+        assert_eq!(negotiate("x-webkit-deflate-frame"), None);
+        assert_eq!(
+            negotiate("permessage-deflate; client_max_window_bits"),
+            Some(DeflateParams::default())
+        );
+        assert_eq!(
+            negotiate(
+                "permessage-deflate; server_max_window_bits=10, permessage-deflate; \
+                 server_no_context_takeover; server_max_window_bits=\"15\""
+            ),
+            Some(DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: false,
+                server_max_window_bits: true,
+            })
+        );
+        assert_eq!(
+            negotiate("permessage-deflate; client_no_context_takeover; client_no_context_takeover"),
+            None
+        );
+        assert_eq!(negotiate("permessage-deflate; unknown"), None);
+        assert_eq!(
+            negotiate("permessage-deflate; client_max_window_bits=16"),
+            None
+        );
+
+        assert_eq!(
+            DeflateParams {
+                server_no_context_takeover: true,
+                client_no_context_takeover: true,
+                server_max_window_bits: true,
+            }
+            .to_header_value(),
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; \
+             server_max_window_bits=15"
+        );
+    }
+
+    #[test]
+    fn compress_messages() {
+        // https://datatracker.ietf.org/doc/html/rfc7692#section-7.2.3.1
+        let mut deflate = Deflate::new(DeflateParams::default());
+        assert_eq!(
+            deflate
+                .decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], true, 1024)
+                .unwrap(),
+            b"Hello"
+        );
+
+        let data = "hello world ".repeat(100);
+        for params in [
+            DeflateParams::default(),
+            DeflateParams {
+                server_no_context_takeover: true,
+                ..DeflateParams::default()
+            },
+        ] {
+            let mut sender = Deflate::new(params);
+            let mut receiver = Deflate::new(params);
+            for _ in 0..3 {
+                let compressed = sender.compress(data.as_bytes(), true).unwrap();
+                assert!(compressed.len() < data.len());
+                assert!(!compressed.ends_with(&SYNC_TAIL));
+                assert_eq!(
+                    receiver.decompress(&compressed, true, 4096).unwrap(),
+                    data.as_bytes()
+                );
+            }
+
+            let first = sender.compress(b"hello ", false).unwrap();
+            let last = sender.compress(b"world", true).unwrap();
+            assert_eq!(receiver.decompress(&first, false, 4096).unwrap(), b"hello ");
+            assert_eq!(receiver.decompress(&last, true, 4096).unwrap(), b"world");
+        }
+
+        let mut sender = Deflate::new(DeflateParams::default());
+        let compressed = sender.compress(data.as_bytes(), true).unwrap();
+        let mut receiver = Deflate::new(DeflateParams::default());
+        assert_eq!(
+            receiver
+                .decompress(&compressed, true, 100)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
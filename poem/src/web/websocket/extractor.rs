@@ -3,14 +3,14 @@ use std::{borrow::Cow, future::Future};
 use headers::HeaderMapExt;
 use tokio_tungstenite::tungstenite::protocol::Role;
 
-use super::{utils::sign, WebSocketStream};
+use super::{deflate::DeflateParams, utils::sign, WebSocketConfig, WebSocketStream};
 use crate::{
     error::WebSocketError,
     http::{
         header::{self, HeaderValue},
         Method, StatusCode,
     },
-    Body, FromRequest, IntoResponse, OnUpgrade, Request, RequestBody, Response, Result,
+    Body, Error, FromRequest, IntoResponse, OnUpgrade, Request, RequestBody, Response, Result,
 };
 
 /// An extractor that can accept websocket connections.
@@ -23,6 +23,9 @@ pub struct WebSocket {
     on_upgrade: OnUpgrade,
     protocols: Option<Box<[Cow<'static, str>]>>,
     sec_websocket_protocol: Option<HeaderValue>,
+    origin: Option<HeaderValue>,
+    deflate: Option<DeflateParams>,
+    config: WebSocketConfig,
 }
 
 impl WebSocket {
@@ -51,12 +54,21 @@ impl WebSocket {
             .ok_or(WebSocketError::InvalidProtocol)?;
 
         let sec_websocket_protocol = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+        let origin = req.headers().get(header::ORIGIN).cloned();
+        let deflate = DeflateParams::negotiate(
+            req.headers()
+                .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+                .iter(),
+        );
 
         Ok(Self {
             key,
             on_upgrade: req.take_upgrade()?,
             protocols: None,
             sec_websocket_protocol,
+            origin,
+            deflate,
+            config: WebSocketConfig::default(),
         })
     }
 }
@@ -105,6 +117,12 @@ impl WebSocket {
         self
     }
 
+    /// Sets the configuration of the websocket connection.
+    #[must_use]
+    pub fn config(self, config: WebSocketConfig) -> Self {
+        Self { config, ..self }
+    }
+
     /// Finalize upgrading the connection and call the provided `callback` with
     /// the stream.
     ///
@@ -134,6 +152,14 @@ where
     Fut: Future + Send + 'static,
 {
     fn into_response(self) -> Response {
+        if !self
+            .websocket
+            .config
+            .is_allowed_origin(self.websocket.origin.as_ref())
+        {
+            return Error::from(WebSocketError::OriginNotAllowed).as_response();
+        }
+
         // check requested protocols
         let protocol = self
             .websocket
@@ -164,7 +190,16 @@ where
             );
         }
 
+        let deflate = self
+            .websocket
+            .deflate
+            .filter(|_| self.websocket.config.is_permessage_deflate());
+        if let Some(deflate) = deflate {
+            builder = builder.header(header::SEC_WEBSOCKET_EXTENSIONS, deflate.to_header_value());
+        }
+
         let resp = builder.body(Body::empty());
+        let config = self.websocket.config;
 
         tokio::spawn(async move {
             let upgraded = match self.websocket.on_upgrade.await {
@@ -172,9 +207,12 @@ where
                 Err(_) => return,
             };
 
-            let stream =
-                tokio_tungstenite::WebSocketStream::from_raw_socket(upgraded, Role::Server, None)
-                    .await;
+            let stream = tokio_tungstenite::WebSocketStream::from_raw_socket(
+                config.create_io(upgraded, deflate),
+                Role::Server,
+                Some(config.protocol_config()),
+            )
+            .await;
             (self.callback)(WebSocketStream::new(stream)).await;
         });
 
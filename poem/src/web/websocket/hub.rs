@@ -0,0 +1,848 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    task::AtomicWaker,
+    SinkExt, Stream, StreamExt,
+};
+use parking_lot::Mutex;
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Notify,
+    },
+    time::Interval,
+};
+
+use super::{CloseCode, Message, WebSocketStream};
+
+/// The identifier of a connection in the [`WsHub`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ConnectionId(u64);
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The action when the outgoing queue of a connection is full, see
+/// [`WsHub::slow_consumer_policy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SlowConsumerPolicy {
+    /// Drops the message for the connection.
+    DropMessage,
+
+    /// Removes the connection from the hub, and closes it with
+    /// [`CloseCode::Policy`].
+    Disconnect,
+}
+
+/// The state shared by a connection and its writer task.
+struct Shared {
+    last_seen: Mutex<Instant>,
+    close: Mutex<Option<Message>>,
+    notify: Notify,
+    closed: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Shared {
+    fn new() -> Self {
+        Self {
+            last_seen: Mutex::new(Instant::now()),
+            close: Mutex::new(None),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Asks the writer task to send the close message and stop.
+    fn close(&self, msg: Message) {
+        self.close.lock().get_or_insert(msg);
+        self.notify.notify_one();
+    }
+
+    /// Marks the connection as closed, which ends the incoming messages.
+    fn finish(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.waker.wake();
+    }
+}
+
+struct Connection<T> {
+    info: T,
+    sender: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+    rooms: HashSet<String>,
+}
+
+struct State<T> {
+    next_id: u64,
+    connections: HashMap<ConnectionId, Connection<T>>,
+    rooms: HashMap<String, HashSet<ConnectionId>>,
+}
+
+impl<T> State<T> {
+    fn remove(&mut self, id: ConnectionId) -> Option<Connection<T>> {
+        let conn = self.connections.remove(&id)?;
+        for room in &conn.rooms {
+            self.leave_room(room, id);
+        }
+        Some(conn)
+    }
+
+    fn leave_room(&mut self, room: &str, id: ConnectionId) {
+        if let Some(members) = self.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+/// A hub of websocket connections, which supports the named rooms,
+/// broadcasting and presence lists.
+///
+/// Each connection has a bounded outgoing queue which is written by a
+/// background task, so broadcasting never waits for the slow connections,
+/// see [`WsHub::slow_consumer_policy`]. The task also sends the heartbeat
+/// pings, and closes the connection if nothing is received from the client
+/// within the idle timeout.
+///
+/// The type parameter `T` is the information attached to each connection,
+/// such as the user name, which is returned in the presence lists.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use poem::{
+///     get, handler,
+///     web::{
+///         websocket::{Message, WebSocket, WsHub},
+///         Data, Path,
+///     },
+///     EndpointExt, IntoResponse, Route,
+/// };
+///
+/// #[handler]
+/// fn ws(Path(name): Path<String>, ws: WebSocket, hub: Data<&WsHub<String>>) -> impl IntoResponse {
+///     let hub = hub.clone();
+///     ws.on_upgrade(move |socket| async move {
+///         let mut conn = hub.connect_with(socket, name.clone());
+///         conn.join("lobby");
+///
+///         while let Some(Ok(msg)) = conn.next().await {
+///             if let Message::Text(text) = msg {
+///                 hub.broadcast_to_except(
+///                     "lobby",
+///                     conn.id(),
+///                     Message::text(format!("{}: {}", name, text)),
+///                 );
+///             }
+///         }
+///     })
+/// }
+///
+/// let app = Route::new()
+///     .at("/ws/:name", get(ws))
+///     .data(WsHub::<String>::new());
+/// ```
+pub struct WsHub<T = ()> {
+    queue_size: usize,
+    policy: SlowConsumerPolicy,
+    heartbeat_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Clone for WsHub<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue_size: self.queue_size,
+            policy: self.policy,
+            heartbeat_interval: self.heartbeat_interval,
+            idle_timeout: self.idle_timeout,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Default for WsHub<T> {
+    fn default() -> Self {
+        Self {
+            queue_size: 64,
+            policy: SlowConsumerPolicy::DropMessage,
+            heartbeat_interval: Some(Duration::from_secs(30)),
+            idle_timeout: Some(Duration::from_secs(90)),
+            state: Arc::new(Mutex::new(State {
+                next_id: 0,
+                connections: HashMap::new(),
+                rooms: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl<T> WsHub<T> {
+    /// Create a `WsHub`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the size of the outgoing queue of each connection. Default is
+    /// `64`.
+    #[must_use]
+    pub fn queue_size(self, size: usize) -> Self {
+        Self {
+            queue_size: size.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the action when the outgoing queue of a connection is full.
+    /// Default is [`SlowConsumerPolicy::DropMessage`].
+    #[must_use]
+    pub fn slow_consumer_policy(self, policy: SlowConsumerPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Sets the interval of sending the heartbeat pings. Default is `30`
+    /// seconds.
+    #[must_use]
+    pub fn heartbeat_interval(self, interval: impl Into<Option<Duration>>) -> Self {
+        Self {
+            heartbeat_interval: interval.into(),
+            ..self
+        }
+    }
+
+    /// Sets the idle timeout, the connection is closed with
+    /// [`CloseCode::Away`] if nothing (including the pongs) is received from
+    /// the client within the specified duration. Default is `90` seconds.
+    ///
+    /// NOTE: The incoming messages are only received when the
+    /// [`WsConnection`] is polled.
+    #[must_use]
+    pub fn idle_timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            idle_timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Adds a connection to the hub with the default information.
+    pub fn connect(&self, stream: WebSocketStream) -> WsConnection<T>
+    where
+        T: Default + Send + 'static,
+    {
+        self.connect_with(stream, T::default())
+    }
+
+    /// Adds a connection to the hub with the specified information.
+    ///
+    /// The connection is removed from the hub when the returned
+    /// [`WsConnection`] is dropped.
+    pub fn connect_with(&self, stream: WebSocketStream, info: T) -> WsConnection<T>
+    where
+        T: Send + 'static,
+    {
+        let (sink, stream) = stream.split();
+        let (sender, receiver) = mpsc::channel(self.queue_size);
+        let shared = Arc::new(Shared::new());
+
+        let id = {
+            let mut state = self.state.lock();
+            let id = ConnectionId(state.next_id);
+            state.next_id += 1;
+            state.connections.insert(
+                id,
+                Connection {
+                    info,
+                    sender: sender.clone(),
+                    shared: shared.clone(),
+                    rooms: HashSet::new(),
+                },
+            );
+            id
+        };
+
+        tokio::spawn(run_writer(self.clone(), id, sink, receiver, shared.clone()));
+
+        WsConnection {
+            id,
+            hub: self.clone(),
+            stream,
+            sender,
+            shared,
+        }
+    }
+
+    /// Adds the connection to the room, the room is created if it does not
+    /// exist.
+    ///
+    /// Returns `false` if the connection does not exist.
+    pub fn join(&self, id: ConnectionId, room: impl Into<String>) -> bool {
+        let mut state = self.state.lock();
+        let room = room.into();
+        match state.connections.get_mut(&id) {
+            Some(conn) => {
+                conn.rooms.insert(room.clone());
+                state.rooms.entry(room).or_default().insert(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the connection from the room, the room is removed if it is
+    /// empty.
+    pub fn leave(&self, id: ConnectionId, room: &str) {
+        let mut state = self.state.lock();
+        if let Some(conn) = state.connections.get_mut(&id) {
+            if conn.rooms.remove(room) {
+                state.leave_room(room, id);
+            }
+        }
+    }
+
+    /// Removes the connection from the hub, and closes it with the specified
+    /// code and reason.
+    pub fn disconnect(&self, id: ConnectionId, code: CloseCode, reason: impl Into<String>) {
+        if let Some(conn) = self.state.lock().remove(id) {
+            conn.shared.close(Message::close_with(code, reason));
+        }
+    }
+
+    /// Sends a message to the connection.
+    ///
+    /// Returns `false` if the connection does not exist or the message is
+    /// dropped.
+    pub fn send_to(&self, id: ConnectionId, msg: Message) -> bool {
+        self.deliver(
+            |state| state.connections.contains_key(&id).then(|| vec![id]),
+            msg,
+        ) > 0
+    }
+
+    /// Sends a message to all connections, and returns the number of the
+    /// connections which received it.
+    pub fn broadcast(&self, msg: Message) -> usize {
+        self.deliver(
+            |state| Some(state.connections.keys().copied().collect()),
+            msg,
+        )
+    }
+
+    /// Sends a message to all connections except the specified one, and
+    /// returns the number of the connections which received it.
+    pub fn broadcast_except(&self, except: ConnectionId, msg: Message) -> usize {
+        self.deliver(
+            |state| {
+                Some(
+                    state
+                        .connections
+                        .keys()
+                        .copied()
+                        .filter(|id| *id != except)
+                        .collect(),
+                )
+            },
+            msg,
+        )
+    }
+
+    /// Sends a message to all connections in the room, and returns the number
+    /// of the connections which received it.
+    pub fn broadcast_to(&self, room: &str, msg: Message) -> usize {
+        self.deliver(
+            |state| Some(state.rooms.get(room)?.iter().copied().collect()),
+            msg,
+        )
+    }
+
+    /// Sends a message to all connections in the room except the specified
+    /// one, which is usually the sender, and returns the number of the
+    /// connections which received it.
+    pub fn broadcast_to_except(&self, room: &str, except: ConnectionId, msg: Message) -> usize {
+        self.deliver(
+            |state| {
+                Some(
+                    state
+                        .rooms
+                        .get(room)?
+                        .iter()
+                        .copied()
+                        .filter(|id| *id != except)
+                        .collect(),
+                )
+            },
+            msg,
+        )
+    }
+
+    /// Returns the names of all rooms.
+    pub fn rooms(&self) -> Vec<String> {
+        self.state.lock().rooms.keys().cloned().collect()
+    }
+
+    /// Returns the names of the rooms which the connection joined.
+    pub fn rooms_of(&self, id: ConnectionId) -> Vec<String> {
+        self.state
+            .lock()
+            .connections
+            .get(&id)
+            .map(|conn| conn.rooms.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the connections in the room with their information, sorted by
+    /// the order they connected.
+    pub fn members(&self, room: &str) -> Vec<(ConnectionId, T)>
+    where
+        T: Clone,
+    {
+        let state = self.state.lock();
+        let mut members = state
+            .rooms
+            .get(room)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| Some((*id, state.connections.get(id)?.info.clone())))
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(id, _)| *id);
+        members
+    }
+
+    /// Returns all connections with their information, sorted by the order
+    /// they connected.
+    pub fn connections(&self) -> Vec<(ConnectionId, T)>
+    where
+        T: Clone,
+    {
+        let mut connections = self
+            .state
+            .lock()
+            .connections
+            .iter()
+            .map(|(id, conn)| (*id, conn.info.clone()))
+            .collect::<Vec<_>>();
+        connections.sort_by_key(|(id, _)| *id);
+        connections
+    }
+
+    /// Enqueues the message to the connections, and applies the
+    /// [`SlowConsumerPolicy`] to the connections whose queue is full.
+    fn deliver(
+        &self,
+        targets: impl FnOnce(&State<T>) -> Option<Vec<ConnectionId>>,
+        msg: Message,
+    ) -> usize {
+        let mut state = self.state.lock();
+        let targets = match targets(&state) {
+            Some(targets) => targets,
+            None => return 0,
+        };
+        let mut delivered = 0;
+        let mut slow_consumers = Vec::new();
+
+        for id in targets {
+            if let Some(conn) = state.connections.get(&id) {
+                match conn.sender.try_send(msg.clone()) {
+                    Ok(()) => delivered += 1,
+                    Err(TrySendError::Full(_)) => {
+                        if self.policy == SlowConsumerPolicy::Disconnect {
+                            slow_consumers.push(id);
+                        }
+                    }
+                    Err(TrySendError::Closed(_)) => {}
+                }
+            }
+        }
+
+        for id in slow_consumers {
+            if let Some(conn) = state.remove(id) {
+                conn.shared
+                    .close(Message::close_with(CloseCode::Policy, "slow consumer"));
+            }
+        }
+
+        delivered
+    }
+
+    fn remove(&self, id: ConnectionId) {
+        self.state.lock().remove(id);
+    }
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Sends a message unless the connection is asked to close in the meantime.
+async fn send(
+    sink: &mut SplitSink<WebSocketStream, Message>,
+    shared: &Shared,
+    msg: Message,
+) -> bool {
+    tokio::select! {
+        res = sink.send(msg) => res.is_ok(),
+        _ = shared.notify.notified() => false,
+    }
+}
+
+async fn run_writer<T>(
+    hub: WsHub<T>,
+    id: ConnectionId,
+    mut sink: SplitSink<WebSocketStream, Message>,
+    mut receiver: mpsc::Receiver<Message>,
+    shared: Arc<Shared>,
+) {
+    let mut interval = hub
+        .heartbeat_interval
+        .or(hub.idle_timeout)
+        .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+
+    loop {
+        tokio::select! {
+            _ = shared.notify.notified() => break,
+            msg = receiver.recv() => match msg {
+                Some(msg) => {
+                    if !send(&mut sink, &shared, msg).await {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            _ = tick(&mut interval) => {
+                let last_seen = *shared.last_seen.lock();
+                if let Some(idle_timeout) = hub.idle_timeout {
+                    if last_seen.elapsed() >= idle_timeout {
+                        let _ = send(
+                            &mut sink,
+                            &shared,
+                            Message::close_with(CloseCode::Away, "idle timeout"),
+                        )
+                        .await;
+                        break;
+                    }
+                }
+                if hub.heartbeat_interval.is_some()
+                    && !send(&mut sink, &shared, Message::ping(Vec::new())).await
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    // The close notification may have been consumed by an interrupted `send`.
+    let msg = shared.close.lock().take();
+    if let Some(msg) = msg {
+        let _ = sink.send(msg).await;
+    }
+
+    hub.remove(id);
+    shared.finish();
+}
+
+/// A connection in the [`WsHub`], which implements [`Stream<Message>`] for
+/// receiving the incoming messages.
+///
+/// The outgoing messages are sent with [`WsConnection::send`] or the
+/// broadcasting methods of the [`WsHub`]. The stream ends when the connection
+/// is closed by the hub.
+pub struct WsConnection<T = ()> {
+    id: ConnectionId,
+    hub: WsHub<T>,
+    stream: SplitStream<WebSocketStream>,
+    sender: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+}
+
+impl<T> WsConnection<T> {
+    /// Returns the identifier of the connection.
+    #[inline]
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the hub of the connection.
+    #[inline]
+    pub fn hub(&self) -> &WsHub<T> {
+        &self.hub
+    }
+
+    /// Adds the connection to the room.
+    pub fn join(&self, room: impl Into<String>) {
+        self.hub.join(self.id, room);
+    }
+
+    /// Removes the connection from the room.
+    pub fn leave(&self, room: &str) {
+        self.hub.leave(self.id, room);
+    }
+
+    /// Sends a message to the connection, waits if the outgoing queue is
+    /// full.
+    pub async fn send(&self, msg: Message) -> IoResult<()> {
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Err(IoError::from(ErrorKind::BrokenPipe));
+        }
+        self.sender
+            .send(msg)
+            .await
+            .map_err(|_| IoError::from(ErrorKind::BrokenPipe))
+    }
+}
+
+impl<T> Stream for WsConnection<T> {
+    type Item = IoResult<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.shared.waker.register(cx.waker());
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(None);
+        }
+
+        let res = self.stream.poll_next_unpin(cx);
+        if let Poll::Ready(Some(Ok(_))) = &res {
+            *self.shared.last_seen.lock() = Instant::now();
+        }
+        res
+    }
+}
+
+impl<T> Drop for WsConnection<T> {
+    fn drop(&mut self) {
+        self.hub.remove(self.id);
+        self.shared.close(Message::close());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+    use super::*;
+    use crate::{
+        get, handler,
+        listener::{Acceptor, Listener, TcpListener},
+        web::{websocket::WebSocket, Data, Path},
+        EndpointExt, IntoResponse, Route, Server,
+    };
+
+    #[handler(internal)]
+    fn ws(Path(name): Path<String>, ws: WebSocket, hub: Data<&WsHub<String>>) -> impl IntoResponse {
+        let hub = hub.clone();
+        ws.on_upgrade(move |socket| async move {
+            let mut conn = hub.connect_with(socket, name.clone());
+            while let Some(Ok(msg)) = conn.next().await {
+                if let Message::Text(text) = msg {
+                    match text.split_once(' ') {
+                        Some(("join", room)) => conn.join(room),
+                        Some(("leave", room)) => conn.leave(room),
+                        Some((room, text)) => {
+                            hub.broadcast_to_except(
+                                room,
+                                conn.id(),
+                                Message::text(format!("{}: {}", name, text)),
+                            );
+                        }
+                        None => {
+                            hub.broadcast(Message::text(format!("{}: {}", name, text)));
+                        }
+                    }
+                    conn.send(Message::text("ok")).await.unwrap();
+                }
+            }
+        })
+    }
+
+    async fn start(hub: WsHub<String>) -> SocketAddr {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor
+            .local_addr()
+            .remove(0)
+            .as_socket_addr()
+            .cloned()
+            .unwrap();
+        let app = Route::new().at("/:name", get(ws)).data(hub);
+        tokio::spawn(async move {
+            let _ = Server::new_with_acceptor(acceptor).run(app).await;
+        });
+        addr
+    }
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn connect(addr: SocketAddr, name: &str) -> Client {
+        tokio_tungstenite::connect_async(format!("ws://{}/{}", addr, name))
+            .await
+            .unwrap()
+            .0
+    }
+
+    async fn request(client: &mut Client, text: &str) {
+        client
+            .send(ClientMessage::Text(text.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(next(client).await, ClientMessage::Text("ok".to_string()));
+    }
+
+    async fn next(client: &mut Client) -> ClientMessage {
+        tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn wait_for(f: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if f() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timeout");
+    }
+
+    #[tokio::test]
+    async fn rooms() {
+        let hub = WsHub::<String>::new();
+        let addr = start(hub.clone()).await;
+
+        let mut a = connect(addr, "a").await;
+        let mut b = connect(addr, "b").await;
+        let mut c = connect(addr, "c").await;
+        wait_for(|| hub.connections().len() == 3).await;
+
+        request(&mut a, "join room1").await;
+        request(&mut b, "join room1").await;
+        request(&mut c, "join room2").await;
+        let members = hub
+            .members("room1")
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(members, vec!["a".to_string(), "b".to_string()]);
+        let mut rooms = hub.rooms();
+        rooms.sort();
+        assert_eq!(rooms, vec!["room1".to_string(), "room2".to_string()]);
+
+        // Broadcast to the room except the sender.
+        request(&mut a, "room1 hello").await;
+        assert_eq!(
+            next(&mut b).await,
+            ClientMessage::Text("a: hello".to_string())
+        );
+
+        // Broadcast to all.
+        a.send(ClientMessage::Text("hi".to_string())).await.unwrap();
+        assert_eq!(next(&mut a).await, ClientMessage::Text("a: hi".to_string()));
+        assert_eq!(next(&mut a).await, ClientMessage::Text("ok".to_string()));
+        assert_eq!(next(&mut b).await, ClientMessage::Text("a: hi".to_string()));
+        assert_eq!(next(&mut c).await, ClientMessage::Text("a: hi".to_string()));
+
+        request(&mut c, "leave room2").await;
+        assert_eq!(hub.rooms(), vec!["room1".to_string()]);
+
+        drop(b);
+        wait_for(|| hub.members("room1").len() == 1).await;
+        assert_eq!(hub.connections().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn slow_consumer() {
+        let hub = WsHub::<String>::new()
+            .queue_size(1)
+            .slow_consumer_policy(SlowConsumerPolicy::Disconnect);
+        let addr = start(hub.clone()).await;
+
+        let mut a = connect(addr, "a").await;
+        wait_for(|| hub.connections().len() == 1).await;
+
+        // The writer task sends one message at most, and the queue is full
+        // after that.
+        let mut delivered = 0;
+        for _ in 0..100 {
+            if hub.broadcast(Message::binary(vec![0; 1024 * 1024])) == 0 {
+                break;
+            }
+            delivered += 1;
+        }
+        assert!(delivered < 100);
+        assert!(hub.connections().is_empty());
+
+        loop {
+            match next(&mut a).await {
+                ClientMessage::Binary(_) => {}
+                ClientMessage::Close(Some(frame)) => {
+                    assert_eq!(frame.code, CloseCode::Policy.into());
+                    break;
+                }
+                msg => panic!("unexpected message: {:?}", msg),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn heartbeat() {
+        let hub = WsHub::<String>::new()
+            .heartbeat_interval(Duration::from_millis(100))
+            .idle_timeout(None);
+        let addr = start(hub.clone()).await;
+
+        let mut a = connect(addr, "a").await;
+        assert!(matches!(next(&mut a).await, ClientMessage::Ping(_)));
+        assert!(matches!(next(&mut a).await, ClientMessage::Ping(_)));
+        assert_eq!(hub.connections().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() {
+        let hub = WsHub::<String>::new()
+            .heartbeat_interval(None)
+            .idle_timeout(Duration::from_millis(300));
+        let addr = start(hub.clone()).await;
+
+        let mut a = connect(addr, "a").await;
+        let mut b = connect(addr, "b").await;
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            request(&mut b, "join room").await;
+        }
+
+        assert!(matches!(next(&mut a).await, ClientMessage::Close(_)));
+        wait_for(|| hub.connections().len() == 1).await;
+        assert_eq!(hub.connections()[0].1, "b");
+    }
+}
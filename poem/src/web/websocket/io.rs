@@ -0,0 +1,302 @@
+use std::{
+    io::{Cursor, Error as IoError, ErrorKind, Result as IoResult},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::protocol::frame::{
+    coding::{Data, OpCode},
+    FrameHeader,
+};
+
+use super::deflate::Deflate;
+use crate::Upgraded;
+
+/// The IO of a websocket connection, which buffers the outgoing frames and
+/// implements the `permessage-deflate` extension below `tungstenite`.
+///
+/// The compressed frames are decompressed before they are read by
+/// `tungstenite`, and the frames written by `tungstenite` are compressed
+/// before they are written to the connection.
+pub(crate) struct WebSocketIo {
+    inner: Upgraded,
+    deflate: Option<Deflate>,
+    write_buffer_size: usize,
+    max_frame_size: Option<usize>,
+    max_message_size: Option<usize>,
+
+    /// The bytes read from the connection which are not processed.
+    read_buf: BytesMut,
+    /// The processed bytes to be read by `tungstenite`.
+    read_out: BytesMut,
+    /// The remaining payload size of the uncompressed frame being read.
+    read_passthrough: u64,
+    /// Whether the data message being read is compressed.
+    read_compressed: bool,
+    /// The decompressed size of the message being read.
+    read_message_size: usize,
+
+    /// The bytes written by `tungstenite` which are not processed.
+    write_in: BytesMut,
+    /// The bytes to be written to the connection.
+    write_buf: BytesMut,
+    /// Whether the data message being written is compressed.
+    write_compressed: bool,
+    /// Whether the buffered bytes must be written by the next flush.
+    flush: bool,
+}
+
+impl WebSocketIo {
+    pub(crate) fn new(
+        inner: Upgraded,
+        deflate: Option<Deflate>,
+        write_buffer_size: usize,
+        max_frame_size: Option<usize>,
+        max_message_size: Option<usize>,
+    ) -> Self {
+        Self {
+            inner,
+            deflate,
+            write_buffer_size,
+            max_frame_size,
+            max_message_size,
+            read_buf: BytesMut::new(),
+            read_out: BytesMut::new(),
+            read_passthrough: 0,
+            read_compressed: false,
+            read_message_size: 0,
+            write_in: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            write_compressed: false,
+            flush: false,
+        }
+    }
+
+    /// Makes the next flush write all the buffered frames to the connection.
+    pub(crate) fn request_flush(&mut self) {
+        self.flush = true;
+    }
+
+    /// Moves the frames in `read_buf` to `read_out`, and decompresses the
+    /// compressed frames.
+    fn process_read(&mut self) -> IoResult<()> {
+        loop {
+            if self.read_passthrough > 0 {
+                let len = self.read_passthrough.min(self.read_buf.len() as u64) as usize;
+                if len == 0 {
+                    return Ok(());
+                }
+                self.read_out
+                    .extend_from_slice(&self.read_buf.split_to(len));
+                self.read_passthrough -= len as u64;
+                continue;
+            }
+
+            let mut cursor = Cursor::new(&self.read_buf[..]);
+            let (header, len) = match FrameHeader::parse(&mut cursor).map_err(invalid_data)? {
+                Some(res) => res,
+                None => return Ok(()),
+            };
+            let header_len = cursor.position() as usize;
+
+            let compressed = match header.opcode {
+                OpCode::Data(Data::Text | Data::Binary) => {
+                    self.read_compressed = header.rsv1;
+                    self.read_message_size = 0;
+                    header.rsv1
+                }
+                OpCode::Data(Data::Continue) => self.read_compressed,
+                _ => false,
+            };
+            let deflate = match &mut self.deflate {
+                Some(deflate) if compressed => deflate,
+                _ => {
+                    // Let `tungstenite` check the uncompressed frames.
+                    self.read_out
+                        .extend_from_slice(&self.read_buf.split_to(header_len));
+                    self.read_passthrough = len;
+                    continue;
+                }
+            };
+
+            if matches!(self.max_frame_size, Some(max_frame_size) if len > max_frame_size as u64) {
+                return Err(IoError::new(ErrorKind::InvalidData, "frame is too large"));
+            }
+            if self.read_buf.len() < header_len + len as usize {
+                self.read_buf.reserve(header_len + len as usize);
+                return Ok(());
+            }
+
+            self.read_buf.advance(header_len);
+            let mut payload = self.read_buf.split_to(len as usize);
+            if let Some(mask) = header.mask {
+                apply_mask(&mut payload, mask);
+            }
+            let limit = self.max_message_size.unwrap_or(usize::MAX) - self.read_message_size;
+            let data = deflate.decompress(&payload, header.is_final, limit)?;
+            self.read_message_size += data.len();
+
+            // Split the decompressed payload to the frames which are not larger
+            // than `max_frame_size`, and mask them with a zero key for
+            // `tungstenite`.
+            let chunk_size = self.max_frame_size.unwrap_or(usize::MAX).max(1);
+            let mut chunks = data.chunks(chunk_size).peekable();
+            let mut opcode = header.opcode;
+            loop {
+                let chunk = chunks.next().unwrap_or_default();
+                let is_last = chunks.peek().is_none();
+                let frame_header = FrameHeader {
+                    is_final: header.is_final && is_last,
+                    opcode,
+                    mask: header.mask.map(|_| [0; 4]),
+                    ..FrameHeader::default()
+                };
+                write_frame(&mut self.read_out, &frame_header, chunk);
+                opcode = OpCode::Data(Data::Continue);
+                if is_last {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Moves the frames in `write_in` to `write_buf`, and compresses the
+    /// data frames.
+    fn process_write(&mut self) -> IoResult<()> {
+        loop {
+            let mut cursor = Cursor::new(&self.write_in[..]);
+            let (mut header, len) = match FrameHeader::parse(&mut cursor).map_err(invalid_data)? {
+                Some(res) => res,
+                None => return Ok(()),
+            };
+            let header_len = cursor.position() as usize;
+            if self.write_in.len() < header_len + len as usize {
+                return Ok(());
+            }
+
+            let compressed = match header.opcode {
+                OpCode::Data(Data::Text | Data::Binary) => {
+                    self.write_compressed = self.deflate.is_some();
+                    header.rsv1 = self.write_compressed;
+                    self.write_compressed
+                }
+                OpCode::Data(Data::Continue) => self.write_compressed,
+                OpCode::Control(_) => {
+                    // Do not delay the pongs and the close frames.
+                    self.flush = true;
+                    false
+                }
+                _ => false,
+            };
+
+            match &mut self.deflate {
+                Some(deflate) if compressed => {
+                    self.write_in.advance(header_len);
+                    let payload = self.write_in.split_to(len as usize);
+                    let data = deflate.compress(&payload, header.is_final)?;
+                    write_frame(&mut self.write_buf, &header, &data);
+                }
+                _ => self
+                    .write_buf
+                    .extend_from_slice(&self.write_in.split_to(header_len + len as usize)),
+            }
+        }
+    }
+
+    /// Writes all the buffered bytes to the connection.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        while !self.write_buf.is_empty() {
+            let n =
+                futures_util::ready!(Pin::new(&mut self.inner).poll_write(cx, &self.write_buf))?;
+            if n == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+            self.write_buf.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for WebSocketIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        if this.deflate.is_none() {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+
+        loop {
+            if !this.read_out.is_empty() {
+                let len = this.read_out.len().min(buf.remaining());
+                buf.put_slice(&this.read_out.split_to(len));
+                return Poll::Ready(Ok(()));
+            }
+
+            this.process_read()?;
+            if !this.read_out.is_empty() {
+                continue;
+            }
+
+            let mut data = [0; 8192];
+            let mut data_buf = ReadBuf::new(&mut data);
+            futures_util::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut data_buf))?;
+            if data_buf.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.read_buf.extend_from_slice(data_buf.filled());
+        }
+    }
+}
+
+impl AsyncWrite for WebSocketIo {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        let this = self.get_mut();
+        if this.write_buf.len() >= this.write_buffer_size {
+            futures_util::ready!(this.poll_write_buf(cx))?;
+        }
+        this.write_in.extend_from_slice(buf);
+        this.process_write()?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        if !this.flush && this.write_buf.len() < this.write_buffer_size {
+            return Poll::Ready(Ok(()));
+        }
+        futures_util::ready!(this.poll_write_buf(cx))?;
+        futures_util::ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+        this.flush = false;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        futures_util::ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+fn invalid_data(err: tokio_tungstenite::tungstenite::Error) -> IoError {
+    IoError::new(ErrorKind::InvalidData, err.to_string())
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i & 3];
+    }
+}
+
+fn write_frame(output: &mut BytesMut, header: &FrameHeader, payload: &[u8]) {
+    let mut head = Vec::with_capacity(header.len(payload.len() as u64));
+    header
+        .format(payload.len() as u64, &mut head)
+        .expect("Bug: can't write to vector");
+    output.extend_from_slice(&head);
+    output.extend_from_slice(payload);
+}
@@ -22,23 +22,42 @@
 //! let app = Route::new().at("/", get(index));
 //! ```
 
+mod config;
+mod deflate;
 mod extractor;
+mod hub;
+mod io;
 mod message;
 mod stream;
+mod typed;
 mod utils;
 
+pub use config::WebSocketConfig;
 pub use extractor::WebSocket;
+pub use hub::{ConnectionId, SlowConsumerPolicy, WsConnection, WsHub};
 pub use message::{CloseCode, Message};
 pub use stream::WebSocketStream;
+pub use typed::{DecodeErrorPolicy, MessageCodec, TypedWebSocketStream};
 
 #[cfg(test)]
 mod tests {
-    use std::net::SocketAddr;
+    use std::{io::Cursor, net::SocketAddr, time::Duration};
 
     use futures_util::{SinkExt, StreamExt};
     use http::{header, HeaderValue};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+    use tokio_tungstenite::tungstenite::protocol::frame::{
+        coding::{Data, OpCode},
+        FrameHeader,
+    };
 
-    use super::*;
+    use super::{
+        deflate::{Deflate, DeflateParams},
+        *,
+    };
     use crate::{
         handler,
         listener::{Acceptor, Listener, TcpListener},
@@ -160,4 +179,304 @@ mod tests {
 
         handle.abort();
     }
+
+    #[tokio::test]
+    async fn test_config() {
+        #[handler(internal)]
+        async fn index(ws: WebSocket) -> impl IntoResponse {
+            ws.config(
+                WebSocketConfig::new()
+                    .max_message_size(8)
+                    .allow_origins(["http://a.com", "http://b.com"]),
+            )
+            .on_upgrade(|mut stream| async move {
+                while let Some(Ok(msg)) = stream.next().await {
+                    if stream.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            })
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor
+            .local_addr()
+            .remove(0)
+            .as_socket_addr()
+            .cloned()
+            .unwrap();
+
+        let handle = tokio::spawn(async move {
+            let _ = Server::new_with_acceptor(acceptor).run(index).await;
+        });
+
+        fn request(addr: SocketAddr, origin: &str) -> http::Request<()> {
+            http::Request::builder()
+                .uri(format!("ws://{}", addr))
+                .header(header::ORIGIN, origin)
+                .header(header::SEC_WEBSOCKET_KEY, "test_key")
+                .header(header::UPGRADE, "websocket")
+                .header(header::HOST, "localhost")
+                .header(header::CONNECTION, "upgrade")
+                .header(header::SEC_WEBSOCKET_VERSION, "13")
+                .body(())
+                .unwrap()
+        }
+
+        match tokio_tungstenite::connect_async(request(addr, "http://c.com")).await {
+            Err(tokio_tungstenite::tungstenite::Error::Http(resp)) => {
+                assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+            }
+            _ => panic!("the origin should be rejected"),
+        }
+
+        let (mut client_stream, _) =
+            tokio_tungstenite::connect_async(request(addr, "http://b.com"))
+                .await
+                .unwrap();
+        client_stream
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                "abc".to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            client_stream.next().await.unwrap().unwrap(),
+            tokio_tungstenite::tungstenite::Message::Text("abc".to_string())
+        );
+
+        // The message is too large.
+        client_stream
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                "abcdefghijk".to_string(),
+            ))
+            .await
+            .unwrap();
+        assert!(!matches!(
+            client_stream.next().await,
+            Some(Ok(tokio_tungstenite::tungstenite::Message::Text(_)))
+        ));
+
+        // Requests without the `Origin` header are accepted.
+        tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_write_buffer_size() {
+        #[handler(internal)]
+        async fn index(ws: WebSocket) -> impl IntoResponse {
+            ws.on_upgrade(|mut stream| async move {
+                stream.feed(Message::text("a")).await.unwrap();
+                stream.feed(Message::text("b")).await.unwrap();
+                // The messages are buffered until the stream is flushed.
+                stream.next().await;
+                stream.flush().await.unwrap();
+            })
+        }
+
+        #[handler(internal)]
+        async fn unbuffered(ws: WebSocket) -> impl IntoResponse {
+            ws.config(WebSocketConfig::new().write_buffer_size(0))
+                .on_upgrade(|mut stream| async move {
+                    stream.feed(Message::text("a")).await.unwrap();
+                    stream.next().await;
+                })
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor
+            .local_addr()
+            .remove(0)
+            .as_socket_addr()
+            .cloned()
+            .unwrap();
+
+        let handle = tokio::spawn(async move {
+            let app = crate::Route::new()
+                .at("/", crate::get(index))
+                .at("/unbuffered", crate::get(unbuffered));
+            let _ = Server::new_with_acceptor(acceptor).run(app).await;
+        });
+
+        let (mut client_stream, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), client_stream.next())
+                .await
+                .is_err()
+        );
+        client_stream
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                "flush".to_string(),
+            ))
+            .await
+            .unwrap();
+        for text in ["a", "b"] {
+            assert_eq!(
+                client_stream.next().await.unwrap().unwrap(),
+                tokio_tungstenite::tungstenite::Message::Text(text.to_string())
+            );
+        }
+
+        let (mut client_stream, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/unbuffered", addr))
+                .await
+                .unwrap();
+        assert_eq!(
+            client_stream.next().await.unwrap().unwrap(),
+            tokio_tungstenite::tungstenite::Message::Text("a".to_string())
+        );
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_permessage_deflate() {
+        #[handler(internal)]
+        async fn index(ws: WebSocket) -> impl IntoResponse {
+            ws.config(WebSocketConfig::new().permessage_deflate(true))
+                .on_upgrade(|mut stream| async move {
+                    while let Some(Ok(Message::Text(text))) = stream.next().await {
+                        if stream
+                            .send(Message::Text(text.to_uppercase()))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                })
+        }
+
+        async fn read_frame(stream: &mut TcpStream, buf: &mut Vec<u8>) -> (FrameHeader, Vec<u8>) {
+            loop {
+                let mut cursor = Cursor::new(&buf[..]);
+                if let Some((header, len)) = FrameHeader::parse(&mut cursor).unwrap() {
+                    let start = cursor.position() as usize;
+                    let end = start + len as usize;
+                    if buf.len() >= end {
+                        let payload = buf[start..end].to_vec();
+                        buf.drain(..end);
+                        return (header, payload);
+                    }
+                }
+                let mut data = [0; 1024];
+                let n = stream.read(&mut data).await.unwrap();
+                assert!(n > 0);
+                buf.extend_from_slice(&data[..n]);
+            }
+        }
+
+        async fn write_frame(
+            stream: &mut TcpStream,
+            opcode: Data,
+            rsv1: bool,
+            is_final: bool,
+            payload: &[u8],
+        ) {
+            // Masks the frame with a zero key.
+            let header = FrameHeader {
+                is_final,
+                rsv1,
+                opcode: OpCode::Data(opcode),
+                mask: Some([0; 4]),
+                ..FrameHeader::default()
+            };
+            let mut data = Vec::new();
+            header.format(payload.len() as u64, &mut data).unwrap();
+            data.extend_from_slice(payload);
+            stream.write_all(&data).await.unwrap();
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor
+            .local_addr()
+            .remove(0)
+            .as_socket_addr()
+            .cloned()
+            .unwrap();
+
+        let handle = tokio::spawn(async move {
+            let _ = Server::new_with_acceptor(acceptor).run(index).await;
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\n\
+                  Host: localhost\r\n\
+                  Upgrade: websocket\r\n\
+                  Connection: upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\
+                  \r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut buf = Vec::new();
+        let end = loop {
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            let mut data = [0; 1024];
+            let n = stream.read(&mut data).await.unwrap();
+            assert!(n > 0);
+            buf.extend_from_slice(&data[..n]);
+        };
+        let head = String::from_utf8(buf.drain(..end).collect())
+            .unwrap()
+            .to_ascii_lowercase();
+        assert!(head.starts_with("http/1.1 101"));
+        assert!(head.contains("sec-websocket-extensions: permessage-deflate\r\n"));
+
+        let mut client = Deflate::new(DeflateParams::default());
+        let mut check = |header: FrameHeader, payload: Vec<u8>, text: &str| {
+            assert!(header.rsv1);
+            assert!(header.is_final);
+            assert_eq!(header.opcode, OpCode::Data(Data::Text));
+            assert_eq!(
+                client.decompress(&payload, true, 1024).unwrap(),
+                text.as_bytes()
+            );
+        };
+
+        let mut sender = Deflate::new(DeflateParams::default());
+        for _ in 0..2 {
+            let payload = sender.compress(b"hello", true).unwrap();
+            write_frame(&mut stream, Data::Text, true, true, &payload).await;
+            let (header, payload) = read_frame(&mut stream, &mut buf).await;
+            check(header, payload, "HELLO");
+        }
+
+        // A fragmented compressed message.
+        let payload = sender.compress(b"abc", false).unwrap();
+        write_frame(&mut stream, Data::Text, true, false, &payload).await;
+        let payload = sender.compress(b"def", true).unwrap();
+        write_frame(&mut stream, Data::Continue, false, true, &payload).await;
+        let (header, payload) = read_frame(&mut stream, &mut buf).await;
+        check(header, payload, "ABCDEF");
+
+        // An uncompressed message.
+        write_frame(&mut stream, Data::Text, false, true, b"xyz").await;
+        let (header, payload) = read_frame(&mut stream, &mut buf).await;
+        check(header, payload, "XYZ");
+
+        handle.abort();
+    }
 }
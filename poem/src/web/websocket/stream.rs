@@ -6,17 +6,16 @@ use std::{
 
 use futures_util::{Sink, SinkExt, Stream, StreamExt};
 
-use super::{utils::tungstenite_error_to_io_error, Message};
-use crate::Upgraded;
+use super::{io::WebSocketIo, utils::tungstenite_error_to_io_error, Message};
 
 /// A `WebSocket` stream, which implements [`Stream<Message>`] and
 /// [`Sink<Message>`].
 pub struct WebSocketStream {
-    inner: tokio_tungstenite::WebSocketStream<Upgraded>,
+    inner: tokio_tungstenite::WebSocketStream<WebSocketIo>,
 }
 
 impl WebSocketStream {
-    pub(crate) fn new(inner: tokio_tungstenite::WebSocketStream<Upgraded>) -> Self {
+    pub(crate) fn new(inner: tokio_tungstenite::WebSocketStream<WebSocketIo>) -> Self {
         Self { inner }
     }
 }
@@ -52,12 +51,14 @@ impl Sink<Message> for WebSocketStream {
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.get_mut().request_flush();
         self.inner
             .poll_flush_unpin(cx)
             .map_err(tungstenite_error_to_io_error)
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.get_mut().request_flush();
         self.inner
             .poll_close_unpin(cx)
             .map_err(tungstenite_error_to_io_error)
@@ -0,0 +1,434 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{ready, Sink, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{CloseCode, Message, WebSocketStream};
+
+/// The codec used by the [`TypedWebSocketStream`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MessageCodec {
+    /// JSON, the messages are sent in the text frames.
+    Json,
+    /// MessagePack, the messages are sent in the binary frames.
+    #[cfg(feature = "websocket-msgpack")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket-msgpack")))]
+    MessagePack,
+    /// CBOR, the messages are sent in the binary frames.
+    #[cfg(feature = "websocket-cbor")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket-cbor")))]
+    Cbor,
+}
+
+impl MessageCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> IoResult<Message> {
+        match self {
+            MessageCodec::Json => serde_json::to_string(value)
+                .map(Message::Text)
+                .map_err(|err| IoError::new(ErrorKind::InvalidData, err)),
+            #[cfg(feature = "websocket-msgpack")]
+            MessageCodec::MessagePack => rmp_serde::to_vec_named(value)
+                .map(Message::Binary)
+                .map_err(|err| IoError::new(ErrorKind::InvalidData, err)),
+            #[cfg(feature = "websocket-cbor")]
+            MessageCodec::Cbor => {
+                let mut data = Vec::new();
+                ciborium::ser::into_writer(value, &mut data)
+                    .map_err(|err| IoError::new(ErrorKind::InvalidData, err.to_string()))?;
+                Ok(Message::Binary(data))
+            }
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> IoResult<T> {
+        match self {
+            MessageCodec::Json => serde_json::from_slice(data)
+                .map_err(|err| IoError::new(ErrorKind::InvalidData, err)),
+            #[cfg(feature = "websocket-msgpack")]
+            MessageCodec::MessagePack => {
+                rmp_serde::from_slice(data).map_err(|err| IoError::new(ErrorKind::InvalidData, err))
+            }
+            #[cfg(feature = "websocket-cbor")]
+            MessageCodec::Cbor => ciborium::de::from_reader(data)
+                .map_err(|err| IoError::new(ErrorKind::InvalidData, err.to_string())),
+        }
+    }
+}
+
+/// The action when a message cannot be decoded, see
+/// [`TypedWebSocketStream::on_decode_error`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DecodeErrorPolicy {
+    /// Closes the connection with [`CloseCode::Invalid`], and yields the
+    /// error before the stream ends.
+    Close,
+
+    /// Skips the message.
+    Skip,
+
+    /// Yields the error, and continues to receive the messages.
+    Forward,
+}
+
+enum State {
+    Open,
+    Closing(Option<Message>, Option<IoError>),
+    Closed,
+}
+
+/// A typed `WebSocket` stream, which implements [`Stream<In>`] and
+/// [`Sink<Out>`], see [`WebSocketStream::typed`].
+///
+/// The errors of decoding and encoding are reported as [`std::io::Error`]
+/// with the kind [`ErrorKind::InvalidData`]. The ping, pong and close
+/// messages are handled internally.
+pub struct TypedWebSocketStream<In, Out> {
+    inner: WebSocketStream,
+    codec: MessageCodec,
+    policy: DecodeErrorPolicy,
+    state: State,
+    _mark: PhantomData<fn(Out) -> In>,
+}
+
+impl WebSocketStream {
+    /// Converts this stream to a typed stream which decodes the incoming
+    /// messages to `In` and encodes `Out` to the outgoing messages with the
+    /// specified codec.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::{SinkExt, StreamExt};
+    /// use poem::{
+    ///     get, handler,
+    ///     web::websocket::{MessageCodec, WebSocket},
+    ///     IntoResponse, Route,
+    /// };
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Request {
+    ///     a: i32,
+    ///     b: i32,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Response {
+    ///     sum: i32,
+    /// }
+    ///
+    /// #[handler]
+    /// async fn index(ws: WebSocket) -> impl IntoResponse {
+    ///     ws.on_upgrade(|socket| async move {
+    ///         let mut socket = socket.typed::<Request, Response>(MessageCodec::Json);
+    ///         while let Some(Ok(req)) = socket.next().await {
+    ///             let _ = socket.send(Response { sum: req.a + req.b }).await;
+    ///         }
+    ///     })
+    /// }
+    ///
+    /// let app = Route::new().at("/", get(index));
+    /// ```
+    pub fn typed<In, Out>(self, codec: MessageCodec) -> TypedWebSocketStream<In, Out> {
+        TypedWebSocketStream {
+            inner: self,
+            codec,
+            policy: DecodeErrorPolicy::Close,
+            state: State::Open,
+            _mark: PhantomData,
+        }
+    }
+}
+
+impl<In, Out> TypedWebSocketStream<In, Out> {
+    /// Sets the action when a message cannot be decoded. Default is
+    /// [`DecodeErrorPolicy::Close`].
+    #[must_use]
+    pub fn on_decode_error(self, policy: DecodeErrorPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Consumes this stream, returning the underlying [`WebSocketStream`].
+    pub fn into_inner(self) -> WebSocketStream {
+        self.inner
+    }
+}
+
+impl<In: DeserializeOwned, Out> Stream for TypedWebSocketStream<In, Out> {
+    type Item = IoResult<In>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            match &mut this.state {
+                State::Open => {}
+                State::Closing(msg, err) => {
+                    if let Some(close) = msg.take() {
+                        // Ignores the errors because the connection is closing.
+                        match this.inner.poll_ready_unpin(cx) {
+                            Poll::Ready(Ok(())) => {
+                                let _ = this.inner.start_send_unpin(close);
+                            }
+                            Poll::Ready(Err(_)) => {}
+                            Poll::Pending => {
+                                *msg = Some(close);
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+                    if this.inner.poll_flush_unpin(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    let err = err.take();
+                    this.state = State::Closed;
+                    return Poll::Ready(err.map(Err));
+                }
+                State::Closed => return Poll::Ready(None),
+            }
+
+            let msg = match ready!(this.inner.poll_next_unpin(cx)) {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => {
+                    this.state = State::Closed;
+                    return Poll::Ready(None);
+                }
+            };
+            let data = match msg {
+                Message::Text(text) => text.into_bytes(),
+                Message::Binary(data) => data,
+                Message::Ping(_) | Message::Pong(_) => continue,
+                Message::Close(_) => {
+                    this.state = State::Closed;
+                    return Poll::Ready(None);
+                }
+            };
+
+            match this.codec.decode(&data) {
+                Ok(value) => return Poll::Ready(Some(Ok(value))),
+                Err(err) => match this.policy {
+                    DecodeErrorPolicy::Close => {
+                        let close = Message::close_with(CloseCode::Invalid, close_reason(&err));
+                        this.state = State::Closing(Some(close), Some(err));
+                    }
+                    DecodeErrorPolicy::Skip => {}
+                    DecodeErrorPolicy::Forward => return Poll::Ready(Some(Err(err))),
+                },
+            }
+        }
+    }
+}
+
+/// The maximum size of the reason of a close frame, the payload of a control
+/// frame must not be larger than 125 bytes and the code takes 2 bytes.
+const MAX_CLOSE_REASON_SIZE: usize = 123;
+
+/// Returns the error message truncated to a valid close reason.
+fn close_reason(err: &IoError) -> String {
+    let mut reason = err.to_string();
+    if reason.len() > MAX_CLOSE_REASON_SIZE {
+        let mut len = MAX_CLOSE_REASON_SIZE;
+        while !reason.is_char_boundary(len) {
+            len -= 1;
+        }
+        reason.truncate(len);
+    }
+    reason
+}
+
+impl<In, Out: Serialize> Sink<Out> for TypedWebSocketStream<In, Out> {
+    type Error = IoError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Out) -> Result<(), Self::Error> {
+        let msg = self.codec.encode(&item)?;
+        self.inner.start_send_unpin(msg)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use serde::Deserialize;
+    use tokio_tungstenite::tungstenite::{
+        protocol::{frame::coding::CloseCode as ClientCloseCode, CloseFrame},
+        Message as ClientMessage,
+    };
+
+    use super::*;
+    use crate::{
+        get, handler,
+        listener::{Acceptor, Listener, TcpListener},
+        web::{websocket::WebSocket, Path},
+        IntoResponse, Route, Server,
+    };
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Request {
+        a: i32,
+        b: i32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Response {
+        sum: Option<i32>,
+        error: Option<String>,
+    }
+
+    fn codecs() -> Vec<(&'static str, MessageCodec)> {
+        let mut codecs = vec![("json", MessageCodec::Json)];
+        #[cfg(feature = "websocket-msgpack")]
+        codecs.push(("msgpack", MessageCodec::MessagePack));
+        #[cfg(feature = "websocket-cbor")]
+        codecs.push(("cbor", MessageCodec::Cbor));
+        codecs
+    }
+
+    #[handler(internal)]
+    async fn index(
+        Path((codec, policy)): Path<(String, String)>,
+        ws: WebSocket,
+    ) -> impl IntoResponse {
+        let codec = codecs()
+            .into_iter()
+            .find(|(name, _)| *name == codec)
+            .unwrap()
+            .1;
+        let policy = match policy.as_str() {
+            "close" => DecodeErrorPolicy::Close,
+            "skip" => DecodeErrorPolicy::Skip,
+            _ => DecodeErrorPolicy::Forward,
+        };
+
+        ws.on_upgrade(move |socket| async move {
+            let mut socket = socket
+                .typed::<Request, Response>(codec)
+                .on_decode_error(policy);
+            while let Some(res) = socket.next().await {
+                let resp = match res {
+                    Ok(req) => Response {
+                        sum: Some(req.a + req.b),
+                        error: None,
+                    },
+                    Err(err) => Response {
+                        sum: None,
+                        error: Some(format!("{:?}", err.kind())),
+                    },
+                };
+                if socket.send(resp).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    async fn start() -> SocketAddr {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor
+            .local_addr()
+            .remove(0)
+            .as_socket_addr()
+            .cloned()
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = Server::new_with_acceptor(acceptor)
+                .run(Route::new().at("/:codec/:policy", get(index)))
+                .await;
+        });
+        addr
+    }
+
+    #[test]
+    fn truncate_close_reason() {
+        let err = IoError::new(ErrorKind::InvalidData, "short");
+        assert_eq!(close_reason(&err), "short");
+
+        let err = IoError::new(ErrorKind::InvalidData, format!("{}é", "a".repeat(122)));
+        assert_eq!(close_reason(&err), "a".repeat(122));
+
+        let err = IoError::new(ErrorKind::InvalidData, "é".repeat(100));
+        assert_eq!(close_reason(&err), "é".repeat(61));
+    }
+
+    #[tokio::test]
+    async fn typed() {
+        let addr = start().await;
+
+        for (name, codec) in codecs() {
+            for policy in ["close", "skip", "forward"] {
+                let (mut client, _) =
+                    tokio_tungstenite::connect_async(format!("ws://{}/{}/{}", addr, name, policy))
+                        .await
+                        .unwrap();
+
+                let msg = codec.encode(&Request { a: 1, b: 2 }).unwrap();
+                assert_eq!(msg.is_text(), codec == MessageCodec::Json);
+                client.send(msg.into()).await.unwrap();
+                let data = client.next().await.unwrap().unwrap().into_data();
+                assert_eq!(
+                    codec.decode::<Response>(&data).unwrap(),
+                    Response {
+                        sum: Some(3),
+                        error: None,
+                    }
+                );
+
+                client
+                    .send(ClientMessage::Binary(vec![0xc1, 0xff]))
+                    .await
+                    .unwrap();
+                match policy {
+                    "close" => {
+                        match client.next().await.unwrap().unwrap() {
+                            ClientMessage::Close(Some(CloseFrame { code, .. })) => {
+                                assert_eq!(code, ClientCloseCode::Invalid)
+                            }
+                            msg => panic!("unexpected message: {:?}", msg),
+                        }
+                        continue;
+                    }
+                    "forward" => {
+                        let data = client.next().await.unwrap().unwrap().into_data();
+                        assert_eq!(
+                            codec.decode::<Response>(&data).unwrap(),
+                            Response {
+                                sum: None,
+                                error: Some(format!("{:?}", ErrorKind::InvalidData)),
+                            }
+                        );
+                    }
+                    _ => {}
+                }
+
+                // The stream still works.
+                client
+                    .send(codec.encode(&Request { a: 3, b: 4 }).unwrap().into())
+                    .await
+                    .unwrap();
+                let data = client.next().await.unwrap().unwrap().into_data();
+                assert_eq!(codec.decode::<Response>(&data).unwrap().sum, Some(7));
+            }
+        }
+    }
+}